@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use miniz_oxide::inflate::decompress_to_vec;
+
+use crate::from_bytes_struct;
+use crate::memory::FromBytes;
+use crate::memory::Memory;
+use crate::memory::MemoryError;
+use crate::memory_source::open_source;
+use crate::memory_source::MopenOptions;
+
+const ZIP_EOCD_SIGNATURE    : u32 = 0x06054b50;
+const ZIP_CENTRAL_SIGNATURE : u32 = 0x02014b50;
+const ZIP_LOCAL_SIGNATURE   : u32 = 0x04034b50;
+const ZIP_MAX_COMMENT       : usize = 0xFFFF;
+const ZIP_FLAG_ENCRYPTED    : u16 = 0x1;
+const ZIP_METHOD_STORED     : u16 = 0;
+const ZIP_METHOD_DEFLATE    : u16 = 8;
+
+const GZIP_IDENTIFIER       : [u8;2] = [0x1F, 0x8B];
+const GZIP_FHCRC            : u8 = 0x02;
+const GZIP_FEXTRA           : u8 = 0x04;
+const GZIP_FNAME            : u8 = 0x08;
+const GZIP_FCOMMENT         : u8 = 0x10;
+
+from_bytes_struct! {
+    struct ZipEndOfCentralDirectory {
+        signature       : u32,
+        disk            : u16,
+        central_disk    : u16,
+        n_disk_entries  : u16,
+        n_entries       : u16,
+        central_size    : u32,
+        central_offset  : u32,
+        comment_len     : u16,
+    }
+}
+
+from_bytes_struct! {
+    struct ZipCentralHeader {
+        signature       : u32,
+        version_made    : u16,
+        version_needed  : u16,
+        flags           : u16,
+        method          : u16,
+        mod_time        : u16,
+        mod_date        : u16,
+        crc32           : u32,
+        compressed      : u32,
+        uncompressed    : u32,
+        name_len        : u16,
+        extra_len       : u16,
+        comment_len     : u16,
+        disk_start      : u16,
+        internal_attr   : u16,
+        external_attr   : u32,
+        local_offset    : u32,
+    }
+}
+
+from_bytes_struct! {
+    struct ZipLocalHeader {
+        signature       : u32,
+        version_needed  : u16,
+        flags           : u16,
+        method          : u16,
+        mod_time        : u16,
+        mod_date        : u16,
+        crc32           : u32,
+        compressed      : u32,
+        uncompressed    : u32,
+        name_len        : u16,
+        extra_len       : u16,
+    }
+}
+
+struct ZipEntry {
+    method       : u16,
+    crc32        : u32,
+    offset       : usize,
+    compressed   : usize,
+    uncompressed : usize,
+}
+
+/// A ZIP archive held in memory. `Memory::mopen` reads archive entries
+/// through virtual paths of the form `<archive url>#<entry path>`, such as
+/// `pack.zip#shaders/vertex.glsl`, opening each archive once.
+pub struct Archive {
+    url         : String,
+    buffer      : Vec<u8>,
+    entry_array : HashMap<String, ZipEntry>,
+}
+
+fn archives() -> &'static Mutex<HashMap<String, Arc<Archive>>> {
+    static ARCHIVES: OnceLock<Mutex<HashMap<String, Arc<Archive>>>> =
+        OnceLock::new();
+    ARCHIVES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn archive_error(url: &str, msg: &str) -> MemoryError {
+    MemoryError::ArchiveError(url.into(), msg.into())
+}
+
+impl Archive {
+    /// Returns the archive at `url`, reading it on first use. Later opens
+    /// share it until `close` is called.
+    pub async fn open(url: &str) -> Result<Arc<Archive>, MemoryError> {
+        Archive::open_with(url, &mut MopenOptions::default()).await
+    }
+    /// Like `open`, applying `options` if the archive has to be read.
+    pub async fn open_with(url: &str, options: &mut MopenOptions)
+        -> Result<Arc<Archive>, MemoryError>
+    {
+        if let Some(archive) = archives().lock().unwrap().get(url) {
+            return Ok( archive.clone() );
+        }
+        let buffer  = open_source(url, options).await?.bytes;
+        let archive = Arc::new(Archive::from_bytes(url, buffer)?);
+
+        archives().lock().unwrap().insert(url.into(), archive.clone());
+        Ok( archive )
+    }
+    /// Drops the shared copy of the archive at `url`, freeing it once no
+    /// caller holds it.
+    pub fn close(url: &str) {
+        archives().lock().unwrap().remove(url);
+    }
+    /// Reads the central directory of the ZIP archive in `bytes`; `url` only
+    /// names it in errors.
+    pub fn from_bytes(url: &str, bytes: Vec<u8>) -> Result<Self, MemoryError>
+    {
+        let mut memory = Memory::from_bytes(url, bytes);
+        let eocd       = find_end_of_central_directory(&memory)
+                         .ok_or_else(|| {
+                             archive_error(url, "no end of central directory \
+                                                 record was found")
+                         })?;
+        memory.mseek(SeekFrom::Start(eocd as u64))?;
+
+        let end = memory.read_le::<ZipEndOfCentralDirectory>()?;
+
+        if end.disk != 0 || end.n_disk_entries != end.n_entries {
+            Err( archive_error(url, "multi disk archives aren't supported") )?
+        }
+        if end.central_offset == u32::MAX {
+            Err( archive_error(url, "ZIP64 archives aren't supported") )?
+        }
+        let mut entry_array = HashMap::new();
+        memory.mseek(SeekFrom::Start(end.central_offset as u64))?;
+
+        for _ in 0..end.n_entries {
+            let header = memory.read_le::<ZipCentralHeader>()?;
+
+            if header.signature != ZIP_CENTRAL_SIGNATURE {
+                Err( archive_error(url, "central directory is corrupt") )?
+            }
+            let name = memory.read_bytes(header.name_len as usize)?;
+            let name = String::from_utf8_lossy(name).into_owned();
+
+            memory.mseek(SeekFrom::Current(header.extra_len   as i64 +
+                                           header.comment_len as i64))?;
+            if name.ends_with('/') {
+                continue;
+            }
+            if header.flags & ZIP_FLAG_ENCRYPTED != 0 {
+                let msg = format!("entry ({}) is encrypted", name);
+                Err( archive_error(url, &msg) )?
+            }
+            if header.compressed   == u32::MAX ||
+               header.uncompressed == u32::MAX ||
+               header.local_offset == u32::MAX
+            {
+                let msg = format!("entry ({}) needs ZIP64", name);
+                Err( archive_error(url, &msg) )?
+            }
+            entry_array.insert(name, ZipEntry {
+                method       : header.method,
+                crc32        : header.crc32,
+                offset       : header.local_offset as usize,
+                compressed   : header.compressed   as usize,
+                uncompressed : header.uncompressed as usize,
+            });
+        }
+        // Entry data follows a local header whose variable length fields
+        // can differ from the central directory's copy.
+        for entry in entry_array.values_mut() {
+            let start = memory.mseek(SeekFrom::Start(entry.offset as u64))?;
+            let local = memory.read_le::<ZipLocalHeader>()?;
+
+            if local.signature != ZIP_LOCAL_SIGNATURE {
+                Err( archive_error(url, "local file header is corrupt") )?
+            }
+            entry.offset = start
+                         + ZipLocalHeader::SIZE
+                         + local.name_len  as usize
+                         + local.extra_len as usize;
+        }
+        Ok( Archive { url: url.into(), buffer: memory.into_bytes(),
+                      entry_array } )
+    }
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    /// Returns the paths of the files in the archive.
+    pub fn entry_names(&self) -> impl Iterator<Item = &str> {
+        self.entry_array.keys().map(|k| k.as_str())
+    }
+    pub fn contains(&self, path: &str) -> bool {
+        self.entry_array.contains_key(path)
+    }
+    /// Extracts the file at `path`, checking its size and CRC.
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, MemoryError> {
+        use MemoryError::*;
+
+        let url   = format!("{}#{}", self.url, path);
+        let entry = self.entry_array
+                        .get(path)
+                        .ok_or_else(|| NotFound(url.clone()))?;
+        let data  = self.buffer
+                        .get(entry.offset..entry.offset + entry.compressed)
+                        .ok_or_else(|| OutOfBounds(url.clone(),
+                                                   entry.offset,
+                                                   entry.compressed))?;
+        let bytes = match entry.method {
+            ZIP_METHOD_STORED  => data.to_vec(),
+            ZIP_METHOD_DEFLATE => decompress_to_vec(data).map_err(|e| {
+                archive_error(&url, &format!("inflate failed ({:?})",
+                                             e.status))
+            })?,
+            method => {
+                let msg = format!("compression method {} isn't supported",
+                                  method);
+                Err( archive_error(&url, &msg) )?
+            },
+        };
+        if bytes.len() != entry.uncompressed || crc32(&bytes) != entry.crc32 {
+            Err( archive_error(&url, "extracted data doesn't match its \
+                                      size or CRC") )?
+        }
+        Ok( bytes )
+    }
+}
+
+fn find_end_of_central_directory(memory: &Memory) -> Option<usize> {
+    let bytes = memory.bytes();
+    let last  = bytes.len().checked_sub(ZipEndOfCentralDirectory::SIZE)?;
+    let first = last.saturating_sub(ZIP_MAX_COMMENT);
+
+    (first..=last).rev().find(|&i| {
+        bytes[i..i + 4] == ZIP_EOCD_SIGNATURE.to_le_bytes()
+    })
+}
+
+/// Returns `bytes` decompressed if they hold a single member gzip file.
+pub(crate) fn gunzip(url: &str, bytes: &[u8]) -> Result<Vec<u8>, MemoryError>
+{
+    let mut memory = Memory::from_bytes(url, bytes.to_vec());
+
+    if memory.read_le::<[u8; 2]>()? != GZIP_IDENTIFIER ||
+       memory.read_u8()? != ZIP_METHOD_DEFLATE as u8
+    {
+        Err( archive_error(url, "not a gzip file") )?
+    }
+    let flags = memory.read_u8()?;
+
+    // Skip the modification time, extra flags and OS.
+    memory.mseek(SeekFrom::Current(6))?;
+
+    if flags & GZIP_FEXTRA != 0 {
+        let len = memory.read_u16_le()?;
+        memory.mseek(SeekFrom::Current(len as i64))?;
+    }
+    if flags & GZIP_FNAME != 0 {
+        memory.read_cstr().ok();
+    }
+    if flags & GZIP_FCOMMENT != 0 {
+        memory.read_cstr().ok();
+    }
+    if flags & GZIP_FHCRC != 0 {
+        memory.read_u16_le()?;
+    }
+    let start = memory.mtell();
+    let end   = memory.size().checked_sub(8)
+                      .filter(|&end| end >= start)
+                      .ok_or_else(|| archive_error(url, "gzip file is \
+                                                         truncated"))?;
+    let bytes = decompress_to_vec(&memory.bytes()[start..end]).map_err(|e| {
+        archive_error(url, &format!("inflate failed ({:?})", e.status))
+    })?;
+    memory.mseek(SeekFrom::Start(end as u64))?;
+
+    let crc  = memory.read_u32_le()?;
+    let size = memory.read_u32_le()?;
+
+    if size != bytes.len() as u32 || crc != crc32(&bytes) {
+        Err( archive_error(url, "decompressed data doesn't match its size \
+                                 or CRC") )?
+    }
+    Ok( bytes )
+}
+
+/// CRC-32 as used by ZIP and gzip.
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i     = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    !bytes.iter().fold(!0, |c, &b| TABLE[((c ^ b as u32) & 0xFF) as usize]
+                                   ^ (c >> 8))
+}
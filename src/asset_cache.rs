@@ -0,0 +1,319 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use js_sys::Array;
+use js_sys::ArrayBuffer;
+use js_sys::Uint8Array;
+use web_sys::Cache;
+use web_sys::Headers;
+use web_sys::Request;
+use web_sys::Response;
+use web_sys::ResponseInit;
+
+use crate::memory::MemoryError;
+
+/// Separates the version from the URL in cache keys. Versions must not
+/// contain it; URLs may.
+const KEY_SEPARATOR : char = '|';
+
+/// Cache Storage only keys on http(s) URLs, so entries are stored under this
+/// prefix with the key percent encoded after it.
+const CACHE_STORAGE_PREFIX : &str = "https://blueshift.cache/";
+
+/// Where an `AssetCache` keeps its bytes. Keys are opaque strings.
+pub trait CacheStore {
+    fn get<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<Option<Vec<u8>>, MemoryError>>;
+
+    fn put<'a>(&'a self, key: &'a str, bytes: &'a [u8])
+        -> LocalBoxFuture<'a, Result<(), MemoryError>>;
+
+    /// Removes the entry for `key`, returning whether there was one.
+    fn remove<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<bool, MemoryError>>;
+
+    /// Returns every stored key with the size of its entry in bytes.
+    fn entries(&self)
+        -> LocalBoxFuture<'_, Result<Vec<(String, usize)>, MemoryError>>;
+}
+
+/// Stores entries in a named bucket of the browser's Cache Storage, where
+/// they outlive the page.
+pub struct CacheStorageStore {
+    name : String,
+}
+
+impl CacheStorageStore {
+    pub fn new(name: &str) -> Self {
+        CacheStorageStore { name: name.into() }
+    }
+    async fn cache(&self) -> Result<Cache, MemoryError> {
+        let window = web_sys::window().unwrap();
+        let caches = window.caches().map_err(|e| self.error(e))?;
+        let cache  = JsFuture::from(caches.open(&self.name))
+                     .await
+                     .map_err(|e| self.error(e))?;
+        Ok( cache.unchecked_into::<Cache>() )
+    }
+    fn error(&self, e: wasm_bindgen::JsValue) -> MemoryError {
+        MemoryError::CacheError(self.name.clone(), e)
+    }
+}
+
+fn cache_storage_url(key: &str) -> String {
+    format!("{}{}", CACHE_STORAGE_PREFIX,
+            String::from(js_sys::encode_uri_component(key)))
+}
+
+impl CacheStore for CacheStorageStore {
+    fn get<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<Option<Vec<u8>>, MemoryError>>
+    {
+        async move {
+            let cache = self.cache().await?;
+            let rsp   = JsFuture::from(cache.match_with_str(
+                                           &cache_storage_url(key)))
+                        .await
+                        .map_err(|e| self.error(e))?;
+            if rsp.is_undefined() {
+                return Ok( None );
+            }
+            let rsp   = rsp.unchecked_into::<Response>();
+            let data  = rsp.array_buffer().map_err(|e| self.error(e))?;
+            let data  = JsFuture::from(data).await.map_err(|e| self.error(e))?;
+            let bytes = Uint8Array::new(&data.unchecked_into::<ArrayBuffer>());
+            Ok( Some(bytes.to_vec()) )
+        }.boxed_local()
+    }
+    fn put<'a>(&'a self, key: &'a str, bytes: &'a [u8])
+        -> LocalBoxFuture<'a, Result<(), MemoryError>>
+    {
+        async move {
+            let cache = self.cache().await?;
+
+            // The size goes in a header so `entries` needn't read bodies.
+            let headers = Headers::new().map_err(|e| self.error(e))?;
+            headers.set("Content-Length", &bytes.len().to_string())
+                   .map_err(|e| self.error(e))?;
+            let init = ResponseInit::new();
+            init.set_headers_headers(&headers);
+
+            let mut body = bytes.to_vec();
+            let rsp   = Response::new_with_opt_u8_array_and_init(
+                            Some(&mut body), &init)
+                        .map_err(|e| self.error(e))?;
+            JsFuture::from(cache.put_with_str(&cache_storage_url(key), &rsp))
+                .await
+                .map_err(|e| self.error(e))?;
+            Ok( () )
+        }.boxed_local()
+    }
+    fn remove<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<bool, MemoryError>>
+    {
+        async move {
+            let cache = self.cache().await?;
+            let found = JsFuture::from(cache.delete_with_str(
+                                           &cache_storage_url(key)))
+                        .await
+                        .map_err(|e| self.error(e))?;
+            Ok( found.as_bool().unwrap_or(false) )
+        }.boxed_local()
+    }
+    fn entries(&self)
+        -> LocalBoxFuture<'_, Result<Vec<(String, usize)>, MemoryError>>
+    {
+        async move {
+            let cache    = self.cache().await?;
+            let requests = JsFuture::from(cache.keys())
+                           .await
+                           .map_err(|e| self.error(e))?
+                           .unchecked_into::<Array>();
+            let mut entries = vec![];
+
+            for request in requests.iter() {
+                let request = request.unchecked_into::<Request>();
+                let url     = request.url();
+                let key = match url.strip_prefix(CACHE_STORAGE_PREFIX) {
+                    Some(key) => js_sys::decode_uri_component(key)
+                                 .map_err(|e| self.error(e))?,
+                    None      => continue,
+                };
+                let key = String::from(key);
+                let rsp = JsFuture::from(cache.match_with_request(&request))
+                          .await
+                          .map_err(|e| self.error(e))?;
+                if rsp.is_undefined() {
+                    continue;
+                }
+                let length = rsp.unchecked_into::<Response>()
+                                .headers()
+                                .get("Content-Length")
+                                .map_err(|e| self.error(e))?
+                                .and_then(|len| len.parse().ok());
+
+                // Entries stored without the header are measured by reading
+                // them.
+                let len = match length {
+                    Some(len) => len,
+                    None      => self.get(&key).await?.map_or(0, |b| b.len()),
+                };
+                entries.push((key, len));
+            }
+            Ok( entries )
+        }.boxed_local()
+    }
+}
+
+/// Keeps entries in memory for the life of the store. Used where Cache
+/// Storage isn't available, such as native tests.
+#[derive(Default)]
+pub struct MemoryCacheStore {
+    entries : RefCell<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryCacheStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<Option<Vec<u8>>, MemoryError>>
+    {
+        async move {
+            Ok( self.entries.borrow().get(key).cloned() )
+        }.boxed_local()
+    }
+    fn put<'a>(&'a self, key: &'a str, bytes: &'a [u8])
+        -> LocalBoxFuture<'a, Result<(), MemoryError>>
+    {
+        async move {
+            self.entries.borrow_mut().insert(key.into(), bytes.to_vec());
+            Ok( () )
+        }.boxed_local()
+    }
+    fn remove<'a>(&'a self, key: &'a str)
+        -> LocalBoxFuture<'a, Result<bool, MemoryError>>
+    {
+        async move {
+            Ok( self.entries.borrow_mut().remove(key).is_some() )
+        }.boxed_local()
+    }
+    fn entries(&self)
+        -> LocalBoxFuture<'_, Result<Vec<(String, usize)>, MemoryError>>
+    {
+        async move {
+            Ok( self.entries.borrow()
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.len()))
+                            .collect() )
+        }.boxed_local()
+    }
+}
+
+/// The number of entries and bytes an `AssetCache` holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub entries : usize,
+    pub bytes   : usize,
+}
+
+/// Keeps the bytes `Memory::mopen` reads so later loads, including those of
+/// later page loads, don't download them again. Entries are keyed by URL
+/// and the cache's version; bumping the version when assets change makes
+/// the old entries miss, and `purge_stale` frees them.
+#[derive(Clone)]
+pub struct AssetCache {
+    store   : Rc<dyn CacheStore>,
+    version : String,
+}
+
+impl AssetCache {
+    pub fn new(store: Rc<dyn CacheStore>, version: &str) -> Self {
+        AssetCache { store, version: version.into() }
+    }
+    /// Returns a cache kept in Cache Storage bucket `name` in the browser, or
+    /// in memory elsewhere.
+    pub fn open(name: &str, version: &str) -> Self {
+        let store: Rc<dyn CacheStore> = if cfg!(target_arch = "wasm32") {
+            Rc::new(CacheStorageStore::new(name))
+        } else {
+            Rc::new(MemoryCacheStore::new())
+        };
+        AssetCache::new(store, version)
+    }
+    #[inline]
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+    fn key(&self, url: &str) -> String {
+        format!("{}{}{}", self.version, KEY_SEPARATOR, url)
+    }
+    /// Returns the cached bytes of `url`, if any.
+    pub async fn get(&self, url: &str) -> Result<Option<Vec<u8>>, MemoryError>
+    {
+        self.store.get(&self.key(url)).await
+    }
+    pub async fn put(&self, url: &str, bytes: &[u8]) -> Result<(), MemoryError>
+    {
+        self.store.put(&self.key(url), bytes).await
+    }
+    /// Drops the cached copy of `url`, returning whether there was one.
+    pub async fn invalidate(&self, url: &str) -> Result<bool, MemoryError> {
+        self.store.remove(&self.key(url)).await
+    }
+    /// Drops every entry, whatever its version, returning how many there
+    /// were.
+    pub async fn clear(&self) -> Result<usize, MemoryError> {
+        self.remove_where(|_| true).await
+    }
+    /// Drops the entries of other versions, returning how many there were.
+    pub async fn purge_stale(&self) -> Result<usize, MemoryError> {
+        self.remove_where(|version| version != self.version).await
+    }
+    /// Returns how much the entries of every version take up.
+    pub async fn usage(&self) -> Result<CacheUsage, MemoryError> {
+        let entries = self.store.entries().await?;
+
+        Ok( CacheUsage { entries : entries.len(),
+                         bytes   : entries.iter().map(|(_, len)| len).sum() } )
+    }
+    async fn remove_where<F>(&self, f: F) -> Result<usize, MemoryError>
+        where F: Fn(&str) -> bool
+    {
+        let mut count = 0;
+
+        for (key, _) in self.store.entries().await? {
+            let version = key.split(KEY_SEPARATOR).next().unwrap_or("");
+            if f(version) && self.store.remove(&key).await? {
+                count += 1;
+            }
+        }
+        Ok( count )
+    }
+}
+
+thread_local! {
+    static ASSET_CACHE: RefCell<Option<AssetCache>> = const {
+        RefCell::new(None)
+    };
+}
+
+/// Makes `cache` serve every `Memory::mopen` of an HTTP URL that doesn't
+/// choose otherwise through `MopenOptions`. `None` turns caching off.
+pub fn set_asset_cache(cache: Option<AssetCache>) {
+    ASSET_CACHE.with(|c| *c.borrow_mut() = cache);
+}
+
+pub fn asset_cache() -> Option<AssetCache> {
+    ASSET_CACHE.with(|c| c.borrow().clone())
+}
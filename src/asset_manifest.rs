@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use futures::stream;
+use futures::StreamExt;
+
+use serde::Deserialize;
+
+use crate::backend::GlBackend;
+use crate::error::GfxError;
+use crate::memory::Memory;
+use crate::memory::MemoryError;
+use crate::memory_source::url_scheme;
+use crate::memory_source::MopenOptions;
+use crate::texture::Texture;
+
+const DEFAULT_CONCURRENCY : usize = 4;
+
+/// What an asset in a manifest holds, which decides how the preloader
+/// stores it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetKind {
+    Shader,
+    Texture,
+    Mesh,
+    Sound,
+}
+
+/// One asset listed in a manifest. Relative URLs are resolved against the
+/// manifest's `base`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AssetEntry {
+    pub name       : String,
+    #[serde(rename = "type")]
+    pub kind       : AssetKind,
+    pub url        : String,
+    /// Milliseconds to wait for the asset before failing it.
+    #[serde(default)]
+    pub timeout    : Option<u32>,
+    /// Whether the asset may be read from and stored in the asset cache.
+    #[serde(default = "default_cache")]
+    pub cache      : bool,
+    /// Texture flags, filter and anisotropy, passed on by
+    /// `TextureAsset::create`.
+    #[serde(default)]
+    pub flags      : u32,
+    #[serde(default)]
+    pub filter     : u8,
+    #[serde(default)]
+    pub anisotropy : f32,
+}
+
+fn default_cache() -> bool {
+    true
+}
+
+/// A list of the assets an application needs, read from JSON:
+///
+/// ```json
+/// { "base": "assets/",
+///   "assets": [
+///     { "name": "basic.vert", "type": "shader",  "url": "basic.vert" },
+///     { "name": "crate",      "type": "texture", "url": "crate.ktx",
+///       "filter": 2, "anisotropy": 4.0 },
+///     { "name": "cube",       "type": "mesh",    "url": "cube.obj" },
+///     { "name": "click",      "type": "sound",   "url": "click.ogg",
+///       "timeout": 5000, "cache": false } ] }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub base   : String,
+    pub assets : Vec<AssetEntry>,
+}
+
+impl AssetManifest {
+    /// Parses a manifest, checking that asset names are unique.
+    pub fn from_json(json: &str) -> Result<Self, ManifestError> {
+        use ManifestError::*;
+
+        let manifest: AssetManifest = serde_json::from_str(json).map_err(|e| {
+            ManifestFormatError(format!("Asset manifest can't be parsed; {}.",
+                                        e))
+        })?;
+        let mut names = HashSet::new();
+
+        for entry in &manifest.assets {
+            if !names.insert(entry.name.as_str()) {
+                Err( DuplicateAsset(format!("Asset ({}) is listed more than \
+                                             once.", entry.name)) )?
+            }
+        }
+        Ok( manifest )
+    }
+    /// Reads the manifest at `url`. Without a `base`, asset URLs are
+    /// relative to the manifest.
+    pub async fn load(url: &str) -> Result<Self, GfxError> {
+        let memory       = Memory::mopen(url).await?;
+        let mut manifest = AssetManifest::from_json(memory.as_str())?;
+
+        if manifest.base.is_empty() {
+            if let Some(i) = url.rfind('/') {
+                manifest.base = url[..=i].into();
+            }
+        }
+        Ok( manifest )
+    }
+    /// Returns the URL `entry` is read from.
+    pub fn url_of(&self, entry: &AssetEntry) -> String {
+        if url_scheme(&entry.url).is_some() || entry.url.starts_with('/') {
+            entry.url.clone()
+        } else {
+            format!("{}{}", self.base, entry.url)
+        }
+    }
+}
+
+/// A preloaded texture file and the options to create it with.
+pub struct TextureAsset {
+    memory     : Memory,
+    flags      : u32,
+    filter     : u8,
+    anisotropy : f32,
+}
+
+impl TextureAsset {
+    #[inline]
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+    /// Uploads the texture to `context` with the manifest's options.
+    pub fn create<B: GlBackend>(&mut self, name: &str, context: Arc<B>)
+        -> Result<Texture<B>, GfxError>
+    {
+        self.memory.mseek(std::io::SeekFrom::Start(0))?;
+        Texture::from_memory(name, &mut self.memory, self.flags, self.filter,
+                             self.anisotropy, context)
+    }
+}
+
+/// A preloaded asset. Shaders are decoded to text; meshes and sounds are
+/// left as bytes for their loaders.
+pub enum Asset {
+    Shader(String),
+    Texture(TextureAsset),
+    Mesh(Memory),
+    Sound(Memory),
+}
+
+impl Asset {
+    pub fn kind(&self) -> AssetKind {
+        match self {
+            Asset::Shader(_)  => AssetKind::Shader,
+            Asset::Texture(_) => AssetKind::Texture,
+            Asset::Mesh(_)    => AssetKind::Mesh,
+            Asset::Sound(_)   => AssetKind::Sound,
+        }
+    }
+}
+
+/// The assets a `Preloader` read, by manifest name.
+#[derive(Default)]
+pub struct AssetRegistry {
+    assets : HashMap<String, Asset>,
+}
+
+impl AssetRegistry {
+    pub fn get(&self, name: &str) -> Option<&Asset> {
+        self.assets.get(name)
+    }
+    pub fn shader(&self, name: &str) -> Option<&str> {
+        match self.assets.get(name) {
+            Some(Asset::Shader(source)) => Some(source),
+            _ => None,
+        }
+    }
+    pub fn texture(&mut self, name: &str) -> Option<&mut TextureAsset> {
+        match self.assets.get_mut(name) {
+            Some(Asset::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+    pub fn mesh(&self, name: &str) -> Option<&Memory> {
+        match self.assets.get(name) {
+            Some(Asset::Mesh(memory)) => Some(memory),
+            _ => None,
+        }
+    }
+    pub fn sound(&self, name: &str) -> Option<&Memory> {
+        match self.assets.get(name) {
+            Some(Asset::Sound(memory)) => Some(memory),
+            _ => None,
+        }
+    }
+    /// Removes the asset, handing over ownership of its data.
+    pub fn take(&mut self, name: &str) -> Option<Asset> {
+        self.assets.remove(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.assets.keys().map(|k| k.as_str())
+    }
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+/// How far a preload has got, reported as each asset finishes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreloadProgress {
+    pub loaded : usize,
+    pub failed : usize,
+    pub total  : usize,
+    pub bytes  : usize,
+}
+
+impl PreloadProgress {
+    /// Returns the fraction of assets finished, failed or not.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 { 1.0 }
+        else               { (self.loaded + self.failed) as f32
+                             / self.total as f32 }
+    }
+}
+
+/// Reads every asset of a manifest through `Memory`, several at a time.
+/// An asset failing doesn't stop the others.
+pub struct Preloader {
+    manifest    : AssetManifest,
+    concurrency : usize,
+    progress    : Option<Box<dyn FnMut(PreloadProgress)>>,
+}
+
+impl Preloader {
+    pub fn new(manifest: AssetManifest) -> Self {
+        Preloader { manifest, concurrency: DEFAULT_CONCURRENCY,
+                    progress: None }
+    }
+    /// Sets how many assets are read at once; at least one.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+    pub fn progress<F>(mut self, callback: F) -> Self
+        where F: FnMut(PreloadProgress) + 'static
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+    /// Reads the assets, returning them all or, if any failed, every
+    /// failure along with the assets that did load.
+    pub async fn run(mut self) -> Result<AssetRegistry, PreloadError> {
+        let manifest = &self.manifest;
+        let mut loads = stream::iter(manifest.assets.iter())
+                        .map(|entry| async move {
+                            let url = manifest.url_of(entry);
+                            (entry, load_asset(entry, &url).await, url)
+                        })
+                        .buffer_unordered(self.concurrency);
+
+        let mut registry = AssetRegistry::default();
+        let mut failures = vec![];
+        let mut progress = PreloadProgress { total: manifest.assets.len(),
+                                             ..Default::default() };
+        while let Some((entry, result, url)) = loads.next().await {
+            match result {
+                Ok((asset, size)) => {
+                    progress.loaded += 1;
+                    progress.bytes  += size;
+                    registry.assets.insert(entry.name.clone(), asset);
+                },
+                Err(error) => {
+                    progress.failed += 1;
+                    failures.push(AssetFailure { name: entry.name.clone(),
+                                                 url, error });
+                },
+            }
+            if let Some(callback) = self.progress.as_mut() {
+                callback(progress);
+            }
+        }
+        if failures.is_empty() {
+            Ok( registry )
+        } else {
+            Err( PreloadError { registry, failures } )
+        }
+    }
+}
+
+async fn load_asset(entry: &AssetEntry, url: &str)
+    -> Result<(Asset, usize), GfxError>
+{
+    let mut options = MopenOptions::new();
+
+    if let Some(timeout) = entry.timeout {
+        options = options.timeout(timeout);
+    }
+    if !entry.cache {
+        options = options.no_cache();
+    }
+    let memory = Memory::mopen_with(url, options).await?;
+    let size   = memory.size();
+    let asset  = match entry.kind {
+        AssetKind::Shader => {
+            let source = String::from_utf8(memory.into_bytes()).map_err(|e| {
+                MemoryError::Utf8Error(url.into(),
+                                       e.utf8_error().valid_up_to())
+            })?;
+            Asset::Shader(source)
+        },
+        AssetKind::Texture => Asset::Texture(TextureAsset {
+            memory,
+            flags      : entry.flags,
+            filter     : entry.filter,
+            anisotropy : entry.anisotropy,
+        }),
+        AssetKind::Mesh  => Asset::Mesh(memory),
+        AssetKind::Sound => Asset::Sound(memory),
+    };
+    Ok( (asset, size) )
+}
+
+/// An asset a `Preloader` couldn't read.
+#[derive(Debug)]
+pub struct AssetFailure {
+    pub name  : String,
+    pub url   : String,
+    pub error : GfxError,
+}
+
+/// Every asset that failed during a preload, and the registry of those
+/// that didn't.
+pub struct PreloadError {
+    pub registry : AssetRegistry,
+    pub failures : Vec<AssetFailure>,
+}
+
+impl fmt::Debug for PreloadError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreloadError")
+         .field("loaded", &self.registry.len())
+         .field("failures", &self.failures)
+         .finish()
+    }
+}
+
+impl Error for PreloadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.failures.first().map(|f| &f.error as &(dyn Error + 'static))
+    }
+}
+
+impl fmt::Display for PreloadError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} asset(s) failed to load", self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "; {} ({}): {}", failure.name, failure.url,
+                   failure.error)?;
+        }
+        write!(f, ".")
+    }
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    ManifestFormatError(String),
+    DuplicateAsset(String),
+}
+
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        use ManifestError::*;
+        match self {
+            ManifestFormatError ( msg ) => {
+                write!(f, "{}", msg)
+            },
+            DuplicateAsset ( msg ) => {
+                write!(f, "{}", msg)
+            },
+        }
+    }
+}
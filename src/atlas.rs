@@ -0,0 +1,580 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::error::GfxError;
+use crate::image::Image;
+use crate::matrix::Mat4;
+use crate::memory::Memory;
+use crate::texture::Texture;
+
+/// Rectangle packing strategies for `AtlasBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackingAlgorithm {
+    /// Tracks every maximal free rectangle; packs tightest.
+    MaxRects,
+    /// Tracks only the top outline of the packed area; faster.
+    Skyline,
+}
+
+/// A named sprite within an atlas texture. `x`, `y`, `width` and `height`
+/// are in texels, `width` and `height` being the sprite's own size. A
+/// `rotated` sprite is stored turned 90 degrees clockwise, so it covers
+/// `height` x `width` texels of the atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub name    : String,
+    pub x       : u32,
+    pub y       : u32,
+    pub width   : u32,
+    pub height  : u32,
+    pub rotated : bool,
+    pub uv      : [f32; 4],
+}
+
+impl AtlasRegion {
+    fn new(name         : String,
+           x            : u32,
+           y            : u32,
+           width        : u32,
+           height       : u32,
+           rotated      : bool,
+           atlas_size   : (u32, u32)) -> Self
+    {
+        let (w, h)   = if rotated { (height, width) } else { (width, height) };
+        let (aw, ah) = (atlas_size.0 as f32, atlas_size.1 as f32);
+        AtlasRegion {
+            name, x, y, width, height, rotated,
+            uv : [ x      as f32 / aw,  y      as f32 / ah,
+                  (x + w) as f32 / aw, (y + h) as f32 / ah],
+        }
+    }
+    /// Returns a matrix mapping the unit texture square onto the region, for
+    /// loading into the `Gfx` texture matrix stack.
+    pub fn texture_matrix(&self) -> Mat4 {
+        let [u0, v0, u1, v1] = self.uv;
+        let (du, dv) = (u1 - u0, v1 - v0);
+        let mut mat  = Mat4::new_identity();
+
+        if self.rotated {
+            // (s, t) lands at (1 - t, s) within the region.
+            mat.m[0].x = 0.0;
+            mat.m[0].y = dv;
+            mat.m[1].x = -du;
+            mat.m[1].y = 0.0;
+            mat.m[3].x = u0 + du;
+            mat.m[3].y = v0;
+        } else {
+            mat.m[0].x = du;
+            mat.m[1].y = dv;
+            mat.m[3].x = u0;
+            mat.m[3].y = v0;
+        }
+        mat
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rect {
+    x : u32,
+    y : u32,
+    w : u32,
+    h : u32,
+}
+
+impl Rect {
+    fn intersects(&self, r: &Rect) -> bool {
+        self.x < r.x + r.w && r.x < self.x + self.w &&
+        self.y < r.y + r.h && r.y < self.y + self.h
+    }
+    fn contains(&self, r: &Rect) -> bool {
+        r.x >= self.x && r.x + r.w <= self.x + self.w &&
+        r.y >= self.y && r.y + r.h <= self.y + self.h
+    }
+}
+
+struct MaxRects {
+    free : Vec<Rect>,
+}
+
+impl MaxRects {
+    fn new(width: u32, height: u32) -> Self {
+        MaxRects { free: vec![Rect { x: 0, y: 0, w: width, h: height }] }
+    }
+    /// Places a `w` x `h` rectangle using the best short side fit rule.
+    fn insert(&mut self, w: u32, h: u32) -> Option<Rect> {
+        let best = *self.free.iter()
+                             .filter(|f| f.w >= w && f.h >= h)
+                             .min_by_key(|f| {
+                                 let (dw, dh) = (f.w - w, f.h - h);
+                                 (dw.min(dh), dw.max(dh))
+                             })?;
+        let used = Rect { x: best.x, y: best.y, w, h };
+        self.split(&used);
+        Some(used)
+    }
+    fn split(&mut self, used: &Rect) {
+        let mut free = vec![];
+
+        for f in &self.free {
+            if !f.intersects(used) {
+                free.push(*f);
+                continue;
+            }
+            if used.x > f.x {
+                free.push(Rect { w: used.x - f.x, ..*f });
+            }
+            if used.x + used.w < f.x + f.w {
+                free.push(Rect { x: used.x + used.w,
+                                 w: f.x + f.w - (used.x + used.w), ..*f });
+            }
+            if used.y > f.y {
+                free.push(Rect { h: used.y - f.y, ..*f });
+            }
+            if used.y + used.h < f.y + f.h {
+                free.push(Rect { y: used.y + used.h,
+                                 h: f.y + f.h - (used.y + used.h), ..*f });
+            }
+        }
+        // Drop rectangles that another one already covers.
+        self.free = free.iter()
+                        .enumerate()
+                        .filter(|(i, a)| {
+                            !free.iter().enumerate().any(|(j, b)| {
+                                *i != j && b.contains(a) && (b != *a || j < *i)
+                            })
+                        })
+                        .map(|(_, r)| *r)
+                        .collect();
+    }
+}
+
+struct SkylineNode {
+    x : u32,
+    y : u32,
+    w : u32,
+}
+
+struct Skyline {
+    width  : u32,
+    height : u32,
+    nodes  : Vec<SkylineNode>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Skyline { width, height, nodes: vec![SkylineNode { x: 0, y: 0,
+                                                           w: width }] }
+    }
+    /// Returns the lowest y a `w` x `h` rectangle fits at when its left edge
+    /// is at node `i`.
+    fn fit(&self, i: usize, w: u32, h: u32) -> Option<u32> {
+        let x = self.nodes[i].x;
+
+        if x + w > self.width {
+            return None;
+        }
+        let mut y         = 0;
+        let mut remaining = w as i64;
+
+        for node in &self.nodes[i..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(node.y);
+            if y + h > self.height {
+                return None;
+            }
+            remaining -= node.w as i64;
+        }
+        Some(y)
+    }
+    /// Places a `w` x `h` rectangle using the bottom left rule.
+    fn insert(&mut self, w: u32, h: u32) -> Option<Rect> {
+        let (i, y) = (0..self.nodes.len())
+                     .filter_map(|i| self.fit(i, w, h).map(|y| (i, y)))
+                     .min_by_key(|&(i, y)| (y + h, self.nodes[i].x))?;
+        let x = self.nodes[i].x;
+
+        self.nodes.insert(i, SkylineNode { x, y: y + h, w });
+
+        // Trim the nodes the new one now shadows.
+        let end = x + w;
+        while i + 1 < self.nodes.len() && self.nodes[i + 1].x < end {
+            let next = &mut self.nodes[i + 1];
+            let cut  = end - next.x;
+            if next.w <= cut {
+                self.nodes.remove(i + 1);
+            } else {
+                next.x += cut;
+                next.w -= cut;
+                break;
+            }
+        }
+        // Merge neighbours at the same height.
+        let mut k = 0;
+        while k + 1 < self.nodes.len() {
+            if self.nodes[k].y == self.nodes[k + 1].y {
+                self.nodes[k].w += self.nodes[k + 1].w;
+                self.nodes.remove(k + 1);
+            } else {
+                k += 1;
+            }
+        }
+        Some(Rect { x, y, w, h })
+    }
+}
+
+/// Returns the largest power of two no greater than `n`, or 0 for 0.
+fn power_of_two_floor(n: u32) -> u32 {
+    n.checked_ilog2().map_or(0, |log| 1 << log)
+}
+
+/// Packs decoded images into a single power of two texture.
+pub struct AtlasBuilder {
+    max_width   : u32,
+    max_height  : u32,
+    padding     : u32,
+    extrude     : u32,
+    algorithm   : PackingAlgorithm,
+    image_array : Vec<(String, Image)>,
+}
+
+impl AtlasBuilder {
+    pub fn new(max_width: u32, max_height: u32) -> Self {
+        AtlasBuilder {
+            max_width,
+            max_height,
+            padding     : 0,
+            extrude     : 0,
+            algorithm   : PackingAlgorithm::MaxRects,
+            image_array : vec![],
+        }
+    }
+    /// Sets the number of empty texels left between sprites.
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+    /// Sets how many times each sprite's edge texels are repeated around it,
+    /// so filtering at the edge doesn't pick up the neighbouring sprite.
+    pub fn extrude(mut self, extrude: u32) -> Self {
+        self.extrude = extrude;
+        self
+    }
+    pub fn algorithm(mut self, algorithm: PackingAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+    /// Adds `image` under `name`. Empty images can't be packed.
+    pub fn add(&mut self, name: &str, image: Image) -> Result<(), AtlasError> {
+        if image.width == 0 || image.height == 0 {
+            let msg = format!("Atlas image ({}) is empty.", name);
+            Err( AtlasError::EmptyImage(msg) )?
+        }
+        self.image_array.push((name.into(), image));
+        Ok(())
+    }
+    /// Packs the images into the smallest power of two atlas that holds
+    /// them, returning the atlas image and each image's region. A maximum
+    /// size that isn't a power of two is rounded down to one.
+    pub fn pack(&self) -> Result<(Image, Vec<AtlasRegion>), AtlasError> {
+        use AtlasError::*;
+
+        let channels = self.image_array.first()
+                                       .map(|(_, i)| i.channels)
+                                       .unwrap_or(4);
+        if let Some((name, _)) = self.image_array
+                                     .iter()
+                                     .find(|(_, i)| i.channels != channels)
+        {
+            Err( ImageMismatch(format!("Atlas image ({}) has a different \
+                                        channel count than the first \
+                                        image.", name)) )?
+        }
+        let border = self.extrude * 2 + self.padding;
+        let cells  = self.image_array
+                         .iter()
+                         .map(|(_, i)| (i.width + border, i.height + border))
+                         .collect::<Vec<_>>();
+
+        // Place large images first; they're the hardest to fit.
+        let mut order = (0..cells.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            let (w, h) = cells[i];
+            std::cmp::Reverse((w.max(h), w as u64 * h as u64))
+        });
+        let does_not_fit = || {
+            DoesNotFit(format!("Atlas images don't fit in {}x{}.",
+                               self.max_width, self.max_height))
+        };
+        let max_width  = power_of_two_floor(self.max_width);
+        let max_height = power_of_two_floor(self.max_height);
+        if max_width == 0 || max_height == 0 {
+            Err( does_not_fit() )?
+        }
+        let area: u64 = cells.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+        let side      = ((area as f64).sqrt() as u32)
+                            .max(1)
+                            .checked_next_power_of_two()
+                            .unwrap_or(max_width);
+        let mut size  = (side.min(max_width), side.min(max_height));
+
+        let rects = loop {
+            if let Some(rects) = self.place(size, &cells, &order) {
+                break rects;
+            }
+            size = if size.0 <= size.1 && size.0 < max_width {
+                (size.0 * 2, size.1)
+            } else if size.1 < max_height {
+                (size.0, size.1 * 2)
+            } else if size.0 < max_width {
+                (size.0 * 2, size.1)
+            } else {
+                Err( does_not_fit() )?
+            };
+        };
+        let (width, height) = size;
+        let mut atlas   = Image { width, height, channels,
+                                  pixels: vec![0; (width * height) as usize
+                                                  * channels] };
+        let mut regions = vec![];
+
+        for ((name, image), rect) in self.image_array.iter().zip(rects) {
+            self.blit(&mut atlas, image, rect.x, rect.y);
+            regions.push(AtlasRegion::new(name.clone(),
+                                          rect.x + self.extrude,
+                                          rect.y + self.extrude,
+                                          image.width,
+                                          image.height,
+                                          false,
+                                          size));
+        }
+        Ok( (atlas, regions) )
+    }
+    /// Packs the images and uploads the result as a texture.
+    pub fn build(&self,
+                 name                 : &str,
+                 flags                : u32,
+                 filter               : u8,
+                 anisotropic_filter   : f32,
+                 context              : Arc<WebGlRenderingContext>
+                ) -> Result<TextureAtlas, GfxError>
+    {
+        let (image, regions) = self.pack()?;
+        let texture = Texture::from_image(name, &image, flags, filter,
+                                          anisotropic_filter, context)?;
+        Ok( TextureAtlas { texture, regions } )
+    }
+    /// Returns each cell's position, in image order, or `None` if they don't
+    /// all fit in `size`.
+    fn place(&self,
+             size  : (u32, u32),
+             cells : &[(u32, u32)],
+             order : &[usize]) -> Option<Vec<Rect>>
+    {
+        let mut rects = vec![Rect { x: 0, y: 0, w: 0, h: 0 }; cells.len()];
+
+        match self.algorithm {
+            PackingAlgorithm::MaxRects => {
+                let mut packer = MaxRects::new(size.0, size.1);
+                for &i in order {
+                    rects[i] = packer.insert(cells[i].0, cells[i].1)?;
+                }
+            },
+            PackingAlgorithm::Skyline => {
+                let mut packer = Skyline::new(size.0, size.1);
+                for &i in order {
+                    rects[i] = packer.insert(cells[i].0, cells[i].1)?;
+                }
+            },
+        }
+        Some(rects)
+    }
+    /// Copies `image` into `atlas` with its top left corner (before
+    /// extrusion) at (`x`, `y`), repeating its edges `extrude` times.
+    fn blit(&self, atlas: &mut Image, image: &Image, x: u32, y: u32) {
+        let c = image.channels;
+        let e = self.extrude as i64;
+
+        for dy in -e..image.height as i64 + e {
+            let sy = dy.clamp(0, image.height as i64 - 1) as usize;
+            let ty = (y as i64 + e + dy) as usize;
+
+            for dx in -e..image.width as i64 + e {
+                let sx  = dx.clamp(0, image.width as i64 - 1) as usize;
+                let tx  = (x as i64 + e + dx) as usize;
+                let src = (sy * image.width as usize + sx) * c;
+                let dst = (ty * atlas.width as usize + tx) * c;
+
+                atlas.pixels[dst..dst + c]
+                     .copy_from_slice(&image.pixels[src..src + c]);
+            }
+        }
+    }
+}
+
+/// A texture holding several named sprites.
+pub struct TextureAtlas {
+    texture : Texture,
+    regions : Vec<AtlasRegion>,
+}
+
+impl TextureAtlas {
+    /// Loads a sprite sheet description and the texture it names, which is
+    /// looked up relative to `url`.
+    pub async fn load(name                 : &str,
+                      url                  : &str,
+                      flags                : u32,
+                      filter               : u8,
+                      anisotropic_filter   : f32,
+                      context              : Arc<WebGlRenderingContext>
+                     ) -> Result<Self, GfxError>
+    {
+        let memory    = Memory::mopen(url).await?;
+        let sheet     = SpriteSheet::from_json(memory.as_str())?;
+        let image_url = match url.rfind('/') {
+            Some(i) if !sheet.image.contains("://") &&
+                       !sheet.image.starts_with('/') => {
+                format!("{}{}", &url[..=i], sheet.image)
+            },
+            _ => sheet.image.clone(),
+        };
+        let texture = Texture::new(name, &image_url, flags, filter,
+                                   anisotropic_filter, context).await?;
+        Ok( TextureAtlas { texture, regions: sheet.regions } )
+    }
+    #[inline]
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    #[inline]
+    pub fn regions(&self) -> &[AtlasRegion] {
+        &self.regions
+    }
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+struct SheetRect {
+    x : u32,
+    y : u32,
+    w : u32,
+    h : u32,
+}
+
+#[derive(Deserialize)]
+struct SheetSize {
+    w : u32,
+    h : u32,
+}
+
+#[derive(Deserialize)]
+struct SheetFrame {
+    #[serde(default)]
+    filename : String,
+    frame    : SheetRect,
+    #[serde(default)]
+    rotated  : bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SheetFrames {
+    Hash(BTreeMap<String, SheetFrame>),
+    Array(Vec<SheetFrame>),
+}
+
+#[derive(Deserialize)]
+struct SheetMeta {
+    image : String,
+    size  : SheetSize,
+}
+
+#[derive(Deserialize)]
+struct SheetJson {
+    frames : SheetFrames,
+    meta   : SheetMeta,
+}
+
+/// Sprite sheet metadata in TexturePacker's JSON (hash) or JSON (array)
+/// format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteSheet {
+    pub image   : String,
+    pub width   : u32,
+    pub height  : u32,
+    pub regions : Vec<AtlasRegion>,
+}
+
+impl SpriteSheet {
+    pub fn from_json(json: &str) -> Result<Self, AtlasError> {
+        let sheet: SheetJson = serde_json::from_str(json).map_err(|e| {
+            AtlasError::SheetFormatError(format!("Sprite sheet JSON is \
+                                                  invalid; {}.", e))
+        })?;
+        let (width, height) = (sheet.meta.size.w, sheet.meta.size.h);
+        let frames = match sheet.frames {
+            SheetFrames::Hash(map) => {
+                map.into_iter()
+                   .map(|(name, f)| SheetFrame { filename: name, ..f })
+                   .collect()
+            },
+            SheetFrames::Array(array) => array,
+        };
+        let regions = frames.into_iter()
+                            .map(|f| AtlasRegion::new(f.filename,
+                                                      f.frame.x,
+                                                      f.frame.y,
+                                                      f.frame.w,
+                                                      f.frame.h,
+                                                      f.rotated,
+                                                      (width, height)))
+                            .collect();
+        Ok( SpriteSheet { image: sheet.meta.image, width, height, regions } )
+    }
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    ImageMismatch(String),
+    EmptyImage(String),
+    DoesNotFit(String),
+    SheetFormatError(String),
+}
+
+impl Error for AtlasError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        use AtlasError::*;
+        match self {
+            ImageMismatch ( msg ) => {
+                write!(f, "{}", msg)
+            },
+            EmptyImage ( msg ) => {
+                write!(f, "{}", msg)
+            },
+            DoesNotFit ( msg ) => {
+                write!(f, "{}", msg)
+            },
+            SheetFormatError ( msg ) => {
+                write!(f, "{}", msg)
+            },
+        }
+    }
+}
@@ -0,0 +1,787 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use wasm_bindgen::JsCast;
+
+use js_sys::Object;
+use js_sys::WeakMap;
+
+use web_sys::AngleInstancedArrays;
+use web_sys::OesVertexArrayObject;
+use web_sys::WebGl2RenderingContext;
+use web_sys::WebGlBuffer;
+use web_sys::WebGlFramebuffer;
+use web_sys::WebGlProgram;
+use web_sys::WebGlRenderbuffer;
+use web_sys::WebGlRenderingContext;
+use web_sys::WebGlShader;
+use web_sys::WebGlTexture;
+use web_sys::WebGlUniformLocation;
+use web_sys::WebGlVertexArrayObject;
+use web_sys::WebglDrawBuffers;
+
+/// The WebGL version a backend implements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GlVersion {
+    WebGl1,
+    WebGl2,
+}
+
+/// The name, type and array size of an active attribute or uniform.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveInfo {
+    pub name     : String,
+    pub var_type : u32,
+    pub size     : i32,
+}
+
+/// The GL calls the crate makes, so `Shader`, `Program`, `Texture` and `Gfx`
+/// can run on something other than a browser's WebGL context, such as the
+/// `RecordingBackend` used by native tests. Enums are the WebGL constants;
+/// methods follow the `WebGlRenderingContext` calls they stand for.
+pub trait GlBackend {
+    type Shader          : Clone + PartialEq;
+    type Program         : Clone + PartialEq;
+    type Texture         : Clone + PartialEq;
+    type Buffer          : Clone + PartialEq;
+    type UniformLocation : Clone + PartialEq;
+    type VertexArray     : Clone + PartialEq;
+    type Framebuffer     : Clone + PartialEq;
+    type Renderbuffer    : Clone + PartialEq;
+
+    // State.
+    fn enable(&self, cap: u32);
+    fn disable(&self, cap: u32);
+    fn hint(&self, target: u32, mode: u32);
+    fn depth_mask(&self, flag: bool);
+    fn depth_func(&self, func: u32);
+    fn depth_range(&self, near: f32, far: f32);
+    fn cull_face(&self, mode: u32);
+    fn front_face(&self, mode: u32);
+    fn stencil_mask(&self, mask: u32);
+    fn stencil_func(&self, func: u32, reference: i32, mask: u32);
+    fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32);
+    fn blend_func_separate(&self,
+                           src_rgb   : u32,
+                           dst_rgb   : u32,
+                           src_alpha : u32,
+                           dst_alpha : u32);
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32);
+    fn blend_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool);
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+    fn polygon_offset(&self, factor: f32, units: f32);
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn clear_depth(&self, depth: f32);
+    fn clear_stencil(&self, stencil: i32);
+    fn clear(&self, mask: u32);
+    fn get_error(&self) -> u32;
+
+    // Queries.
+    /// Returns a string parameter such as `VENDOR` or `VERSION`.
+    fn get_string(&self, pname: u32) -> Option<String>;
+    /// Returns a numeric parameter such as `MAX_TEXTURE_SIZE`.
+    fn get_float(&self, pname: u32) -> Option<f32>;
+    fn supported_extensions(&self) -> Vec<String>;
+    /// Enables extension `name`, returning whether it's available.
+    fn enable_extension(&self, name: &str) -> bool;
+
+    // Shaders.
+    fn create_shader(&self, shader_type: u32) -> Option<Self::Shader>;
+    fn delete_shader(&self, shader: &Self::Shader);
+    fn shader_source(&self, shader: &Self::Shader, source: &str);
+    fn compile_shader(&self, shader: &Self::Shader);
+    fn shader_info_log(&self, shader: &Self::Shader) -> Option<String>;
+    /// Returns a shader parameter, with booleans as 0 or 1.
+    fn shader_parameter(&self, shader: &Self::Shader, pname: u32) -> i32;
+
+    // Programs.
+    fn create_program(&self) -> Option<Self::Program>;
+    fn delete_program(&self, program: &Self::Program);
+    fn attach_shader(&self, program: &Self::Program, shader: &Self::Shader);
+    fn link_program(&self, program: &Self::Program);
+    fn program_info_log(&self, program: &Self::Program) -> Option<String>;
+    /// Returns a program parameter, with booleans as 0 or 1.
+    fn program_parameter(&self, program: &Self::Program, pname: u32) -> i32;
+    fn active_attrib(&self, program: &Self::Program, index: u32)
+        -> Option<ActiveInfo>;
+    fn active_uniform(&self, program: &Self::Program, index: u32)
+        -> Option<ActiveInfo>;
+    fn attrib_location(&self, program: &Self::Program, name: &str) -> i32;
+    fn uniform_location(&self, program: &Self::Program, name: &str)
+        -> Option<Self::UniformLocation>;
+    fn use_program(&self, program: Option<&Self::Program>);
+    fn uniform1i(&self, location: Option<&Self::UniformLocation>, x: i32);
+    fn uniform1f(&self, location: Option<&Self::UniformLocation>, x: f32);
+    fn uniform2f(&self,
+                 location : Option<&Self::UniformLocation>,
+                 x        : f32,
+                 y        : f32);
+    fn uniform3f(&self,
+                 location : Option<&Self::UniformLocation>,
+                 x        : f32,
+                 y        : f32,
+                 z        : f32);
+    fn uniform4f(&self,
+                 location : Option<&Self::UniformLocation>,
+                 x        : f32,
+                 y        : f32,
+                 z        : f32,
+                 w        : f32);
+    fn uniform_matrix3fv(&self,
+                         location  : Option<&Self::UniformLocation>,
+                         transpose : bool,
+                         data      : &[f32]);
+    fn uniform_matrix4fv(&self,
+                         location  : Option<&Self::UniformLocation>,
+                         transpose : bool,
+                         data      : &[f32]);
+
+    // Textures.
+    fn create_texture(&self) -> Option<Self::Texture>;
+    fn delete_texture(&self, texture: &Self::Texture);
+    fn active_texture(&self, unit: u32);
+    fn bind_texture(&self, target: u32, texture: Option<&Self::Texture>);
+    fn pixel_storei(&self, pname: u32, param: i32);
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32);
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32);
+    #[allow(clippy::too_many_arguments)]
+    fn tex_image_2d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>;
+    #[allow(clippy::too_many_arguments)]
+    fn compressed_tex_image_2d(&self,
+                               target          : u32,
+                               level           : i32,
+                               internal_format : u32,
+                               width           : i32,
+                               height          : i32,
+                               border          : i32,
+                               data            : &[u8]);
+    fn generate_mipmap(&self, target: u32);
+
+    // Buffers and drawing.
+    fn create_buffer(&self) -> Option<Self::Buffer>;
+    fn delete_buffer(&self, buffer: &Self::Buffer);
+    fn bind_buffer(&self, target: u32, buffer: Option<&Self::Buffer>);
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32);
+    fn enable_vertex_attrib_array(&self, index: u32);
+    fn disable_vertex_attrib_array(&self, index: u32);
+    fn vertex_attrib_pointer(&self,
+                             index      : u32,
+                             size       : i32,
+                             data_type  : u32,
+                             normalized : bool,
+                             stride     : i32,
+                             offset     : i32);
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+
+    // Framebuffers.
+    fn create_framebuffer(&self) -> Option<Self::Framebuffer>;
+    fn delete_framebuffer(&self, framebuffer: &Self::Framebuffer);
+    fn bind_framebuffer(&self,
+                        target      : u32,
+                        framebuffer : Option<&Self::Framebuffer>);
+    fn framebuffer_texture_2d(&self,
+                              target     : u32,
+                              attachment : u32,
+                              tex_target : u32,
+                              texture    : Option<&Self::Texture>,
+                              level      : i32);
+    fn framebuffer_renderbuffer(&self,
+                                target       : u32,
+                                attachment   : u32,
+                                rb_target    : u32,
+                                renderbuffer : Option<&Self::Renderbuffer>);
+    fn check_framebuffer_status(&self, target: u32) -> u32;
+    fn create_renderbuffer(&self) -> Option<Self::Renderbuffer>;
+    fn delete_renderbuffer(&self, renderbuffer: &Self::Renderbuffer);
+    fn bind_renderbuffer(&self,
+                         target       : u32,
+                         renderbuffer : Option<&Self::Renderbuffer>);
+    fn renderbuffer_storage(&self,
+                            target          : u32,
+                            internal_format : u32,
+                            width           : i32,
+                            height          : i32);
+
+    /// Returns the WebGL version the backend implements.
+    fn version(&self) -> GlVersion {
+        GlVersion::WebGl1
+    }
+
+    // WebGL2, or WebGL1 extensions. Check `GlCapabilities` before calling
+    // these; backends without the feature panic.
+    fn create_vertex_array(&self) -> Option<Self::VertexArray> {
+        unsupported("create_vertex_array")
+    }
+    fn delete_vertex_array(&self, vertex_array: &Self::VertexArray) {
+        unsupported("delete_vertex_array");
+    }
+    fn bind_vertex_array(&self, vertex_array: Option<&Self::VertexArray>) {
+        unsupported("bind_vertex_array");
+    }
+    fn draw_arrays_instanced(&self,
+                             mode      : u32,
+                             first     : i32,
+                             count     : i32,
+                             instances : i32)
+    {
+        unsupported("draw_arrays_instanced");
+    }
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        unsupported("vertex_attrib_divisor");
+    }
+    /// Selects the color attachments fragment shader outputs write to.
+    fn draw_buffers(&self, buffers: &[u32]) {
+        unsupported("draw_buffers");
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn tex_image_3d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    depth           : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        unsupported("tex_image_3d")
+    }
+    fn uniform_block_index(&self, program: &Self::Program, name: &str) -> u32 {
+        unsupported("uniform_block_index")
+    }
+    fn uniform_block_binding(&self,
+                             program : &Self::Program,
+                             index   : u32,
+                             binding : u32)
+    {
+        unsupported("uniform_block_binding");
+    }
+    fn bind_buffer_base(&self,
+                        target : u32,
+                        index  : u32,
+                        buffer : Option<&Self::Buffer>)
+    {
+        unsupported("bind_buffer_base");
+    }
+}
+
+fn unsupported(call: &str) -> ! {
+    panic!("{} isn't supported by this context; check GlCapabilities first.",
+           call)
+}
+
+/// Reads a parameter that WebGL reports as a boolean or a number.
+fn jsval_to_i32(value: &JsValue) -> i32 {
+    match value.as_bool() {
+        Some(flag) => flag as i32,
+        None       => value.as_f64().unwrap_or(0.0) as i32,
+    }
+}
+
+/// Implements the calls WebGL1 and WebGL2 contexts share, which have the
+/// same names in both, followed by `$extra`.
+macro_rules! impl_gl_backend {
+    ($context:ty, { $($extra:tt)* }) => {
+        impl GlBackend for $context {
+
+            type Shader          = WebGlShader;
+            type Program         = WebGlProgram;
+            type Texture         = WebGlTexture;
+            type Buffer          = WebGlBuffer;
+            type UniformLocation = WebGlUniformLocation;
+            type VertexArray     = WebGlVertexArrayObject;
+            type Framebuffer     = WebGlFramebuffer;
+            type Renderbuffer    = WebGlRenderbuffer;
+
+            fn enable(&self, cap: u32) {
+                self.enable(cap);
+            }
+            fn disable(&self, cap: u32) {
+                self.disable(cap);
+            }
+            fn hint(&self, target: u32, mode: u32) {
+                self.hint(target, mode);
+            }
+            fn depth_mask(&self, flag: bool) {
+                self.depth_mask(flag);
+            }
+            fn depth_func(&self, func: u32) {
+                self.depth_func(func);
+            }
+            fn depth_range(&self, near: f32, far: f32) {
+                self.depth_range(near, far);
+            }
+            fn cull_face(&self, mode: u32) {
+                self.cull_face(mode);
+            }
+            fn front_face(&self, mode: u32) {
+                self.front_face(mode);
+            }
+            fn stencil_mask(&self, mask: u32) {
+                self.stencil_mask(mask);
+            }
+            fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+                self.stencil_func(func, reference, mask);
+            }
+            fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+                self.stencil_op(fail, depth_fail, pass);
+            }
+            fn blend_func_separate(&self,
+                                   src_rgb   : u32,
+                                   dst_rgb   : u32,
+                                   src_alpha : u32,
+                                   dst_alpha : u32)
+            {
+                self.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+            }
+            fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+                self.blend_equation_separate(mode_rgb, mode_alpha);
+            }
+            fn blend_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+                self.blend_color(red, green, blue, alpha);
+            }
+            fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) {
+                self.color_mask(red, green, blue, alpha);
+            }
+            fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+                self.viewport(x, y, width, height);
+            }
+            fn polygon_offset(&self, factor: f32, units: f32) {
+                self.polygon_offset(factor, units);
+            }
+            fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+                self.clear_color(red, green, blue, alpha);
+            }
+            fn clear_depth(&self, depth: f32) {
+                self.clear_depth(depth);
+            }
+            fn clear_stencil(&self, stencil: i32) {
+                self.clear_stencil(stencil);
+            }
+            fn clear(&self, mask: u32) {
+                self.clear(mask);
+            }
+            fn get_error(&self) -> u32 {
+                self.get_error()
+            }
+            fn get_string(&self, pname: u32) -> Option<String> {
+                self.get_parameter(pname).ok().and_then(|v| v.as_string())
+            }
+            fn get_float(&self, pname: u32) -> Option<f32> {
+                self.get_parameter(pname)
+                    .ok()
+                    .and_then(|v| v.as_f64())
+                    .map(|v| v as f32)
+            }
+            fn supported_extensions(&self) -> Vec<String> {
+                self.get_supported_extensions()
+                    .map(|names| names.iter().filter_map(|n| n.as_string()).collect())
+                    .unwrap_or_default()
+            }
+            fn enable_extension(&self, name: &str) -> bool {
+                matches!(self.get_extension(name), Ok(Some(_)))
+            }
+            fn create_shader(&self, shader_type: u32) -> Option<WebGlShader> {
+                self.create_shader(shader_type)
+            }
+            fn delete_shader(&self, shader: &WebGlShader) {
+                self.delete_shader(Some(shader));
+            }
+            fn shader_source(&self, shader: &WebGlShader, source: &str) {
+                self.shader_source(shader, source);
+            }
+            fn compile_shader(&self, shader: &WebGlShader) {
+                self.compile_shader(shader);
+            }
+            fn shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+                self.get_shader_info_log(shader)
+            }
+            fn shader_parameter(&self, shader: &WebGlShader, pname: u32) -> i32 {
+                jsval_to_i32(&self.get_shader_parameter(shader, pname))
+            }
+            fn create_program(&self) -> Option<WebGlProgram> {
+                self.create_program()
+            }
+            fn delete_program(&self, program: &WebGlProgram) {
+                self.delete_program(Some(program));
+            }
+            fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+                self.attach_shader(program, shader);
+            }
+            fn link_program(&self, program: &WebGlProgram) {
+                self.link_program(program);
+            }
+            fn program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+                self.get_program_info_log(program)
+            }
+            fn program_parameter(&self, program: &WebGlProgram, pname: u32) -> i32 {
+                jsval_to_i32(&self.get_program_parameter(program, pname))
+            }
+            fn active_attrib(&self, program: &WebGlProgram, index: u32)
+                -> Option<ActiveInfo>
+            {
+                self.get_active_attrib(program, index).map(|info| {
+                    ActiveInfo { name     : info.name(),
+                                 var_type : info.type_(),
+                                 size     : info.size() }
+                })
+            }
+            fn active_uniform(&self, program: &WebGlProgram, index: u32)
+                -> Option<ActiveInfo>
+            {
+                self.get_active_uniform(program, index).map(|info| {
+                    ActiveInfo { name     : info.name(),
+                                 var_type : info.type_(),
+                                 size     : info.size() }
+                })
+            }
+            fn attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+                self.get_attrib_location(program, name)
+            }
+            fn uniform_location(&self, program: &WebGlProgram, name: &str)
+                -> Option<WebGlUniformLocation>
+            {
+                self.get_uniform_location(program, name)
+            }
+            fn use_program(&self, program: Option<&WebGlProgram>) {
+                self.use_program(program);
+            }
+            fn uniform1i(&self, location: Option<&WebGlUniformLocation>, x: i32) {
+                self.uniform1i(location, x);
+            }
+            fn uniform1f(&self, location: Option<&WebGlUniformLocation>, x: f32) {
+                self.uniform1f(location, x);
+            }
+            fn uniform2f(&self,
+                         location : Option<&WebGlUniformLocation>,
+                         x        : f32,
+                         y        : f32)
+            {
+                self.uniform2f(location, x, y);
+            }
+            fn uniform3f(&self,
+                         location : Option<&WebGlUniformLocation>,
+                         x        : f32,
+                         y        : f32,
+                         z        : f32)
+            {
+                self.uniform3f(location, x, y, z);
+            }
+            fn uniform4f(&self,
+                         location : Option<&WebGlUniformLocation>,
+                         x        : f32,
+                         y        : f32,
+                         z        : f32,
+                         w        : f32)
+            {
+                self.uniform4f(location, x, y, z, w);
+            }
+            fn uniform_matrix3fv(&self,
+                                 location  : Option<&WebGlUniformLocation>,
+                                 transpose : bool,
+                                 data      : &[f32])
+            {
+                self.uniform_matrix3fv_with_f32_array(location, transpose, data);
+            }
+            fn uniform_matrix4fv(&self,
+                                 location  : Option<&WebGlUniformLocation>,
+                                 transpose : bool,
+                                 data      : &[f32])
+            {
+                self.uniform_matrix4fv_with_f32_array(location, transpose, data);
+            }
+            fn create_texture(&self) -> Option<WebGlTexture> {
+                self.create_texture()
+            }
+            fn delete_texture(&self, texture: &WebGlTexture) {
+                self.delete_texture(Some(texture));
+            }
+            fn active_texture(&self, unit: u32) {
+                self.active_texture(unit);
+            }
+            fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+                self.bind_texture(target, texture);
+            }
+            fn pixel_storei(&self, pname: u32, param: i32) {
+                self.pixel_storei(pname, param);
+            }
+            fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+                self.tex_parameteri(target, pname, param);
+            }
+            fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+                self.tex_parameterf(target, pname, param);
+            }
+            fn tex_image_2d(&self,
+                            target          : u32,
+                            level           : i32,
+                            internal_format : i32,
+                            width           : i32,
+                            height          : i32,
+                            border          : i32,
+                            format          : u32,
+                            texel_type      : u32,
+                            data            : Option<&[u8]>) -> Result<(), JsValue>
+            {
+                self.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    target, level, internal_format, width, height, border, format,
+                    texel_type, data)
+            }
+            fn compressed_tex_image_2d(&self,
+                                       target          : u32,
+                                       level           : i32,
+                                       internal_format : u32,
+                                       width           : i32,
+                                       height          : i32,
+                                       border          : i32,
+                                       data            : &[u8])
+            {
+                self.compressed_tex_image_2d_with_u8_array(target, level,
+                                                           internal_format, width,
+                                                           height, border, data);
+            }
+            fn generate_mipmap(&self, target: u32) {
+                self.generate_mipmap(target);
+            }
+            fn create_buffer(&self) -> Option<WebGlBuffer> {
+                self.create_buffer()
+            }
+            fn delete_buffer(&self, buffer: &WebGlBuffer) {
+                self.delete_buffer(Some(buffer));
+            }
+            fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+                self.bind_buffer(target, buffer);
+            }
+            fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+                self.buffer_data_with_u8_array(target, data, usage);
+            }
+            fn enable_vertex_attrib_array(&self, index: u32) {
+                self.enable_vertex_attrib_array(index);
+            }
+            fn disable_vertex_attrib_array(&self, index: u32) {
+                self.disable_vertex_attrib_array(index);
+            }
+            fn vertex_attrib_pointer(&self,
+                                     index      : u32,
+                                     size       : i32,
+                                     data_type  : u32,
+                                     normalized : bool,
+                                     stride     : i32,
+                                     offset     : i32)
+            {
+                self.vertex_attrib_pointer_with_i32(index, size, data_type,
+                                                    normalized, stride, offset);
+            }
+            fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+                self.draw_arrays(mode, first, count);
+            }
+            fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+                self.create_framebuffer()
+            }
+            fn delete_framebuffer(&self, framebuffer: &WebGlFramebuffer) {
+                self.delete_framebuffer(Some(framebuffer));
+            }
+            fn bind_framebuffer(&self,
+                                target      : u32,
+                                framebuffer : Option<&WebGlFramebuffer>)
+            {
+                self.bind_framebuffer(target, framebuffer);
+            }
+            fn framebuffer_texture_2d(&self,
+                                      target     : u32,
+                                      attachment : u32,
+                                      tex_target : u32,
+                                      texture    : Option<&WebGlTexture>,
+                                      level      : i32)
+            {
+                self.framebuffer_texture_2d(target, attachment, tex_target, texture,
+                                            level);
+            }
+            fn framebuffer_renderbuffer(&self,
+                                        target       : u32,
+                                        attachment   : u32,
+                                        rb_target    : u32,
+                                        renderbuffer : Option<&WebGlRenderbuffer>)
+            {
+                self.framebuffer_renderbuffer(target, attachment, rb_target,
+                                              renderbuffer);
+            }
+            fn check_framebuffer_status(&self, target: u32) -> u32 {
+                self.check_framebuffer_status(target)
+            }
+            fn create_renderbuffer(&self) -> Option<WebGlRenderbuffer> {
+                self.create_renderbuffer()
+            }
+            fn delete_renderbuffer(&self, renderbuffer: &WebGlRenderbuffer) {
+                self.delete_renderbuffer(Some(renderbuffer));
+            }
+            fn bind_renderbuffer(&self,
+                                 target       : u32,
+                                 renderbuffer : Option<&WebGlRenderbuffer>)
+            {
+                self.bind_renderbuffer(target, renderbuffer);
+            }
+            fn renderbuffer_storage(&self,
+                                    target          : u32,
+                                    internal_format : u32,
+                                    width           : i32,
+                                    height          : i32)
+            {
+                self.renderbuffer_storage(target, internal_format, width, height);
+            }
+
+            $($extra)*
+        }
+    };
+}
+
+impl_gl_backend!(WebGlRenderingContext, {
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        self.extension::<OesVertexArrayObject>("OES_vertex_array_object")
+            .create_vertex_array_oes()
+    }
+    fn delete_vertex_array(&self, vertex_array: &WebGlVertexArrayObject) {
+        self.extension::<OesVertexArrayObject>("OES_vertex_array_object")
+            .delete_vertex_array_oes(Some(vertex_array));
+    }
+    fn bind_vertex_array(&self, vertex_array: Option<&WebGlVertexArrayObject>)
+    {
+        self.extension::<OesVertexArrayObject>("OES_vertex_array_object")
+            .bind_vertex_array_oes(vertex_array);
+    }
+    fn draw_arrays_instanced(&self,
+                             mode      : u32,
+                             first     : i32,
+                             count     : i32,
+                             instances : i32)
+    {
+        self.extension::<AngleInstancedArrays>("ANGLE_instanced_arrays")
+            .draw_arrays_instanced_angle(mode, first, count, instances);
+    }
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.extension::<AngleInstancedArrays>("ANGLE_instanced_arrays")
+            .vertex_attrib_divisor_angle(index, divisor);
+    }
+    fn draw_buffers(&self, buffers: &[u32]) {
+        self.extension::<WebglDrawBuffers>("WEBGL_draw_buffers")
+            .draw_buffers_webgl(&buffer_list(buffers));
+    }
+});
+
+impl_gl_backend!(WebGl2RenderingContext, {
+    fn version(&self) -> GlVersion {
+        GlVersion::WebGl2
+    }
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        self.create_vertex_array()
+    }
+    fn delete_vertex_array(&self, vertex_array: &WebGlVertexArrayObject) {
+        self.delete_vertex_array(Some(vertex_array));
+    }
+    fn bind_vertex_array(&self, vertex_array: Option<&WebGlVertexArrayObject>)
+    {
+        self.bind_vertex_array(vertex_array);
+    }
+    fn draw_arrays_instanced(&self,
+                             mode      : u32,
+                             first     : i32,
+                             count     : i32,
+                             instances : i32)
+    {
+        self.draw_arrays_instanced(mode, first, count, instances);
+    }
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.vertex_attrib_divisor(index, divisor);
+    }
+    fn draw_buffers(&self, buffers: &[u32]) {
+        self.draw_buffers(&buffer_list(buffers));
+    }
+    fn tex_image_3d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    depth           : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        self.tex_image_3d_with_opt_u8_array(target, level, internal_format,
+                                            width, height, depth, border,
+                                            format, texel_type, data)
+    }
+    fn uniform_block_index(&self, program: &WebGlProgram, name: &str) -> u32 {
+        self.get_uniform_block_index(program, name)
+    }
+    fn uniform_block_binding(&self,
+                             program : &WebGlProgram,
+                             index   : u32,
+                             binding : u32)
+    {
+        self.uniform_block_binding(program, index, binding);
+    }
+    fn bind_buffer_base(&self,
+                        target : u32,
+                        index  : u32,
+                        buffer : Option<&WebGlBuffer>)
+    {
+        self.bind_buffer_base(target, index, buffer);
+    }
+});
+
+thread_local! {
+    /// The extension objects of each WebGL1 context, by extension name, so
+    /// each is only looked up once. The maps don't keep contexts alive.
+    static EXTENSIONS: RefCell<HashMap<&'static str, WeakMap>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Looks up the WebGL1 extension object behind a call, which the caller was
+/// to check for through `GlCapabilities`.
+trait ExtensionLookup {
+    fn extension<E: JsCast>(&self, name: &'static str) -> E;
+}
+
+impl ExtensionLookup for WebGlRenderingContext {
+    fn extension<E: JsCast>(&self, name: &'static str) -> E {
+        let context = self.unchecked_ref::<Object>();
+        let map     = EXTENSIONS.with(|maps| {
+                          maps.borrow_mut()
+                              .entry(name)
+                              .or_insert_with(WeakMap::new)
+                              .clone()
+                      });
+        let cached  = map.get(context);
+
+        if !cached.is_undefined() {
+            return cached.unchecked_into::<E>();
+        }
+        match self.get_extension(name) {
+            Ok(Some(extension)) => {
+                map.set(context, &extension);
+                extension.unchecked_into::<E>()
+            },
+            _ => panic!("{} isn't supported by this context; check \
+                         GlCapabilities first.", name),
+        }
+    }
+}
+
+fn buffer_list(buffers: &[u32]) -> js_sys::Array {
+    buffers.iter().map(|&b| JsValue::from(b)).collect()
+}
@@ -0,0 +1,98 @@
+use web_sys::WebGl2RenderingContext as Ctx2;
+use web_sys::WebGlRenderingContext as Ctx;
+
+use crate::backend::GlBackend;
+use crate::backend::GlVersion;
+
+/// WEBGL_draw_buffers reports its limit under the same enum as WebGL2.
+const MAX_DRAW_BUFFERS : u32 = Ctx2::MAX_DRAW_BUFFERS;
+
+/// Which rendering path a context takes and the features it has, natively
+/// on WebGL2 or through extensions on WebGL1. Querying enables the WebGL1
+/// extensions found, so their calls can be made through `GlBackend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlCapabilities {
+    pub version              : GlVersion,
+    /// `OES_vertex_array_object` on WebGL1.
+    pub vertex_array_objects : bool,
+    /// `ANGLE_instanced_arrays` on WebGL1.
+    pub instancing           : bool,
+    /// The number of color outputs a draw can write, through
+    /// `WEBGL_draw_buffers` on WebGL1; 1 without it.
+    pub max_draw_buffers     : u32,
+    pub integer_textures     : bool,
+    /// Depth textures a `RenderTarget` can sample, through
+    /// `WEBGL_depth_texture` on WebGL1.
+    pub depth_texture        : bool,
+    /// 3D and 2D array textures.
+    pub texture_3d           : bool,
+    /// Float color formats, such as `TexelFormat::RGBA16F`, a `RenderTarget`
+    /// can draw to, through `EXT_color_buffer_float` on WebGL2.
+    pub color_buffer_float   : bool,
+    pub uniform_buffers      : bool,
+    pub max_texture_size     : u32,
+    pub max_texture_units    : u32,
+}
+
+impl GlCapabilities {
+    pub fn query<B: GlBackend>(context: &B) -> Self {
+        let integer = |pname| context.get_float(pname).unwrap_or(0.0) as u32;
+
+        let max_texture_size  = integer(Ctx::MAX_TEXTURE_SIZE);
+        let max_texture_units = integer(Ctx::MAX_COMBINED_TEXTURE_IMAGE_UNITS);
+
+        match context.version() {
+            GlVersion::WebGl2 => GlCapabilities {
+                version              : GlVersion::WebGl2,
+                vertex_array_objects : true,
+                instancing           : true,
+                max_draw_buffers     : integer(MAX_DRAW_BUFFERS).max(1),
+                integer_textures     : true,
+                depth_texture        : true,
+                texture_3d           : true,
+                color_buffer_float   : context.enable_extension(
+                                           "EXT_color_buffer_float"),
+                uniform_buffers      : true,
+                max_texture_size,
+                max_texture_units,
+            },
+            GlVersion::WebGl1 => {
+                let draw_buffers = context.enable_extension("WEBGL_draw_buffers");
+                GlCapabilities {
+                    version              : GlVersion::WebGl1,
+                    vertex_array_objects : context.enable_extension(
+                                               "OES_vertex_array_object"),
+                    instancing           : context.enable_extension(
+                                               "ANGLE_instanced_arrays"),
+                    max_draw_buffers     : if draw_buffers {
+                                               integer(MAX_DRAW_BUFFERS).max(1)
+                                           } else { 1 },
+                    integer_textures     : false,
+                    depth_texture        : context.enable_extension(
+                                               "WEBGL_depth_texture"),
+                    texture_3d           : false,
+                    color_buffer_float   : false,
+                    uniform_buffers      : false,
+                    max_texture_size,
+                    max_texture_units,
+                }
+            },
+        }
+    }
+    #[inline]
+    pub fn is_webgl2(&self) -> bool {
+        self.version == GlVersion::WebGl2
+    }
+    #[inline]
+    pub fn multiple_render_targets(&self) -> bool {
+        self.max_draw_buffers > 1
+    }
+    /// Returns the `#version` line shaders written for this context start
+    /// with. `Shader::compile` converts GLSL ES 3.00 to 1.00 on WebGL1.
+    pub fn glsl_version(&self) -> &'static str {
+        match self.version {
+            GlVersion::WebGl1 => "#version 100",
+            GlVersion::WebGl2 => "#version 300 es",
+        }
+    }
+}
@@ -22,6 +22,11 @@ pub enum GfxError {
         inner : OptInnerError, 
     },
     MemoryError(crate::memory::MemoryError),
+    TextureError(crate::texture::TextureError),
+    AtlasError(crate::atlas::AtlasError),
+    ManifestError(crate::asset_manifest::ManifestError),
+    RenderTargetError(crate::render_target::RenderTargetError),
+    MaterialError(crate::material::MaterialError),
 }
 
 impl GfxError {
@@ -53,14 +58,29 @@ impl fmt::Display for GfxError {
                 write!(f, "{}", msg)
             },
             JSError { jsval } => {
-                write!(f, "{}", jsval_to_string(&jsval))
+                write!(f, "{}", jsval_to_string(jsval))
             },
             ResourceLoadError { msg, inner: _ } => { 
                 write!(f, "{}", msg) 
             },
             MemoryError(e) => {
                 write!(f, "{}", e)
-            }
+            },
+            TextureError(e) => {
+                write!(f, "{}", e)
+            },
+            AtlasError(e) => {
+                write!(f, "{}", e)
+            },
+            ManifestError(e) => {
+                write!(f, "{}", e)
+            },
+            RenderTargetError(e) => {
+                write!(f, "{}", e)
+            },
+            MaterialError(e) => {
+                write!(f, "{}", e)
+            },
         }
     }
 }
@@ -75,7 +95,22 @@ impl Error for GfxError {
             },
             MemoryError(e) => {
                 Some(e)
-            }
+            },
+            TextureError(e) => {
+                Some(e)
+            },
+            AtlasError(e) => {
+                Some(e)
+            },
+            ManifestError(e) => {
+                Some(e)
+            },
+            RenderTargetError(e) => {
+                Some(e)
+            },
+            MaterialError(e) => {
+                Some(e)
+            },
             _ => { None },
         }
     }
@@ -87,4 +122,32 @@ impl From<crate::memory::MemoryError> for GfxError {
     }
 }
 
+impl From<crate::texture::TextureError> for GfxError {
+    fn from(e: crate::texture::TextureError) -> Self {
+        GfxError::TextureError(e)
+    }
+}
+
+impl From<crate::atlas::AtlasError> for GfxError {
+    fn from(e: crate::atlas::AtlasError) -> Self {
+        GfxError::AtlasError(e)
+    }
+}
 
+impl From<crate::asset_manifest::ManifestError> for GfxError {
+    fn from(e: crate::asset_manifest::ManifestError) -> Self {
+        GfxError::ManifestError(e)
+    }
+}
+
+impl From<crate::render_target::RenderTargetError> for GfxError {
+    fn from(e: crate::render_target::RenderTargetError) -> Self {
+        GfxError::RenderTargetError(e)
+    }
+}
+
+impl From<crate::material::MaterialError> for GfxError {
+    fn from(e: crate::material::MaterialError) -> Self {
+        GfxError::MaterialError(e)
+    }
+}
@@ -1,29 +1,39 @@
 #![allow(dead_code)]
 
+use std::cell::RefCell;
+use std::sync::Arc;
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use web_sys::console;
+use web_sys::WebGl2RenderingContext;
 use web_sys::WebGlRenderingContext;
 
 use js_sys::Math::{sin, cos};
 
+use crate::backend::GlBackend;
+use crate::capabilities::GlCapabilities;
 use crate::console_log;
+use crate::gl_state::FrameStats;
+use crate::gl_state::GlState;
 use crate::matrix::*;
+use crate::program::Program;
+use crate::render_state::*;
 use crate::types::DEG_TO_RAD;
 use crate::vector::*;
+use crate::webgl_context::WebGlContext;
 
 const MAX_MODELVIEW_MATRIX  : usize = 8;
 const MAX_PROJECTION_MATRIX : usize = 2;
 const MAX_TEXTURE_MATRIX    : usize = 2;
 
-enum MatrixMode {
+pub enum MatrixMode {
     ModelView  = 0,
     Projection = 1,
     Texture    = 2,
 }
 
-struct Gfx {
+pub struct Gfx<B: GlBackend = WebGlRenderingContext> {
     matrix_mode                 : MatrixMode,
     modelview_matrix_index      : usize,
     projection_matrix_index     : usize,
@@ -33,19 +43,83 @@ struct Gfx {
     texture_matrix              : [Mat4; MAX_TEXTURE_MATRIX    ],
     modelview_projection_matrix : Mat4,
     normal_matrix               : Mat3,
-    context                     : WebGlRenderingContext,
+    capabilities                : GlCapabilities,
+    state                       : RefCell<GlState<B>>,
+    context                     : Arc<B>,
+}
+
+/// How `Gfx::with_options` sets up the canvas.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GfxOptions {
+    webgl2 : bool,
+}
+
+impl GfxOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Asks for a WebGL2 context, falling back to WebGL1 where the browser
+    /// has none.
+    pub fn webgl2(mut self, webgl2: bool) -> Self {
+        self.webgl2 = webgl2;
+        self
+    }
 }
 
-impl Gfx {
+fn query_canvas(canvas_selector: &str)
+    -> Result<web_sys::HtmlCanvasElement, JsValue>
+{
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas   = document.query_selector(canvas_selector)?
+                           .ok_or_else(|| {
+                               JsValue::from_str(&format!(
+                                   "No canvas matches ({}).", canvas_selector))
+                           })?
+                           .dyn_into::<web_sys::HtmlCanvasElement>()?;
+    Ok( canvas )
+}
+
+impl Gfx<WebGlRenderingContext> {
     pub fn new(canvas_selector: &str) -> Result<Self, JsValue> 
     {
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas   = document.query_selector(canvas_selector)?
-                               .unwrap()
-                               .dyn_into::<web_sys::HtmlCanvasElement>()?;
-        let context  = canvas.get_context("webgl")?
-                             .unwrap()
-                             .dyn_into::<WebGlRenderingContext>()?;
+        let canvas  = query_canvas(canvas_selector)?;
+        let context = canvas.get_context("webgl")?
+                            .unwrap()
+                            .dyn_into::<WebGlRenderingContext>()?;
+
+        Ok( Gfx::from_context(Arc::new(context)) )
+    }
+}
+
+impl Gfx<WebGlContext> {
+    /// Sets up rendering on the canvas with a context of either WebGL
+    /// version; `capabilities()` tells which was created.
+    pub fn with_options(canvas_selector: &str, options: GfxOptions)
+        -> Result<Self, JsValue>
+    {
+        let canvas = query_canvas(canvas_selector)?;
+
+        if options.webgl2 {
+            if let Some(context) = canvas.get_context("webgl2")? {
+                let context = context.dyn_into::<WebGl2RenderingContext>()?;
+                return Ok( Gfx::from_context(Arc::new(
+                               WebGlContext::WebGl2(context))) );
+            }
+            console_log!("WebGL2 isn't available; falling back to WebGL1.");
+        }
+        let context = canvas.get_context("webgl")?
+                            .ok_or_else(|| JsValue::from_str(
+                                "WebGL isn't available."))?
+                            .dyn_into::<WebGlRenderingContext>()?;
+
+        Ok( Gfx::from_context(Arc::new(WebGlContext::WebGl1(context))) )
+    }
+}
+
+impl<B: GlBackend> Gfx<B> {
+    /// Sets up rendering on an existing context, such as a
+    /// `RecordingBackend` in tests.
+    pub fn from_context(context: Arc<B>) -> Self {
         let mut gfx = Gfx {
                 matrix_mode                 : MatrixMode::ModelView,
                 modelview_matrix_index      : 0,
@@ -56,49 +130,300 @@ impl Gfx {
                 texture_matrix              : Default::default(),
                 modelview_projection_matrix : Mat4::new(),
                 normal_matrix               : Mat3::new(),
+                capabilities                : GlCapabilities::query(&*context),
+                state                       : RefCell::new(GlState::default()),
                 context
         };
         gfx.start();
-        Ok(gfx)
+        gfx
+    }
+    #[inline]
+    pub fn context(&self) -> &Arc<B> {
+        &self.context
+    }
+    /// Returns the WebGL version in use and the features it has.
+    #[inline]
+    pub fn capabilities(&self) -> &GlCapabilities {
+        &self.capabilities
+    }
+    /// Returns what's been sent to the context since `begin_frame`.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.state.borrow().stats
+    }
+    /// Starts counting a new frame, returning the last frame's statistics.
+    pub fn begin_frame(&self) -> FrameStats {
+        std::mem::take(&mut self.state.borrow_mut().stats)
+    }
+    /// Forgets the state `Gfx` has set. The state methods below skip calls
+    /// that wouldn't change it, so call this after changing state through
+    /// the context directly. The crate's own texture uploads, render targets
+    /// and vertex arrays account for the bindings they change.
+    pub fn invalidate_state(&self) {
+        self.state.borrow_mut().invalidate();
+    }
+    pub fn enable(&self, cap: u32) {
+        if self.state.borrow_mut().cap(cap, true) {
+            self.context.enable(cap);
+        }
+    }
+    pub fn disable(&self, cap: u32) {
+        if self.state.borrow_mut().cap(cap, false) {
+            self.context.disable(cap);
+        }
+    }
+    pub fn blend_func(&self, src: u32, dst: u32) {
+        self.blend_func_separate(src, dst, src, dst);
+    }
+    pub fn blend_func_separate(&self,
+                               src_rgb   : u32,
+                               dst_rgb   : u32,
+                               src_alpha : u32,
+                               dst_alpha : u32)
+    {
+        let func = [src_rgb, dst_rgb, src_alpha, dst_alpha];
+        if self.state.borrow_mut().blend_func(func) {
+            self.context.blend_func_separate(src_rgb, dst_rgb,
+                                             src_alpha, dst_alpha);
+        }
+    }
+    pub fn blend_equation(&self, mode: u32) {
+        self.blend_equation_separate(mode, mode);
+    }
+    pub fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        if self.state.borrow_mut().blend_equation([mode_rgb, mode_alpha]) {
+            self.context.blend_equation_separate(mode_rgb, mode_alpha);
+        }
+    }
+    pub fn depth_func(&self, func: u32) {
+        if self.state.borrow_mut().depth_func(func) {
+            self.context.depth_func(func);
+        }
+    }
+    pub fn depth_mask(&self, flag: bool) {
+        if self.state.borrow_mut().depth_mask(flag) {
+            self.context.depth_mask(flag);
+        }
+    }
+    pub fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+        if self.state.borrow_mut().stencil_func((func, reference, mask)) {
+            self.context.stencil_func(func, reference, mask);
+        }
+    }
+    pub fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+        if self.state.borrow_mut().stencil_op([fail, depth_fail, pass]) {
+            self.context.stencil_op(fail, depth_fail, pass);
+        }
+    }
+    pub fn stencil_mask(&self, mask: u32) {
+        if self.state.borrow_mut().stencil_mask(mask) {
+            self.context.stencil_mask(mask);
+        }
+    }
+    pub fn cull_face(&self, mode: u32) {
+        if self.state.borrow_mut().cull_face(mode) {
+            self.context.cull_face(mode);
+        }
+    }
+    pub fn front_face(&self, mode: u32) {
+        if self.state.borrow_mut().front_face(mode) {
+            self.context.front_face(mode);
+        }
+    }
+    pub fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) {
+        if self.state.borrow_mut().color_mask([red, green, blue, alpha]) {
+            self.context.color_mask(red, green, blue, alpha);
+        }
+    }
+    pub fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        if self.state.borrow_mut().viewport([x, y, width, height]) {
+            self.context.viewport(x, y, width, height);
+        }
+    }
+    pub fn polygon_offset(&self, factor: f32, units: f32) {
+        if self.state.borrow_mut().polygon_offset([factor, units]) {
+            self.context.polygon_offset(factor, units);
+        }
     }
-    fn start(&mut self) -> Result<(), JsValue> 
+    pub fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        if self.state.borrow_mut().clear_color([red, green, blue, alpha]) {
+            self.context.clear_color(red, green, blue, alpha);
+        }
+    }
+    /// Clears the buffers in `mask` of the bound framebuffer.
+    pub fn clear(&self, mask: u32) {
+        self.context.clear(mask);
+    }
+    /// Returns the last viewport set through `viewport`, as x, y, width and
+    /// height.
+    pub fn current_viewport(&self) -> Option<[i32; 4]> {
+        self.state.borrow().current_viewport()
+    }
+    /// Returns the last color set through `clear_color`.
+    pub fn current_clear_color(&self) -> Option<[f32; 4]> {
+        self.state.borrow().current_clear_color()
+    }
+    /// Returns the depth state last set, or `None` if part of it isn't
+    /// known, as after `invalidate_state`.
+    pub fn current_depth_state(&self) -> Option<DepthState> {
+        self.state.borrow().current_depth_state()
+    }
+    /// Returns the raster state last set, or `None` if part of it isn't
+    /// known.
+    pub fn current_raster_state(&self) -> Option<RasterState> {
+        self.state.borrow().current_raster_state()
+    }
+    /// Applies every part of `state`.
+    pub fn apply_render_state(&self, state: &RenderState) {
+        self.apply_blend_state(&state.blend);
+        self.apply_depth_state(&state.depth);
+        self.apply_stencil_state(&state.stencil);
+        self.apply_raster_state(&state.raster);
+    }
+    pub fn apply_blend_state(&self, blend: &BlendState) {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        if !blend.enabled {
+            self.disable(GLRendCtx::BLEND);
+            return;
+        }
+        self.enable(GLRendCtx::BLEND);
+        self.blend_func_separate(blend.src_rgb,   blend.dst_rgb,
+                                 blend.src_alpha, blend.dst_alpha);
+        self.blend_equation_separate(blend.equation_rgb, blend.equation_alpha);
+    }
+    pub fn apply_depth_state(&self, depth: &DepthState) {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        if depth.test {
+            self.enable(GLRendCtx::DEPTH_TEST);
+            self.depth_func(depth.func);
+        } else {
+            self.disable(GLRendCtx::DEPTH_TEST);
+        }
+        self.depth_mask(depth.write);
+    }
+    pub fn apply_stencil_state(&self, stencil: &StencilState) {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        if stencil.enabled {
+            self.enable(GLRendCtx::STENCIL_TEST);
+            self.stencil_func(stencil.func, stencil.reference,
+                              stencil.read_mask);
+            self.stencil_op(stencil.fail, stencil.depth_fail, stencil.pass);
+        } else {
+            self.disable(GLRendCtx::STENCIL_TEST);
+        }
+        self.stencil_mask(stencil.write_mask);
+    }
+    pub fn apply_raster_state(&self, raster: &RasterState) {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        match raster.cull_face {
+            Some(mode) => {
+                self.enable(GLRendCtx::CULL_FACE);
+                self.cull_face(mode);
+            },
+            None => self.disable(GLRendCtx::CULL_FACE),
+        }
+        self.front_face(raster.front_face);
+        let [red, green, blue, alpha] = raster.color_mask;
+        self.color_mask(red, green, blue, alpha);
+
+        match raster.polygon_offset {
+            Some((factor, units)) => {
+                self.enable(GLRendCtx::POLYGON_OFFSET_FILL);
+                self.polygon_offset(factor, units);
+            },
+            None => self.disable(GLRendCtx::POLYGON_OFFSET_FILL),
+        }
+    }
+    pub fn use_program(&self, program: Option<&B::Program>) {
+        if self.state.borrow_mut().program(program) {
+            self.context.use_program(program);
+        }
+    }
+    pub fn bind_buffer(&self, target: u32, buffer: Option<&B::Buffer>) {
+        if self.state.borrow_mut().buffer(target, buffer) {
+            self.context.bind_buffer(target, buffer);
+        }
+    }
+    /// Forgets what's bound to buffer `target`, for state the context
+    /// changed on its own, as binding a vertex array object does.
+    pub(crate) fn forget_buffer(&self, target: u32) {
+        self.state.borrow_mut().forget_buffer(target);
+    }
+    /// Binds `texture` to `target` of texture unit `unit`, counting from 0,
+    /// making the unit active if the bind is needed.
+    pub fn bind_texture(&self,
+                        unit    : u32,
+                        target  : u32,
+                        texture : Option<&B::Texture>)
+    {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        let (activate, bind) = self.state
+                                   .borrow_mut()
+                                   .texture(unit, target, texture);
+        if activate {
+            self.context.active_texture(GLRendCtx::TEXTURE0 + unit);
+        }
+        if bind {
+            self.context.bind_texture(target, texture);
+        }
+    }
+    /// Binds `framebuffer` as the target of draws, `None` being the canvas.
+    /// `RenderTarget::bind` also sets the viewport.
+    pub fn bind_framebuffer(&self, framebuffer: Option<&B::Framebuffer>) {
+        use web_sys::WebGlRenderingContext as GLRendCtx;
+
+        if self.state.borrow_mut().framebuffer(framebuffer) {
+            self.context.bind_framebuffer(GLRendCtx::FRAMEBUFFER, framebuffer);
+        }
+    }
+    /// Returns the framebuffer last bound through `bind_framebuffer`, `None`
+    /// being the canvas.
+    pub fn current_framebuffer(&self) -> Option<B::Framebuffer> {
+        self.state.borrow_mut().current_framebuffer()
+    }
+    pub fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.state.borrow_mut().stats.draw_calls += 1;
+        self.context.draw_arrays(mode, first, count);
+    }
+    pub fn draw_arrays_instanced(&self,
+                                 mode      : u32,
+                                 first     : i32,
+                                 count     : i32,
+                                 instances : i32)
+    {
+        self.state.borrow_mut().stats.draw_calls += 1;
+        self.context.draw_arrays_instanced(mode, first, count, instances);
+    }
+    fn start(&mut self)
     {
         use web_sys::WebGlRenderingContext as GLRendCtx;
         
         let context = &self.context;
+        let string  = |pname| context.get_string(pname).unwrap_or_default();
         
-        console::log_2(&"GL_VENDOR     : ".into(), 
-                       &context.get_parameter(GLRendCtx::VENDOR)
-                       .unwrap());
-        console::log_2(&"GL_RENDERER   : ".into(), 
-                       &context.get_parameter(GLRendCtx::RENDERER)
-                       .unwrap());
-        console::log_2(&"GL_VERSION    : ".into(), 
-                       &context.get_parameter(GLRendCtx::VERSION)
-                       .unwrap());
-        console::log_2(&"GL_EXTENSIONS : ".into(), 
-                       &context.get_supported_extensions()
-                       .unwrap());
+        console_log!("GL_VENDOR     : {}", string(GLRendCtx::VENDOR));
+        console_log!("GL_RENDERER   : {}", string(GLRendCtx::RENDERER));
+        console_log!("GL_VERSION    : {}", string(GLRendCtx::VERSION));
+        console_log!("GL_EXTENSIONS : {}",
+                     context.supported_extensions().join(","));
 
         context.hint(GLRendCtx::GENERATE_MIPMAP_HINT, 
                      GLRendCtx::NICEST);
         //context.hint(GLRendCtx::FRAGMENT_SHADER_DERIVATIVE_HINT_OES, 
         //             GLRendCtx::NICEST);
         
-        context.enable(GLRendCtx::DEPTH_TEST);
-        context.enable(GLRendCtx::CULL_FACE);
-        context.disable(GLRendCtx::DITHER);
+        self.apply_render_state(&RenderState::OPAQUE);
+        self.disable(GLRendCtx::DITHER);
         
-        context.depth_mask(true);
-        context.depth_func(GLRendCtx::LESS);
         context.depth_range(0.0, 1.0);
         context.clear_depth(1.0);
-        context.cull_face(GLRendCtx::BACK);
-        context.front_face(GLRendCtx::CCW);
         context.clear_stencil(0);
-        context.stencil_mask(0xFFFFFFFF);
         
-        context.clear_color(0.0, 0.0, 0.0, 1.0);
+        self.clear_color(0.0, 0.0, 0.0, 1.0);
         context.clear(GLRendCtx::DEPTH_BUFFER_BIT   | 
                       GLRendCtx::STENCIL_BUFFER_BIT |
                       GLRendCtx::COLOR_BUFFER_BIT   );
@@ -113,13 +438,11 @@ impl Gfx {
         self.load_identity();
         
         self.log_errors();
-        
-        Ok(())
     }
-    fn set_matrix_mode(&mut self, mode: MatrixMode) {
+    pub fn set_matrix_mode(&mut self, mode: MatrixMode) {
         self.matrix_mode = mode;
     }
-    fn load_identity(&mut self) {
+    pub fn load_identity(&mut self) {
         match self.matrix_mode {
             MatrixMode::ModelView => { 
                 self.get_modelview_matrix_mut().identity(); 
@@ -135,22 +458,24 @@ impl Gfx {
     fn get_modelview_matrix_mut(&mut self) -> &mut Mat4 {
         &mut self.modelview_matrix[self.modelview_matrix_index]
     }
-    fn get_modelview_matrix(&self) -> &Mat4 {
+    pub fn get_modelview_matrix(&self) -> &Mat4 {
         &self.modelview_matrix[self.modelview_matrix_index]
     }
     fn get_projection_matrix_mut(&mut self) -> &mut Mat4 {
         &mut self.projection_matrix[self.projection_matrix_index]
     }
-    fn get_projection_matrix(&self) -> &Mat4 {
+    pub fn get_projection_matrix(&self) -> &Mat4 {
         &self.projection_matrix[self.projection_matrix_index]
     }
     fn get_texture_matrix_mut(&mut self) -> &mut Mat4 {
         &mut self.texture_matrix[self.texture_matrix_index]
     }
-    fn get_texture_matrix(&self) -> &Mat4 {
+    pub fn get_texture_matrix(&self) -> &Mat4 {
         &self.texture_matrix[self.texture_matrix_index]
     }
-    fn log_errors(&self) {
+    /// Logs and returns the names of the errors the context has recorded,
+    /// clearing them.
+    pub fn log_errors(&self) -> Vec<&'static str> {
         use web_sys::WebGlRenderingContext as GLRendCtx;
         let mut errors = vec![];
         
@@ -178,12 +503,11 @@ impl Gfx {
             }
         }
         if !errors.is_empty() {
-            let mut estring = errors.join(", ");
-            estring.insert_str(0, "GL_ERRORS: ");
-            console::log_1(&estring.into());
+            console_log!("GL_ERRORS: {}", errors.join(", "));
         }
+        errors
     }
-    fn push_matrix(&mut self) {
+    pub fn push_matrix(&mut self) {
         match self.matrix_mode {
             MatrixMode::ModelView => {
                 let i = self.modelview_matrix_index;
@@ -208,7 +532,7 @@ impl Gfx {
             }
         }
     }
-    fn pop_matrix(&mut self) {
+    pub fn pop_matrix(&mut self) {
         match self.matrix_mode {
             MatrixMode::ModelView => {
                 self.modelview_matrix_index -= 1;
@@ -221,7 +545,7 @@ impl Gfx {
             }
         }
     }
-    fn load_matrix(&mut self, m: &Mat4) {
+    pub fn load_matrix(&mut self, m: &Mat4) {
         match self.matrix_mode {
             MatrixMode::ModelView => {
                 m.copy_to(self.get_modelview_matrix_mut());
@@ -234,7 +558,7 @@ impl Gfx {
             }
         }
     }
-    fn multiply_matrix(&mut self, m: &Mat4) {
+    pub fn multiply_matrix(&mut self, m: &Mat4) {
         match self.matrix_mode {
             MatrixMode::ModelView => {
                 let result = self.get_modelview_matrix().multiply(m);
@@ -301,16 +625,41 @@ impl Gfx {
         //        being copied are small.
         let proj_mtx = self.get_projection_matrix();
         let modv_mtx = self.get_modelview_matrix();
-        self.modelview_projection_matrix = proj_mtx.multiply(&modv_mtx);
+        self.modelview_projection_matrix = proj_mtx.multiply(modv_mtx);
         &self.modelview_projection_matrix
     }
     fn get_normal_matrix(&mut self) -> &Mat3 {
+        self.normal_matrix = self.normal_matrix();
+        &self.normal_matrix
+    }
+    /// Returns the inverse transpose of the model view matrix, which takes
+    /// normals to view space.
+    pub fn normal_matrix(&self) -> Mat3 {
         let mut mat = Mat4::new();
+        let mut nrm = Mat3::new();
         self.get_modelview_matrix().copy_to(&mut mat);
         mat.invert_full();
         mat.transpose();
-        mat.copy_to_mat3(&mut self.normal_matrix);
-        &self.normal_matrix
+        mat.copy_to_mat3(&mut nrm);
+        nrm
+    }
+    /// Uploads the current matrices to the uniforms of `program` named by
+    /// convention, those of them it has: `MODELVIEWPROJECTIONMATRIX`,
+    /// `MODELVIEWMATRIX`, `PROJECTIONMATRIX` and `NORMALMATRIX`. The program
+    /// must be current.
+    pub fn set_matrix_uniforms(&self, program: &Program<B>) {
+        let modelview  = self.get_modelview_matrix();
+        let projection = self.get_projection_matrix();
+
+        program.set_uniform("MODELVIEWPROJECTIONMATRIX",
+                            &(&projection.multiply(modelview)).into());
+        program.set_uniform("MODELVIEWMATRIX", &modelview.into());
+        program.set_uniform("PROJECTIONMATRIX", &projection.into());
+
+        if program.has_uniform("NORMALMATRIX") {
+            let normal = self.normal_matrix();
+            program.set_uniform("NORMALMATRIX", &(&normal).into());
+        }
     }
     fn ortho(&mut self, 
              left       : f32,
@@ -0,0 +1,288 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::render_state::DepthState;
+use crate::render_state::RasterState;
+
+/// What a `Gfx` sent to its context, or saved it from sending, since the
+/// frame began.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Calls made that changed state, bindings included.
+    pub state_changes   : u32,
+    /// Calls skipped because the context was already in that state.
+    pub redundant_calls : u32,
+    pub program_binds   : u32,
+    pub texture_binds   : u32,
+    pub buffer_binds    : u32,
+    pub draw_calls      : u32,
+}
+
+/// Binding points that calls made straight on a context, outside any `Gfx`,
+/// change: texture uploads bind textures, and setting up render targets and
+/// vertex arrays binds framebuffers and buffers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Binding {
+    Buffers     = 0,
+    Textures    = 1,
+    Framebuffer = 2,
+}
+
+thread_local! {
+    /// How many times each `Binding` was changed outside a `Gfx`. WebGL
+    /// contexts belong to the thread that made them, as do these counts.
+    static UNTRACKED_BINDS: Cell<[u64; 3]> = const { Cell::new([0; 3]) };
+}
+
+/// Notes that `binding` was changed straight on a context, so every
+/// `GlState` forgets what it held there before its next use.
+pub(crate) fn untracked_bind(binding: Binding) {
+    UNTRACKED_BINDS.with(|binds| {
+        let mut counts = binds.get();
+        counts[binding as usize] += 1;
+        binds.set(counts);
+    });
+}
+
+fn untracked_binds() -> [u64; 3] {
+    UNTRACKED_BINDS.with(|binds| binds.get())
+}
+
+/// The state `Gfx` last set on its context. `None` is state that isn't
+/// known, which the next call always sets.
+pub(crate) struct GlState<B: GlBackend = WebGlRenderingContext> {
+    caps           : HashMap<u32, Option<bool>>,
+    blend_func     : Option<[u32; 4]>,
+    blend_equation : Option<[u32; 2]>,
+    depth_func     : Option<u32>,
+    depth_mask     : Option<bool>,
+    stencil_func   : Option<(u32, i32, u32)>,
+    stencil_op     : Option<[u32; 3]>,
+    stencil_mask   : Option<u32>,
+    cull_face      : Option<u32>,
+    front_face     : Option<u32>,
+    color_mask     : Option<[bool; 4]>,
+    viewport       : Option<[i32; 4]>,
+    polygon_offset : Option<[f32; 2]>,
+    clear_color    : Option<[f32; 4]>,
+    program        : Option<Option<B::Program>>,
+    buffers        : HashMap<u32, Option<Option<B::Buffer>>>,
+    active_unit    : Option<u32>,
+    textures       : HashMap<(u32, u32), Option<Option<B::Texture>>>,
+    framebuffer    : Option<Option<B::Framebuffer>>,
+    /// The `untracked_binds` the bindings above account for.
+    untracked      : [u64; 3],
+    pub stats      : FrameStats,
+}
+
+impl<B: GlBackend> Default for GlState<B> {
+    fn default() -> Self {
+        GlState {
+            caps           : HashMap::new(),
+            blend_func     : None,
+            blend_equation : None,
+            depth_func     : None,
+            depth_mask     : None,
+            stencil_func   : None,
+            stencil_op     : None,
+            stencil_mask   : None,
+            cull_face      : None,
+            front_face     : None,
+            color_mask     : None,
+            viewport       : None,
+            polygon_offset : None,
+            clear_color    : None,
+            program        : None,
+            buffers        : HashMap::new(),
+            active_unit    : None,
+            textures       : HashMap::new(),
+            framebuffer    : None,
+            untracked      : untracked_binds(),
+            stats          : FrameStats::default(),
+        }
+    }
+}
+
+/// Records `value` in `slot`, returning whether it differs from what was
+/// there and so must be sent.
+fn update<T: PartialEq>(slot: &mut Option<T>, value: T, stats: &mut FrameStats)
+    -> bool
+{
+    if slot.as_ref() == Some(&value) {
+        stats.redundant_calls += 1;
+        false
+    } else {
+        *slot = Some(value);
+        stats.state_changes += 1;
+        true
+    }
+}
+
+impl<B: GlBackend> GlState<B> {
+    /// Forgets the state, keeping the statistics.
+    pub fn invalidate(&mut self) {
+        *self = GlState { stats: self.stats, ..Default::default() };
+    }
+    /// Forgets `binding` if it was changed outside a `Gfx` since last
+    /// checked.
+    fn sync(&mut self, binding: Binding) {
+        let counts = untracked_binds();
+        let i      = binding as usize;
+
+        if counts[i] == self.untracked[i] {
+            return;
+        }
+        self.untracked[i] = counts[i];
+
+        match binding {
+            Binding::Buffers     => self.buffers.clear(),
+            Binding::Textures    => self.textures.clear(),
+            Binding::Framebuffer => self.framebuffer = None,
+        }
+    }
+    /// Forgets what's bound to buffer `target`, as when binding a vertex
+    /// array object replaces the element array buffer.
+    pub fn forget_buffer(&mut self, target: u32) {
+        self.buffers.remove(&target);
+    }
+    pub fn cap(&mut self, cap: u32, enabled: bool) -> bool {
+        update(self.caps.entry(cap).or_insert(None), enabled, &mut self.stats)
+    }
+    pub fn blend_func(&mut self, func: [u32; 4]) -> bool {
+        update(&mut self.blend_func, func, &mut self.stats)
+    }
+    pub fn blend_equation(&mut self, equation: [u32; 2]) -> bool {
+        update(&mut self.blend_equation, equation, &mut self.stats)
+    }
+    pub fn depth_func(&mut self, func: u32) -> bool {
+        update(&mut self.depth_func, func, &mut self.stats)
+    }
+    pub fn depth_mask(&mut self, flag: bool) -> bool {
+        update(&mut self.depth_mask, flag, &mut self.stats)
+    }
+    pub fn stencil_func(&mut self, func: (u32, i32, u32)) -> bool {
+        update(&mut self.stencil_func, func, &mut self.stats)
+    }
+    pub fn stencil_op(&mut self, op: [u32; 3]) -> bool {
+        update(&mut self.stencil_op, op, &mut self.stats)
+    }
+    pub fn stencil_mask(&mut self, mask: u32) -> bool {
+        update(&mut self.stencil_mask, mask, &mut self.stats)
+    }
+    pub fn cull_face(&mut self, mode: u32) -> bool {
+        update(&mut self.cull_face, mode, &mut self.stats)
+    }
+    pub fn front_face(&mut self, mode: u32) -> bool {
+        update(&mut self.front_face, mode, &mut self.stats)
+    }
+    pub fn color_mask(&mut self, mask: [bool; 4]) -> bool {
+        update(&mut self.color_mask, mask, &mut self.stats)
+    }
+    pub fn viewport(&mut self, viewport: [i32; 4]) -> bool {
+        update(&mut self.viewport, viewport, &mut self.stats)
+    }
+    pub fn polygon_offset(&mut self, offset: [f32; 2]) -> bool {
+        update(&mut self.polygon_offset, offset, &mut self.stats)
+    }
+    pub fn clear_color(&mut self, color: [f32; 4]) -> bool {
+        update(&mut self.clear_color, color, &mut self.stats)
+    }
+    #[inline]
+    pub fn current_viewport(&self) -> Option<[i32; 4]> {
+        self.viewport
+    }
+    #[inline]
+    pub fn current_clear_color(&self) -> Option<[f32; 4]> {
+        self.clear_color
+    }
+    fn current_cap(&self, cap: u32) -> Option<bool> {
+        self.caps.get(&cap).copied().flatten()
+    }
+    /// Returns the depth state set, if every part of it that applies is
+    /// known.
+    pub fn current_depth_state(&self) -> Option<DepthState> {
+        use WebGlRenderingContext as Ctx;
+
+        let test  = self.current_cap(Ctx::DEPTH_TEST)?;
+        let write = self.depth_mask?;
+        let func  = if test { self.depth_func? }
+                    else    { self.depth_func.unwrap_or(Ctx::LESS) };
+        Some( DepthState { test, write, func } )
+    }
+    /// Returns the raster state set, if every part of it that applies is
+    /// known.
+    pub fn current_raster_state(&self) -> Option<RasterState> {
+        use WebGlRenderingContext as Ctx;
+
+        let cull_face      = if self.current_cap(Ctx::CULL_FACE)? {
+                                 Some( self.cull_face? )
+                             } else {
+                                 None
+                             };
+        let polygon_offset = if self.current_cap(Ctx::POLYGON_OFFSET_FILL)? {
+                                 let [factor, units] = self.polygon_offset?;
+                                 Some( (factor, units) )
+                             } else {
+                                 None
+                             };
+        Some( RasterState { cull_face,
+                            front_face : self.front_face?,
+                            color_mask : self.color_mask?,
+                            polygon_offset } )
+    }
+    pub fn program(&mut self, program: Option<&B::Program>) -> bool {
+        let changed = update(&mut self.program, program.cloned(),
+                             &mut self.stats);
+        if changed {
+            self.stats.program_binds += 1;
+        }
+        changed
+    }
+    pub fn buffer(&mut self, target: u32, buffer: Option<&B::Buffer>) -> bool {
+        self.sync(Binding::Buffers);
+        let slot    = self.buffers.entry(target).or_insert(None);
+        let changed = update(slot, buffer.cloned(), &mut self.stats);
+        if changed {
+            self.stats.buffer_binds += 1;
+        }
+        changed
+    }
+    pub fn framebuffer(&mut self, framebuffer: Option<&B::Framebuffer>)
+        -> bool
+    {
+        self.sync(Binding::Framebuffer);
+        update(&mut self.framebuffer, framebuffer.cloned(), &mut self.stats)
+    }
+    /// Returns the framebuffer last bound, `None` being the canvas or not
+    /// known.
+    pub fn current_framebuffer(&mut self) -> Option<B::Framebuffer> {
+        self.sync(Binding::Framebuffer);
+        self.framebuffer.clone().flatten()
+    }
+    /// Records a bind of `texture` to `target` of texture unit `unit`,
+    /// returning whether the unit must be made active and whether the bind
+    /// must be sent.
+    pub fn texture(&mut self,
+                   unit    : u32,
+                   target  : u32,
+                   texture : Option<&B::Texture>) -> (bool, bool)
+    {
+        self.sync(Binding::Textures);
+        let slot = self.textures.entry((unit, target)).or_insert(None);
+        let bind = update(slot, texture.cloned(), &mut self.stats);
+
+        if !bind {
+            return (false, false);
+        }
+        self.stats.texture_binds += 1;
+        let activate = self.active_unit != Some(unit);
+        if activate {
+            self.active_unit = Some(unit);
+            self.stats.state_changes += 1;
+        }
+        (activate, true)
+    }
+}
@@ -0,0 +1,281 @@
+use std::f32::consts::PI;
+
+use crate::texture::TextureError;
+
+/// Filters used to build mip levels on the CPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipmapFilter {
+    /// Averages each 2x2 block. Fast, but blurs and aliases a little.
+    Box,
+    /// Kaiser windowed sinc. Keeps the smaller levels noticeably sharper.
+    Kaiser,
+}
+
+/// Decoded 8 bit per channel texels. Images with 2 or 4 channels keep alpha
+/// in the last channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width       : u32,
+    pub height      : u32,
+    pub channels    : usize,
+    pub pixels      : Vec<u8>,
+}
+
+impl Image {
+    /// Wraps `pixels`, which must hold `width` x `height` texels of
+    /// `channels` bytes each. Images can't be empty.
+    pub fn new(width: u32, height: u32, channels: usize, pixels: Vec<u8>)
+        -> Result<Self, TextureError>
+    {
+        let expected = width as usize * height as usize * channels;
+
+        if width == 0 || height == 0 || channels == 0 {
+            let msg = format!("Image is {}x{} with {} channels; none may be \
+                               zero.", width, height, channels);
+            Err( TextureError::ImageError(msg) )?
+        }
+        if pixels.len() != expected {
+            let msg = format!("Image pixel buffer is {} bytes; a {}x{} image \
+                               of {} channels needs {}.", pixels.len(), width,
+                               height, channels, expected);
+            Err( TextureError::ImageError(msg) )?
+        }
+        Ok( Image { width, height, channels, pixels } )
+    }
+    pub fn is_power_of_two(&self) -> bool {
+        self.width.is_power_of_two() && self.height.is_power_of_two()
+    }
+    /// Returns the `width` x `height` region whose top left corner is at
+    /// (`x`, `y`).
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32)
+        -> Result<Image, TextureError>
+    {
+        if x.saturating_add(width)  > self.width
+            || y.saturating_add(height) > self.height
+        {
+            let msg = format!("Crop region {}x{} at ({}, {}) is outside the \
+                               {}x{} image.", width, height, x, y, self.width,
+                               self.height);
+            Err( TextureError::ImageError(msg) )?
+        }
+        let c      = self.channels;
+        let stride = self.width as usize * c;
+        let mut pixels = Vec::with_capacity(width as usize
+                                            * height as usize * c);
+        for row in y..y + height {
+            let start = row as usize * stride + x as usize * c;
+            pixels.extend_from_slice(&self.pixels[start..
+                                                  start + width as usize * c]);
+        }
+        Ok( Image { width, height, channels: c, pixels } )
+    }
+    /// Returns the image turned upside down and mirrored.
+    pub fn rotate_180(&self) -> Image {
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+
+        for texel in self.pixels.chunks_exact(self.channels).rev() {
+            pixels.extend_from_slice(texel);
+        }
+        Image { pixels, ..*self }
+    }
+    /// Returns the image bilinearly resampled to `width` x `height`.
+    pub fn resize(&self, width: u32, height: u32) -> Image {
+        let c     = self.channels;
+        let src   = self.to_linear(false);
+        let sx    = self.width  as f32 / width  as f32;
+        let sy    = self.height as f32 / height as f32;
+        let max_x = self.width  as f32 - 1.0;
+        let max_y = self.height as f32 - 1.0;
+        let mut dst = Vec::with_capacity(width as usize * height as usize * c);
+
+        for y in 0..height {
+            let fy = ((y as f32 + 0.5) * sy - 0.5).clamp(0.0, max_y);
+            let y0 = fy as usize;
+            let y1 = (y0 + 1).min(self.height as usize - 1);
+            let ty = fy - y0 as f32;
+
+            for x in 0..width {
+                let fx = ((x as f32 + 0.5) * sx - 0.5).clamp(0.0, max_x);
+                let x0 = fx as usize;
+                let x1 = (x0 + 1).min(self.width as usize - 1);
+                let tx = fx - x0 as f32;
+
+                for ch in 0..c {
+                    let at = |x: usize, y: usize| {
+                        src[(y * self.width as usize + x) * c + ch]
+                    };
+                    let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+                    let bot = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+                    dst.push(top * (1.0 - ty) + bot * ty);
+                }
+            }
+        }
+        Image::from_linear(width, height, c, &dst, false)
+    }
+    /// Returns the next mip level; half the size on each axis that isn't
+    /// already 1. With `gamma_correct` the color channels are filtered in
+    /// linear space rather than sRGB.
+    pub fn downsample(&self, filter: MipmapFilter, gamma_correct: bool)
+        -> Image
+    {
+        let weights = filter_weights(filter);
+        let (mut w, mut h) = (self.width as usize, self.height as usize);
+        let mut texels = self.to_linear(gamma_correct);
+
+        if w > 1 {
+            texels = downsample_axis(&texels, w, h, self.channels,
+                                     true, &weights);
+            w /= 2;
+        }
+        if h > 1 {
+            texels = downsample_axis(&texels, w, h, self.channels,
+                                     false, &weights);
+            h /= 2;
+        }
+        Image::from_linear(w as u32, h as u32, self.channels, &texels,
+                           gamma_correct)
+    }
+    /// Returns mip levels 1..n, down to 1x1.
+    pub fn mipmaps(&self, filter: MipmapFilter, gamma_correct: bool)
+        -> Vec<Image>
+    {
+        let mut levels: Vec<Image> = vec![];
+
+        while {
+            let last = levels.last().unwrap_or(self);
+            last.width > 1 || last.height > 1
+        } {
+            let next = levels.last()
+                             .unwrap_or(self)
+                             .downsample(filter, gamma_correct);
+            levels.push(next);
+        }
+        levels
+    }
+    fn is_alpha(&self, channel: usize) -> bool {
+        (self.channels == 2 || self.channels == 4)
+            && channel == self.channels - 1
+    }
+    fn to_linear(&self, gamma_correct: bool) -> Vec<f32> {
+        self.pixels.iter()
+                   .enumerate()
+                   .map(|(i, &p)| {
+                       let v = p as f32 / 255.0;
+                       if gamma_correct && !self.is_alpha(i % self.channels) {
+                           srgb_to_linear(v)
+                       } else {
+                           v
+                       }
+                   })
+                   .collect()
+    }
+    fn from_linear(width         : u32,
+                   height        : u32,
+                   channels      : usize,
+                   texels        : &[f32],
+                   gamma_correct : bool) -> Image
+    {
+        let mut image = Image { width, height, channels, pixels: vec![] };
+
+        image.pixels = texels.iter()
+                             .enumerate()
+                             .map(|(i, &v)| {
+                                 let v = v.clamp(0.0, 1.0);
+                                 let v = if gamma_correct
+                                         && !image.is_alpha(i % channels)
+                                         { linear_to_srgb(v) }
+                                         else { v };
+                                 (v * 255.0 + 0.5) as u8
+                             })
+                             .collect();
+        image
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Zeroth order modified Bessel function of the first kind.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum  = 1.0;
+    let mut term = 1.0;
+    let mut k    = 1.0;
+
+    while term > sum * 1e-7 {
+        term *= (x * 0.5 / k) * (x * 0.5 / k);
+        sum  += term;
+        k    += 1.0;
+    }
+    sum
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 { 1.0 } else { (PI * x).sin() / (PI * x) }
+}
+
+/// Returns (source offset from 2 * x, weight) pairs for a 2:1 downsample.
+fn filter_weights(filter: MipmapFilter) -> Vec<(isize, f32)> {
+    const KAISER_WIDTH : f32 = 2.0;
+    const KAISER_ALPHA : f32 = 4.0;
+
+    match filter {
+        MipmapFilter::Box => vec![(0, 0.5), (1, 0.5)],
+        MipmapFilter::Kaiser => {
+            // Taps are measured in destination texels from the center of the
+            // destination texel, which sits between source texels 0 and 1.
+            let mut weights: Vec<(isize, f32)> =
+                (-3..=4).map(|k: isize| {
+                    let x = (k as f32 - 0.5) * 0.5;
+                    let r = x / KAISER_WIDTH;
+                    let w = bessel_i0(KAISER_ALPHA * (1.0 - r * r).sqrt())
+                          / bessel_i0(KAISER_ALPHA);
+                    (k, sinc(x) * w)
+                })
+                .collect();
+            let total: f32 = weights.iter().map(|(_, w)| w).sum();
+
+            for (_, w) in weights.iter_mut() {
+                *w /= total;
+            }
+            weights
+        },
+    }
+}
+
+fn downsample_axis(src        : &[f32],
+                   width      : usize,
+                   height     : usize,
+                   channels   : usize,
+                   horizontal : bool,
+                   weights    : &[(isize, f32)]) -> Vec<f32>
+{
+    let (dw, dh) = if horizontal { (width / 2, height) }
+                   else          { (width, height / 2) };
+    let len      = if horizontal { width } else { height } as isize;
+    let mut dst  = vec![0.0; dw * dh * channels];
+
+    for y in 0..dh {
+        for x in 0..dw {
+            for &(k, w) in weights {
+                // Clamp taps that fall outside the image onto its edge.
+                let (sx, sy) = if horizontal {
+                    (((2 * x) as isize + k).clamp(0, len - 1) as usize, y)
+                } else {
+                    (x, ((2 * y) as isize + k).clamp(0, len - 1) as usize)
+                };
+                let s = (sy * width + sx) * channels;
+                let d = (y  * dw    + x ) * channels;
+
+                for c in 0..channels {
+                    dst[d + c] += src[s + c] * w;
+                }
+            }
+        }
+    }
+    dst
+}
@@ -1,26 +1,66 @@
 #![allow(unused_must_use, unused_imports, dead_code, unused_variables)]
 
+mod archive;
+mod asset_cache;
+mod asset_manifest;
+mod atlas;
+mod backend;
+mod capabilities;
 mod error;
 mod gfx;
+mod gl_state;
+mod image;
+mod light;
+mod material;
 mod matrix;
 mod memory;
+mod memory_source;
+mod post_process;
 mod program;
+mod recording_backend;
+mod render_state;
+mod render_target;
 mod shader;
+mod shadow;
+mod skybox;
 mod texture;
+mod texture_manager;
 mod types;
 mod utils;
 mod vector;
+mod vertex_array;
+mod webgl_context;
 
+pub use crate::archive::*;
+pub use crate::asset_cache::*;
+pub use crate::asset_manifest::*;
+pub use crate::atlas::*;
+pub use crate::backend::*;
+pub use crate::capabilities::*;
 pub use crate::error::*;
 pub use crate::gfx::*;
+pub use crate::gl_state::*;
+pub use crate::image::*;
+pub use crate::light::*;
+pub use crate::material::*;
 pub use crate::matrix::*;
 pub use crate::memory::*;
+pub use crate::memory_source::*;
+pub use crate::post_process::*;
 pub use crate::program::*;
+pub use crate::recording_backend::*;
+pub use crate::render_state::*;
+pub use crate::render_target::*;
 pub use crate::shader::*;
+pub use crate::shadow::*;
+pub use crate::skybox::*;
 pub use crate::texture::*;
+pub use crate::texture_manager::*;
 pub use crate::types::*;
 pub use crate::utils::*;
 pub use crate::vector::*;
+pub use crate::vertex_array::*;
+pub use crate::webgl_context::*;
 
 
 
@@ -0,0 +1,465 @@
+use std::sync::Arc;
+
+use crate::backend::GlBackend;
+use crate::error::GfxError;
+use crate::matrix::Mat4;
+use crate::program::Program;
+use crate::program::UniformValue;
+use crate::types::DEG_TO_RAD;
+use crate::vector::Vec3;
+use crate::vector::Vec4;
+
+/// The most lights a `LightList` holds, and the length of the light arrays
+/// in `LIGHTS_GLSL`.
+pub const MAX_LIGHTS : usize = 8;
+
+/// How a positional light fades with distance `d`, dividing its color by
+/// `constant + linear * d + quadratic * d * d`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant  : f32,
+    pub linear    : f32,
+    pub quadratic : f32,
+}
+
+impl Attenuation {
+    /// No fading.
+    pub const NONE : Attenuation = Attenuation { constant  : 1.0,
+                                                 linear    : 0.0,
+                                                 quadratic : 0.0 };
+
+    pub const fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation { constant, linear, quadratic }
+    }
+    /// Fading to about 1% of the light's color at distance `range`.
+    pub fn range(range: f32) -> Self {
+        Attenuation::new(1.0, 4.5 / range, 75.0 / (range * range))
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::NONE
+    }
+}
+
+/// Where a light is and which way it shines.
+#[derive(Clone, Copy, Debug)]
+pub enum LightKind {
+    /// Shining along `direction` from infinitely far, like the sun.
+    Directional { direction: Vec3 },
+    /// Shining every way from `position`.
+    Point { position: Vec3 },
+    /// Shining from `position` along `direction` in a cone, fully lit
+    /// within `inner_angle` degrees of the axis and fading out to
+    /// `outer_angle`.
+    Spot {
+        position    : Vec3,
+        direction   : Vec3,
+        inner_angle : f32,
+        outer_angle : f32,
+    },
+}
+
+/// A light source in world space, as collected into a `LightList`.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind        : LightKind,
+    pub color       : [f32; 3],
+    pub intensity   : f32,
+    /// Ignored by directional lights.
+    pub attenuation : Attenuation,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3) -> Self {
+        Light::with_kind(LightKind::Directional { direction })
+    }
+    pub fn point(position: Vec3, attenuation: Attenuation) -> Self {
+        Light { attenuation, ..Light::with_kind(LightKind::Point { position }) }
+    }
+    pub fn spot(position    : Vec3,
+                direction   : Vec3,
+                inner_angle : f32,
+                outer_angle : f32,
+                attenuation : Attenuation) -> Self
+    {
+        Light {
+            attenuation,
+            ..Light::with_kind(LightKind::Spot { position,
+                                                 direction,
+                                                 inner_angle,
+                                                 outer_angle })
+        }
+    }
+    fn with_kind(kind: LightKind) -> Self {
+        Light { kind,
+                color       : [1.0, 1.0, 1.0],
+                intensity   : 1.0,
+                attenuation : Attenuation::NONE }
+    }
+    pub fn color(mut self, color: [f32; 3]) -> Self {
+        self.color = color;
+        self
+    }
+    pub fn intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+}
+
+fn to_view(v: &Vec3, w: f32, view: &Mat4) -> Vec3 {
+    Vec4::new(v.x, v.y, v.z, w).multiply_mat4(view).into()
+}
+
+/// The lights of a frame, cleared and refilled each frame and uploaded with
+/// `Program::set_lights`. Shaders declare the uniforms of `LIGHTS_GLSL`:
+///
+/// * `LIGHTCOUNT`, the number of lights, and `AMBIENT`, the ambient color.
+/// * `LIGHTPOSITION[i]`, the view space position with w 1, or for
+///   directional lights the direction toward the light with w 0.
+/// * `LIGHTCOLOR[i]`, the color scaled by intensity.
+/// * `LIGHTDIRECTION[i]`, the view space direction spot lights shine in.
+/// * `LIGHTATTENUATION[i]`, the constant, linear and quadratic terms.
+/// * `LIGHTCONE[i]`, the cosines of spot lights' outer and inner angles,
+///   which leave other lights uncut.
+#[derive(Clone, Debug, Default)]
+pub struct LightList {
+    lights  : Vec<Light>,
+    ambient : [f32; 3],
+}
+
+impl LightList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds `light`, returning false if the list already holds `MAX_LIGHTS`.
+    pub fn push(&mut self, light: Light) -> bool {
+        if self.lights.len() == MAX_LIGHTS {
+            return false;
+        }
+        self.lights.push(light);
+        true
+    }
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+    #[inline]
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+    #[inline]
+    pub fn lights_mut(&mut self) -> &mut [Light] {
+        &mut self.lights
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.ambient = ambient;
+    }
+    #[inline]
+    pub fn ambient(&self) -> [f32; 3] {
+        self.ambient
+    }
+    /// Returns the uniforms the lights set, in view space for the camera's
+    /// `view` matrix, by name.
+    pub fn uniforms(&self, view: &Mat4) -> Vec<(String, UniformValue)> {
+        let mut uniforms = vec![
+            ("LIGHTCOUNT".to_string(), UniformValue::Int(self.len() as i32)),
+            ("AMBIENT".to_string(),    UniformValue::Vec3(self.ambient)),
+        ];
+        for (i, light) in self.lights.iter().enumerate() {
+            let (position, direction, cone) = match light.kind {
+                LightKind::Directional { direction } => {
+                    let d = to_view(&direction, 0.0, view)
+                                .normalized();
+                    ([-d.x, -d.y, -d.z, 0.0], [0.0; 3], [-2.0, -1.0])
+                },
+                LightKind::Point { position } => {
+                    let p = to_view(&position, 1.0, view);
+                    ([p.x, p.y, p.z, 1.0], [0.0; 3], [-2.0, -1.0])
+                },
+                LightKind::Spot { position, direction, inner_angle,
+                                  outer_angle } => {
+                    let p     = to_view(&position, 1.0, view);
+                    let d     = to_view(&direction, 0.0, view)
+                                    .normalized();
+                    let outer = (outer_angle * DEG_TO_RAD).cos();
+                    // The fade needs the inner cosine strictly above.
+                    let inner = (inner_angle * DEG_TO_RAD).cos()
+                                                          .max(outer + 1e-4);
+                    ([p.x, p.y, p.z, 1.0], [d.x, d.y, d.z], [outer, inner])
+                },
+            };
+            let attenuation = match light.kind {
+                LightKind::Directional { .. } => Attenuation::NONE,
+                _                             => light.attenuation,
+            };
+            let [r, g, b] = light.color;
+            let s         = light.intensity;
+
+            uniforms.extend([
+                (format!("LIGHTPOSITION[{}]", i), position.into()),
+                (format!("LIGHTCOLOR[{}]", i), [r * s, g * s, b * s].into()),
+                (format!("LIGHTDIRECTION[{}]", i), direction.into()),
+                (format!("LIGHTATTENUATION[{}]", i),
+                 [attenuation.constant, attenuation.linear,
+                  attenuation.quadratic].into()),
+                (format!("LIGHTCONE[{}]", i), cone.into()),
+            ]);
+        }
+        uniforms
+    }
+}
+
+/// The light uniforms `LightList` sets, and `light_incoming`, which returns
+/// the light reaching view space `position` from light `i` and sets `l` to
+/// the unit direction toward it. Loop to `MAX_LIGHTS`, breaking at
+/// `LIGHTCOUNT`, since loop bounds must be constant. Positions are
+/// `FRAGMENT_HIGHP`, defined as for `SHADOW_GLSL`.
+pub const LIGHTS_GLSL: &str = "
+#define MAX_LIGHTS 8
+
+#ifndef FRAGMENT_HIGHP
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+#define FRAGMENT_HIGHP highp
+#else
+#define FRAGMENT_HIGHP mediump
+#endif
+#endif
+
+uniform int LIGHTCOUNT;
+uniform mediump vec3 AMBIENT;
+uniform FRAGMENT_HIGHP vec4 LIGHTPOSITION[ MAX_LIGHTS ];
+uniform mediump vec3 LIGHTCOLOR[ MAX_LIGHTS ];
+uniform mediump vec3 LIGHTDIRECTION[ MAX_LIGHTS ];
+uniform mediump vec3 LIGHTATTENUATION[ MAX_LIGHTS ];
+uniform mediump vec2 LIGHTCONE[ MAX_LIGHTS ];
+
+mediump vec3 light_incoming( int i, FRAGMENT_HIGHP vec3 position,
+                             out mediump vec3 l ) {
+
+	if( LIGHTPOSITION[ i ].w == 0.0 ) {
+		l = LIGHTPOSITION[ i ].xyz;
+		return LIGHTCOLOR[ i ];
+	}
+	FRAGMENT_HIGHP vec3 to_light = LIGHTPOSITION[ i ].xyz - position;
+	FRAGMENT_HIGHP float d = length( to_light );
+
+	l = to_light / d;
+
+	mediump vec3 k = LIGHTATTENUATION[ i ];
+	mediump float cone = smoothstep( LIGHTCONE[ i ].x, LIGHTCONE[ i ].y,
+	                                 dot( -l, LIGHTDIRECTION[ i ] ) );
+
+	return LIGHTCOLOR[ i ] * cone / ( k.x + k.y * d + k.z * d * d );
+
+}
+";
+
+/// The vertex shader of the shipped lighting models. It reads `POSITION`,
+/// `NORMAL` and `TEXCOORD0` and the matrices `Gfx::set_matrix_uniforms`
+/// sets, and passes on the view space position and normal.
+pub const LIT_VERTEX_SHADER: &str = "
+uniform highp mat4 MODELVIEWPROJECTIONMATRIX;
+uniform highp mat4 MODELVIEWMATRIX;
+uniform mediump mat3 NORMALMATRIX;
+
+attribute highp vec3 POSITION;
+attribute mediump vec3 NORMAL;
+attribute mediump vec2 TEXCOORD0;
+
+varying highp vec3 position;
+varying mediump vec3 normal;
+varying mediump vec2 texcoord;
+
+void main( void ) {
+
+	position = ( MODELVIEWMATRIX * vec4( POSITION, 1.0 ) ).xyz;
+	normal = NORMALMATRIX * NORMAL;
+	texcoord = TEXCOORD0;
+
+	gl_Position = MODELVIEWPROJECTIONMATRIX * vec4( POSITION, 1.0 );
+
+}
+";
+
+const BLINN_PHONG_FRAGMENT_SHADER: &str = "
+uniform lowp vec4 DIFFUSE;
+uniform mediump vec3 SPECULAR;
+uniform mediump float SHININESS;
+
+#ifdef DIFFUSE_MAP
+uniform sampler2D DIFFUSEMAP;
+#endif
+
+varying FRAGMENT_HIGHP vec3 position;
+varying mediump vec3 normal;
+varying mediump vec2 texcoord;
+
+void main( void ) {
+
+	mediump vec4 diffuse = DIFFUSE;
+#ifdef DIFFUSE_MAP
+	diffuse *= texture2D( DIFFUSEMAP, texcoord );
+#endif
+	mediump vec3 n = normalize( normal );
+	mediump vec3 v = normalize( -position );
+	mediump vec3 color = AMBIENT * diffuse.rgb;
+
+	for( int i = 0; i < MAX_LIGHTS; ++i ) {
+		if( i >= LIGHTCOUNT ) {
+			break;
+		}
+		mediump vec3 l;
+		mediump vec3 radiance = light_incoming( i, position, l );
+		mediump vec3 h = normalize( l + v );
+		mediump float n_dot_l = max( dot( n, l ), 0.0 );
+		mediump float spec = n_dot_l > 0.0
+		                   ? pow( max( dot( n, h ), 0.0 ), SHININESS ) : 0.0;
+
+		color += radiance * ( diffuse.rgb * n_dot_l + SPECULAR * spec );
+	}
+	gl_FragColor = vec4( color, diffuse.a );
+
+}
+";
+
+const METALLIC_ROUGHNESS_FRAGMENT_SHADER: &str = "
+uniform lowp vec4 BASECOLOR;
+uniform mediump float METALLIC;
+uniform mediump float ROUGHNESS;
+
+#ifdef BASECOLOR_MAP
+uniform sampler2D BASECOLORMAP;
+#endif
+#ifdef METALLICROUGHNESS_MAP
+// Roughness in green and metalness in blue, as in glTF.
+uniform sampler2D METALLICROUGHNESSMAP;
+#endif
+
+varying FRAGMENT_HIGHP vec3 position;
+varying mediump vec3 normal;
+varying mediump vec2 texcoord;
+
+const mediump float PI = 3.14159265;
+
+void main( void ) {
+
+	mediump vec4 base = BASECOLOR;
+	mediump float metallic = METALLIC;
+	mediump float roughness = ROUGHNESS;
+#ifdef BASECOLOR_MAP
+	base *= texture2D( BASECOLORMAP, texcoord );
+#endif
+#ifdef METALLICROUGHNESS_MAP
+	mediump vec4 mr = texture2D( METALLICROUGHNESSMAP, texcoord );
+	roughness *= mr.g;
+	metallic *= mr.b;
+#endif
+	roughness = clamp( roughness, 0.04, 1.0 );
+
+	mediump vec3 n = normalize( normal );
+	mediump vec3 v = normalize( -position );
+	mediump vec3 f0 = mix( vec3( 0.04 ), base.rgb, metallic );
+	mediump float a2 = roughness * roughness * roughness * roughness;
+	mediump float k = ( roughness + 1.0 ) * ( roughness + 1.0 ) / 8.0;
+	mediump float n_dot_v = max( dot( n, v ), 1e-4 );
+	mediump vec3 color = AMBIENT * base.rgb * ( 1.0 - metallic );
+
+	for( int i = 0; i < MAX_LIGHTS; ++i ) {
+		if( i >= LIGHTCOUNT ) {
+			break;
+		}
+		mediump vec3 l;
+		mediump vec3 radiance = light_incoming( i, position, l );
+		mediump vec3 h = normalize( l + v );
+		mediump float n_dot_l = max( dot( n, l ), 0.0 );
+		mediump float n_dot_h = max( dot( n, h ), 0.0 );
+
+		// GGX distribution, Smith-Schlick geometry and Schlick Fresnel.
+		mediump float dd = n_dot_h * n_dot_h * ( a2 - 1.0 ) + 1.0;
+		mediump float d = a2 / ( PI * dd * dd );
+		mediump float g = n_dot_v / ( n_dot_v * ( 1.0 - k ) + k )
+		                * n_dot_l / ( n_dot_l * ( 1.0 - k ) + k );
+		mediump vec3 f = f0 + ( 1.0 - f0 )
+		               * pow( 1.0 - max( dot( h, v ), 0.0 ), 5.0 );
+
+		mediump vec3 specular = d * g * f / max( 4.0 * n_dot_v * n_dot_l, 1e-4 );
+		mediump vec3 diffuse = ( 1.0 - f ) * ( 1.0 - metallic ) * base.rgb / PI;
+
+		color += ( diffuse + specular ) * radiance * n_dot_l;
+	}
+	gl_FragColor = vec4( color, base.a );
+
+}
+";
+
+/// The lighting models shipped with the crate, as fragment shaders over
+/// `LIT_VERTEX_SHADER` reading the lights of a `LightList`. Both output
+/// linear color, for the tone mapping effect to finish.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadingModel {
+    /// Reads `DIFFUSE`, `SPECULAR` and `SHININESS`, and `DIFFUSEMAP` with
+    /// `DIFFUSE_MAP` defined.
+    BlinnPhong,
+    /// Reads `BASECOLOR`, `METALLIC` and `ROUGHNESS`, and `BASECOLORMAP` and
+    /// `METALLICROUGHNESSMAP` with `BASECOLOR_MAP` and
+    /// `METALLICROUGHNESS_MAP` defined.
+    MetallicRoughness,
+}
+
+impl ShadingModel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShadingModel::BlinnPhong        => "blinn_phong",
+            ShadingModel::MetallicRoughness => "metallic_roughness",
+        }
+    }
+    /// Returns the model named `name`, as `name` returns it.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [ShadingModel::BlinnPhong, ShadingModel::MetallicRoughness]
+            .iter()
+            .copied()
+            .find(|m| m.name() == name)
+    }
+    /// Returns the fragment shader, with `LIGHTS_GLSL` included.
+    pub fn fragment_shader(&self) -> String {
+        use ShadingModel::*;
+
+        let body = match self {
+            BlinnPhong        => BLINN_PHONG_FRAGMENT_SHADER,
+            MetallicRoughness => METALLIC_ROUGHNESS_FRAGMENT_SHADER,
+        };
+        format!("precision mediump float;\n{}{}", LIGHTS_GLSL, body)
+    }
+    /// Compiles and links the model's program with `defines` set in both
+    /// shaders.
+    pub fn program<B: GlBackend>(&self,
+                                 defines : &[&str],
+                                 context : Arc<B>)
+        -> Result<Program<B>, GfxError>
+    {
+        let defines = defines.iter()
+                             .map(|d| format!("#define {}\n", d))
+                             .collect::<String>();
+        let mut program = Program::from_source(
+                              self.name().into(),
+                              &format!("{}{}", defines, LIT_VERTEX_SHADER),
+                              &format!("{}{}", defines,
+                                       self.fragment_shader()),
+                              None, None,
+                              context);
+        if !program.link() {
+            let msg = format!("The {} program failed to link.", self.name());
+            Err( GfxError::new_resource_load_error(msg, None) )?
+        }
+        Ok( program )
+    }
+}
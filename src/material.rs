@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::light::ShadingModel;
+use crate::memory::Memory;
+use crate::program::Program;
+use crate::program::UniformValue;
+use crate::render_state::RenderState;
+use crate::texture::Texture;
+
+/// A texture a `Material` binds, to sampler `unit` of uniform `uniform`.
+pub struct TextureBinding<B: GlBackend = WebGlRenderingContext> {
+    pub uniform : String,
+    pub unit    : u32,
+    pub texture : Rc<Texture<B>>,
+}
+
+/// A program with the textures, uniform values and render state it's drawn
+/// with, applied at once with `apply` before drawing. Materials share
+/// programs and textures, so many can differ in parameters alone.
+pub struct Material<B: GlBackend = WebGlRenderingContext> {
+    name     : String,
+    program  : Rc<Program<B>>,
+    textures : Vec<TextureBinding<B>>,
+    params   : Vec<(String, UniformValue)>,
+    state    : RenderState,
+}
+
+impl<B: GlBackend> Material<B> {
+    /// Creates an opaque material drawn with `program`, which must be
+    /// linked.
+    pub fn new(name: &str, program: Rc<Program<B>>) -> Self {
+        Material {
+            name     : name.into(),
+            program,
+            textures : vec![],
+            params   : vec![],
+            state    : RenderState::OPAQUE,
+        }
+    }
+    /// Binds `texture` to `unit` for sampler `uniform`.
+    pub fn with_texture(mut self,
+                        uniform : &str,
+                        unit    : u32,
+                        texture : Rc<Texture<B>>) -> Self
+    {
+        self.set_texture(uniform, unit, texture);
+        self
+    }
+    pub fn with_param(mut self, name: &str, value: UniformValue) -> Self {
+        self.set_param(name, value);
+        self
+    }
+    pub fn with_state(mut self, state: RenderState) -> Self {
+        self.state = state;
+        self
+    }
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn program(&self) -> &Rc<Program<B>> {
+        &self.program
+    }
+    #[inline]
+    pub fn textures(&self) -> &[TextureBinding<B>] {
+        &self.textures
+    }
+    #[inline]
+    pub fn state(&self) -> &RenderState {
+        &self.state
+    }
+    pub fn set_state(&mut self, state: RenderState) {
+        self.state = state;
+    }
+    /// Binds `texture` to `unit` for sampler `uniform`, replacing what the
+    /// uniform was bound to.
+    pub fn set_texture(&mut self,
+                       uniform : &str,
+                       unit    : u32,
+                       texture : Rc<Texture<B>>)
+    {
+        let binding = TextureBinding { uniform: uniform.into(), unit, texture };
+
+        match self.textures.iter_mut().find(|b| b.uniform == uniform) {
+            Some(b) => *b = binding,
+            None    => self.textures.push(binding),
+        }
+    }
+    /// Returns the texture bound for sampler `uniform`.
+    pub fn texture(&self, uniform: &str) -> Option<&Rc<Texture<B>>> {
+        self.textures
+            .iter()
+            .find(|b| b.uniform == uniform)
+            .map(|b| &b.texture)
+    }
+    /// Sets the value uniform `name` is given, replacing any before.
+    pub fn set_param(&mut self, name: &str, value: UniformValue) {
+        match self.params.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None         => self.params.push((name.into(), value)),
+        }
+    }
+    pub fn param(&self, name: &str) -> Option<UniformValue> {
+        self.params.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+    }
+    #[inline]
+    pub fn params(&self) -> &[(String, UniformValue)] {
+        &self.params
+    }
+    /// Makes the program current, applies the render state, binds the
+    /// textures and uploads the parameters, ready to draw. Uniforms the
+    /// program lacks are skipped. Per-draw uniforms, such as the matrices of
+    /// `Gfx::set_matrix_uniforms`, are left to the caller.
+    pub fn apply(&self, gfx: &Gfx<B>) {
+        let program = &self.program;
+
+        gfx.use_program(Some(program.pid()));
+        gfx.apply_render_state(&self.state);
+
+        for binding in &self.textures {
+            gfx.bind_texture(binding.unit, binding.texture.target(),
+                             Some(binding.texture.tid()));
+            program.set_uniform(&binding.uniform,
+                                &UniformValue::Int(binding.unit as i32));
+        }
+        for (name, value) in &self.params {
+            program.set_uniform(name, value);
+        }
+    }
+}
+
+/// A render state preset named in a material file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatePreset {
+    #[default]
+    Opaque,
+    Transparent,
+    Additive,
+}
+
+impl StatePreset {
+    pub fn render_state(&self) -> RenderState {
+        match self {
+            StatePreset::Opaque      => RenderState::OPAQUE,
+            StatePreset::Transparent => RenderState::TRANSPARENT,
+            StatePreset::Additive    => RenderState::ADDITIVE,
+        }
+    }
+}
+
+/// A texture in a material file, by its name in the `MaterialLibrary`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TextureDesc {
+    pub texture : String,
+    /// The sampler unit; textures without one take the units left free, in
+    /// uniform order.
+    #[serde(default)]
+    pub unit    : Option<u32>,
+}
+
+/// A uniform value in a material file: a number, which sets a float, a list
+/// of 2, 3, 4, 9 or 16 numbers, which sets a vector or matrix, or
+/// `{ "int": n }`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ParamDesc {
+    Float  ( f32 ),
+    Vector ( Vec<f32> ),
+    Int    { int: i32 },
+}
+
+impl ParamDesc {
+    pub fn to_uniform(&self) -> Option<UniformValue> {
+        use UniformValue::*;
+
+        match self {
+            ParamDesc::Float  ( x )   => Some( Float(*x) ),
+            ParamDesc::Int    { int } => Some( Int(*int) ),
+            ParamDesc::Vector ( v )   => match v.len() {
+                2  => Some( Vec2(v[..].try_into().ok()?) ),
+                3  => Some( Vec3(v[..].try_into().ok()?) ),
+                4  => Some( Vec4(v[..].try_into().ok()?) ),
+                9  => Some( Mat3(v[..].try_into().ok()?) ),
+                16 => Some( Mat4(v[..].try_into().ok()?) ),
+                _  => None,
+            },
+        }
+    }
+}
+
+/// A material as read from JSON, resolved against a `MaterialLibrary`:
+///
+/// ```json
+/// { "name": "crate",
+///   "program": "metallic_roughness",
+///   "defines": ["BASECOLOR_MAP"],
+///   "textures": { "BASECOLORMAP": { "texture": "crate.ktx" } },
+///   "params": { "BASECOLOR": [1.0, 1.0, 1.0, 1.0],
+///               "METALLIC": 0.0, "ROUGHNESS": 0.8 },
+///   "state": "opaque",
+///   "double_sided": false }
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct MaterialDesc {
+    pub name         : String,
+    /// A program added to the library, or a `ShadingModel` by name.
+    pub program      : String,
+    /// Defines selecting the program's variant.
+    #[serde(default)]
+    pub defines      : Vec<String>,
+    #[serde(default)]
+    pub textures     : BTreeMap<String, TextureDesc>,
+    #[serde(default)]
+    pub params       : BTreeMap<String, ParamDesc>,
+    #[serde(default)]
+    pub state        : StatePreset,
+    #[serde(default)]
+    pub double_sided : bool,
+}
+
+impl MaterialDesc {
+    pub fn from_json(json: &str) -> Result<Self, MaterialError> {
+        serde_json::from_str(json).map_err(|e| {
+            MaterialError::MaterialFormatError(
+                format!("Material can't be parsed; {}.", e))
+        })
+    }
+}
+
+/// Returns the key a program variant is stored under in a
+/// `MaterialLibrary`: its name, then its defines, sorted, after `#`s.
+pub fn program_variant_key(program: &str, defines: &[String]) -> String {
+    let mut defines = defines.to_vec();
+    defines.sort();
+    defines.iter().fold(program.to_string(), |key, d| key + "#" + d)
+}
+
+/// The programs and textures material files name, and the materials made
+/// from them. Programs of the shipped shading models are built on first
+/// use, one per set of defines.
+pub struct MaterialLibrary<B: GlBackend = WebGlRenderingContext> {
+    programs  : HashMap<String, Rc<Program<B>>>,
+    textures  : HashMap<String, Rc<Texture<B>>>,
+    materials : HashMap<String, Rc<Material<B>>>,
+    context   : Arc<B>,
+}
+
+impl<B: GlBackend> MaterialLibrary<B> {
+    pub fn new(context: Arc<B>) -> Self {
+        MaterialLibrary {
+            programs  : HashMap::new(),
+            textures  : HashMap::new(),
+            materials : HashMap::new(),
+            context,
+        }
+    }
+    /// Adds `program` under `name`, as the variant with no defines; add
+    /// other variants under their `program_variant_key`.
+    pub fn add_program(&mut self, name: &str, program: Rc<Program<B>>) {
+        self.programs.insert(name.into(), program);
+    }
+    pub fn add_texture(&mut self, name: &str, texture: Rc<Texture<B>>) {
+        self.textures.insert(name.into(), texture);
+    }
+    pub fn program(&self, key: &str) -> Option<&Rc<Program<B>>> {
+        self.programs.get(key)
+    }
+    pub fn texture(&self, name: &str) -> Option<&Rc<Texture<B>>> {
+        self.textures.get(name)
+    }
+    pub fn material(&self, name: &str) -> Option<&Rc<Material<B>>> {
+        self.materials.get(name)
+    }
+    /// Creates the material `desc` describes and adds it to the library,
+    /// replacing any of the same name.
+    pub fn create(&mut self, desc: &MaterialDesc)
+        -> Result<Rc<Material<B>>, GfxError>
+    {
+        use MaterialError::*;
+
+        let program = self.program_variant(&desc.program, &desc.defines)?;
+
+        let mut state = desc.state.render_state();
+        if desc.double_sided {
+            state.raster.cull_face = None;
+        }
+        let mut material = Material::new(&desc.name, program)
+                               .with_state(state);
+
+        let mut taken = vec![];
+        for (uniform, texture) in &desc.textures {
+            if let Some(unit) = texture.unit {
+                if taken.contains(&unit) {
+                    let msg = format!("Material ({}) texture ({}) uses unit \
+                                       {}, which another texture uses.",
+                                      desc.name, uniform, unit);
+                    Err( MaterialFormatError(msg) )?
+                }
+                taken.push(unit);
+            }
+        }
+        let mut free = (0..).filter(|u| !taken.contains(u));
+
+        for (uniform, texture) in &desc.textures {
+            let found = match self.textures.get(&texture.texture) {
+                Some(found) => found.clone(),
+                None        => {
+                    let msg = format!("Material ({}) uses texture ({}), which \
+                                       isn't in the library.", desc.name,
+                                      texture.texture);
+                    Err( MissingTexture(msg) )?
+                },
+            };
+            let unit = texture.unit.unwrap_or_else(|| free.next().unwrap());
+            material.set_texture(uniform, unit, found);
+        }
+        for (name, param) in &desc.params {
+            match param.to_uniform() {
+                Some(value) => material.set_param(name, value),
+                None        => {
+                    let msg = format!("Material ({}) parameter ({}) isn't a \
+                                       number, a vector or a matrix.",
+                                      desc.name, name);
+                    Err( MaterialFormatError(msg) )?
+                },
+            }
+        }
+        let material = Rc::new(material);
+        self.materials.insert(desc.name.clone(), material.clone());
+        Ok( material )
+    }
+    /// Parses a material from `json` and creates it.
+    pub fn create_from_json(&mut self, json: &str)
+        -> Result<Rc<Material<B>>, GfxError>
+    {
+        self.create(&MaterialDesc::from_json(json)?)
+    }
+    /// Reads the material file at `url` and creates its material.
+    pub async fn load(&mut self, url: &str)
+        -> Result<Rc<Material<B>>, GfxError>
+    {
+        let memory = Memory::mopen(url).await?;
+        self.create_from_json(memory.as_str())
+    }
+    fn program_variant(&mut self, name: &str, defines: &[String])
+        -> Result<Rc<Program<B>>, GfxError>
+    {
+        let key = program_variant_key(name, defines);
+
+        if let Some(program) = self.programs.get(&key) {
+            return Ok( program.clone() );
+        }
+        let model = match ShadingModel::from_name(name) {
+            Some(model) => model,
+            None        => {
+                let msg = format!("Program ({}) isn't in the material \
+                                   library.", key);
+                Err( MaterialError::MissingProgram(msg) )?
+            },
+        };
+        let defines = defines.iter()
+                             .map(|d| d.as_str())
+                             .collect::<Vec<&str>>();
+        let program = Rc::new(model.program(&defines, self.context.clone())?);
+
+        self.programs.insert(key, program.clone());
+        Ok( program )
+    }
+}
+
+#[derive(Debug)]
+pub enum MaterialError {
+    MaterialFormatError(String),
+    MissingProgram(String),
+    MissingTexture(String),
+}
+
+impl Error for MaterialError {}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        use MaterialError::*;
+        match self {
+            MaterialFormatError ( msg ) => write!(f, "{}", msg),
+            MissingProgram      ( msg ) => write!(f, "{}", msg),
+            MissingTexture      ( msg ) => write!(f, "{}", msg),
+        }
+    }
+}
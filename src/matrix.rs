@@ -30,6 +30,13 @@ impl Mat3 {
     pub fn new() -> Self {
         MAT3_BLANK
     }
+    /// Returns the matrix in the column-major order GL uniforms expect.
+    pub fn to_array(&self) -> [f32; 9] {
+        let [m0, m1, m2] = &self.m;
+        [m0.x, m0.y, m0.z,
+         m1.x, m1.y, m1.z,
+         m2.x, m2.y, m2.z]
+    }
 }
 
 impl Default for Mat3 {
@@ -56,6 +63,14 @@ impl Mat4 {
     pub fn copy_to(&self, dest: &mut Mat4) {
         dest.m = self.m;
     }
+    /// Returns the matrix in the column-major order GL uniforms expect.
+    pub fn to_array(&self) -> [f32; 16] {
+        let [m0, m1, m2, m3] = &self.m;
+        [m0.x, m0.y, m0.z, m0.w,
+         m1.x, m1.y, m1.z, m1.w,
+         m2.x, m2.y, m2.z, m2.w,
+         m3.x, m3.y, m3.z, m3.w]
+    }
     pub fn multiply(&self, m: &Mat4) -> Self {
         let mut mat = Mat4::new();
         
@@ -115,7 +130,7 @@ impl Mat4 {
     
     pub fn rotate(&mut self, v: &Vec4) {
         if v.w == 0.0 || 
-          (v.x == 0.0 && v.y == 0.0 && v.z == 0.0 && v.z == 0.0) 
+          (v.x == 0.0 && v.y == 0.0 && v.z == 0.0) 
         { 
             return; 
         }
@@ -412,6 +427,79 @@ impl Mat4 {
     }
 }
 
+/// Maps clip space, -1 to 1 on each axis, to the 0 to 1 of texture
+/// coordinates and depth, as when looking up a shadow map.
+pub const MAT4_BIAS : Mat4 =
+                Mat4 { m: [Vec4 { x: 0.5, y: 0.0, z: 0.0, w: 0.0 },
+                           Vec4 { x: 0.0, y: 0.5, z: 0.0, w: 0.0 },
+                           Vec4 { x: 0.0, y: 0.0, z: 0.5, w: 0.0 },
+                           Vec4 { x: 0.5, y: 0.5, z: 0.5, w: 1.0 }] };
+
+impl Mat4 {
+    /// Returns a view matrix from `eye` toward `center`, with `up` pointing
+    /// up on screen.
+    pub fn look_at(eye: &Vec3, center: &Vec3, up: &Vec3) -> Self {
+        let f = center.diff(eye).normalized();
+        let s = f.cross(up).normalized();
+        let u = s.cross(&f);
+
+        let mut mat = Mat4::new_identity();
+
+        mat.m[0].x =  s.x;
+        mat.m[1].x =  s.y;
+        mat.m[2].x =  s.z;
+        mat.m[0].y =  u.x;
+        mat.m[1].y =  u.y;
+        mat.m[2].y =  u.z;
+        mat.m[0].z = -f.x;
+        mat.m[1].z = -f.y;
+        mat.m[2].z = -f.z;
+        mat.m[3].x = -s.dot_vec3(eye);
+        mat.m[3].y = -u.dot_vec3(eye);
+        mat.m[3].z =  f.dot_vec3(eye);
+        mat
+    }
+    /// Returns an orthographic projection of the box between the planes.
+    pub fn orthographic(left   : f32,
+                        right  : f32,
+                        bottom : f32,
+                        top    : f32,
+                        near   : f32,
+                        far    : f32) -> Self
+    {
+        let mut mat = Mat4::new_identity();
+        mat.ortho(left, right, bottom, top, near, far);
+        mat
+    }
+    /// Returns a perspective projection with a vertical field of view of
+    /// `fovy` degrees.
+    pub fn perspective(fovy: f32, aspect_ratio: f32, near: f32, far: f32)
+        -> Self
+    {
+        let c = 1.0 / (fovy * 0.5 * DEG_TO_RAD).tan();
+        let d = far - near;
+
+        let mut mat = Mat4::new();
+
+        mat.m[0].x = c / aspect_ratio;
+        mat.m[1].y = c;
+        mat.m[2].z = -(far + near) / d;
+        mat.m[2].w = -1.0;
+        mat.m[3].z = -2.0 * near * far / d;
+        mat
+    }
+    /// Returns the inverse, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut mat = *self;
+        if mat.invert_full() { Some(mat) } else { None }
+    }
+    /// Transforms point `p`, dividing by the resulting w.
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let v = Vec4::new(p.x, p.y, p.z, 1.0).multiply_mat4(self);
+        Vec3::new(v.x / v.w, v.y / v.w, v.z / v.w)
+    }
+}
+
 impl Default for Mat4 {
     fn default() -> Self {
         MAT4_BLANK
@@ -1,7 +1,13 @@
 
 use std::any::Any;
 use std::error::Error;
+use std::convert::TryInto;
 use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -15,43 +21,242 @@ use web_sys::Blob;
 use web_sys::console;
 use web_sys::Response;
 
+use crate::archive::gunzip;
+use crate::archive::Archive;
 use crate::error::*;
+use crate::memory_source::open_source;
+use crate::memory_source::slice_range;
+use crate::memory_source::MopenOptions;
+use crate::memory_source::SourceResponse;
 use crate::utils::jsval_to_string;
 
+/// The first bytes of a gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Returns whether `url`, less any query, names a zip archive.
+fn is_zip(url: &str) -> bool {
+    let path = url.split('?').next().unwrap_or(url);
+    path.to_ascii_lowercase().ends_with(".zip")
+}
+
 #[derive(Debug)]
 pub struct Memory {
-    url         : String,
-    size        : usize,
-    position    : usize,
-    buffer      : Vec<u8>,
+    url           : String,
+    size          : usize,
+    position      : usize,
+    buffer        : Vec<u8>,
+    status        : u16,
+    etag          : Option<String>,
+    last_modified : Option<String>,
 }
 
 impl Memory {
 
+    /// Reads the whole resource at `url` from the source registered for its
+    /// scheme (`http:`, `file:`, `mem:` or one added with
+    /// `register_memory_source`). A `#` after a `.zip` URL separates it from
+    /// the path of a file in the archive, as in
+    /// `pack.zip#shaders/vertex.glsl`; other URLs are fetched as given.
+    /// Files ending in `.gz` are decompressed unless they came already
+    /// decoded, as when served with `Content-Encoding: gzip`.
     pub async fn mopen(url: &str) -> Result<Self, MemoryError>
     {
+        Memory::mopen_with(url, MopenOptions::default()).await
+    }
+    /// Like `mopen`, with progress reporting, cancellation, a timeout, a
+    /// byte range or a conditional request. For archive entries the range
+    /// applies to the entry and the rest to reading the archive.
+    pub async fn mopen_with(url: &str, mut options: MopenOptions)
+        -> Result<Self, MemoryError>
+    {
+        let archive_path = url.split_once('#')
+                              .filter(|(archive_url, _)| is_zip(archive_url));
+        let response = match archive_path {
+            Some((archive_url, path)) => {
+                let range = options.take_range();
+                options.clear_conditions();
+
+                let bytes = Archive::open_with(archive_url, &mut options)
+                                    .await?
+                                    .read(path)?;
+                match range {
+                    Some(range) => SourceResponse {
+                        bytes  : slice_range(bytes, range),
+                        status : 206,
+                        ..Default::default()
+                    },
+                    None => SourceResponse { bytes, status: 200,
+                                             ..Default::default() },
+                }
+            },
+            None => open_source(url, &mut options).await?,
+        };
+        // A range of a gzip file can't be decompressed on its own.
+        let buffer = if url.ends_with(".gz") && response.status != 206
+                        && response.bytes.starts_with(&GZIP_MAGIC)
+        {
+            gunzip(url, &response.bytes)?
+        } else {
+            response.bytes
+        };
+        Ok( Memory { status        : response.status,
+                     etag          : response.etag,
+                     last_modified : response.last_modified,
+                     ..Memory::from_bytes(url, buffer) } )
+    }
+    /// Wraps bytes already in memory; `url` only names them in errors.
+    pub fn from_bytes(url: &str, bytes: Vec<u8>) -> Self
+    {
+        Memory { url: url.into(), size: bytes.len(), position: 0,
+                 buffer: bytes, status: 200, etag: None,
+                 last_modified: None }
+    }
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// Returns the HTTP status the data came with; 206 when it's a range.
+    #[inline]
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+    /// Returns the response's `ETag`, for use with
+    /// `MopenOptions::if_none_match`.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+    /// Returns the response's `Last-Modified` date, for use with
+    /// `MopenOptions::if_modified_since`.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.last_modified.as_deref()
+    }
+    /// Copies bytes from the current position into `buf`, returning how many
+    /// were copied; fewer than `buf.len()` only at the end of the buffer.
+    pub fn mread(&mut self, buf: &mut [u8]) -> usize {
+        let count = buf.len().min(self.size - self.position);
+        buf[..count].copy_from_slice(&self.buffer[self.position..
+                                                  self.position + count]);
+        self.position += count;
+        count
+    }
+    /// Moves the read position, returning the new one. The position can't
+    /// go before the start or past the end of the buffer.
+    pub fn mseek(&mut self, pos: SeekFrom) -> Result<usize, MemoryError> {
+        use MemoryError::SeekError;
+        let target = match pos {
+            SeekFrom::Start(offset)   => offset as i64,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+            SeekFrom::End(offset)     => self.size as i64 + offset,
+        };
+        if target < 0 || target > self.size as i64 {
+            Err( SeekError(self.url.clone(), target) )?
+        }
+        self.position = target as usize;
+        Ok( self.position )
+    }
+    #[inline]
+    pub fn mtell(&self) -> usize {
+        self.position
+    }
+    /// Returns the next `count` bytes and moves past them.
+    pub fn read_bytes(&mut self, count: usize) -> Result<&[u8], MemoryError> {
+        use MemoryError::OutOfBounds;
+        let start = self.position;
+
+        if count > self.size - start {
+            Err( OutOfBounds(self.url.clone(), start, count) )?
+        }
+        self.position += count;
+        Ok( &self.buffer[start..start + count] )
+    }
+    /// Decodes a little endian `T` and moves past it.
+    pub fn read_le<T: FromBytes>(&mut self) -> Result<T, MemoryError> {
+        Ok( T::from_le_bytes(self.read_bytes(T::SIZE)?) )
+    }
+    /// Decodes a big endian `T` and moves past it.
+    pub fn read_be<T: FromBytes>(&mut self) -> Result<T, MemoryError> {
+        Ok( T::from_be_bytes(self.read_bytes(T::SIZE)?) )
+    }
+    /// Decodes `count` consecutive little endian `T`s and moves past them.
+    pub fn read_slice_le<T: FromBytes>(&mut self, count: usize)
+        -> Result<Vec<T>, MemoryError>
+    {
+        let size  = T::SIZE.saturating_mul(count);
+        let bytes = self.read_bytes(size)?;
+        Ok( bytes.chunks_exact(T::SIZE).map(T::from_le_bytes).collect() )
+    }
+    /// Decodes `count` consecutive big endian `T`s and moves past them.
+    pub fn read_slice_be<T: FromBytes>(&mut self, count: usize)
+        -> Result<Vec<T>, MemoryError>
+    {
+        let size  = T::SIZE.saturating_mul(count);
+        let bytes = self.read_bytes(size)?;
+        Ok( bytes.chunks_exact(T::SIZE).map(T::from_be_bytes).collect() )
+    }
+    pub fn read_u8(&mut self) -> Result<u8, MemoryError> {
+        self.read_le()
+    }
+    pub fn read_u16_le(&mut self) -> Result<u16, MemoryError> {
+        self.read_le()
+    }
+    pub fn read_u16_be(&mut self) -> Result<u16, MemoryError> {
+        self.read_be()
+    }
+    pub fn read_u32_le(&mut self) -> Result<u32, MemoryError> {
+        self.read_le()
+    }
+    pub fn read_u32_be(&mut self) -> Result<u32, MemoryError> {
+        self.read_be()
+    }
+    pub fn read_u64_le(&mut self) -> Result<u64, MemoryError> {
+        self.read_le()
+    }
+    pub fn read_u64_be(&mut self) -> Result<u64, MemoryError> {
+        self.read_be()
+    }
+    pub fn read_f32_le(&mut self) -> Result<f32, MemoryError> {
+        self.read_le()
+    }
+    pub fn read_f32_be(&mut self) -> Result<f32, MemoryError> {
+        self.read_be()
+    }
+    /// Reads a NUL terminated string, moving past the terminator.
+    pub fn read_cstr(&mut self) -> Result<&str, MemoryError> {
         use MemoryError::*;
-        
-        let window = web_sys::window().unwrap();
-        let url    = url.to_string();
-        
-        let rsp = JsFuture::from(window.fetch_with_str(&url))
-                  .await
-                  .map_err(|e| FetchError(url.clone(), e))?;
-                  
-        let rsp = rsp.dyn_into::<Response>().unwrap();
-        
-        if !rsp.ok() {
-            Err( FetchStatusError(url.clone(), 
-                                  rsp.status(), 
-                                  rsp.status_text()) )?;
+        let start = self.position;
+        let len   = self.buffer[start..]
+                        .iter()
+                        .position(|&b| b == 0)
+                        .ok_or_else(|| OutOfBounds(self.url.clone(), start,
+                                                   self.size - start + 1))?;
+        self.position += len + 1;
+        std::str::from_utf8(&self.buffer[start..start + len])
+                  .map_err(|_| Utf8Error(self.url.clone(), start))
+    }
+    /// Reads up to the next line feed, moving past it. The line ending,
+    /// `\n` or `\r\n`, isn't included. Returns `None` at the end of the
+    /// buffer.
+    pub fn read_line(&mut self) -> Result<Option<&str>, MemoryError> {
+        use MemoryError::Utf8Error;
+        let start = self.position;
+
+        if start == self.size {
+            return Ok( None );
         }
-        let buf = rsp.array_buffer()        .map_err(|e| DataError(e))?;
-        let buf = JsFuture::from(buf).await .map_err(|e| DataError(e))?;
-        
-        let u8vec: Vec<u8> = js_sys::Uint8Array::new(&buf).to_vec();
-        
-        Ok( Memory { url, size: u8vec.len(), position: 0, buffer: u8vec } )
+        let (end, next) = match self.buffer[start..]
+                                    .iter()
+                                    .position(|&b| b == b'\n')
+        {
+            Some(len) => (start + len, start + len + 1),
+            None      => (self.size, self.size),
+        };
+        self.position = next;
+
+        let line = &self.buffer[start..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        std::str::from_utf8(line)
+                  .map(Some)
+                  .map_err(|_| Utf8Error(self.url.clone(), start))
     }
     pub fn as_str(&self) -> &str 
     {
@@ -61,6 +266,22 @@ impl Memory {
     pub fn bytes(&self) -> &[u8] {
         &self.buffer
     }
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+    /// Decodes a little endian `T` from the start of the buffer without
+    /// moving the read position.
+    pub fn peek_le<T: FromBytes>(&self) -> Result<T, MemoryError> {
+        use MemoryError::OutOfBounds;
+        let bytes = self.buffer.get(..T::SIZE).ok_or_else(|| {
+            OutOfBounds(self.url.clone(), 0, T::SIZE)
+        })?;
+        Ok( T::from_le_bytes(bytes) )
+    }
+    /// # Safety
+    /// The buffer must hold a valid, suitably aligned `T` at its start.
+    #[deprecated(note = "use `peek_le` or `read_le` with a `FromBytes` type")]
+    #[allow(deprecated)]
     pub unsafe fn bytes_as_ref<T>(&self) -> Result<&T, MemoryError> {
         use MemoryError::RefTransmuteError;
         if std::mem::size_of::<T>() > self.buffer.len() {
@@ -68,9 +289,12 @@ impl Memory {
                                    "Target type size is larger than the number \
                                    of bytes in the memory buffer.".into()) )
         } else {
-            Ok( std::mem::transmute::<*const u8, &T>(self.buffer.as_ptr()) )
+            Ok( &*(self.buffer.as_ptr() as *const T) )
         }
     }
+    /// Copies a `T` in native byte order from the start of the buffer.
+    #[deprecated(note = "use `peek_le` with a `FromBytes` type")]
+    #[allow(deprecated)]
     pub fn bytes_copy_into_new<T>(&self) -> Result<T, MemoryError> {
         use MemoryError::RefTransmuteError;
         if std::mem::size_of::<T>() > self.buffer.len() {
@@ -78,31 +302,167 @@ impl Memory {
                                    "Target type size is larger than the number \
                                    of bytes in the memory buffer.".into()) )
         } else {
-            Ok( unsafe { std::mem::transmute_copy(&self.buffer) } )
+            Ok( unsafe {
+                std::ptr::read_unaligned(self.buffer.as_ptr() as *const T)
+            } )
+        }
+    }
+}
+
+/// Plain data types that can be decoded from a byte slice of any alignment.
+/// Structs get an implementation from `from_bytes_struct!`.
+pub trait FromBytes: Sized {
+    /// Number of bytes the encoded value takes.
+    const SIZE: usize;
+
+    /// Decodes from exactly `SIZE` little endian bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+
+    /// Decodes from exactly `SIZE` big endian bytes.
+    fn from_be_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! from_bytes_number {
+    ($($t:ty),*) => {
+        $(
+            impl FromBytes for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn from_le_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+                fn from_be_bytes(bytes: &[u8]) -> Self {
+                    <$t>::from_be_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    }
+}
+
+from_bytes_number!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<T: FromBytes, const N: usize> FromBytes for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        std::array::from_fn(|i| T::from_le_bytes(&bytes[i * T::SIZE..
+                                                         (i + 1) * T::SIZE]))
+    }
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        std::array::from_fn(|i| T::from_be_bytes(&bytes[i * T::SIZE..
+                                                         (i + 1) * T::SIZE]))
+    }
+}
+
+/// Declares a struct and implements `FromBytes` for it, decoding the fields
+/// in declaration order with no padding between them.
+#[macro_export]
+macro_rules! from_bytes_struct {
+    ($(#[$meta:meta])*
+     $vis:vis struct $name:ident {
+         $($(#[$fmeta:meta])* $fvis:vis $field:ident : $ty:ty),* $(,)?
+     }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field : $ty),*
+        }
+        impl $crate::FromBytes for $name {
+            const SIZE: usize = 0 $(+ <$ty as $crate::FromBytes>::SIZE)*;
+
+            fn from_le_bytes(bytes: &[u8]) -> Self {
+                let mut offset = 0;
+                $(
+                    let size   = <$ty as $crate::FromBytes>::SIZE;
+                    let $field = <$ty as $crate::FromBytes>::from_le_bytes(
+                                     &bytes[offset..offset + size]);
+                    offset += size;
+                )*
+                let _ = offset;
+                $name { $($field),* }
+            }
+            fn from_be_bytes(bytes: &[u8]) -> Self {
+                let mut offset = 0;
+                $(
+                    let size   = <$ty as $crate::FromBytes>::SIZE;
+                    let $field = <$ty as $crate::FromBytes>::from_be_bytes(
+                                     &bytes[offset..offset + size]);
+                    offset += size;
+                )*
+                let _ = offset;
+                $name { $($field),* }
+            }
         }
     }
 }
 
+impl Read for Memory {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok( self.mread(buf) )
+    }
+}
+
+impl Seek for Memory {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.mseek(pos)
+            .map(|p| p as u64)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput,
+                                        e.to_string()))
+    }
+}
+
+impl BufRead for Memory {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok( &self.buffer[self.position..] )
+    }
+    fn consume(&mut self, amt: usize) {
+        self.position = (self.position + amt).min(self.size);
+    }
+}
+
 #[derive(Debug)]
 pub enum MemoryError {
     FetchError(String, JsValue),
     FetchStatusError(String, u16, String),
     DataError(JsValue),
+    #[deprecated(note = "only the deprecated `bytes_as_ref` and \
+                         `bytes_copy_into_new` return it")]
     RefTransmuteError(String, String),
+    OutOfBounds(String, usize, usize),
+    SeekError(String, i64),
+    Utf8Error(String, usize),
+    IoError(String, io::Error),
+    NotFound(String),
+    UnsupportedScheme(String),
+    ArchiveError(String, String),
+    Aborted(String),
+    Timeout(String, u32),
+    CacheError(String, JsValue),
+}
+
+impl MemoryError {
+    /// Returns whether a conditional request found the resource unchanged.
+    pub fn is_not_modified(&self) -> bool {
+        matches!(self, MemoryError::FetchStatusError(_, 304, _))
+    }
 }
 
 impl Error for MemoryError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        None
+        use MemoryError::*;
+        match self {
+            IoError(_, e) => Some(e),
+            _ => None,
+        }
     }
 }
 impl fmt::Display for MemoryError {
+    #[allow(deprecated)]
     fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
         use MemoryError::*;
         match self {
             FetchError(url, jsval) => {
                 write!(f, "Fetch from ({}) failed with error ({}).", 
-                       url, jsval_to_string(&jsval))
+                       url, jsval_to_string(jsval))
             },
             FetchStatusError(url, status, status_text) => {
                 write!(f, "Fetch response for ({}) reported status {} ({})", 
@@ -111,12 +471,47 @@ impl fmt::Display for MemoryError {
             DataError(jsval) => {
                 write!(f, "Extracting ArrayBuffer from fetched data \
                        failed with error ({}).", 
-                       jsval_to_string(&jsval))
+                       jsval_to_string(jsval))
             },
             RefTransmuteError(type_name, msg) => {
                 write!(f, "Error converting buffer from &[u8] to {}; {}", 
                        type_name, msg)
             },
+            OutOfBounds(url, offset, count) => {
+                write!(f, "Reading {} bytes at offset {} runs past the end \
+                       of ({}).", count, offset, url)
+            },
+            SeekError(url, offset) => {
+                write!(f, "Seek to offset {} is outside of ({}).",
+                       offset, url)
+            },
+            Utf8Error(url, offset) => {
+                write!(f, "Text at offset {} in ({}) isn't valid utf-8.",
+                       offset, url)
+            },
+            IoError(url, e) => {
+                write!(f, "Reading ({}) failed with error ({}).", url, e)
+            },
+            NotFound(url) => {
+                write!(f, "Nothing is stored at ({}).", url)
+            },
+            Aborted(url) => {
+                write!(f, "Reading ({}) was aborted.", url)
+            },
+            Timeout(url, ms) => {
+                write!(f, "Reading ({}) timed out after {} ms.", url, ms)
+            },
+            ArchiveError(url, msg) => {
+                write!(f, "Archive ({}) can't be read; {}.", url, msg)
+            },
+            CacheError(name, jsval) => {
+                write!(f, "Asset cache ({}) failed with error ({}).",
+                       name, jsval_to_string(jsval))
+            },
+            UnsupportedScheme(url) => {
+                write!(f, "No memory source is registered for the scheme \
+                       of ({}).", url)
+            },
         }
     }
 }
@@ -135,6 +530,7 @@ impl MemoryError {
         match self {
             FetchError(_, v) => &v,
             DataError(v) => &v,
+            CacheError(_, v) => &v,
             _ => panic!("{:?} doesn't have an associated JsValue.", self),
         }
     }
@@ -146,6 +542,15 @@ impl MemoryError {
         match self {
             FetchError(url, _) => &url,
             FetchStatusError(url, _, _) => &url,
+            OutOfBounds(url, _, _) => &url,
+            SeekError(url, _) => &url,
+            Utf8Error(url, _) => &url,
+            IoError(url, _) => &url,
+            NotFound(url) => &url,
+            UnsupportedScheme(url) => &url,
+            ArchiveError(url, _) => &url,
+            Aborted(url) => &url,
+            Timeout(url, _) => &url,
             _ => panic!("{:?} doesn't have an associated URL.", self),
         }
     }
@@ -0,0 +1,537 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+use js_sys::Reflect;
+use js_sys::Uint8Array;
+use web_sys::AbortController;
+use web_sys::AbortSignal;
+use web_sys::Headers;
+use web_sys::ReadableStreamDefaultReader;
+use web_sys::Request;
+use web_sys::RequestInit;
+use web_sys::Response;
+
+use crate::asset_cache::asset_cache;
+use crate::asset_cache::AssetCache;
+use crate::memory::MemoryError;
+
+/// Called with the bytes received so far and, when the server reports it,
+/// the total expected.
+pub type ProgressCallback = Box<dyn FnMut(usize, Option<usize>)>;
+
+#[derive(Default)]
+enum CacheChoice {
+    #[default]
+    Global,
+    Use(AssetCache),
+    Bypass,
+}
+
+/// Optional behavior for `Memory::mopen_with`. Timeouts, abort signals and
+/// conditional requests only apply to fetched URLs; other sources read in
+/// one step and always have their data.
+#[derive(Default)]
+pub struct MopenOptions {
+    progress          : Option<ProgressCallback>,
+    signal            : Option<AbortSignal>,
+    timeout           : Option<u32>,
+    range             : Option<(u64, Option<u64>)>,
+    if_none_match     : Option<String>,
+    if_modified_since : Option<String>,
+    cache             : CacheChoice,
+}
+
+impl MopenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reports download progress to `callback` as the body arrives.
+    pub fn progress<F>(mut self, callback: F) -> Self
+        where F: FnMut(usize, Option<usize>) + 'static
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+    /// Cancels the request when `signal`'s `AbortController` aborts, failing
+    /// it with `MemoryError::Aborted`.
+    pub fn signal(mut self, signal: AbortSignal) -> Self {
+        self.signal = Some(signal);
+        self
+    }
+    /// Fails the request with `MemoryError::Timeout` if it hasn't finished
+    /// after `milliseconds`.
+    pub fn timeout(mut self, milliseconds: u32) -> Self {
+        self.timeout = Some(milliseconds);
+        self
+    }
+    /// Reads only `length` bytes from `offset`, or everything from `offset`
+    /// when `length` is `None`. Fetches send a `Range` header; if the server
+    /// ignores it the region is cut out of the full response. A `length` of
+    /// 0 reads nothing and makes no request.
+    pub fn range(mut self, offset: u64, length: Option<u64>) -> Self {
+        self.range = Some((offset, length));
+        self
+    }
+    /// Asks the server to answer 304 Not Modified, reported as a
+    /// `FetchStatusError`, if the resource still has entity tag `etag`.
+    pub fn if_none_match(mut self, etag: &str) -> Self {
+        self.if_none_match = Some(etag.into());
+        self
+    }
+    /// Asks the server to answer 304 Not Modified, reported as a
+    /// `FetchStatusError`, if the resource hasn't changed since `date`, an
+    /// HTTP date such as a previous response's `Last-Modified`.
+    pub fn if_modified_since(mut self, date: &str) -> Self {
+        self.if_modified_since = Some(date.into());
+        self
+    }
+    /// Reads through `cache` rather than the one set by `set_asset_cache`.
+    /// Unlike that one, it also serves sources other than HTTP.
+    pub fn cache(mut self, cache: AssetCache) -> Self {
+        self.cache = CacheChoice::Use(cache);
+        self
+    }
+    /// Reads from the source even if a cache is set, and doesn't store what
+    /// was read.
+    pub fn no_cache(mut self) -> Self {
+        self.cache = CacheChoice::Bypass;
+        self
+    }
+    /// Returns the cache to read `url` through. Ranges and conditional
+    /// requests always go to the source. The global cache only serves HTTP,
+    /// as files and memory can change without it knowing.
+    fn cache_to_use(&self, url: &str) -> Option<AssetCache> {
+        if self.range.is_some()         ||
+           self.if_none_match.is_some() ||
+           self.if_modified_since.is_some()
+        {
+            return None;
+        }
+        match &self.cache {
+            CacheChoice::Global if is_http(url) => asset_cache(),
+            CacheChoice::Use(cache)             => Some(cache.clone()),
+            _                                   => None,
+        }
+    }
+    /// Removes and returns the range, for callers that apply it themselves.
+    pub(crate) fn take_range(&mut self) -> Option<(u64, Option<u64>)> {
+        self.range.take()
+    }
+    /// Returns the empty response a zero-length range reads, if that's the
+    /// range; HTTP can't ask for no bytes, so there's nothing to fetch.
+    fn empty_range(&mut self) -> Option<SourceResponse> {
+        match self.range {
+            Some((_, Some(0))) => {
+                self.report(0, Some(0));
+                Some( SourceResponse { status: 206, ..Default::default() } )
+            },
+            _ => None,
+        }
+    }
+    pub(crate) fn clear_conditions(&mut self) {
+        self.if_none_match     = None;
+        self.if_modified_since = None;
+    }
+    fn report(&mut self, received: usize, total: Option<usize>) {
+        if let Some(progress) = self.progress.as_mut() {
+            progress(received, total);
+        }
+    }
+}
+
+/// Cuts `range`, as given to `MopenOptions::range`, out of `bytes`.
+pub(crate) fn slice_range(bytes: Vec<u8>, range: (u64, Option<u64>))
+    -> Vec<u8>
+{
+    let start = (range.0 as usize).min(bytes.len());
+    let end   = match range.1 {
+        Some(length) => start.saturating_add(length as usize).min(bytes.len()),
+        None         => bytes.len(),
+    };
+    if start == 0 && end == bytes.len() {
+        bytes
+    } else {
+        bytes[start..end].to_vec()
+    }
+}
+
+/// What a source read, with the HTTP details needed to validate or resume
+/// it later. Sources that aren't HTTP report 200, or 206 for a range, and
+/// no validators.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceResponse {
+    pub bytes         : Vec<u8>,
+    pub status        : u16,
+    pub etag          : Option<String>,
+    pub last_modified : Option<String>,
+}
+
+/// Reads `url` from the asset cache if it's there, or from its source,
+/// storing it in the cache for next time. The cache failing only costs the
+/// download, so its errors are ignored.
+pub(crate) async fn open_source(url: &str, options: &mut MopenOptions)
+    -> Result<SourceResponse, MemoryError>
+{
+    if let Some(response) = options.empty_range() {
+        return Ok( response );
+    }
+    let cache = options.cache_to_use(url);
+
+    if let Some(cache) = &cache {
+        if let Ok(Some(bytes)) = cache.get(url).await {
+            options.report(bytes.len(), Some(bytes.len()));
+            return Ok( SourceResponse { bytes, status: 200,
+                                        ..Default::default() } );
+        }
+    }
+    let response = memory_source_for(url)?.open_with(url, options).await?;
+
+    if let Some(cache) = &cache {
+        if response.status == 200 {
+            cache.put(url, &response.bytes).await.ok();
+        }
+    }
+    Ok( response )
+}
+
+/// Somewhere `Memory::mopen` can read bytes from. Sources are picked by the
+/// scheme of the URL being opened; see `register_memory_source`.
+pub trait MemorySource: Send + Sync {
+    fn open<'a>(&'a self, url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>;
+
+    /// Like `open`, honoring `options`. Sources that read in one step only
+    /// need `open`; this reports their progress once, at the end, and cuts
+    /// out the requested range.
+    fn open_with<'a>(&'a self, url: &'a str, options: &'a mut MopenOptions)
+        -> LocalBoxFuture<'a, Result<SourceResponse, MemoryError>>
+    {
+        async move {
+            let bytes = self.open(url).await?;
+            options.report(bytes.len(), Some(bytes.len()));
+
+            let (bytes, status) = match options.range {
+                Some(range) => (slice_range(bytes, range), 206),
+                None        => (bytes, 200),
+            };
+            Ok( SourceResponse { bytes, status, ..Default::default() } )
+        }.boxed_local()
+    }
+}
+
+/// Reads `http:` and `https:` URLs, and relative URLs in the browser, with
+/// `window.fetch`.
+pub struct FetchSource;
+
+impl MemorySource for FetchSource {
+    fn open<'a>(&'a self, url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>
+    {
+        async move {
+            let mut options = MopenOptions::default();
+            Ok( self.open_with(url, &mut options).await?.bytes )
+        }.boxed_local()
+    }
+    fn open_with<'a>(&'a self, url: &'a str, options: &'a mut MopenOptions)
+        -> LocalBoxFuture<'a, Result<SourceResponse, MemoryError>>
+    {
+        async move {
+            use MemoryError::*;
+
+            if let Some(response) = options.empty_range() {
+                return Ok( response );
+            }
+            let window     = web_sys::window().unwrap();
+            let controller = AbortController::new()
+                             .map_err(|e| FetchError(url.into(), e))?;
+            let timed_out  = Rc::new(Cell::new(false));
+
+            // Both the caller's signal and the timer abort the request
+            // through our own controller.
+            let on_abort = Closure::<dyn FnMut()>::new({
+                let controller = controller.clone();
+                move || controller.abort()
+            });
+            let on_timeout = Closure::<dyn FnMut()>::new({
+                let controller = controller.clone();
+                let timed_out  = timed_out.clone();
+                move || {
+                    timed_out.set(true);
+                    controller.abort();
+                }
+            });
+            if let Some(signal) = &options.signal {
+                if signal.aborted() {
+                    Err( Aborted(url.into()) )?
+                }
+                signal.add_event_listener_with_callback(
+                           "abort", on_abort.as_ref().unchecked_ref())
+                      .map_err(|e| FetchError(url.into(), e))?;
+            }
+            let callback = on_timeout.as_ref().unchecked_ref();
+            let timer    = match options.timeout {
+                // setTimeout takes an i32 and fires at once on overflow.
+                Some(ms) => Some(
+                    window.set_timeout_with_callback_and_timeout_and_arguments_0(
+                               callback, ms.min(i32::MAX as u32) as i32)
+                          .map_err(|e| FetchError(url.into(), e))?),
+                None => None,
+            };
+            let result = fetch_body(&window, url, &controller.signal(),
+                                    options).await;
+            if let Some(timer) = timer {
+                window.clear_timeout_with_handle(timer);
+            }
+            if let Some(signal) = &options.signal {
+                signal.remove_event_listener_with_callback(
+                           "abort", on_abort.as_ref().unchecked_ref())
+                      .ok();
+            }
+            match result {
+                Err(_) if timed_out.get() => {
+                    Err( Timeout(url.into(), options.timeout.unwrap_or(0)) )
+                },
+                Err(_) if controller.signal().aborted() => {
+                    Err( Aborted(url.into()) )
+                },
+                result => result,
+            }
+        }.boxed_local()
+    }
+}
+
+/// Fetches `url` and reads its body a chunk at a time so progress can be
+/// reported.
+async fn fetch_body(window  : &web_sys::Window,
+                    url     : &str,
+                    signal  : &AbortSignal,
+                    options : &mut MopenOptions)
+    -> Result<SourceResponse, MemoryError>
+{
+    use MemoryError::*;
+
+    let headers = Headers::new().map_err(|e| FetchError(url.into(), e))?;
+    let set = |name: &str, value: &str| {
+        headers.set(name, value).map_err(|e| FetchError(url.into(), e))
+    };
+    if let Some((offset, length)) = options.range {
+        match length {
+            Some(length) => {
+                // `empty_range` has answered zero lengths already.
+                let last = offset.checked_add(length - 1).ok_or_else(|| {
+                    OutOfBounds(url.into(), offset as usize, length as usize)
+                })?;
+                set("Range", &format!("bytes={}-{}", offset, last))?
+            },
+            None => set("Range", &format!("bytes={}-", offset))?,
+        }
+    }
+    if let Some(etag) = &options.if_none_match {
+        set("If-None-Match", etag)?;
+    }
+    if let Some(date) = &options.if_modified_since {
+        set("If-Modified-Since", date)?;
+    }
+    let init = RequestInit::new();
+    init.set_signal(Some(signal));
+    init.set_headers(headers.as_ref());
+
+    let req = Request::new_with_str_and_init(url, &init)
+              .map_err(|e| FetchError(url.into(), e))?;
+    let rsp = JsFuture::from(window.fetch_with_request(&req))
+              .await
+              .map_err(|e| FetchError(url.into(), e))?;
+
+    let rsp = rsp.dyn_into::<Response>().unwrap();
+
+    if !rsp.ok() {
+        Err( FetchStatusError(url.into(),
+                              rsp.status(),
+                              rsp.status_text()) )?;
+    }
+    let header = |name: &str| rsp.headers().get(name).ok().flatten();
+    let total  = header("Content-Length").and_then(|len| len.parse().ok());
+    let mut response = SourceResponse {
+        bytes         : vec![],
+        status        : rsp.status(),
+        etag          : header("ETag"),
+        last_modified : header("Last-Modified"),
+    };
+    let body = match rsp.body() {
+        Some(body) => body,
+        None       => {
+            options.report(0, total);
+            return Ok( response );
+        },
+    };
+    let reader     = body.get_reader()
+                         .unchecked_into::<ReadableStreamDefaultReader>();
+    let mut buffer = Vec::with_capacity(total.unwrap_or(0));
+
+    loop {
+        let chunk = JsFuture::from(reader.read()).await.map_err(DataError)?;
+        let done  = Reflect::get(&chunk, &"done".into()).map_err(DataError)?;
+
+        if done.as_bool().unwrap_or(true) {
+            break;
+        }
+        let value = Reflect::get(&chunk, &"value".into()).map_err(DataError)?;
+
+        buffer.extend_from_slice(&Uint8Array::new(&value).to_vec());
+        options.report(buffer.len(), total);
+    }
+    // The server sent the whole resource rather than the range; once cut
+    // it's the range, as other sources report.
+    response.bytes = match options.range {
+        Some(range) if response.status != 206 => {
+            response.status = 206;
+            slice_range(buffer, range)
+        },
+        _ => buffer,
+    };
+    Ok( response )
+}
+
+/// Reads `file:` URLs, and relative URLs outside the browser, with
+/// `std::fs`. Both `file:///abs/path` and `file:rel/path` are accepted.
+pub struct FileSource;
+
+impl MemorySource for FileSource {
+    fn open<'a>(&'a self, url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>
+    {
+        async move {
+            let path = url.strip_prefix("file://")
+                          .or_else(|| url.strip_prefix("file:"))
+                          .unwrap_or(url);
+            std::fs::read(path)
+                .map_err(|e| MemoryError::IoError(url.to_string(), e))
+        }.boxed_local()
+    }
+}
+
+/// Serves `mem:` URLs from bytes registered with `insert`, such as assets
+/// embedded with `include_bytes!` or generated by tests.
+pub struct InMemorySource;
+
+fn mem_files() -> &'static Mutex<HashMap<String, Cow<'static, [u8]>>> {
+    static FILES: OnceLock<Mutex<HashMap<String, Cow<'static, [u8]>>>> =
+        OnceLock::new();
+    FILES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn mem_path(url: &str) -> &str {
+    let path = url.strip_prefix("mem:").unwrap_or(url);
+    path.strip_prefix("//").unwrap_or(path)
+}
+
+impl InMemorySource {
+    /// Makes `bytes` readable at `mem:<path>`, replacing what was there.
+    pub fn insert(path: &str, bytes: Vec<u8>) {
+        mem_files().lock()
+                   .unwrap()
+                   .insert(mem_path(path).into(), Cow::Owned(bytes));
+    }
+    /// Like `insert`, without copying bytes that live for the whole program.
+    pub fn insert_static(path: &str, bytes: &'static [u8]) {
+        mem_files().lock()
+                   .unwrap()
+                   .insert(mem_path(path).into(), Cow::Borrowed(bytes));
+    }
+    pub fn remove(path: &str) {
+        mem_files().lock().unwrap().remove(mem_path(path));
+    }
+}
+
+impl MemorySource for InMemorySource {
+    fn open<'a>(&'a self, url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>
+    {
+        async move {
+            mem_files().lock()
+                       .unwrap()
+                       .get(mem_path(url))
+                       .map(|bytes| bytes.to_vec())
+                       .ok_or_else(|| MemoryError::NotFound(url.to_string()))
+        }.boxed_local()
+    }
+}
+
+type SourceMap = HashMap<String, Arc<dyn MemorySource>>;
+
+fn sources() -> &'static Mutex<SourceMap> {
+    static SOURCES: OnceLock<Mutex<SourceMap>> = OnceLock::new();
+    SOURCES.get_or_init(|| {
+        let mut map: SourceMap = HashMap::new();
+        map.insert("mem".into(), Arc::new(InMemorySource));
+
+        // Fetch only exists in the browser, and the file system only outside
+        // it; there `file:` URLs are fetched, which reports the browser's
+        // refusal.
+        if cfg!(target_arch = "wasm32") {
+            map.insert("file".into(),  Arc::new(FetchSource));
+            map.insert("http".into(),  Arc::new(FetchSource));
+            map.insert("https".into(), Arc::new(FetchSource));
+        } else {
+            map.insert("file".into(), Arc::new(FileSource));
+        }
+        Mutex::new(map)
+    })
+}
+
+/// Makes `Memory::mopen` read URLs starting with `<scheme>:` from `source`,
+/// replacing any source already registered for the scheme.
+pub fn register_memory_source(scheme: &str, source: Arc<dyn MemorySource>) {
+    sources().lock().unwrap().insert(scheme.to_ascii_lowercase(), source);
+}
+
+/// Returns the scheme of `url`, if it has one.
+pub(crate) fn url_scheme(url: &str) -> Option<&str> {
+    // A one letter scheme is a Windows drive, not a scheme.
+    url.find(':')
+       .map(|i| &url[..i])
+       .filter(|s| s.len() > 1 && s.chars().all(|c| {
+           c.is_ascii_alphanumeric() || "+-.".contains(c)
+       }))
+}
+
+/// Whether `url` is downloaded over HTTP, as relative URLs are in the
+/// browser.
+fn is_http(url: &str) -> bool {
+    match url_scheme(url) {
+        Some(scheme) => scheme.eq_ignore_ascii_case("http") ||
+                        scheme.eq_ignore_ascii_case("https"),
+        None         => cfg!(target_arch = "wasm32"),
+    }
+}
+
+/// Returns the source for `url`'s scheme. URLs without one are relative;
+/// they're fetched in the browser and read from the file system elsewhere.
+pub(crate) fn memory_source_for(url: &str)
+    -> Result<Arc<dyn MemorySource>, MemoryError>
+{
+    match url_scheme(url) {
+        Some(scheme) => {
+            sources().lock()
+                     .unwrap()
+                     .get(&scheme.to_ascii_lowercase())
+                     .cloned()
+                     .ok_or_else(|| MemoryError::UnsupportedScheme(
+                                        url.to_string()))
+        },
+        None if cfg!(target_arch = "wasm32") => Ok( Arc::new(FetchSource) ),
+        None => Ok( Arc::new(FileSource) ),
+    }
+}
@@ -0,0 +1,785 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::capabilities::GlCapabilities;
+use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::gl_state::untracked_bind;
+use crate::gl_state::Binding;
+use crate::program::Program;
+use crate::program::UniformValue;
+use crate::render_state::DepthState;
+use crate::render_state::RasterState;
+use crate::render_state::RenderState;
+use crate::render_target::DepthBuffer;
+use crate::render_target::RenderTarget;
+use crate::render_target::RenderTargetOptions;
+use crate::texture::Texture;
+
+const FULLSCREEN_VERTEX_SHADER: &str = "
+attribute mediump vec2 POSITION;
+
+varying mediump vec2 texcoord;
+
+void main( void ) {
+
+	texcoord    = POSITION * 0.5 + 0.5;
+	gl_Position = vec4( POSITION, 0.0, 1.0 );
+
+}
+";
+
+const COPY_FRAGMENT_SHADER: &str = "
+uniform lowp sampler2D INPUT;
+
+varying mediump vec2 texcoord;
+
+void main( void ) {
+
+	gl_FragColor = texture2D( INPUT, texcoord );
+
+}
+";
+
+const FXAA_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform vec2      TEXELSIZE;
+uniform float     SPANMAX;
+uniform float     REDUCEMUL;
+
+varying vec2 texcoord;
+
+const float REDUCEMIN = 1.0 / 128.0;
+const vec3  LUMA      = vec3( 0.299, 0.587, 0.114 );
+
+void main( void ) {
+
+	vec4  color = texture2D( INPUT, texcoord );
+	float nw    = dot( texture2D( INPUT, texcoord + vec2( -1.0, -1.0 ) * TEXELSIZE ).rgb, LUMA );
+	float ne    = dot( texture2D( INPUT, texcoord + vec2(  1.0, -1.0 ) * TEXELSIZE ).rgb, LUMA );
+	float sw    = dot( texture2D( INPUT, texcoord + vec2( -1.0,  1.0 ) * TEXELSIZE ).rgb, LUMA );
+	float se    = dot( texture2D( INPUT, texcoord + vec2(  1.0,  1.0 ) * TEXELSIZE ).rgb, LUMA );
+	float m     = dot( color.rgb, LUMA );
+
+	float luma_min = min( m, min( min( nw, ne ), min( sw, se ) ) );
+	float luma_max = max( m, max( max( nw, ne ), max( sw, se ) ) );
+
+	// Blur along the edge, across the luma gradient.
+	vec2 dir = vec2( -( ( nw + ne ) - ( sw + se ) ), ( nw + sw ) - ( ne + se ) );
+
+	float reduce  = max( ( nw + ne + sw + se ) * 0.25 * REDUCEMUL, REDUCEMIN );
+	float rcp_min = 1.0 / ( min( abs( dir.x ), abs( dir.y ) ) + reduce );
+
+	dir = clamp( dir * rcp_min, -SPANMAX, SPANMAX ) * TEXELSIZE;
+
+	vec3 a = 0.5 * ( texture2D( INPUT, texcoord + dir * ( 1.0 / 3.0 - 0.5 ) ).rgb +
+	                 texture2D( INPUT, texcoord + dir * ( 2.0 / 3.0 - 0.5 ) ).rgb );
+	vec3 b = a * 0.5 + 0.25 * ( texture2D( INPUT, texcoord - dir * 0.5 ).rgb +
+	                            texture2D( INPUT, texcoord + dir * 0.5 ).rgb );
+
+	float luma_b = dot( b, LUMA );
+
+	// The wider sample strayed past the local contrast; keep the narrow one.
+	if( luma_b < luma_min || luma_b > luma_max ) {
+		gl_FragColor = vec4( a, color.a );
+	} else {
+		gl_FragColor = vec4( b, color.a );
+	}
+
+}
+";
+
+const TONE_MAPPING_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform float     EXPOSURE;
+uniform float     GAMMA;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec4 color = texture2D( INPUT, texcoord );
+	vec3 c     = color.rgb * EXPOSURE;
+
+	// Narkowicz's fit of the ACES filmic curve.
+	c = clamp( ( c * ( 2.51 * c + 0.03 ) ) / ( c * ( 2.43 * c + 0.59 ) + 0.14 ),
+	           0.0, 1.0 );
+
+	gl_FragColor = vec4( pow( c, vec3( 1.0 / GAMMA ) ), color.a );
+
+}
+";
+
+const COLOR_GRADING_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform sampler2D LUT;
+uniform float     LUTSIZE;
+uniform float     INTENSITY;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec4  color = texture2D( INPUT, texcoord );
+	vec3  c     = clamp( color.rgb, 0.0, 1.0 );
+	float last  = LUTSIZE - 1.0;
+
+	// The LUT is LUTSIZE slices of LUTSIZE by LUTSIZE texels side by side,
+	// red across each slice, green down it and blue from slice to slice.
+	float blue   = c.b * last;
+	float slice0 = floor( blue );
+	float slice1 = min( slice0 + 1.0, last );
+	vec2  uv     = vec2( ( c.r * last + 0.5 ) / ( LUTSIZE * LUTSIZE ),
+	                     ( c.g * last + 0.5 ) / LUTSIZE );
+
+	vec3 graded = mix( texture2D( LUT, uv + vec2( slice0 / LUTSIZE, 0.0 ) ).rgb,
+	                   texture2D( LUT, uv + vec2( slice1 / LUTSIZE, 0.0 ) ).rgb,
+	                   blue - slice0 );
+
+	gl_FragColor = vec4( mix( color.rgb, graded, INTENSITY ), color.a );
+
+}
+";
+
+const VIGNETTE_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform float     INTENSITY;
+uniform float     RADIUS;
+uniform float     SOFTNESS;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec4  color = texture2D( INPUT, texcoord );
+	float edge  = smoothstep( RADIUS, RADIUS - SOFTNESS,
+	                          distance( texcoord, vec2( 0.5 ) ) );
+
+	gl_FragColor = vec4( color.rgb * mix( 1.0, edge, INTENSITY ), color.a );
+
+}
+";
+
+/// One direction of a 9 tap gaussian, in 5 samples by sampling between
+/// texels.
+const BLUR_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform vec2      DIRECTION;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec2 offset1 = DIRECTION * 1.3846153846;
+	vec2 offset2 = DIRECTION * 3.2307692308;
+
+	gl_FragColor = texture2D( INPUT, texcoord ) * 0.2270270270
+	             + texture2D( INPUT, texcoord + offset1 ) * 0.3162162162
+	             + texture2D( INPUT, texcoord - offset1 ) * 0.3162162162
+	             + texture2D( INPUT, texcoord + offset2 ) * 0.0702702703
+	             + texture2D( INPUT, texcoord - offset2 ) * 0.0702702703;
+
+}
+";
+
+const BRIGHT_PASS_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform float     THRESHOLD;
+uniform float     KNEE;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec3  c          = texture2D( INPUT, texcoord ).rgb;
+	float brightness = max( c.r, max( c.g, c.b ) );
+
+	// Fade in over the knee below the threshold rather than cutting off.
+	float soft = clamp( brightness - THRESHOLD + KNEE, 0.0, 2.0 * KNEE );
+	soft       = soft * soft / ( 4.0 * KNEE + 0.0001 );
+
+	float contribution = max( soft, brightness - THRESHOLD ) /
+	                     max( brightness, 0.0001 );
+
+	gl_FragColor = vec4( c * contribution, 1.0 );
+
+}
+";
+
+const BLOOM_COMPOSITE_FRAGMENT_SHADER: &str = "
+precision mediump float;
+
+uniform sampler2D INPUT;
+uniform sampler2D BLOOM;
+uniform float     INTENSITY;
+
+varying vec2 texcoord;
+
+void main( void ) {
+
+	vec4 color = texture2D( INPUT, texcoord );
+
+	gl_FragColor = vec4( color.rgb + texture2D( BLOOM, texcoord ).rgb * INTENSITY,
+	                     color.a );
+
+}
+";
+
+/// One triangle covering the viewport, overhanging it so the visible part
+/// is exactly the screen.
+const FULLSCREEN_VERTICES: [f32; 6] = [-1.0, -1.0,  3.0, -1.0,  -1.0, 3.0];
+
+/// The state effects draw in: no depth, culling or blending.
+const FULLSCREEN_STATE : RenderState = RenderState {
+    depth  : DepthState::DISABLED,
+    raster : RasterState::NO_CULL,
+    ..RenderState::OPAQUE
+};
+
+/// Draws a full-screen triangle with a program whose vertex shader reads
+/// it from `POSITION`, as the effects' shared vertex shader does.
+pub struct FullscreenPass<B: GlBackend = WebGlRenderingContext> {
+    vbo     : Option<B::Buffer>,
+    context : Arc<B>,
+}
+
+impl<B: GlBackend> FullscreenPass<B> {
+    pub fn new(context: Arc<B>) -> Self {
+        use WebGlRenderingContext as Ctx;
+
+        let bytes = FULLSCREEN_VERTICES.iter()
+                                       .flat_map(|v| v.to_le_bytes())
+                                       .collect::<Vec<u8>>();
+        let vbo   = context.create_buffer();
+
+        context.bind_buffer(Ctx::ARRAY_BUFFER, vbo.as_ref());
+        context.buffer_data(Ctx::ARRAY_BUFFER, &bytes, Ctx::STATIC_DRAW);
+        context.bind_buffer(Ctx::ARRAY_BUFFER, None);
+        untracked_bind(Binding::Buffers);
+
+        FullscreenPass { vbo, context }
+    }
+    /// Compiles and links a program of the full-screen vertex shader and
+    /// `fragment_source`, which reads `varying vec2 texcoord`.
+    pub fn program(name            : &str,
+                   fragment_source : &str,
+                   context         : Arc<B>) -> Result<Program<B>, GfxError>
+    {
+        let mut program = Program::from_source(name.into(),
+                                               FULLSCREEN_VERTEX_SHADER,
+                                               fragment_source,
+                                               None, None,
+                                               context);
+        if !program.link() {
+            let msg = format!("The {} program failed to link.", name);
+            Err( GfxError::new_resource_load_error(msg, None) )?
+        }
+        Ok( program )
+    }
+    /// Draws over `output`, or over what's bound if `None`, with `program`,
+    /// which must be current.
+    pub fn draw(&self,
+                gfx     : &Gfx<B>,
+                program : &Program<B>,
+                output  : Option<&RenderTarget<B>>)
+    {
+        use WebGlRenderingContext as Ctx;
+
+        let ctx = &self.context;
+        let pos = program.get_vertex_attrib_location("POSITION") as u32;
+
+        if let Some(target) = output {
+            target.bind(gfx);
+        }
+        gfx.bind_buffer(Ctx::ARRAY_BUFFER, self.vbo.as_ref());
+        ctx.enable_vertex_attrib_array(pos);
+        ctx.vertex_attrib_pointer(pos, 2, Ctx::FLOAT, false, 0, 0);
+
+        gfx.draw_arrays(Ctx::TRIANGLES, 0, 3);
+
+        ctx.disable_vertex_attrib_array(pos);
+        gfx.bind_buffer(Ctx::ARRAY_BUFFER, None);
+
+        if let Some(target) = output {
+            target.unbind(gfx);
+        }
+    }
+    /// Makes `program` current, binds `textures` to units from 0 with their
+    /// samplers pointing at them, uploads `params` and draws. `TEXELSIZE`
+    /// is set from the first texture.
+    fn draw_program(&self,
+                    gfx      : &Gfx<B>,
+                    program  : &Program<B>,
+                    textures : &[(&str, &Texture<B>)],
+                    params   : &[(String, UniformValue)],
+                    output   : Option<&RenderTarget<B>>)
+    {
+        use WebGlRenderingContext as Ctx;
+
+        gfx.use_program(Some(program.pid()));
+
+        for (unit, (name, texture)) in textures.iter().enumerate() {
+            gfx.bind_texture(unit as u32, Ctx::TEXTURE_2D, Some(texture.tid()));
+            program.set_uniform(name, &UniformValue::Int(unit as i32));
+        }
+        if let Some((_, input)) = textures.first() {
+            program.set_uniform("TEXELSIZE",
+                                &UniformValue::Vec2([
+                                    1.0 / input.width().max(1)  as f32,
+                                    1.0 / input.height().max(1) as f32]));
+        }
+        for (name, value) in params {
+            program.set_uniform(name, value);
+        }
+        self.draw(gfx, program, output);
+    }
+}
+
+impl<B: GlBackend> Drop for FullscreenPass<B> {
+    fn drop(&mut self) {
+        if let Some(vbo) = self.vbo.take() {
+            self.context.delete_buffer(&vbo);
+        }
+    }
+}
+
+/// Sets `name` among `params` if it's there, returning whether it was.
+fn set_param(params : &mut [(String, UniformValue)],
+             name   : &str,
+             value  : UniformValue) -> bool
+{
+    match params.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => { *v = value; true },
+        None         => false,
+    }
+}
+
+fn get_param(params: &[(String, UniformValue)], name: &str)
+    -> Option<UniformValue>
+{
+    params.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+}
+
+/// A step of a `PostProcessChain`, drawing one texture into a target.
+pub trait PostEffect<B: GlBackend> {
+    fn name(&self) -> &str;
+    /// Sets parameter `name`, returning whether the effect has it.
+    fn set_param(&mut self, name: &str, value: UniformValue) -> bool;
+    fn param(&self, name: &str) -> Option<UniformValue>;
+    /// Draws `input` through the effect over `output`, or over what's bound
+    /// if `None`.
+    fn apply(&self,
+             gfx    : &Gfx<B>,
+             pass   : &FullscreenPass<B>,
+             input  : &Texture<B>,
+             output : Option<&RenderTarget<B>>);
+}
+
+/// An effect of a single full-screen program. The input is bound to the
+/// `INPUT` sampler and its texel size to `TEXELSIZE`; each parameter is a
+/// uniform of the same name, and extra textures follow the input on units
+/// from 1.
+pub struct ShaderEffect<B: GlBackend = WebGlRenderingContext> {
+    name     : String,
+    program  : Program<B>,
+    params   : Vec<(String, UniformValue)>,
+    textures : Vec<(String, Texture<B>)>,
+}
+
+impl<B: GlBackend> ShaderEffect<B> {
+    pub fn new(name            : &str,
+               fragment_source : &str,
+               context         : Arc<B>) -> Result<Self, GfxError>
+    {
+        Ok( ShaderEffect {
+                name     : name.into(),
+                program  : FullscreenPass::program(name, fragment_source,
+                                                   context)?,
+                params   : vec![],
+                textures : vec![],
+            } )
+    }
+    /// Adds parameter `name` with its initial value.
+    pub fn with_param<V: Into<UniformValue>>(mut self, name: &str, value: V)
+        -> Self
+    {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+    /// Adds a texture, such as a lookup table, sampled through `name`.
+    pub fn with_texture(mut self, name: &str, texture: Texture<B>) -> Self {
+        self.textures.push((name.into(), texture));
+        self
+    }
+    #[inline]
+    pub fn program(&self) -> &Program<B> {
+        &self.program
+    }
+    /// Draws the input unchanged.
+    pub fn copy(context: Arc<B>) -> Result<Self, GfxError> {
+        ShaderEffect::new("copy", COPY_FRAGMENT_SHADER, context)
+    }
+    /// Fast approximate anti-aliasing, best applied last to tone mapped
+    /// colors. `SPANMAX` limits the blur in texels; `REDUCEMUL` keeps it off
+    /// low contrast detail.
+    pub fn fxaa(context: Arc<B>) -> Result<Self, GfxError> {
+        Ok( ShaderEffect::new("fxaa", FXAA_FRAGMENT_SHADER, context)?
+                         .with_param("SPANMAX", 8.0)
+                         .with_param("REDUCEMUL", 1.0 / 8.0) )
+    }
+    /// Maps HDR colors into displayable range with the ACES filmic curve,
+    /// after scaling them by `EXPOSURE`, then gamma corrects by `GAMMA`.
+    pub fn tone_mapping(context: Arc<B>) -> Result<Self, GfxError> {
+        Ok( ShaderEffect::new("tone_mapping", TONE_MAPPING_FRAGMENT_SHADER,
+                              context)?
+                         .with_param("EXPOSURE", 1.0)
+                         .with_param("GAMMA", 2.2) )
+    }
+    /// Recolors through `lut`, a 3D lookup table of `lut_size` slices laid
+    /// side by side in a `lut_size` squared by `lut_size` texture, with
+    /// red across each slice, green down it and blue from slice to slice.
+    /// `INTENSITY` mixes the graded color with the original.
+    pub fn color_grading(lut      : Texture<B>,
+                         lut_size : u32,
+                         context  : Arc<B>) -> Result<Self, GfxError>
+    {
+        Ok( ShaderEffect::new("color_grading", COLOR_GRADING_FRAGMENT_SHADER,
+                              context)?
+                         .with_texture("LUT", lut)
+                         .with_param("LUTSIZE", lut_size as f32)
+                         .with_param("INTENSITY", 1.0) )
+    }
+    /// Darkens toward the corners, from `RADIUS` from the center over
+    /// `SOFTNESS`, in texture coordinates, by up to `INTENSITY`.
+    pub fn vignette(context: Arc<B>) -> Result<Self, GfxError> {
+        Ok( ShaderEffect::new("vignette", VIGNETTE_FRAGMENT_SHADER, context)?
+                         .with_param("INTENSITY", 0.5)
+                         .with_param("RADIUS", 0.75)
+                         .with_param("SOFTNESS", 0.45) )
+    }
+}
+
+impl<B: GlBackend> PostEffect<B> for ShaderEffect<B> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn set_param(&mut self, name: &str, value: UniformValue) -> bool {
+        set_param(&mut self.params, name, value)
+    }
+    fn param(&self, name: &str) -> Option<UniformValue> {
+        get_param(&self.params, name)
+    }
+    fn apply(&self,
+             gfx    : &Gfx<B>,
+             pass   : &FullscreenPass<B>,
+             input  : &Texture<B>,
+             output : Option<&RenderTarget<B>>)
+    {
+        let mut textures = vec![("INPUT", input)];
+        textures.extend(self.textures.iter().map(|(n, t)| (n.as_str(), t)));
+
+        pass.draw_program(gfx, &self.program, &textures, &self.params, output);
+    }
+}
+
+/// A separable gaussian blur, drawn horizontally into a target of its own
+/// then vertically into the output. `RADIUS` spreads the taps, in texels.
+pub struct GaussianBlur<B: GlBackend = WebGlRenderingContext> {
+    program : Program<B>,
+    target  : RenderTarget<B>,
+    radius  : f32,
+}
+
+impl<B: GlBackend> GaussianBlur<B> {
+    /// Creates a blur of `width` by `height` inputs.
+    pub fn new(width        : u32,
+               height       : u32,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        let options = RenderTargetOptions::new().depth(DepthBuffer::None);
+
+        Ok( GaussianBlur {
+                program : FullscreenPass::program("gaussian_blur",
+                                                  BLUR_FRAGMENT_SHADER,
+                                                  context.clone())?,
+                target  : RenderTarget::new("gaussian_blur", width, height,
+                                            &options, capabilities, context)?,
+                radius  : 1.0,
+            } )
+    }
+}
+
+impl<B: GlBackend> PostEffect<B> for GaussianBlur<B> {
+    fn name(&self) -> &str {
+        "gaussian_blur"
+    }
+    fn set_param(&mut self, name: &str, value: UniformValue) -> bool {
+        match (name, value) {
+            ("RADIUS", UniformValue::Float(radius)) => {
+                self.radius = radius;
+                true
+            },
+            _ => false,
+        }
+    }
+    fn param(&self, name: &str) -> Option<UniformValue> {
+        match name {
+            "RADIUS" => Some( UniformValue::Float(self.radius) ),
+            _        => None,
+        }
+    }
+    fn apply(&self,
+             gfx    : &Gfx<B>,
+             pass   : &FullscreenPass<B>,
+             input  : &Texture<B>,
+             output : Option<&RenderTarget<B>>)
+    {
+        let step = |size: u32| self.radius / size.max(1) as f32;
+
+        let horizontal = [("DIRECTION".to_string(),
+                           UniformValue::Vec2([step(input.width()), 0.0]))];
+        pass.draw_program(gfx, &self.program, &[("INPUT", input)],
+                          &horizontal, Some(&self.target));
+
+        let blurred  = self.target.color(0);
+        let vertical = [("DIRECTION".to_string(),
+                         UniformValue::Vec2([0.0, step(blurred.height())]))];
+        pass.draw_program(gfx, &self.program, &[("INPUT", blurred)],
+                          &vertical, output);
+    }
+}
+
+/// Glow around bright areas: what's above `THRESHOLD`, fading in over
+/// `KNEE` below it, is blurred at half resolution by `RADIUS` and added
+/// back scaled by `INTENSITY`.
+pub struct Bloom<B: GlBackend = WebGlRenderingContext> {
+    bright    : Program<B>,
+    composite : Program<B>,
+    blur      : GaussianBlur<B>,
+    target    : RenderTarget<B>,
+    params    : Vec<(String, UniformValue)>,
+}
+
+impl<B: GlBackend> Bloom<B> {
+    /// Creates a bloom of `width` by `height` inputs.
+    pub fn new(width        : u32,
+               height       : u32,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        let (half_width, half_height) = ((width / 2).max(1),
+                                         (height / 2).max(1));
+        let options = RenderTargetOptions::new().depth(DepthBuffer::None);
+
+        Ok( Bloom {
+                bright    : FullscreenPass::program("bloom_bright",
+                                                    BRIGHT_PASS_FRAGMENT_SHADER,
+                                                    context.clone())?,
+                composite : FullscreenPass::program(
+                                "bloom_composite",
+                                BLOOM_COMPOSITE_FRAGMENT_SHADER,
+                                context.clone())?,
+                blur      : GaussianBlur::new(half_width, half_height,
+                                              capabilities, context.clone())?,
+                target    : RenderTarget::new("bloom", half_width,
+                                              half_height, &options,
+                                              capabilities, context)?,
+                params    : vec![("THRESHOLD".into(), 1.0.into()),
+                                 ("KNEE".into(),      0.5.into()),
+                                 ("INTENSITY".into(), 1.0.into())],
+            } )
+    }
+}
+
+impl<B: GlBackend> PostEffect<B> for Bloom<B> {
+    fn name(&self) -> &str {
+        "bloom"
+    }
+    fn set_param(&mut self, name: &str, value: UniformValue) -> bool {
+        set_param(&mut self.params, name, value)
+            || self.blur.set_param(name, value)
+    }
+    fn param(&self, name: &str) -> Option<UniformValue> {
+        get_param(&self.params, name).or_else(|| self.blur.param(name))
+    }
+    fn apply(&self,
+             gfx    : &Gfx<B>,
+             pass   : &FullscreenPass<B>,
+             input  : &Texture<B>,
+             output : Option<&RenderTarget<B>>)
+    {
+        pass.draw_program(gfx, &self.bright, &[("INPUT", input)],
+                          &self.params, Some(&self.target));
+
+        let glow = self.target.color(0);
+        self.blur.apply(gfx, pass, glow, Some(&self.target));
+
+        pass.draw_program(gfx, &self.composite,
+                          &[("INPUT", input), ("BLOOM", glow)],
+                          &self.params, output);
+    }
+}
+
+/// Two render targets of the same size, one read while the other is drawn
+/// into, then swapped.
+pub struct PingPong<B: GlBackend = WebGlRenderingContext> {
+    targets : [RenderTarget<B>; 2],
+    current : Cell<usize>,
+}
+
+impl<B: GlBackend> PingPong<B> {
+    pub fn new(name         : &str,
+               width        : u32,
+               height       : u32,
+               options      : &RenderTargetOptions,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        let target = |i| RenderTarget::new(&format!("{}:{}", name, i),
+                                           width, height, options,
+                                           capabilities, context.clone());
+        Ok( PingPong { targets: [target(0)?, target(1)?],
+                       current: Cell::new(0) } )
+    }
+    /// Returns the target to draw into next.
+    #[inline]
+    pub fn write(&self) -> &RenderTarget<B> {
+        &self.targets[self.current.get()]
+    }
+    /// Returns the target last drawn into, before the last `swap`.
+    #[inline]
+    pub fn read(&self) -> &RenderTarget<B> {
+        &self.targets[1 - self.current.get()]
+    }
+    /// Makes the target drawn into the one read.
+    pub fn swap(&self) {
+        self.current.set(1 - self.current.get());
+    }
+}
+
+struct ChainEntry<B: GlBackend> {
+    effect  : Box<dyn PostEffect<B>>,
+    enabled : bool,
+}
+
+/// Effects applied in order to the scene on its way to the screen. The
+/// scene is drawn between `begin` and `end` into a target of the chain's,
+/// then each enabled effect reads the last one's result, the last drawing
+/// over what was bound before `begin`.
+pub struct PostProcessChain<B: GlBackend = WebGlRenderingContext> {
+    effects : Vec<ChainEntry<B>>,
+    targets : PingPong<B>,
+    pass    : FullscreenPass<B>,
+    copy    : ShaderEffect<B>,
+}
+
+impl<B: GlBackend> PostProcessChain<B> {
+    /// Creates a chain for a `width` by `height` scene, drawn into targets
+    /// with the attachments of `options`; a half float color format keeps
+    /// HDR colors for tone mapping, where `capabilities` has
+    /// `color_buffer_float`.
+    pub fn new(width        : u32,
+               height       : u32,
+               options      : &RenderTargetOptions,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        Ok( PostProcessChain {
+                effects : vec![],
+                targets : PingPong::new("post_process", width, height,
+                                        options, capabilities,
+                                        context.clone())?,
+                pass    : FullscreenPass::new(context.clone()),
+                copy    : ShaderEffect::copy(context)?,
+            } )
+    }
+    /// Appends `effect`, enabled, after the others.
+    pub fn push(&mut self, effect: Box<dyn PostEffect<B>>) {
+        self.effects.push(ChainEntry { effect, enabled: true });
+    }
+    /// Inserts `effect`, enabled, at position `index`.
+    pub fn insert(&mut self, index: usize, effect: Box<dyn PostEffect<B>>) {
+        self.effects.insert(index, ChainEntry { effect, enabled: true });
+    }
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn PostEffect<B>>> {
+        let index = self.effects.iter().position(|e| e.effect.name() == name)?;
+        Some( self.effects.remove(index).effect )
+    }
+    /// Returns the names of the effects, in the order they're applied.
+    pub fn names(&self) -> Vec<&str> {
+        self.effects.iter().map(|e| e.effect.name()).collect()
+    }
+    /// Turns effect `name` on or off, returning whether the chain has it.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.effects.iter_mut().find(|e| e.effect.name() == name) {
+            Some(entry) => { entry.enabled = enabled; true },
+            None        => false,
+        }
+    }
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.effects.iter().any(|e| e.effect.name() == name && e.enabled)
+    }
+    /// Returns effect `name`, to change its parameters.
+    pub fn effect_mut(&mut self, name: &str)
+        -> Option<&mut Box<dyn PostEffect<B>>>
+    {
+        self.effects.iter_mut()
+                    .find(|e| e.effect.name() == name)
+                    .map(|e| &mut e.effect)
+    }
+    /// Returns the target the scene is drawn into between `begin` and `end`.
+    #[inline]
+    pub fn scene_target(&self) -> &RenderTarget<B> {
+        self.targets.write()
+    }
+    /// Directs drawing into the scene target.
+    pub fn begin(&self, gfx: &Gfx<B>) {
+        self.targets.write().bind(gfx);
+    }
+    /// Applies the enabled effects to the scene, the last one drawing over
+    /// what was bound before `begin`, and leaves the opaque render state.
+    pub fn end(&self, gfx: &Gfx<B>) {
+        self.targets.write().unbind(gfx);
+        self.targets.swap();
+
+        gfx.apply_render_state(&FULLSCREEN_STATE);
+
+        let enabled = self.effects.iter()
+                                  .filter(|e| e.enabled)
+                                  .map(|e| &*e.effect)
+                                  .collect::<Vec<&dyn PostEffect<B>>>();
+
+        if enabled.is_empty() {
+            self.copy.apply(gfx, &self.pass, self.targets.read().color(0),
+                            None);
+        }
+        for (i, effect) in enabled.iter().enumerate() {
+            let input = self.targets.read().color(0);
+
+            if i + 1 == enabled.len() {
+                effect.apply(gfx, &self.pass, input, None);
+            } else {
+                effect.apply(gfx, &self.pass, input,
+                             Some(self.targets.write()));
+                self.targets.swap();
+            }
+        }
+        gfx.apply_render_state(&RenderState::OPAQUE);
+    }
+}
@@ -3,22 +3,25 @@ use std::sync::Arc;
 
 use wasm_bindgen_futures::JsFuture;
 
-use web_sys::console;
 use web_sys::WebGlRenderingContext;
-use web_sys::WebGlProgram;
-use web_sys::WebGlUniformLocation;
-
 
+use crate::backend::GlBackend;
+use crate::console_log;
 use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::light::LightList;
+use crate::matrix::Mat3;
+use crate::matrix::Mat4;
 use crate::memory::Memory;
 use crate::shader::Shader;
 use crate::types::MAX_CHAR;
+use crate::vertex_array::VertexArray;
 
-pub struct Uniform {
+pub struct Uniform<B: GlBackend = WebGlRenderingContext> {
     name        : String,
     var_type    : u32,
     //location    : i32,
-    location    : WebGlUniformLocation,
+    location    : B::UniformLocation,
     constant    : u8,
 }
 
@@ -28,29 +31,84 @@ pub struct VertexAttrib {
     location    : i32,
 }
 
-pub type DrawCallback     = dyn Fn(&Program);
+/// A value uploaded to a uniform by name with `Program::set_uniform`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformValue {
+    Int   ( i32       ),
+    Float ( f32       ),
+    Vec2  ( [f32; 2]  ),
+    Vec3  ( [f32; 3]  ),
+    Vec4  ( [f32; 4]  ),
+    Mat3  ( [f32; 9]  ),
+    Mat4  ( [f32; 16] ),
+}
+
+impl From<i32> for UniformValue {
+    fn from(x: i32) -> Self {
+        UniformValue::Int(x)
+    }
+}
+
+impl From<f32> for UniformValue {
+    fn from(x: f32) -> Self {
+        UniformValue::Float(x)
+    }
+}
+
+impl From<[f32; 2]> for UniformValue {
+    fn from(v: [f32; 2]) -> Self {
+        UniformValue::Vec2(v)
+    }
+}
+
+impl From<[f32; 3]> for UniformValue {
+    fn from(v: [f32; 3]) -> Self {
+        UniformValue::Vec3(v)
+    }
+}
+
+impl From<[f32; 4]> for UniformValue {
+    fn from(v: [f32; 4]) -> Self {
+        UniformValue::Vec4(v)
+    }
+}
+
+impl From<&Mat3> for UniformValue {
+    fn from(m: &Mat3) -> Self {
+        UniformValue::Mat3(m.to_array())
+    }
+}
+
+impl From<&Mat4> for UniformValue {
+    fn from(m: &Mat4) -> Self {
+        UniformValue::Mat4(m.to_array())
+    }
+}
+
+pub type DrawCallback<B = WebGlRenderingContext> = dyn Fn(&Program<B>);
 pub type BindAttrCallback = dyn Fn();
 
-pub struct Program {
+pub struct Program<B: GlBackend = WebGlRenderingContext> {
     name                : String,
-    vertex_shader       : Shader,
-    fragment_shader     : Shader,
-    pid                 : Option<WebGlProgram>,
-    uniform_array       : Vec<Uniform>,
+    vertex_shader       : Shader<B>,
+    fragment_shader     : Shader<B>,
+    pid                 : Option<B::Program>,
+    uniform_array       : Vec<Uniform<B>>,
     vertex_attrib_array : Vec<VertexAttrib>,
-    draw_callback       : Option<Box<DrawCallback>>,
+    draw_callback       : Option<Box<DrawCallback<B>>>,
     bind_attr_callback  : Option<Box<BindAttrCallback>>,
-    context             : Arc<WebGlRenderingContext>,
+    vertex_array        : Option<VertexArray<B>>,
+    context             : Arc<B>,
 }
 
-impl Program {
+impl<B: GlBackend> Program<B> {
     
     pub async fn new(name                 : String,
                      vertex_shader_url    : String,
                      fragment_shader_url  : String,
                      bind_attr_callback   : Option<Box<BindAttrCallback>>,
-                     draw_callback        : Option<Box<DrawCallback>>,
-                     context              : Arc<WebGlRenderingContext>,
+                     draw_callback        : Option<Box<DrawCallback<B>>>,
+                     context              : Arc<B>,
                     ) -> Result<Self, GfxError>
     {
         use WebGlRenderingContext as Ctx;
@@ -75,15 +133,50 @@ impl Program {
                 vertex_attrib_array : vec![],
                 draw_callback,
                 bind_attr_callback,
+                vertex_array        : None,
                 context,
             } )
     }
+    /// Creates a program from shader code already in memory, such as the
+    /// shaders built into the crate.
+    pub fn from_source(name                 : String,
+                       vertex_shader_code   : &str,
+                       fragment_shader_code : &str,
+                       bind_attr_callback   : Option<Box<BindAttrCallback>>,
+                       draw_callback        : Option<Box<DrawCallback<B>>>,
+                       context              : Arc<B>,
+                      ) -> Self
+    {
+        use WebGlRenderingContext as Ctx;
+        
+        let mut vert_shader = Shader::new(&format!("{}.vert", name), 
+                                          Ctx::VERTEX_SHADER,
+                                          context.clone());
+        vert_shader.compile(vertex_shader_code);
+        
+        let mut frag_shader = Shader::new(&format!("{}.frag", name),
+                                          Ctx::FRAGMENT_SHADER,
+                                          context.clone());
+        frag_shader.compile(fragment_shader_code);
+        Program {
+            name,
+            vertex_shader       : vert_shader,
+            fragment_shader     : frag_shader,
+            pid                 : None,
+            uniform_array       : vec![],
+            vertex_attrib_array : vec![],
+            draw_callback,
+            bind_attr_callback,
+            vertex_array        : None,
+            context,
+        }
+    }
 
     #[inline]
-    pub fn pid(&self) -> &WebGlProgram {
+    pub fn pid(&self) -> &B::Program {
         self.pid.as_ref().expect("Program pid not set")
     }
-    pub fn set_draw_callback(&mut self, draw_callback: Box<DrawCallback>) {
+    pub fn set_draw_callback(&mut self, draw_callback: Box<DrawCallback<B>>) {
         self.draw_callback = Some(draw_callback);
     }
     pub fn set_bind_attr_callback(&mut self, 
@@ -91,22 +184,39 @@ impl Program {
     {
         self.bind_attr_callback = Some(bind_attr_callback);
     }
-    pub fn draw(&self) {
-        self.context.use_program(self.pid.as_ref());
+    /// Sets the vertex array `draw` binds around the draw callback.
+    pub fn set_vertex_array(&mut self, vertex_array: Option<VertexArray<B>>) {
+        self.vertex_array = vertex_array;
+    }
+    #[inline]
+    pub fn vertex_array(&self) -> Option<&VertexArray<B>> {
+        self.vertex_array.as_ref()
+    }
+    /// Draws, binding the program and vertex array through `gfx` so only
+    /// what isn't already current is bound.
+    pub fn draw(&self, gfx: &Gfx<B>) {
+        gfx.use_program(self.pid.as_ref());
+
+        if let Some(vertex_array) = &self.vertex_array {
+            vertex_array.bind(gfx);
+        }
         if let Some(callback) = &self.draw_callback {
             callback(self);
         }
+        if let Some(vertex_array) = &self.vertex_array {
+            vertex_array.unbind(gfx);
+        }
     }
     fn delete_id(&mut self) {
         if self.pid.is_some() {
-            self.context.delete_program(self.pid.as_ref());
+            self.context.delete_program(self.pid.as_ref().unwrap());
             self.pid = None;
         }
     }
     fn add_vertex_attr(&mut self, name: String, var_type: u32) {
         // TODO - Find out why we're even storing these. These can be retrieved
         //        from the context anyway.
-        let location = self.context.get_attrib_location(self.pid(), &name);
+        let location = self.context.attrib_location(self.pid(), &name);
         self.vertex_attrib_array.push(
             VertexAttrib { 
                 name,
@@ -119,7 +229,7 @@ impl Program {
         //        from the context anyway.
         // TODO - I may need to store loc as-is in the Program struct if the
         //        code below doesn't work for i32. What is 'constant' for?
-        let location = self.context.get_uniform_location(self.pid(), &name)
+        let location = self.context.uniform_location(self.pid(), &name)
                                    .unwrap();
         self.uniform_array.push(
             Uniform {
@@ -130,22 +240,67 @@ impl Program {
             }
         )
     }
-    fn get_vertex_attrib_location(&self, name: &str) -> i32 {
+    pub fn get_vertex_attrib_location(&self, name: &str) -> i32 {
         let attr = self.vertex_attrib_array
                        .iter()
                        .find(|a| a.name == name)
-                       .expect(&format!("{}.{} wasn't found.", 
-                                        self.name, name));
+                       .unwrap_or_else(|| panic!("{}.{} wasn't found.", 
+                                                 self.name, name));
         attr.location
     }
-    fn get_uniform_location(&self, name: &str) -> &WebGlUniformLocation {
+    pub fn get_uniform_location(&self, name: &str) -> &B::UniformLocation {
         let uni = self.uniform_array
                       .iter()
                       .find(|u| u.name == name)
-                      .expect(&format!("{}.{} wasn't found.", 
-                                       self.name, name));
+                      .unwrap_or_else(|| panic!("{}.{} wasn't found.", 
+                                                self.name, name));
         &uni.location
     }
+    /// Returns the location of uniform `name`, or `None` if the program
+    /// has no such active uniform. Arrays are found by their name alone as
+    /// well as by their first element's.
+    pub fn uniform_location(&self, name: &str) -> Option<&B::UniformLocation> {
+        self.uniform_array
+            .iter()
+            .find(|u| u.name == name
+                   || u.name.strip_suffix("[0]") == Some(name))
+            .map(|u| &u.location)
+    }
+    #[inline]
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniform_location(name).is_some()
+    }
+    /// Uploads `value` to uniform `name` of the program, which must be
+    /// current. Returns whether the program has the uniform; uniforms the
+    /// compiler optimized away are skipped.
+    pub fn set_uniform(&self, name: &str, value: &UniformValue) -> bool {
+        use UniformValue::*;
+
+        let location = match self.uniform_location(name) {
+            Some(location) => Some(location),
+            None           => return false,
+        };
+        let ctx = &self.context;
+
+        match value {
+            Int   ( x ) => ctx.uniform1i(location, *x),
+            Float ( x ) => ctx.uniform1f(location, *x),
+            Vec2  ( v ) => ctx.uniform2f(location, v[0], v[1]),
+            Vec3  ( v ) => ctx.uniform3f(location, v[0], v[1], v[2]),
+            Vec4  ( v ) => ctx.uniform4f(location, v[0], v[1], v[2], v[3]),
+            Mat3  ( m ) => ctx.uniform_matrix3fv(location, false, m),
+            Mat4  ( m ) => ctx.uniform_matrix4fv(location, false, m),
+        }
+        true
+    }
+    /// Uploads the lights of `lights` to the uniforms of `LIGHTS_GLSL`, in
+    /// view space for the camera's `view` matrix. The program must be
+    /// current.
+    pub fn set_lights(&self, lights: &LightList, view: &Mat4) {
+        for (name, value) in lights.uniforms(view) {
+            self.set_uniform(&name, &value);
+        }
+    }
     pub fn link(&mut self) -> bool {
         use WebGlRenderingContext as Ctx;
         if self.pid.is_some() {
@@ -156,7 +311,7 @@ impl Program {
         // Create the program.
         self.pid = ctx.create_program();
         
-        let ref pid = self.pid.as_ref().unwrap().clone();
+        let pid = &self.pid.as_ref().unwrap().clone();
         
         // Attach the shaders.
         ctx.attach_shader(pid, self.vertex_shader.sid());
@@ -172,16 +327,15 @@ impl Program {
         #[cfg(debug_assertions)]
         {
             // If debug build, print out any diagnostic info that just happened.
-            if let Some(log) = ctx.get_program_info_log(pid) {
+            if let Some(log) = ctx.program_info_log(pid) {
                 if !log.is_empty() {
-                    let msg = format!("[ {} ]\n", self.name);
-                    console::log_2(&msg.into(), &log.into());
+                    console_log!("[ {} ]\n{}", self.name, log);
                 }
             }
         }
         // Check the link status and exit with 'false' if there was a failure.
-        let status = ctx.get_program_parameter(pid, Ctx::LINK_STATUS);
-        if status.is_falsy() {
+        let status = ctx.program_parameter(pid, Ctx::LINK_STATUS);
+        if status == 0 {
             self.delete_id();
             false
         } else {
@@ -192,30 +346,44 @@ impl Program {
     fn set_var_vectors(&mut self) {
         use WebGlRenderingContext as Ctx;
         
-        let ref pid = self.pid.as_ref().unwrap().clone();
+        let pid = &self.pid.as_ref().unwrap().clone();
         let     ctx = self.context.clone();
 
         
         // Get the number of attributes and add them to the attribute array.
-        let nattr = ctx.get_program_parameter(pid, Ctx::ACTIVE_ATTRIBUTES);
-        let nattr = nattr.as_f64().unwrap() as u32;
+        let nattr = ctx.program_parameter(pid, Ctx::ACTIVE_ATTRIBUTES) as u32;
         
         for i in 0..nattr {
-            let attrib = ctx.get_active_attrib(pid, i).unwrap();
-            self.add_vertex_attr(attrib.name(), attrib.type_());
+            let attrib = ctx.active_attrib(pid, i).unwrap();
+            self.add_vertex_attr(attrib.name, attrib.var_type);
         }
         // Get the number of uniforms and add them to the uniform array.
-        let nuni = ctx.get_program_parameter(pid, Ctx::ACTIVE_UNIFORMS);
-        let nuni = nuni.as_f64().unwrap() as u32;
+        let nuni = ctx.program_parameter(pid, Ctx::ACTIVE_UNIFORMS) as u32;
         
         for i in 0..nuni {
-            let uni = ctx.get_active_uniform(pid, i).unwrap();
-            self.add_uniform(uni.name(), uni.type_());
+            let uni = ctx.active_uniform(pid, i).unwrap();
+
+            // Arrays are reported by their first element; look up the rest
+            // so each can be set by its own name.
+            if let Some(base) = uni.name.strip_suffix("[0]") {
+                for j in 1..uni.size {
+                    let name = format!("{}[{}]", base, j);
+                    if let Some(location) = ctx.uniform_location(pid, &name) {
+                        self.uniform_array.push(Uniform {
+                            name,
+                            var_type : uni.var_type,
+                            location,
+                            constant : 0,
+                        });
+                    }
+                }
+            }
+            self.add_uniform(uni.name, uni.var_type);
         }
     }
 }
 
-impl Drop for Program {
+impl<B: GlBackend> Drop for Program<B> {
     fn drop(&mut self) {
         self.delete_id();
     }
@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use wasm_bindgen::prelude::*;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::ActiveInfo;
+use crate::backend::GlBackend;
+use crate::backend::GlVersion;
+
+#[derive(Default)]
+struct RecordingState {
+    calls            : Vec<String>,
+    next_id          : u32,
+    compile_results  : VecDeque<(bool, String)>,
+    link_results     : VecDeque<(bool, String)>,
+    compile_status   : HashMap<u32, (bool, String)>,
+    link_status      : HashMap<u32, (bool, String)>,
+    attribs          : Vec<ActiveInfo>,
+    uniforms         : Vec<ActiveInfo>,
+    errors           : VecDeque<u32>,
+    strings          : HashMap<u32, String>,
+    floats           : HashMap<u32, f32>,
+    extensions       : Vec<String>,
+    version          : Option<GlVersion>,
+    framebuffer      : Option<u32>,
+}
+
+/// A `GlBackend` that draws nothing. It records each call as text, such as
+/// `"bind_texture(3553, Some(2))"`, and answers queries from results
+/// scripted beforehand, so native tests can check the calls code makes.
+/// Objects are numbered from 1 in the order they're created.
+///
+/// Unless scripted otherwise, the backend is WebGL1, shaders compile,
+/// programs link with no active attributes or uniforms, no extensions exist
+/// and `get_error` reports `NO_ERROR`.
+#[derive(Default)]
+pub struct RecordingBackend {
+    state : Mutex<RecordingState>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the calls made so far.
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+    /// Returns the calls made so far and forgets them.
+    pub fn take_calls(&self) -> Vec<String> {
+        std::mem::take(&mut self.state().calls)
+    }
+    /// Sets the status and info log of the next shader compiled. Repeated
+    /// calls script later compiles in order.
+    pub fn script_compile(&self, success: bool, log: &str) {
+        self.state().compile_results.push_back((success, log.into()));
+    }
+    /// Sets the status and info log of the next program linked. Repeated
+    /// calls script later links in order.
+    pub fn script_link(&self, success: bool, log: &str) {
+        self.state().link_results.push_back((success, log.into()));
+    }
+    /// Sets the active attributes every linked program reports. Their
+    /// locations are their indices.
+    pub fn set_active_attribs(&self, attribs: Vec<ActiveInfo>) {
+        self.state().attribs = attribs;
+    }
+    /// Sets the active uniforms every linked program reports.
+    pub fn set_active_uniforms(&self, uniforms: Vec<ActiveInfo>) {
+        self.state().uniforms = uniforms;
+    }
+    /// Queues an error for `get_error` to report.
+    pub fn push_error(&self, error: u32) {
+        self.state().errors.push_back(error);
+    }
+    pub fn set_string(&self, pname: u32, value: &str) {
+        self.state().strings.insert(pname, value.into());
+    }
+    pub fn set_float(&self, pname: u32, value: f32) {
+        self.state().floats.insert(pname, value);
+    }
+    pub fn set_extensions(&self, names: &[&str]) {
+        self.state().extensions = names.iter()
+                                       .map(|n| n.to_string())
+                                       .collect();
+    }
+    /// Sets the status `check_framebuffer_status` reports, which is
+    /// `FRAMEBUFFER_COMPLETE` unless set.
+    pub fn set_framebuffer_status(&self, status: u32) {
+        self.state().framebuffer = Some(status);
+    }
+    /// Sets the WebGL version the backend claims to be. WebGL2 calls are
+    /// recorded whatever the version.
+    pub fn set_version(&self, version: GlVersion) {
+        self.state().version = Some(version);
+    }
+    fn state(&self) -> MutexGuard<'_, RecordingState> {
+        self.state.lock().unwrap()
+    }
+    fn record(&self, call: String) {
+        self.state().calls.push(call);
+    }
+    fn create(&self, what: &str) -> u32 {
+        let mut state = self.state();
+        state.next_id += 1;
+        let id = state.next_id;
+        state.calls.push(format!("{} -> {}", what, id));
+        id
+    }
+}
+
+impl GlBackend for RecordingBackend {
+    type Shader          = u32;
+    type Program         = u32;
+    type Texture         = u32;
+    type Buffer          = u32;
+    type UniformLocation = u32;
+    type VertexArray     = u32;
+    type Framebuffer     = u32;
+    type Renderbuffer    = u32;
+
+    fn enable(&self, cap: u32) {
+        self.record(format!("enable({})", cap));
+    }
+    fn disable(&self, cap: u32) {
+        self.record(format!("disable({})", cap));
+    }
+    fn hint(&self, target: u32, mode: u32) {
+        self.record(format!("hint({}, {})", target, mode));
+    }
+    fn depth_mask(&self, flag: bool) {
+        self.record(format!("depth_mask({})", flag));
+    }
+    fn depth_func(&self, func: u32) {
+        self.record(format!("depth_func({})", func));
+    }
+    fn depth_range(&self, near: f32, far: f32) {
+        self.record(format!("depth_range({}, {})", near, far));
+    }
+    fn cull_face(&self, mode: u32) {
+        self.record(format!("cull_face({})", mode));
+    }
+    fn front_face(&self, mode: u32) {
+        self.record(format!("front_face({})", mode));
+    }
+    fn stencil_mask(&self, mask: u32) {
+        self.record(format!("stencil_mask({:#x})", mask));
+    }
+    fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+        self.record(format!("stencil_func({}, {}, {:#x})",
+                            func, reference, mask));
+    }
+    fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+        self.record(format!("stencil_op({}, {}, {})", fail, depth_fail, pass));
+    }
+    fn blend_func_separate(&self,
+                           src_rgb   : u32,
+                           dst_rgb   : u32,
+                           src_alpha : u32,
+                           dst_alpha : u32)
+    {
+        self.record(format!("blend_func_separate({}, {}, {}, {})",
+                            src_rgb, dst_rgb, src_alpha, dst_alpha));
+    }
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        self.record(format!("blend_equation_separate({}, {})",
+                            mode_rgb, mode_alpha));
+    }
+    fn blend_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.record(format!("blend_color({}, {}, {}, {})",
+                            red, green, blue, alpha));
+    }
+    fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) {
+        self.record(format!("color_mask({}, {}, {}, {})",
+                            red, green, blue, alpha));
+    }
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.record(format!("viewport({}, {}, {}, {})", x, y, width, height));
+    }
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        self.record(format!("polygon_offset({}, {})", factor, units));
+    }
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.record(format!("clear_color({}, {}, {}, {})",
+                            red, green, blue, alpha));
+    }
+    fn clear_depth(&self, depth: f32) {
+        self.record(format!("clear_depth({})", depth));
+    }
+    fn clear_stencil(&self, stencil: i32) {
+        self.record(format!("clear_stencil({})", stencil));
+    }
+    fn clear(&self, mask: u32) {
+        self.record(format!("clear({:#x})", mask));
+    }
+    fn get_error(&self) -> u32 {
+        self.record("get_error()".into());
+        self.state()
+            .errors
+            .pop_front()
+            .unwrap_or(WebGlRenderingContext::NO_ERROR)
+    }
+    fn get_string(&self, pname: u32) -> Option<String> {
+        self.record(format!("get_string({})", pname));
+        self.state().strings.get(&pname).cloned()
+    }
+    fn get_float(&self, pname: u32) -> Option<f32> {
+        self.record(format!("get_float({})", pname));
+        self.state().floats.get(&pname).copied()
+    }
+    fn supported_extensions(&self) -> Vec<String> {
+        self.record("supported_extensions()".into());
+        self.state().extensions.clone()
+    }
+    fn enable_extension(&self, name: &str) -> bool {
+        self.record(format!("enable_extension({})", name));
+        self.state().extensions.iter().any(|n| n == name)
+    }
+    fn create_shader(&self, shader_type: u32) -> Option<u32> {
+        Some( self.create(&format!("create_shader({})", shader_type)) )
+    }
+    fn delete_shader(&self, shader: &u32) {
+        self.record(format!("delete_shader({})", shader));
+    }
+    fn shader_source(&self, shader: &u32, source: &str) {
+        self.record(format!("shader_source({}, {:?})", shader, source));
+    }
+    fn compile_shader(&self, shader: &u32) {
+        self.record(format!("compile_shader({})", shader));
+        let mut state = self.state();
+        let result    = state.compile_results
+                             .pop_front()
+                             .unwrap_or((true, String::new()));
+        state.compile_status.insert(*shader, result);
+    }
+    fn shader_info_log(&self, shader: &u32) -> Option<String> {
+        self.record(format!("shader_info_log({})", shader));
+        self.state().compile_status.get(shader).map(|r| r.1.clone())
+    }
+    fn shader_parameter(&self, shader: &u32, pname: u32) -> i32 {
+        use WebGlRenderingContext as Ctx;
+
+        self.record(format!("shader_parameter({}, {})", shader, pname));
+        let state = self.state();
+        match pname {
+            Ctx::COMPILE_STATUS => {
+                state.compile_status.get(shader).is_some_and(|r| r.0) as i32
+            },
+            _ => 0,
+        }
+    }
+    fn create_program(&self) -> Option<u32> {
+        Some( self.create("create_program()") )
+    }
+    fn delete_program(&self, program: &u32) {
+        self.record(format!("delete_program({})", program));
+    }
+    fn attach_shader(&self, program: &u32, shader: &u32) {
+        self.record(format!("attach_shader({}, {})", program, shader));
+    }
+    fn link_program(&self, program: &u32) {
+        self.record(format!("link_program({})", program));
+        let mut state = self.state();
+        let result    = state.link_results
+                             .pop_front()
+                             .unwrap_or((true, String::new()));
+        state.link_status.insert(*program, result);
+    }
+    fn program_info_log(&self, program: &u32) -> Option<String> {
+        self.record(format!("program_info_log({})", program));
+        self.state().link_status.get(program).map(|r| r.1.clone())
+    }
+    fn program_parameter(&self, program: &u32, pname: u32) -> i32 {
+        use WebGlRenderingContext as Ctx;
+
+        self.record(format!("program_parameter({}, {})", program, pname));
+        let state = self.state();
+        match pname {
+            Ctx::LINK_STATUS => {
+                state.link_status.get(program).is_some_and(|r| r.0) as i32
+            },
+            Ctx::ACTIVE_ATTRIBUTES => state.attribs.len()  as i32,
+            Ctx::ACTIVE_UNIFORMS   => state.uniforms.len() as i32,
+            _ => 0,
+        }
+    }
+    fn active_attrib(&self, program: &u32, index: u32) -> Option<ActiveInfo> {
+        self.record(format!("active_attrib({}, {})", program, index));
+        self.state().attribs.get(index as usize).cloned()
+    }
+    fn active_uniform(&self, program: &u32, index: u32) -> Option<ActiveInfo>
+    {
+        self.record(format!("active_uniform({}, {})", program, index));
+        self.state().uniforms.get(index as usize).cloned()
+    }
+    fn attrib_location(&self, program: &u32, name: &str) -> i32 {
+        self.record(format!("attrib_location({}, {})", program, name));
+        self.state()
+            .attribs
+            .iter()
+            .position(|a| a.name == name)
+            .map_or(-1, |i| i as i32)
+    }
+    /// Uniforms are located by their index among the active uniforms, and
+    /// elements after the first of an array at 1000 per element past it.
+    fn uniform_location(&self, program: &u32, name: &str) -> Option<u32> {
+        self.record(format!("uniform_location({}, {})", program, name));
+        let state = self.state();
+
+        if let Some(i) = state.uniforms.iter().position(|u| u.name == name) {
+            return Some( i as u32 );
+        }
+        let (base, element) = name.strip_suffix(']')?.rsplit_once('[')?;
+        let element         = element.parse::<i32>().ok()?;
+        let first           = format!("{}[0]", base);
+
+        state.uniforms
+             .iter()
+             .position(|u| u.name == first && element < u.size)
+             .map(|i| i as u32 + 1000 * element as u32)
+    }
+    fn use_program(&self, program: Option<&u32>) {
+        self.record(format!("use_program({:?})", program));
+    }
+    fn uniform1i(&self, location: Option<&u32>, x: i32) {
+        self.record(format!("uniform1i({:?}, {})", location, x));
+    }
+    fn uniform1f(&self, location: Option<&u32>, x: f32) {
+        self.record(format!("uniform1f({:?}, {})", location, x));
+    }
+    fn uniform2f(&self, location: Option<&u32>, x: f32, y: f32) {
+        self.record(format!("uniform2f({:?}, {}, {})", location, x, y));
+    }
+    fn uniform3f(&self, location: Option<&u32>, x: f32, y: f32, z: f32) {
+        self.record(format!("uniform3f({:?}, {}, {}, {})", location, x, y, z));
+    }
+    fn uniform4f(&self, location: Option<&u32>, x: f32, y: f32, z: f32, w: f32)
+    {
+        self.record(format!("uniform4f({:?}, {}, {}, {}, {})",
+                            location, x, y, z, w));
+    }
+    fn uniform_matrix3fv(&self,
+                         location  : Option<&u32>,
+                         transpose : bool,
+                         data      : &[f32])
+    {
+        self.record(format!("uniform_matrix3fv({:?}, {}, {:?})",
+                            location, transpose, data));
+    }
+    fn uniform_matrix4fv(&self,
+                         location  : Option<&u32>,
+                         transpose : bool,
+                         data      : &[f32])
+    {
+        self.record(format!("uniform_matrix4fv({:?}, {}, {:?})",
+                            location, transpose, data));
+    }
+    fn create_texture(&self) -> Option<u32> {
+        Some( self.create("create_texture()") )
+    }
+    fn delete_texture(&self, texture: &u32) {
+        self.record(format!("delete_texture({})", texture));
+    }
+    fn active_texture(&self, unit: u32) {
+        self.record(format!("active_texture({})", unit));
+    }
+    fn bind_texture(&self, target: u32, texture: Option<&u32>) {
+        self.record(format!("bind_texture({}, {:?})", target, texture));
+    }
+    fn pixel_storei(&self, pname: u32, param: i32) {
+        self.record(format!("pixel_storei({}, {})", pname, param));
+    }
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.record(format!("tex_parameteri({}, {}, {})",
+                            target, pname, param));
+    }
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        self.record(format!("tex_parameterf({}, {}, {})",
+                            target, pname, param));
+    }
+    fn tex_image_2d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        self.record(format!("tex_image_2d({}, {}, {}, {}, {}, {}, {}, {}, \
+                             {:?})", target, level, internal_format, width,
+                            height, border, format, texel_type,
+                            data.map(|d| d.len())));
+        Ok(())
+    }
+    fn compressed_tex_image_2d(&self,
+                               target          : u32,
+                               level           : i32,
+                               internal_format : u32,
+                               width           : i32,
+                               height          : i32,
+                               border          : i32,
+                               data            : &[u8])
+    {
+        self.record(format!("compressed_tex_image_2d({}, {}, {:#x}, {}, {}, \
+                             {}, {})", target, level, internal_format, width,
+                            height, border, data.len()));
+    }
+    fn generate_mipmap(&self, target: u32) {
+        self.record(format!("generate_mipmap({})", target));
+    }
+    fn create_buffer(&self) -> Option<u32> {
+        Some( self.create("create_buffer()") )
+    }
+    fn delete_buffer(&self, buffer: &u32) {
+        self.record(format!("delete_buffer({})", buffer));
+    }
+    fn bind_buffer(&self, target: u32, buffer: Option<&u32>) {
+        self.record(format!("bind_buffer({}, {:?})", target, buffer));
+    }
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        self.record(format!("buffer_data({}, {}, {})",
+                            target, data.len(), usage));
+    }
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.record(format!("enable_vertex_attrib_array({})", index));
+    }
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        self.record(format!("disable_vertex_attrib_array({})", index));
+    }
+    fn vertex_attrib_pointer(&self,
+                             index      : u32,
+                             size       : i32,
+                             data_type  : u32,
+                             normalized : bool,
+                             stride     : i32,
+                             offset     : i32)
+    {
+        self.record(format!("vertex_attrib_pointer({}, {}, {}, {}, {}, {})",
+                            index, size, data_type, normalized, stride,
+                            offset));
+    }
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.record(format!("draw_arrays({}, {}, {})", mode, first, count));
+    }
+    fn create_framebuffer(&self) -> Option<u32> {
+        Some( self.create("create_framebuffer()") )
+    }
+    fn delete_framebuffer(&self, framebuffer: &u32) {
+        self.record(format!("delete_framebuffer({})", framebuffer));
+    }
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&u32>) {
+        self.record(format!("bind_framebuffer({}, {:?})", target, framebuffer));
+    }
+    fn framebuffer_texture_2d(&self,
+                              target     : u32,
+                              attachment : u32,
+                              tex_target : u32,
+                              texture    : Option<&u32>,
+                              level      : i32)
+    {
+        self.record(format!("framebuffer_texture_2d({}, {}, {}, {:?}, {})",
+                            target, attachment, tex_target, texture, level));
+    }
+    fn framebuffer_renderbuffer(&self,
+                                target       : u32,
+                                attachment   : u32,
+                                rb_target    : u32,
+                                renderbuffer : Option<&u32>)
+    {
+        self.record(format!("framebuffer_renderbuffer({}, {}, {}, {:?})",
+                            target, attachment, rb_target, renderbuffer));
+    }
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        self.record(format!("check_framebuffer_status({})", target));
+        self.state()
+            .framebuffer
+            .unwrap_or(WebGlRenderingContext::FRAMEBUFFER_COMPLETE)
+    }
+    fn create_renderbuffer(&self) -> Option<u32> {
+        Some( self.create("create_renderbuffer()") )
+    }
+    fn delete_renderbuffer(&self, renderbuffer: &u32) {
+        self.record(format!("delete_renderbuffer({})", renderbuffer));
+    }
+    fn bind_renderbuffer(&self, target: u32, renderbuffer: Option<&u32>) {
+        self.record(format!("bind_renderbuffer({}, {:?})",
+                            target, renderbuffer));
+    }
+    fn renderbuffer_storage(&self,
+                            target          : u32,
+                            internal_format : u32,
+                            width           : i32,
+                            height          : i32)
+    {
+        self.record(format!("renderbuffer_storage({}, {}, {}, {})",
+                            target, internal_format, width, height));
+    }
+    fn version(&self) -> GlVersion {
+        self.state().version.unwrap_or(GlVersion::WebGl1)
+    }
+    fn create_vertex_array(&self) -> Option<u32> {
+        Some( self.create("create_vertex_array()") )
+    }
+    fn delete_vertex_array(&self, vertex_array: &u32) {
+        self.record(format!("delete_vertex_array({})", vertex_array));
+    }
+    fn bind_vertex_array(&self, vertex_array: Option<&u32>) {
+        self.record(format!("bind_vertex_array({:?})", vertex_array));
+    }
+    fn draw_arrays_instanced(&self,
+                             mode      : u32,
+                             first     : i32,
+                             count     : i32,
+                             instances : i32)
+    {
+        self.record(format!("draw_arrays_instanced({}, {}, {}, {})",
+                            mode, first, count, instances));
+    }
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.record(format!("vertex_attrib_divisor({}, {})", index, divisor));
+    }
+    fn draw_buffers(&self, buffers: &[u32]) {
+        self.record(format!("draw_buffers({:?})", buffers));
+    }
+    fn tex_image_3d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    depth           : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        self.record(format!("tex_image_3d({}, {}, {}, {}, {}, {}, {}, {}, {}, \
+                             {:?})", target, level, internal_format, width,
+                            height, depth, border, format, texel_type,
+                            data.map(|d| d.len())));
+        Ok(())
+    }
+    fn uniform_block_index(&self, program: &u32, name: &str) -> u32 {
+        self.record(format!("uniform_block_index({}, {})", program, name));
+        0
+    }
+    fn uniform_block_binding(&self, program: &u32, index: u32, binding: u32) {
+        self.record(format!("uniform_block_binding({}, {}, {})",
+                            program, index, binding));
+    }
+    fn bind_buffer_base(&self, target: u32, index: u32, buffer: Option<&u32>) {
+        self.record(format!("bind_buffer_base({}, {}, {:?})",
+                            target, index, buffer));
+    }
+}
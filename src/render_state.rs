@@ -0,0 +1,233 @@
+use web_sys::WebGlRenderingContext as Ctx;
+
+/// How fragments are blended into the color buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendState {
+    pub enabled        : bool,
+    pub src_rgb        : u32,
+    pub dst_rgb        : u32,
+    pub src_alpha      : u32,
+    pub dst_alpha      : u32,
+    pub equation_rgb   : u32,
+    pub equation_alpha : u32,
+}
+
+impl BlendState {
+    /// Fragments replace what's there.
+    pub const OPAQUE : BlendState = BlendState {
+        enabled        : false,
+        src_rgb        : Ctx::ONE,
+        dst_rgb        : Ctx::ZERO,
+        src_alpha      : Ctx::ONE,
+        dst_alpha      : Ctx::ZERO,
+        equation_rgb   : Ctx::FUNC_ADD,
+        equation_alpha : Ctx::FUNC_ADD,
+    };
+    /// Fragments are mixed in by their alpha.
+    pub const ALPHA : BlendState =
+        BlendState::new(Ctx::SRC_ALPHA, Ctx::ONE_MINUS_SRC_ALPHA)
+                   .with_alpha(Ctx::ONE, Ctx::ONE_MINUS_SRC_ALPHA);
+    /// Fragments with colors already multiplied by their alpha are mixed in.
+    pub const PREMULTIPLIED : BlendState =
+        BlendState::new(Ctx::ONE, Ctx::ONE_MINUS_SRC_ALPHA);
+    /// Fragments, scaled by their alpha, are added to what's there.
+    pub const ADDITIVE : BlendState =
+        BlendState::new(Ctx::SRC_ALPHA, Ctx::ONE)
+                   .with_alpha(Ctx::ONE, Ctx::ONE);
+    /// Fragments multiply what's there.
+    pub const MULTIPLY : BlendState =
+        BlendState::new(Ctx::DST_COLOR, Ctx::ZERO);
+
+    /// Blends color and alpha with the same factors and `FUNC_ADD`.
+    pub const fn new(src: u32, dst: u32) -> Self {
+        BlendState {
+            enabled        : true,
+            src_rgb        : src,
+            dst_rgb        : dst,
+            src_alpha      : src,
+            dst_alpha      : dst,
+            equation_rgb   : Ctx::FUNC_ADD,
+            equation_alpha : Ctx::FUNC_ADD,
+        }
+    }
+    pub const fn with_alpha(mut self, src: u32, dst: u32) -> Self {
+        self.src_alpha = src;
+        self.dst_alpha = dst;
+        self
+    }
+    pub const fn with_equation(mut self, rgb: u32, alpha: u32) -> Self {
+        self.equation_rgb   = rgb;
+        self.equation_alpha = alpha;
+        self
+    }
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        BlendState::OPAQUE
+    }
+}
+
+/// How fragments are tested against and written to the depth buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthState {
+    pub test  : bool,
+    pub write : bool,
+    pub func  : u32,
+}
+
+impl DepthState {
+    /// Nearer fragments pass and their depth is kept.
+    pub const DEFAULT : DepthState =
+        DepthState { test: true, write: true, func: Ctx::LESS };
+    /// Fragments are tested but leave the depth buffer as it is, as for
+    /// transparent geometry drawn after the opaque.
+    pub const READ_ONLY : DepthState =
+        DepthState { test: true, write: false, func: Ctx::LEQUAL };
+    /// Depth is neither tested nor written.
+    pub const DISABLED : DepthState =
+        DepthState { test: false, write: false, func: Ctx::LESS };
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        DepthState::DEFAULT
+    }
+}
+
+/// How fragments are tested against and written to the stencil buffer.
+/// The same settings apply to front and back faces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    pub enabled    : bool,
+    pub func       : u32,
+    pub reference  : i32,
+    pub read_mask  : u32,
+    /// Which bits writes, clears included, may change.
+    pub write_mask : u32,
+    pub fail       : u32,
+    pub depth_fail : u32,
+    pub pass       : u32,
+}
+
+impl StencilState {
+    pub const DISABLED : StencilState = StencilState {
+        enabled    : false,
+        func       : Ctx::ALWAYS,
+        reference  : 0,
+        read_mask  : 0xFFFFFFFF,
+        write_mask : 0xFFFFFFFF,
+        fail       : Ctx::KEEP,
+        depth_fail : Ctx::KEEP,
+        pass       : Ctx::KEEP,
+    };
+
+    /// Writes `reference` wherever fragments pass the depth test.
+    pub const fn write(reference: i32) -> Self {
+        StencilState { enabled: true, reference, pass: Ctx::REPLACE,
+                       ..StencilState::DISABLED }
+    }
+    /// Passes fragments only where the stencil buffer holds `reference`.
+    pub const fn equal(reference: i32) -> Self {
+        StencilState { enabled: true, reference, func: Ctx::EQUAL,
+                       write_mask: 0, ..StencilState::DISABLED }
+    }
+    /// Passes fragments only where the stencil buffer doesn't hold
+    /// `reference`.
+    pub const fn not_equal(reference: i32) -> Self {
+        StencilState { func: Ctx::NOTEQUAL, ..StencilState::equal(reference) }
+    }
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        StencilState::DISABLED
+    }
+}
+
+/// Which faces are drawn, which color channels are written and how depth
+/// is offset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RasterState {
+    /// The faces culled, `BACK`, `FRONT` or `FRONT_AND_BACK`; `None` draws
+    /// both.
+    pub cull_face      : Option<u32>,
+    pub front_face     : u32,
+    pub color_mask     : [bool; 4],
+    /// The factor and units depth is offset by, as when drawing shadow
+    /// casters; `None` turns the offset off.
+    pub polygon_offset : Option<(f32, f32)>,
+}
+
+impl RasterState {
+    /// Counter-clockwise faces are front faces, and back faces are culled.
+    pub const DEFAULT : RasterState = RasterState {
+        cull_face      : Some(Ctx::BACK),
+        front_face     : Ctx::CCW,
+        color_mask     : [true; 4],
+        polygon_offset : None,
+    };
+    pub const NO_CULL : RasterState =
+        RasterState { cull_face: None, ..RasterState::DEFAULT };
+    pub const CULL_FRONT : RasterState =
+        RasterState { cull_face: Some(Ctx::FRONT), ..RasterState::DEFAULT };
+    /// Writes depth and stencil only.
+    pub const NO_COLOR : RasterState =
+        RasterState { color_mask: [false; 4], ..RasterState::DEFAULT };
+}
+
+impl Default for RasterState {
+    fn default() -> Self {
+        RasterState::DEFAULT
+    }
+}
+
+/// Every fixed-function setting a draw depends on, applied at once with
+/// `Gfx::apply_render_state`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderState {
+    pub blend   : BlendState,
+    pub depth   : DepthState,
+    pub stencil : StencilState,
+    pub raster  : RasterState,
+}
+
+impl RenderState {
+    /// Depth tested and written, back faces culled, no blending: the state
+    /// `Gfx` starts in.
+    pub const OPAQUE : RenderState = RenderState {
+        blend   : BlendState::OPAQUE,
+        depth   : DepthState::DEFAULT,
+        stencil : StencilState::DISABLED,
+        raster  : RasterState::DEFAULT,
+    };
+    /// Alpha blended over what's been drawn without hiding what's behind.
+    pub const TRANSPARENT : RenderState = RenderState {
+        blend : BlendState::ALPHA,
+        depth : DepthState::READ_ONLY,
+        ..RenderState::OPAQUE
+    };
+    /// Added to what's been drawn, as for glows and particles.
+    pub const ADDITIVE : RenderState = RenderState {
+        blend : BlendState::ADDITIVE,
+        depth : DepthState::READ_ONLY,
+        ..RenderState::OPAQUE
+    };
+
+    pub const fn with_blend(mut self, blend: BlendState) -> Self {
+        self.blend = blend;
+        self
+    }
+    pub const fn with_depth(mut self, depth: DepthState) -> Self {
+        self.depth = depth;
+        self
+    }
+    pub const fn with_stencil(mut self, stencil: StencilState) -> Self {
+        self.stencil = stencil;
+        self
+    }
+    pub const fn with_raster(mut self, raster: RasterState) -> Self {
+        self.raster = raster;
+        self
+    }
+}
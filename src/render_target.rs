@@ -0,0 +1,315 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use web_sys::WebGl2RenderingContext as Ctx2;
+use web_sys::WebGlRenderingContext;
+use web_sys::WebGlRenderingContext as Ctx;
+
+use crate::backend::GlBackend;
+use crate::capabilities::GlCapabilities;
+use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::gl_state::untracked_bind;
+use crate::gl_state::Binding;
+use crate::texture::TexelFormat;
+use crate::texture::Texture;
+use crate::texture::TEXTURE_FILTER_0X;
+use crate::texture::TEXTURE_FILTER_1X;
+
+/// The depth, and stencil, a `RenderTarget` draws with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthBuffer {
+    None,
+    /// A 16 bit depth renderbuffer, which can't be sampled.
+    Depth,
+    /// A packed depth and stencil renderbuffer.
+    DepthStencil,
+    /// A depth texture that can be sampled, as for shadow maps; see
+    /// `TexelFormat::depth`.
+    Texture(TexelFormat),
+}
+
+/// The attachments `RenderTarget::new` creates.
+#[derive(Clone, Debug)]
+pub struct RenderTargetOptions {
+    colors : Vec<TexelFormat>,
+    depth  : DepthBuffer,
+    filter : u8,
+}
+
+impl Default for RenderTargetOptions {
+    /// One RGBA color texture, a depth renderbuffer and linear filtering.
+    fn default() -> Self {
+        RenderTargetOptions {
+            colors : vec![TexelFormat::RGBA8],
+            depth  : DepthBuffer::Depth,
+            filter : TEXTURE_FILTER_1X,
+        }
+    }
+}
+
+impl RenderTargetOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the formats of the color textures, attached in order from
+    /// `COLOR_ATTACHMENT0`; more than one needs multiple render targets.
+    pub fn colors(mut self, colors: &[TexelFormat]) -> Self {
+        self.colors = colors.to_vec();
+        self
+    }
+    pub fn depth(mut self, depth: DepthBuffer) -> Self {
+        self.depth = depth;
+        self
+    }
+    /// Sets how the textures are sampled; `TEXTURE_FILTER_0X` for nearest,
+    /// linear otherwise.
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// The framebuffer and viewport a bind replaced.
+type SavedBinding<B> = (Option<<B as GlBackend>::Framebuffer>,
+                        Option<[i32; 4]>);
+
+enum DepthAttachment<B: GlBackend> {
+    Renderbuffer(B::Renderbuffer),
+    Texture(Texture<B>),
+}
+
+/// A framebuffer drawn into instead of the canvas, whose color textures can
+/// then be sampled like any other texture, as for post-processing, mirrors
+/// and shadow maps.
+pub struct RenderTarget<B: GlBackend = WebGlRenderingContext> {
+    framebuffer : Option<B::Framebuffer>,
+    colors      : Vec<Texture<B>>,
+    depth       : Option<DepthAttachment<B>>,
+    width       : u32,
+    height      : u32,
+    /// What `bind` replaced, for `unbind`.
+    saved       : RefCell<Option<SavedBinding<B>>>,
+    context     : Arc<B>,
+}
+
+impl<B: GlBackend> RenderTarget<B> {
+    /// Creates a `width` by `height` target with the attachments of
+    /// `options`, failing if `capabilities` lacks what they need or the
+    /// context reports the framebuffer incomplete.
+    pub fn new(name         : &str,
+               width        : u32,
+               height       : u32,
+               options      : &RenderTargetOptions,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        use RenderTargetError::*;
+
+        if options.colors.len() as u32 > capabilities.max_draw_buffers {
+            let msg = format!("Render target ({}) has {} color attachments; \
+                               the context draws to {}.", name,
+                              options.colors.len(),
+                              capabilities.max_draw_buffers);
+            Err( Unsupported(msg) )?
+        }
+        if options.colors.iter().any(|c| c.is_float())
+            && !capabilities.color_buffer_float
+        {
+            let msg = format!("Render target ({}) has a float color \
+                               attachment, which needs WebGL2 and \
+                               EXT_color_buffer_float.", name);
+            Err( Unsupported(msg) )?
+        }
+        if let DepthBuffer::Texture(_) = options.depth {
+            if !capabilities.depth_texture {
+                let msg = format!("Render target ({}) has a depth texture, \
+                                   which needs WEBGL_depth_texture.", name);
+                Err( Unsupported(msg) )?
+            }
+        }
+        let mut colors = Vec::with_capacity(options.colors.len());
+
+        for (i, format) in options.colors.iter().enumerate() {
+            colors.push(Texture::with_size(&format!("{}:color{}", name, i),
+                                           width, height, format,
+                                           options.filter, context.clone())?);
+        }
+        let depth = match options.depth {
+            DepthBuffer::None => None,
+            DepthBuffer::Depth | DepthBuffer::DepthStencil => {
+                let format = if options.depth == DepthBuffer::Depth
+                                  { Ctx::DEPTH_COMPONENT16 }
+                             else { Ctx::DEPTH_STENCIL     };
+                let renderbuffer = context.create_renderbuffer();
+
+                context.bind_renderbuffer(Ctx::RENDERBUFFER,
+                                          renderbuffer.as_ref());
+                context.renderbuffer_storage(Ctx::RENDERBUFFER, format,
+                                             width as i32, height as i32);
+                context.bind_renderbuffer(Ctx::RENDERBUFFER, None);
+                renderbuffer.map(DepthAttachment::Renderbuffer)
+            },
+            DepthBuffer::Texture(format) => {
+                // Depth textures can't be filtered on WebGL1.
+                let texture = Texture::with_size(&format!("{}:depth", name),
+                                                 width, height, &format,
+                                                 TEXTURE_FILTER_0X,
+                                                 context.clone())?;
+                Some( DepthAttachment::Texture(texture) )
+            },
+        };
+        let target = RenderTarget {
+            framebuffer : context.create_framebuffer(),
+            colors,
+            depth,
+            width,
+            height,
+            saved       : RefCell::new(None),
+            context,
+        };
+        target.attach(name, options)?;
+        Ok( target )
+    }
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    #[inline]
+    pub fn framebuffer(&self) -> &B::Framebuffer {
+        self.framebuffer.as_ref().expect("Render target framebuffer not set.")
+    }
+    /// Returns color texture `index`, to sample what was drawn.
+    #[inline]
+    pub fn color(&self, index: usize) -> &Texture<B> {
+        &self.colors[index]
+    }
+    #[inline]
+    pub fn colors(&self) -> &[Texture<B>] {
+        &self.colors
+    }
+    /// Returns the depth texture, if the target was created with one.
+    pub fn depth_texture(&self) -> Option<&Texture<B>> {
+        match &self.depth {
+            Some(DepthAttachment::Texture(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+    /// Directs draws made through `gfx` into the target, covering it with
+    /// the viewport. The framebuffer and viewport this replaces are kept for
+    /// `unbind`.
+    pub fn bind(&self, gfx: &Gfx<B>) {
+        *self.saved.borrow_mut() = Some((gfx.current_framebuffer(),
+                                         gfx.current_viewport()));
+        gfx.bind_framebuffer(self.framebuffer.as_ref());
+        gfx.viewport(0, 0, self.width as i32, self.height as i32);
+    }
+    /// Undoes `bind`, restoring the framebuffer and viewport it replaced.
+    pub fn unbind(&self, gfx: &Gfx<B>) {
+        let (framebuffer, viewport) = self.saved.borrow_mut()
+                                                .take()
+                                                .unwrap_or((None, None));
+        gfx.bind_framebuffer(framebuffer.as_ref());
+
+        if let Some([x, y, width, height]) = viewport {
+            gfx.viewport(x, y, width, height);
+        }
+    }
+    fn attach(&self, name: &str, options: &RenderTargetOptions)
+        -> Result<(), RenderTargetError>
+    {
+        let ctx = &self.context;
+        let fb  = Ctx::FRAMEBUFFER;
+
+        ctx.bind_framebuffer(fb, self.framebuffer.as_ref());
+
+        for (i, color) in self.colors.iter().enumerate() {
+            ctx.framebuffer_texture_2d(fb, Ctx::COLOR_ATTACHMENT0 + i as u32,
+                                       Ctx::TEXTURE_2D, Some(color.tid()), 0);
+        }
+        if self.colors.len() > 1 {
+            let buffers = (0..self.colors.len() as u32)
+                              .map(|i| Ctx::COLOR_ATTACHMENT0 + i)
+                              .collect::<Vec<u32>>();
+            ctx.draw_buffers(&buffers);
+        }
+        let attachment = match options.depth {
+            DepthBuffer::DepthStencil => Ctx::DEPTH_STENCIL_ATTACHMENT,
+            _                         => Ctx::DEPTH_ATTACHMENT,
+        };
+        match &self.depth {
+            Some(DepthAttachment::Renderbuffer(renderbuffer)) => {
+                ctx.framebuffer_renderbuffer(fb, attachment, Ctx::RENDERBUFFER,
+                                             Some(renderbuffer));
+            },
+            Some(DepthAttachment::Texture(texture)) => {
+                ctx.framebuffer_texture_2d(fb, attachment, Ctx::TEXTURE_2D,
+                                           Some(texture.tid()), 0);
+            },
+            None => {},
+        }
+        let status = ctx.check_framebuffer_status(fb);
+        ctx.bind_framebuffer(fb, None);
+        untracked_bind(Binding::Framebuffer);
+
+        if status != Ctx::FRAMEBUFFER_COMPLETE {
+            let msg = format!("Render target ({}) is incomplete ({}).", name,
+                              framebuffer_status_name(status));
+            Err( RenderTargetError::Incomplete(msg) )?
+        }
+        Ok(())
+    }
+}
+
+impl<B: GlBackend> Drop for RenderTarget<B> {
+    fn drop(&mut self) {
+        if let Some(framebuffer) = self.framebuffer.take() {
+            self.context.delete_framebuffer(&framebuffer);
+        }
+        if let Some(DepthAttachment::Renderbuffer(renderbuffer)) = &self.depth {
+            self.context.delete_renderbuffer(renderbuffer);
+        }
+    }
+}
+
+fn framebuffer_status_name(status: u32) -> String {
+    match status {
+        Ctx::FRAMEBUFFER_INCOMPLETE_ATTACHMENT =>
+            "FRAMEBUFFER_INCOMPLETE_ATTACHMENT".into(),
+        Ctx::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT =>
+            "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT".into(),
+        Ctx::FRAMEBUFFER_INCOMPLETE_DIMENSIONS =>
+            "FRAMEBUFFER_INCOMPLETE_DIMENSIONS".into(),
+        Ctx::FRAMEBUFFER_UNSUPPORTED =>
+            "FRAMEBUFFER_UNSUPPORTED".into(),
+        Ctx2::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE =>
+            "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE".into(),
+        _ => format!("{:#x}", status),
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderTargetError {
+    /// The context reported the framebuffer incomplete.
+    Incomplete(String),
+    /// The attachments asked for need what the context lacks.
+    Unsupported(String),
+}
+
+impl Error for RenderTargetError {}
+
+impl fmt::Display for RenderTargetError {
+    fn fmt(&self, f:&mut fmt::Formatter<'_>) -> fmt::Result {
+        use RenderTargetError::*;
+        match self {
+            Incomplete  ( msg ) => write!(f, "{}", msg),
+            Unsupported ( msg ) => write!(f, "{}", msg),
+        }
+    }
+}
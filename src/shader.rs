@@ -1,21 +1,24 @@
 
+use std::borrow::Cow;
 use std::sync::Arc;
 
-use web_sys::console;
 use web_sys::WebGlRenderingContext;
-use web_sys::WebGlShader;
 
-pub struct Shader {
+use crate::backend::GlBackend;
+use crate::backend::GlVersion;
+use crate::console_log;
+
+pub struct Shader<B: GlBackend = WebGlRenderingContext> {
     name    : String,
     sh_type : u32,
-    sid     : Option<WebGlShader>,
-    context : Arc<WebGlRenderingContext>,
+    sid     : Option<B::Shader>,
+    context : Arc<B>,
 }
 
-impl Shader {
+impl<B: GlBackend> Shader<B> {
     pub fn new(name     : &str, 
                sh_type  : u32, 
-               context  : Arc<WebGlRenderingContext>
+               context  : Arc<B>
               ) -> Self 
     {
         Shader {
@@ -26,47 +29,53 @@ impl Shader {
         }
     }
     #[inline]
-    pub fn sid(&self) -> &WebGlShader {
-        &self.sid.as_ref().expect("Shader sid not set.")
+    pub fn sid(&self) -> &B::Shader {
+        self.sid.as_ref().expect("Shader sid not set.")
     }
     fn delete_id(&mut self) {
         if self.sid.is_some() {
-            self.context.delete_shader(self.sid.as_ref());
+            self.context.delete_shader(self.sid.as_ref().unwrap());
             self.sid = None;
         }
     }
+    /// Compiles `code`. GLSL ES 3.00 sources are converted to 1.00 first
+    /// on WebGL1 contexts, so one source serves both.
     pub fn compile(&mut self, code: &str) -> bool {
         use web_sys::WebGlRenderingContext as Ctx;
         if self.sid.is_some() {
             return false;
         } 
+        let code = if self.context.version() == GlVersion::WebGl1
+                      && is_glsl_300(code) {
+            Cow::Owned(downgrade_glsl(code, self.sh_type))
+        } else {
+            Cow::Borrowed(code)
+        };
         // Create shader.
         self.sid = self.context.create_shader(self.sh_type);
         let sid  = self.sid.as_ref().unwrap();
         let ctx  = &self.context;        
 
         // Set source and compile.
-        ctx.shader_source(sid, code);
+        ctx.shader_source(sid, &code);
         ctx.compile_shader(sid);
         
         #[cfg(debug_assertions)]
         {
             // If debug build, print out any diagnostic info that just happened.
-            if let Some(log) = ctx.get_shader_info_log(sid) {
+            if let Some(log) = ctx.shader_info_log(sid) {
                 if !log.is_empty() {
                     let typ = if self.sh_type == Ctx::VERTEX_SHADER 
                                    { "GL_VERTEX_SHADER"   } 
                               else { "GL_FRAGMENT_SHADER" };
 
-                    let msg = format!("[ {}: {} ]\n", self.name, typ);
-                    
-                    console::log_2(&msg.into(), &log.into());
+                    console_log!("[ {}: {} ]\n{}", self.name, typ, log);
                 }
             }
         }
         // Check to make sure the compilation was successful.
-        let status = ctx.get_shader_parameter(sid, Ctx::COMPILE_STATUS);
-        if status.is_falsy() {
+        let status = ctx.shader_parameter(sid, Ctx::COMPILE_STATUS);
+        if status == 0 {
             self.delete_id();
             false
         } else {
@@ -75,8 +84,127 @@ impl Shader {
     }
 }
 
-impl Drop for Shader {
+impl<B: GlBackend> Drop for Shader<B> {
     fn drop(&mut self) {
         self.delete_id();
     }
 }
+
+/// Returns whether `source` declares GLSL ES 3.00.
+pub fn is_glsl_300(source: &str) -> bool {
+    source.lines()
+          .map(|line| line.trim())
+          .find(|line| !line.is_empty())
+          .is_some_and(|line| line.starts_with("#version 300"))
+}
+
+/// Rewrites a GLSL ES 3.00 shader as GLSL ES 1.00 for WebGL1: `in` and
+/// `out` become `attribute` and `varying`, the fragment output becomes
+/// `gl_FragColor`, `texture` becomes `texture2D` or `textureCube` by the
+/// sampler's declared type, and `#version` and `layout` qualifiers are
+/// dropped. Only those differences are handled; shaders needing more of
+/// GLSL ES 3.00 should be written for WebGL2 only.
+pub fn downgrade_glsl(source: &str, sh_type: u32) -> String {
+    use web_sys::WebGlRenderingContext as Ctx;
+    let fragment   = sh_type == Ctx::FRAGMENT_SHADER;
+    let mut cubes  = vec![];
+    let mut output = None;
+    let mut lines  = vec![];
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        let indent  = &line[..line.len() - trimmed.len()];
+
+        if trimmed.starts_with("#version") {
+            continue;
+        }
+        let decl = strip_layout(trimmed);
+
+        if let Some(rest) = decl.strip_prefix("in ") {
+            let qualifier = if fragment { "varying" } else { "attribute" };
+            lines.push(format!("{}{} {}", indent, qualifier, rest));
+        } else if let Some(rest) = decl.strip_prefix("out ") {
+            if fragment {
+                output = declared_name(rest);
+            } else {
+                lines.push(format!("{}varying {}", indent, rest));
+            }
+        } else {
+            if let Some(rest) = decl.strip_prefix("uniform samplerCube ") {
+                cubes.extend(declared_name(rest));
+            }
+            lines.push(format!("{}{}", indent, decl));
+        }
+    }
+    let code = lines.join("\n");
+    map_identifiers(&code, |word, rest| {
+        if Some(word) == output.as_deref() {
+            return Some("gl_FragColor");
+        }
+        if word != "texture" || !rest.trim_start().starts_with('(') {
+            return None;
+        }
+        let sampler = rest.trim_start()[1..].trim_start();
+        let sampler = &sampler[..sampler.find(|c: char| !is_ident(c))
+                                        .unwrap_or(sampler.len())];
+        if cubes.iter().any(|c| c == sampler) { Some("textureCube") }
+        else                                  { Some("texture2D")   }
+    })
+}
+
+/// Drops a leading `layout(...)` qualifier.
+fn strip_layout(decl: &str) -> &str {
+    match decl.strip_prefix("layout") {
+        Some(rest) if rest.trim_start().starts_with('(') => {
+            match rest.find(')') {
+                Some(end) => rest[end + 1..].trim_start(),
+                None      => decl,
+            }
+        },
+        _ => decl,
+    }
+}
+
+/// Returns the variable a declaration such as `highp vec4 color;` names.
+fn declared_name(decl: &str) -> Option<String> {
+    decl.split([';', '['])
+        .next()?
+        .split_whitespace()
+        .next_back()
+        .map(|word| word.to_string())
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Replaces the identifiers in `code` that `f`, given each identifier and
+/// the text after it, returns a replacement for.
+fn map_identifiers<F>(code: &str, f: F) -> String
+    where F: Fn(&str, &str) -> Option<&'static str>
+{
+    let mut result = String::with_capacity(code.len());
+    let mut start  = 0;
+    let mut chars  = code.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if !(c.is_ascii_alphabetic() || c == '_') {
+            if c.is_ascii_digit() {
+                // Skip number suffixes such as the `f` of `1.0f`.
+                while chars.next_if(|&(_, c)| is_ident(c)).is_some() {}
+            }
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some((j, c)) = chars.next_if(|&(_, c)| is_ident(c)) {
+            end = j + c.len_utf8();
+        }
+        if let Some(replacement) = f(&code[i..end], &code[end..]) {
+            result.push_str(&code[start..i]);
+            result.push_str(replacement);
+            start = end;
+        }
+    }
+    result.push_str(&code[start..]);
+    result
+}
@@ -0,0 +1,486 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::capabilities::GlCapabilities;
+use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::matrix::Mat4;
+use crate::matrix::MAT4_BIAS;
+use crate::matrix::MAT4_IDENTITY;
+use crate::program::Program;
+use crate::program::UniformValue;
+use crate::render_state::RasterState;
+use crate::render_state::RenderState;
+use crate::render_target::DepthBuffer;
+use crate::render_target::RenderTarget;
+use crate::render_target::RenderTargetOptions;
+use crate::texture::TexelFormat;
+use crate::texture::Texture;
+use crate::texture::TEXTURE_FILTER_0X;
+use crate::vector::Vec3;
+
+/// The most cascades a `ShadowMap` holds, the length of `SHADOWMATRIX` in
+/// `SHADOW_GLSL`.
+pub const MAX_SHADOW_CASCADES : usize = 4;
+
+const SHADOW_CASTER_VERTEX_SHADER: &str = "
+uniform highp mat4 LIGHTMVP;
+
+attribute highp vec3 POSITION;
+
+void main( void ) {
+
+	gl_Position = LIGHTMVP * vec4( POSITION, 1.0 );
+
+}
+";
+
+const SHADOW_CASTER_FRAGMENT_SHADER: &str = "
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+void main( void ) {
+
+#ifdef PACKED_DEPTH
+	// Spread the depth over the channels, 8 bits each.
+	vec4 enc = fract( gl_FragCoord.z * vec4( 1.0, 255.0, 65025.0, 16581375.0 ) );
+	gl_FragColor = enc - enc.yzww * vec4( vec3( 1.0 / 255.0 ), 0.0 );
+#else
+	gl_FragColor = vec4( 1.0 );
+#endif
+
+}
+";
+
+/// Shadow lookup for fragment shaders of receivers. Define `PACKED_DEPTH`
+/// before it when the map `is_packed`, and set its uniforms with
+/// `ShadowMap::apply`. `shadow_factor` takes the fragment's world position
+/// and its distance in front of the camera, which picks the cascade, and
+/// returns how lit the fragment is, from 0 in shadow to 1, filtered over
+/// 3 by 3 texels of the cascade's tile. Positions are `FRAGMENT_HIGHP`,
+/// defined as `highp` where fragment shaders have it and `mediump`
+/// elsewhere.
+pub const SHADOW_GLSL: &str = "
+#ifndef FRAGMENT_HIGHP
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+#define FRAGMENT_HIGHP highp
+#else
+#define FRAGMENT_HIGHP mediump
+#endif
+#endif
+
+uniform sampler2D SHADOWMAP;
+uniform FRAGMENT_HIGHP mat4 SHADOWMATRIX[ 4 ];
+uniform FRAGMENT_HIGHP vec4 SHADOWSPLITS;
+uniform mediump vec2 SHADOWTEXELSIZE;
+uniform mediump float SHADOWTILEWIDTH;
+uniform mediump float SHADOWBIAS;
+
+FRAGMENT_HIGHP float shadow_depth( FRAGMENT_HIGHP vec2 uv ) {
+
+#ifdef PACKED_DEPTH
+	return dot( texture2D( SHADOWMAP, uv ),
+	            vec4( 1.0, 1.0 / 255.0, 1.0 / 65025.0, 1.0 / 16581375.0 ) );
+#else
+	return texture2D( SHADOWMAP, uv ).r;
+#endif
+
+}
+
+FRAGMENT_HIGHP float shadow_factor( FRAGMENT_HIGHP vec4 world_position,
+                                    FRAGMENT_HIGHP float view_depth ) {
+
+	FRAGMENT_HIGHP vec4 coord;
+	mediump float tile;
+
+	// Fragment shaders may only index uniform arrays with constants.
+	if( view_depth <= SHADOWSPLITS.x ) {
+		coord = SHADOWMATRIX[ 0 ] * world_position;
+		tile  = 0.0;
+	} else if( view_depth <= SHADOWSPLITS.y ) {
+		coord = SHADOWMATRIX[ 1 ] * world_position;
+		tile  = 1.0;
+	} else if( view_depth <= SHADOWSPLITS.z ) {
+		coord = SHADOWMATRIX[ 2 ] * world_position;
+		tile  = 2.0;
+	} else if( view_depth <= SHADOWSPLITS.w ) {
+		coord = SHADOWMATRIX[ 3 ] * world_position;
+		tile  = 3.0;
+	} else {
+		return 1.0;
+	}
+	FRAGMENT_HIGHP vec3 c = coord.xyz / coord.w;
+
+	if( c.z > 1.0 ) {
+		return 1.0;
+	}
+	// Keep the filter inside the cascade's tile, so it doesn't read its
+	// neighbor's depth.
+	FRAGMENT_HIGHP vec2 inset    = SHADOWTEXELSIZE * 0.5;
+	FRAGMENT_HIGHP vec2 tile_min = vec2( tile * SHADOWTILEWIDTH, 0.0 ) + inset;
+	FRAGMENT_HIGHP vec2 tile_max = vec2( ( tile + 1.0 ) * SHADOWTILEWIDTH, 1.0 )
+	                             - inset;
+	FRAGMENT_HIGHP float lit = 0.0;
+
+	for( int x = -1; x <= 1; ++x ) {
+		for( int y = -1; y <= 1; ++y ) {
+			FRAGMENT_HIGHP vec2 uv = c.xy + vec2( float( x ), float( y ) )
+			                              * SHADOWTEXELSIZE;
+			uv   = clamp( uv, tile_min, tile_max );
+			lit += c.z - SHADOWBIAS > shadow_depth( uv ) ? 0.0 : 1.0;
+		}
+	}
+	return lit / 9.0;
+
+}
+";
+
+/// Returns the far distance of each of `count` cascades splitting the view
+/// from `near` to `far`, blending logarithmic splits, which keep texel
+/// density even with distance, with uniform splits by `lambda`, 0 to 1.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32)
+    -> Vec<f32>
+{
+    (1..=count).map(|i| {
+                    let t       = i as f32 / count as f32;
+                    let log     = near * (far / near).powf(t);
+                    let uniform = near + (far - near) * t;
+                    lambda * log + (1.0 - lambda) * uniform
+                })
+                .collect()
+}
+
+/// Returns an up vector that isn't parallel to `direction`.
+fn up_for(direction: &Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 * direction.length() {
+        Vec3::new(0.0, 0.0, 1.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+}
+
+/// The depth of the scene as seen from a light, for each of up to
+/// `MAX_SHADOW_CASCADES` cascades side by side in one target. It's a depth
+/// texture where the context has them, natively on WebGL2 or through
+/// `WEBGL_depth_texture` on WebGL1; elsewhere depth is packed into the
+/// channels of a color texture.
+pub struct ShadowMap<B: GlBackend = WebGlRenderingContext> {
+    target        : RenderTarget<B>,
+    packed        : bool,
+    size          : u32,
+    views         : Vec<Mat4>,
+    projections   : Vec<Mat4>,
+    splits        : Vec<f32>,
+    caster_margin : f32,
+    bias          : f32,
+}
+
+impl<B: GlBackend> ShadowMap<B> {
+    /// Creates a map of `cascades` tiles of `size` by `size` texels, failing
+    /// unless there are 1 to `MAX_SHADOW_CASCADES`.
+    pub fn new(name         : &str,
+               size         : u32,
+               cascades     : usize,
+               capabilities : &GlCapabilities,
+               context      : Arc<B>) -> Result<Self, GfxError>
+    {
+        if !(1..=MAX_SHADOW_CASCADES).contains(&cascades) {
+            let msg = format!("Shadow map ({}) has {} cascades; maps have 1 \
+                               to {}.", name, cascades, MAX_SHADOW_CASCADES);
+            Err( GfxError::new_resource_load_error(msg, None) )?
+        }
+
+        let options = match TexelFormat::depth(capabilities) {
+            Some(format) => RenderTargetOptions::new()
+                                .colors(&[])
+                                .depth(DepthBuffer::Texture(format)),
+            None         => RenderTargetOptions::new()
+                                .colors(&[TexelFormat::RGBA8])
+                                .depth(DepthBuffer::Depth)
+                                .filter(TEXTURE_FILTER_0X),
+        };
+        let target = RenderTarget::new(name, size * cascades as u32, size,
+                                       &options, capabilities, context)?;
+        Ok( ShadowMap {
+                packed        : target.depth_texture().is_none(),
+                target,
+                size,
+                views         : vec![MAT4_IDENTITY; cascades],
+                projections   : vec![MAT4_IDENTITY; cascades],
+                splits        : vec![f32::MAX; cascades],
+                caster_margin : 0.0,
+                bias          : 0.002,
+            } )
+    }
+    /// Returns whether depth is packed into a color texture, for which
+    /// `PACKED_DEPTH` must be defined in the shaders.
+    #[inline]
+    pub fn is_packed(&self) -> bool {
+        self.packed
+    }
+    #[inline]
+    pub fn cascades(&self) -> usize {
+        self.views.len()
+    }
+    /// Returns the size of one cascade's tile, in texels.
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    #[inline]
+    pub fn target(&self) -> &RenderTarget<B> {
+        &self.target
+    }
+    /// Returns the texture receivers sample.
+    pub fn texture(&self) -> &Texture<B> {
+        self.target.depth_texture().unwrap_or_else(|| self.target.color(0))
+    }
+    /// Returns the far distance from the camera of each cascade, as set by
+    /// `fit_cascades`.
+    #[inline]
+    pub fn splits(&self) -> &[f32] {
+        &self.splits
+    }
+    /// Sets how far toward the light, past the volume a cascade covers,
+    /// casters are still drawn.
+    pub fn set_caster_margin(&mut self, margin: f32) {
+        self.caster_margin = margin;
+    }
+    /// Sets the depth difference below which receivers count as lit, which
+    /// keeps surfaces from shadowing themselves.
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+    /// Sets the light's view and projection for `cascade`.
+    pub fn set_matrices(&mut self, cascade: usize, view: Mat4, projection: Mat4)
+    {
+        self.views[cascade]       = view;
+        self.projections[cascade] = projection;
+    }
+    /// Views the sphere at `center` of `radius` along `direction`, as a
+    /// directional light does, with an orthographic projection.
+    pub fn set_directional(&mut self,
+                           cascade   : usize,
+                           direction : &Vec3,
+                           center    : &Vec3,
+                           radius    : f32)
+    {
+        let back = radius + self.caster_margin;
+        let eye  = center.diff(&direction.normalized().scale(back));
+        let view = Mat4::look_at(&eye, center, &up_for(direction));
+        let proj = Mat4::orthographic(-radius, radius, -radius, radius,
+                                      0.0, back + radius);
+        self.set_matrices(cascade, view, proj);
+    }
+    /// Views from `position` along `direction`, as a spot light of
+    /// `cone_angle` degrees across does, with a perspective projection.
+    pub fn set_spot(&mut self,
+                    position   : &Vec3,
+                    direction  : &Vec3,
+                    cone_angle : f32,
+                    near       : f32,
+                    far        : f32)
+    {
+        let view = Mat4::look_at(position, &position.add(direction),
+                                 &up_for(direction));
+        let proj = Mat4::perspective(cone_angle, 1.0, near, far);
+        self.set_matrices(0, view, proj);
+    }
+    /// Splits the view of a camera with `view` matrix and a perspective
+    /// projection of `fovy` degrees from `near` to `far` into the map's
+    /// cascades, by `cascade_splits` with `lambda`, and fits each to the
+    /// bounding sphere of its slice as seen along `direction`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit_cascades(&mut self,
+                        view         : &Mat4,
+                        fovy         : f32,
+                        aspect_ratio : f32,
+                        near         : f32,
+                        far          : f32,
+                        lambda       : f32,
+                        direction    : &Vec3)
+    {
+        use crate::types::DEG_TO_RAD;
+
+        let camera = view.inverse().unwrap_or(MAT4_IDENTITY);
+        let tan_y  = (fovy * 0.5 * DEG_TO_RAD).tan();
+        let tan_x  = tan_y * aspect_ratio;
+        let splits = cascade_splits(near, far, self.cascades(), lambda);
+
+        let mut start = near;
+
+        for (cascade, &end) in splits.iter().enumerate() {
+            let corners = [start, end].iter().flat_map(|&d| {
+                              [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0),
+                               (1.0, 1.0)].map(|(sx, sy)| {
+                                  camera.transform_point(
+                                      &Vec3::new(sx * tan_x * d,
+                                                 sy * tan_y * d, -d))
+                              })
+                          })
+                          .collect::<Vec<Vec3>>();
+
+            let center = corners.iter()
+                                .fold(Vec3::new_zeroed(), |sum, c| sum.add(c))
+                                .scale(1.0 / corners.len() as f32);
+            let radius = corners.iter()
+                                .map(|c| c.diff(&center).length())
+                                .fold(0.0, f32::max);
+
+            // Rounding the radius up keeps the texel size steady as the
+            // camera turns, so shadow edges don't shimmer.
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            self.set_directional(cascade, direction, &center, radius);
+            start = end;
+        }
+        self.splits = splits;
+    }
+    #[inline]
+    pub fn view_projection(&self, cascade: usize) -> Mat4 {
+        self.projections[cascade].multiply(&self.views[cascade])
+    }
+    /// Returns the matrix taking world positions to the map's texture
+    /// coordinates and depth for `cascade`: the light's view and projection,
+    /// the bias matrix, then the offset to the cascade's tile.
+    pub fn shadow_matrix(&self, cascade: usize) -> Mat4 {
+        let count = self.cascades() as f32;
+
+        let mut tile = MAT4_IDENTITY;
+        tile.m[0].x = 1.0 / count;
+        tile.m[3].x = cascade as f32 / count;
+
+        tile.multiply(&MAT4_BIAS).multiply(&self.view_projection(cascade))
+    }
+    /// Binds the map's texture to `unit` and sets the `SHADOW_GLSL`
+    /// uniforms of `program`, which must be current.
+    pub fn apply(&self, gfx: &Gfx<B>, program: &Program<B>, unit: u32) {
+        use WebGlRenderingContext as Ctx;
+
+        gfx.bind_texture(unit, Ctx::TEXTURE_2D, Some(self.texture().tid()));
+        program.set_uniform("SHADOWMAP", &UniformValue::Int(unit as i32));
+
+        for cascade in 0..self.cascades() {
+            program.set_uniform(&format!("SHADOWMATRIX[{}]", cascade),
+                                &(&self.shadow_matrix(cascade)).into());
+        }
+        // Cascades past the last are never picked.
+        let mut splits = [-1.0; 4];
+        splits[..self.splits.len()].copy_from_slice(&self.splits);
+
+        program.set_uniform("SHADOWSPLITS", &UniformValue::Vec4(splits));
+        program.set_uniform("SHADOWTEXELSIZE",
+                            &UniformValue::Vec2([
+                                1.0 / self.target.width()  as f32,
+                                1.0 / self.target.height() as f32]));
+        program.set_uniform("SHADOWTILEWIDTH",
+                            &UniformValue::Float(1.0 / self.cascades() as f32));
+        program.set_uniform("SHADOWBIAS", &UniformValue::Float(self.bias));
+    }
+}
+
+/// Draws shadow casters into a `ShadowMap`, each cascade in turn:
+///
+/// ```ignore
+/// pass.begin(&gfx, &map);
+/// for cascade in 0..map.cascades() {
+///     pass.cascade(&gfx, &map, cascade);
+///     for mesh in &casters {
+///         pass.set_model_matrix(&mesh.model);
+///         // Bind the mesh positions to POSITION and draw.
+///     }
+/// }
+/// pass.end(&gfx, &map);
+/// ```
+pub struct ShadowPass<B: GlBackend = WebGlRenderingContext> {
+    program         : Program<B>,
+    packed          : bool,
+    polygon_offset  : (f32, f32),
+    view_projection : Cell<Mat4>,
+}
+
+impl<B: GlBackend> ShadowPass<B> {
+    /// Creates the depth pass for maps created with `capabilities`.
+    pub fn new(capabilities: &GlCapabilities, context: Arc<B>)
+        -> Result<Self, GfxError>
+    {
+        let packed   = TexelFormat::depth(capabilities).is_none();
+        let fragment = if packed {
+            format!("#define PACKED_DEPTH\n{}", SHADOW_CASTER_FRAGMENT_SHADER)
+        } else {
+            SHADOW_CASTER_FRAGMENT_SHADER.to_string()
+        };
+        let mut program = Program::from_source("shadow_caster".into(),
+                                               SHADOW_CASTER_VERTEX_SHADER,
+                                               &fragment,
+                                               None, None,
+                                               context);
+        if !program.link() {
+            let msg = "The shadow caster program failed to link.".to_string();
+            Err( GfxError::new_resource_load_error(msg, None) )?
+        }
+        Ok( ShadowPass {
+                program,
+                packed,
+                polygon_offset  : (2.0, 4.0),
+                view_projection : Cell::new(MAT4_IDENTITY),
+            } )
+    }
+    /// Sets the factor and units casters' depth is pushed back by, against
+    /// shadow acne on slopes.
+    pub fn set_polygon_offset(&mut self, factor: f32, units: f32) {
+        self.polygon_offset = (factor, units);
+    }
+    /// Returns the caster program, which reads positions from `POSITION`.
+    #[inline]
+    pub fn program(&self) -> &Program<B> {
+        &self.program
+    }
+    /// Directs drawing into `map`, clears it to the far plane and makes the
+    /// caster program current. The clear color is put back if `gfx` knows
+    /// it, having set it through `Gfx::clear_color`.
+    pub fn begin(&self, gfx: &Gfx<B>, map: &ShadowMap<B>) {
+        use WebGlRenderingContext as Ctx;
+
+        assert_eq!(self.packed, map.is_packed(),
+                   "Shadow pass and map were created for different contexts.");
+
+        let raster = RasterState { polygon_offset: Some(self.polygon_offset),
+                                   ..RasterState::DEFAULT };
+        map.target().bind(gfx);
+        gfx.apply_render_state(&RenderState::OPAQUE.with_raster(raster));
+
+        let previous = gfx.current_clear_color();
+        gfx.clear_color(1.0, 1.0, 1.0, 1.0);
+        gfx.clear(Ctx::DEPTH_BUFFER_BIT | Ctx::COLOR_BUFFER_BIT);
+
+        if let Some([red, green, blue, alpha]) = previous {
+            gfx.clear_color(red, green, blue, alpha);
+        }
+
+        gfx.use_program(Some(self.program.pid()));
+    }
+    /// Directs drawing into `cascade`'s tile of `map`.
+    pub fn cascade(&self, gfx: &Gfx<B>, map: &ShadowMap<B>, cascade: usize) {
+        let size = map.size() as i32;
+
+        gfx.viewport(cascade as i32 * size, 0, size, size);
+        self.view_projection.set(map.view_projection(cascade));
+    }
+    /// Sets the model matrix of the casters drawn next.
+    pub fn set_model_matrix(&self, model: &Mat4) {
+        let mvp = self.view_projection.get().multiply(model);
+        self.program.set_uniform("LIGHTMVP", &(&mvp).into());
+    }
+    /// Undoes `begin`, leaving the opaque render state.
+    pub fn end(&self, gfx: &Gfx<B>, map: &ShadowMap<B>) {
+        map.target().unbind(gfx);
+        gfx.apply_render_state(&RenderState::OPAQUE);
+    }
+}
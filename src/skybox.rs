@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::error::GfxError;
+use crate::gfx::Gfx;
+use crate::gl_state::untracked_bind;
+use crate::gl_state::Binding;
+use crate::program::Program;
+use crate::render_state::DepthState;
+use crate::render_state::RasterState;
+use crate::texture::Texture;
+use crate::texture::TextureError;
+
+const SKYBOX_VERTEX_SHADER: &str = "
+uniform mediump mat4 MODELVIEWPROJECTIONMATRIX;
+
+attribute mediump vec3 POSITION;
+
+varying mediump vec3 direction;
+
+void main( void ) {
+
+	direction = POSITION;
+
+	// Force the depth to the far plane so the sky is behind everything.
+	gl_Position = ( MODELVIEWPROJECTIONMATRIX * vec4( POSITION, 1.0 ) ).xyww;
+
+}
+";
+
+const SKYBOX_FRAGMENT_SHADER: &str = "
+uniform lowp samplerCube CUBEMAP;
+
+varying mediump vec3 direction;
+
+void main( void ) {
+
+	gl_FragColor = textureCube( CUBEMAP, direction );
+
+}
+";
+
+/// Corners of a unit cube as 12 triangles.
+const SKYBOX_VERTICES: [f32; 108] = [
+    -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+
+    -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+];
+
+/// Draws a cube map texture as an environment background.
+pub struct Skybox<B: GlBackend = WebGlRenderingContext> {
+    program : Program<B>,
+    vbo     : Option<B::Buffer>,
+    context : Arc<B>,
+}
+
+impl<B: GlBackend> Skybox<B> {
+    pub fn new(context: Arc<B>) -> Result<Self, GfxError> {
+        use WebGlRenderingContext as Ctx;
+
+        let mut program = Program::from_source("skybox".into(),
+                                               SKYBOX_VERTEX_SHADER,
+                                               SKYBOX_FRAGMENT_SHADER,
+                                               None, None,
+                                               context.clone());
+        if !program.link() {
+            let msg = "The skybox program failed to link.".to_string();
+            Err( GfxError::new_resource_load_error(msg, None) )?
+        }
+        let bytes = SKYBOX_VERTICES.iter()
+                                   .flat_map(|v| v.to_le_bytes())
+                                   .collect::<Vec<u8>>();
+        let vbo   = context.create_buffer();
+
+        context.bind_buffer(Ctx::ARRAY_BUFFER, vbo.as_ref());
+        context.buffer_data(Ctx::ARRAY_BUFFER, &bytes, Ctx::STATIC_DRAW);
+        context.bind_buffer(Ctx::ARRAY_BUFFER, None);
+        untracked_bind(Binding::Buffers);
+
+        Ok( Skybox { program, vbo, context } )
+    }
+    /// Draws `cube_map` around the camera, using the projection and the
+    /// rotation part of the model view matrix of `gfx`. Call it after the
+    /// opaque geometry so covered sky pixels fail the depth test.
+    ///
+    /// The depth and raster state are put back as they were; where `gfx`
+    /// doesn't know them, they're left at their defaults.
+    pub fn draw(&self, gfx: &Gfx<B>, cube_map: &Texture<B>)
+        -> Result<(), GfxError>
+    {
+        use WebGlRenderingContext as Ctx;
+
+        if cube_map.target() != Ctx::TEXTURE_CUBE_MAP {
+            let msg = "The skybox texture isn't a cube map.".to_string();
+            Err( TextureError::UnsupportedFormat(msg) )?
+        }
+        let ctx    = &self.context;
+        let depth  = gfx.current_depth_state().unwrap_or(DepthState::DEFAULT);
+        let raster = gfx.current_raster_state()
+                        .unwrap_or(RasterState::DEFAULT);
+
+        // Keep the camera at the center of the box by dropping translation.
+        let mut view = *gfx.get_modelview_matrix();
+        view.m[3].x = 0.0;
+        view.m[3].y = 0.0;
+        view.m[3].z = 0.0;
+
+        let mvp = gfx.get_projection_matrix().multiply(&view);
+        let pos = self.program.get_vertex_attrib_location("POSITION") as u32;
+
+        gfx.use_program(Some(self.program.pid()));
+        ctx.uniform_matrix4fv(
+            Some(self.program.get_uniform_location("MODELVIEWPROJECTIONMATRIX")),
+            false,
+            &mvp.to_array());
+
+        gfx.bind_texture(0, Ctx::TEXTURE_CUBE_MAP, Some(cube_map.tid()));
+        ctx.uniform1i(Some(self.program.get_uniform_location("CUBEMAP")), 0);
+
+        gfx.bind_buffer(Ctx::ARRAY_BUFFER, self.vbo.as_ref());
+        ctx.enable_vertex_attrib_array(pos);
+        ctx.vertex_attrib_pointer(pos, 3, Ctx::FLOAT, false, 0, 0);
+
+        // The box is seen from the inside and sits exactly on the far plane.
+        gfx.apply_raster_state(&RasterState::NO_CULL);
+        gfx.apply_depth_state(&DepthState::READ_ONLY);
+
+        gfx.draw_arrays(Ctx::TRIANGLES, 0, 36);
+
+        gfx.apply_depth_state(&depth);
+        gfx.apply_raster_state(&raster);
+
+        ctx.disable_vertex_attrib_array(pos);
+        gfx.bind_buffer(Ctx::ARRAY_BUFFER, None);
+        Ok( () )
+    }
+}
+
+impl<B: GlBackend> Drop for Skybox<B> {
+    fn drop(&mut self) {
+        if let Some(vbo) = self.vbo.take() {
+            self.context.delete_buffer(&vbo);
+        }
+    }
+}
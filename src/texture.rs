@@ -1,50 +1,558 @@
-
 use std::any::Any;
 use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
+use std::io::SeekFrom;
+
+use wasm_bindgen::prelude::*;
 
+use web_sys::ExtTextureFilterAnisotropic;
+use web_sys::WebglCompressedTextureAstc;
+use web_sys::WebglCompressedTextureEtc;
+use web_sys::WebglCompressedTextureEtc1;
 use web_sys::WebglCompressedTexturePvrtc;
+use web_sys::WebglCompressedTextureS3tc;
+use web_sys::WebglCompressedTextureS3tcSrgb;
+use web_sys::WebGl2RenderingContext as Ctx2;
 use web_sys::WebGlRenderingContext;
-use web_sys::WebGlTexture;
+use web_sys::WebGlRenderingContext as Ctx;
 
+use crate::backend::GlBackend;
+use crate::backend::GlVersion;
+use crate::capabilities::GlCapabilities;
+use crate::console_log;
 use crate::error::GfxError;
+use crate::from_bytes_struct;
+use crate::gl_state::untracked_bind;
+use crate::gl_state::Binding;
+use crate::image::Image;
+use crate::image::MipmapFilter;
 use crate::memory::Memory;
+use crate::memory::MemoryError;
 use crate::memory as memory;
+use crate::utils::jsval_to_string;
 
-const TEXTURE_CLAMP         : u32 = 1 << 0;
-const TEXTURE_MIPMAP        : u32 = 1 << 1;
-const TEXTURE_16_BITS       : u32 = 1 << 2;
-const TEXTURE_16_BITS_5551  : u32 = 1 << 3;
+pub const TEXTURE_CLAMP         : u32 = 1 << 0;
+pub const TEXTURE_MIPMAP        : u32 = 1 << 1;
+pub const TEXTURE_16_BITS       : u32 = 1 << 2;
+pub const TEXTURE_16_BITS_5551  : u32 = 1 << 3;
+pub const TEXTURE_MIPMAP_CPU    : u32 = 1 << 4;
+pub const TEXTURE_MIPMAP_KAISER : u32 = 1 << 5;
+pub const TEXTURE_MIPMAP_SRGB   : u32 = 1 << 6;
+pub const TEXTURE_NPOT_RESIZE   : u32 = 1 << 7;
 
-const TEXTURE_FILTER_0X     : u32 = 0;
-const TEXTURE_FILTER_1X     : u32 = 1;
-const TEXTURE_FILTER_2X     : u32 = 2;
-const TEXTURE_FILTER_3X     : u32 = 3;
+pub const TEXTURE_FILTER_0X     : u8 = 0;
+pub const TEXTURE_FILTER_1X     : u8 = 1;
+pub const TEXTURE_FILTER_2X     : u8 = 2;
+pub const TEXTURE_FILTER_3X     : u8 = 3;
 
 const PVR_IDENTIFIER        : [u8;4] = *b"PVR!";
 
-//#[repr(packed)] isn't needed since all fields are u32.
-#[repr(C)]
-struct PvrHeader {
-    hdr_size        : u32,
-    height          : u32,
+const KTX_IDENTIFIER        : [u8;12] = [0xAB, b'K', b'T', b'X', b' ', b'1',
+                                         b'1', 0xBB, b'\r', b'\n', 0x1A,
+                                         b'\n'];
+const KTX2_IDENTIFIER       : [u8;12] = [0xAB, b'K', b'T', b'X', b' ', b'2',
+                                         b'0', 0xBB, b'\r', b'\n', 0x1A,
+                                         b'\n'];
+const KTX_ENDIANNESS        : u32 = 0x04030201;
+const KTX2_HEADER_SIZE      : usize = 80;
+
+from_bytes_struct! {
+    struct PvrHeader {
+        hdr_size        : u32,
+        height          : u32,
+        width           : u32,
+        n_mipmap        : u32,
+        flags           : u32,
+        data_size       : u32,
+        bpp             : u32,
+        bit_red         : u32,
+        bit_green       : u32,
+        bit_blue        : u32,
+        bit_alpha       : u32,
+        tag             : u32,
+        n_surface       : u32,
+    }
+}
+
+const DDS_IDENTIFIER        : [u8;4] = *b"DDS ";
+const DDSD_MIPMAPCOUNT      : u32 = 0x20000;
+const DDPF_ALPHAPIXELS      : u32 = 0x1;
+const DDPF_FOURCC           : u32 = 0x4;
+const DDSCAPS2_CUBEMAP      : u32 = 0x200;
+const DDSCAPS2_ALLFACES     : u32 = 0xFC00;
+const DDSCAPS2_VOLUME       : u32 = 0x200000;
+
+const PKM_IDENTIFIER        : [u8;4] = *b"PKM ";
+
+const ASTC_IDENTIFIER       : [u8;4] = [0x13, 0xAB, 0xA1, 0x5C];
+
+/// The families of compressed texel formats WebGL exposes, one per
+/// extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionFamily {
+    S3tc,
+    S3tcSrgb,
+    Etc1,
+    Etc,
+    Astc,
+    Pvrtc,
+}
+
+impl CompressionFamily {
+    pub const ALL: [CompressionFamily; 6] = [CompressionFamily::S3tc,
+                                             CompressionFamily::S3tcSrgb,
+                                             CompressionFamily::Etc1,
+                                             CompressionFamily::Etc,
+                                             CompressionFamily::Astc,
+                                             CompressionFamily::Pvrtc];
+
+    /// The name of the WebGL extension that enables the family.
+    pub fn extension(&self) -> &'static str {
+        use CompressionFamily::*;
+        match self {
+            S3tc     => "WEBGL_compressed_texture_s3tc",
+            S3tcSrgb => "WEBGL_compressed_texture_s3tc_srgb",
+            Etc1     => "WEBGL_compressed_texture_etc1",
+            Etc      => "WEBGL_compressed_texture_etc",
+            Astc     => "WEBGL_compressed_texture_astc",
+            Pvrtc    => "WEBGL_compressed_texture_pvrtc",
+        }
+    }
+}
+
+/// The compressed texture families available on the current device.
+#[derive(Clone, Debug, Default)]
+pub struct CompressionSupport {
+    families: Vec<CompressionFamily>,
+}
+
+impl CompressionSupport {
+    /// Queries (and enables) each compressed texture extension on `context`.
+    pub fn query(context: &WebGlRenderingContext) -> Self {
+        CompressionSupport::query_backend(context)
+    }
+    /// Like `query`, for any backend.
+    pub fn query_backend<B: GlBackend>(context: &B) -> Self {
+        let families = CompressionFamily::ALL
+                       .iter()
+                       .filter(|f| context.enable_extension(f.extension()))
+                       .copied()
+                       .collect();
+        CompressionSupport { families }
+    }
+    pub fn supports(&self, family: CompressionFamily) -> bool {
+        self.families.contains(&family)
+    }
+    /// Picks the first of `variants` the device can use. A variant with no
+    /// family is uncompressed and always usable, so it makes a good final
+    /// fallback.
+    pub fn select<'a>(&self,
+                      variants: &[(Option<CompressionFamily>, &'a str)])
+        -> Option<&'a str>
+    {
+        variants.iter()
+                .find(|(family, _)| family.is_none_or(|f| self.supports(f)))
+                .map(|(_, url)| *url)
+    }
+}
+
+/// Block layout of a compressed texel format, and the family whose WebGL
+/// extension has to be enabled before the format can be uploaded.
+struct CompressedFormat {
+    format          : u32,
+    block_width     : u32,
+    block_height    : u32,
+    block_size      : u32,
+    min_blocks      : u32,
+    family          : CompressionFamily,
+}
+
+impl CompressedFormat {
+    /// Returns the number of bytes a `width` x `height` image takes in this
+    /// format.
+    fn level_size(&self, width: u32, height: u32) -> usize {
+        let bw = width .div_ceil(self.block_width ).max(self.min_blocks);
+        let bh = height.div_ceil(self.block_height).max(self.min_blocks);
+        (bw * bh * self.block_size) as usize
+    }
+}
+
+macro_rules! compressed_format {
+    ($fmt:expr, $bw:expr, $bh:expr, $bs:expr, $min:expr, $ext:expr) => {
+        CompressedFormat { format       : $fmt,
+                           block_width  : $bw,
+                           block_height : $bh,
+                           block_size   : $bs,
+                           min_blocks   : $min,
+                           family       : $ext }
+    }
+}
+
+const S3TC  : CompressionFamily = CompressionFamily::S3tc;
+const S3TCS : CompressionFamily = CompressionFamily::S3tcSrgb;
+const ETC1  : CompressionFamily = CompressionFamily::Etc1;
+const ETC   : CompressionFamily = CompressionFamily::Etc;
+const ASTC  : CompressionFamily = CompressionFamily::Astc;
+const PVRTC : CompressionFamily = CompressionFamily::Pvrtc;
+
+const COMPRESSED_FORMAT_ARRAY : [CompressedFormat; 51] = {
+    use WebglCompressedTextureAstc     as AST;
+    use WebglCompressedTextureEtc      as ETC2;
+    use WebglCompressedTextureEtc1     as ET1;
+    use WebglCompressedTexturePvrtc    as PVR;
+    use WebglCompressedTextureS3tc     as S3;
+    use WebglCompressedTextureS3tcSrgb as S3S;
+    [
+        compressed_format!(S3::COMPRESSED_RGB_S3TC_DXT1_EXT,         4, 4,  8, 1, S3TC ),
+        compressed_format!(S3::COMPRESSED_RGBA_S3TC_DXT1_EXT,        4, 4,  8, 1, S3TC ),
+        compressed_format!(S3::COMPRESSED_RGBA_S3TC_DXT3_EXT,        4, 4, 16, 1, S3TC ),
+        compressed_format!(S3::COMPRESSED_RGBA_S3TC_DXT5_EXT,        4, 4, 16, 1, S3TC ),
+        compressed_format!(S3S::COMPRESSED_SRGB_S3TC_DXT1_EXT,       4, 4,  8, 1, S3TCS),
+        compressed_format!(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 4, 4,  8, 1, S3TCS),
+        compressed_format!(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 4, 4, 16, 1, S3TCS),
+        compressed_format!(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 4, 4, 16, 1, S3TCS),
+
+        compressed_format!(ET1::COMPRESSED_RGB_ETC1_WEBGL,           4, 4,  8, 1, ETC1 ),
+
+        compressed_format!(ETC2::COMPRESSED_R11_EAC,                 4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_SIGNED_R11_EAC,          4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_RG11_EAC,                4, 4, 16, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_SIGNED_RG11_EAC,         4, 4, 16, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_RGB8_ETC2,               4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_SRGB8_ETC2,              4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+                                                                     4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+                                                                     4, 4,  8, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_RGBA8_ETC2_EAC,          4, 4, 16, 1, ETC  ),
+        compressed_format!(ETC2::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,   4, 4, 16, 1, ETC  ),
+
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_4X4_KHR,        4,  4, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_5X4_KHR,        5,  4, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_5X5_KHR,        5,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_6X5_KHR,        6,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_6X6_KHR,        6,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_8X5_KHR,        8,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_8X6_KHR,        8,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_8X8_KHR,        8,  8, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_10X5_KHR,      10,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_10X6_KHR,      10,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_10X8_KHR,      10,  8, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_10X10_KHR,     10, 10, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_12X10_KHR,     12, 10, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_RGBA_ASTC_12X12_KHR,     12, 12, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR,
+                                                                     4,  4, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_5X4_KHR,
+                                                                     5,  4, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_5X5_KHR,
+                                                                     5,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_6X5_KHR,
+                                                                     6,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_6X6_KHR,
+                                                                     6,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_8X5_KHR,
+                                                                     8,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_8X6_KHR,
+                                                                     8,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_8X8_KHR,
+                                                                     8,  8, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_10X5_KHR,
+                                                                    10,  5, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_10X6_KHR,
+                                                                    10,  6, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_10X8_KHR,
+                                                                    10,  8, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_10X10_KHR,
+                                                                    10, 10, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_12X10_KHR,
+                                                                    12, 10, 16, 1, ASTC),
+        compressed_format!(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_12X12_KHR,
+                                                                    12, 12, 16, 1, ASTC),
+
+        compressed_format!(PVR::COMPRESSED_RGB_PVRTC_4BPPV1_IMG,     4, 4,  8, 2, PVRTC),
+        compressed_format!(PVR::COMPRESSED_RGB_PVRTC_2BPPV1_IMG,     8, 4,  8, 2, PVRTC),
+        compressed_format!(PVR::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG,    4, 4,  8, 2, PVRTC),
+        compressed_format!(PVR::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG,    8, 4,  8, 2, PVRTC),
+    ]
+};
+
+fn find_compressed_format(format: u32) -> Option<&'static CompressedFormat> {
+    COMPRESSED_FORMAT_ARRAY.iter().find(|f| f.format == format)
+}
+
+/// Maps a KTX2 `vkFormat` onto the WebGL (internal format, format, type,
+/// bytes per pixel) it is uploaded with. Compressed formats have a type and
+/// byte count of 0.
+fn vk_format_to_gl(vk_format: u32) -> Option<(u32, u32, u32, u8)> {
+    use WebGlRenderingContext          as Ctx;
+    use WebglCompressedTextureAstc     as AST;
+    use WebglCompressedTextureEtc      as ETC2;
+    use WebglCompressedTexturePvrtc    as PVR;
+    use WebglCompressedTextureS3tc     as S3;
+    use WebglCompressedTextureS3tcSrgb as S3S;
+
+    let c = |f| Some((f, f, 0, 0));
+
+    match vk_format {
+        2   => Some((Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_SHORT_4_4_4_4, 2)),
+        4   => Some((Ctx::RGB,  Ctx::RGB,  Ctx::UNSIGNED_SHORT_5_6_5,   2)),
+        6   => Some((Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_SHORT_5_5_5_1, 2)),
+        9   => Some((Ctx::LUMINANCE, Ctx::LUMINANCE, Ctx::UNSIGNED_BYTE, 1)),
+        16  => Some((Ctx::LUMINANCE_ALPHA, Ctx::LUMINANCE_ALPHA,
+                     Ctx::UNSIGNED_BYTE, 2)),
+        23  => Some((Ctx::RGB,  Ctx::RGB,  Ctx::UNSIGNED_BYTE, 3)),
+        37  => Some((Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_BYTE, 4)),
+
+        131 => c(S3::COMPRESSED_RGB_S3TC_DXT1_EXT),
+        132 => c(S3S::COMPRESSED_SRGB_S3TC_DXT1_EXT),
+        133 => c(S3::COMPRESSED_RGBA_S3TC_DXT1_EXT),
+        134 => c(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT),
+        135 => c(S3::COMPRESSED_RGBA_S3TC_DXT3_EXT),
+        136 => c(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT),
+        137 => c(S3::COMPRESSED_RGBA_S3TC_DXT5_EXT),
+        138 => c(S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT),
+
+        147 => c(ETC2::COMPRESSED_RGB8_ETC2),
+        148 => c(ETC2::COMPRESSED_SRGB8_ETC2),
+        149 => c(ETC2::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2),
+        150 => c(ETC2::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2),
+        151 => c(ETC2::COMPRESSED_RGBA8_ETC2_EAC),
+        152 => c(ETC2::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC),
+        153 => c(ETC2::COMPRESSED_R11_EAC),
+        154 => c(ETC2::COMPRESSED_SIGNED_R11_EAC),
+        155 => c(ETC2::COMPRESSED_RG11_EAC),
+        156 => c(ETC2::COMPRESSED_SIGNED_RG11_EAC),
+
+        // The ASTC formats alternate UNORM / SRGB for each block size, in the
+        // same block size order as the GL enums.
+        157..=184 => {
+            let i = (vk_format - 157) / 2;
+            if (vk_format - 157).is_multiple_of(2) {
+                c(AST::COMPRESSED_RGBA_ASTC_4X4_KHR + i)
+            } else {
+                c(AST::COMPRESSED_SRGB8_ALPHA8_ASTC_4X4_KHR + i)
+            }
+        },
+
+        1000054000 => c(PVR::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG),
+        1000054001 => c(PVR::COMPRESSED_RGBA_PVRTC_4BPPV1_IMG),
+
+        _ => None,
+    }
+}
+
+/// Returns the number of bytes per pixel for an uncompressed format and type
+/// pair, or `None` if WebGL 1 can't upload the combination.
+fn texel_size(format: u32, texel_type: u32) -> Option<u8> {
+    use WebGlRenderingContext as Ctx;
+    match texel_type {
+        Ctx::UNSIGNED_SHORT_5_6_5   |
+        Ctx::UNSIGNED_SHORT_4_4_4_4 |
+        Ctx::UNSIGNED_SHORT_5_5_5_1 => Some(2),
+        Ctx::UNSIGNED_BYTE => {
+            match format {
+                Ctx::ALPHA | Ctx::LUMINANCE => Some(1),
+                Ctx::LUMINANCE_ALPHA        => Some(2),
+                Ctx::RGB                    => Some(3),
+                Ctx::RGBA                   => Some(4),
+                _                           => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+fn read_u32(memory: &mut Memory, big_endian: bool)
+    -> Result<u32, MemoryError>
+{
+    if big_endian { memory.read_u32_be() } else { memory.read_u32_le() }
+}
+
+/// Moves `memory` forward to the next multiple of 4 bytes from `start`, or
+/// to its end if that comes first.
+fn skip_padding(memory: &mut Memory, start: usize) -> Result<(), MemoryError> {
+    let next = start + align4(memory.mtell() - start);
+    memory.mseek(SeekFrom::Start(next.min(memory.size()) as u64))?;
+    Ok(())
+}
+
+#[inline]
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+/// Parses the `size` byte KTX/KTX2 key/value block at the current position
+/// of `memory` into (key, value) pairs.
+fn parse_key_values(memory: &mut Memory, size: usize, big_endian: bool)
+    -> Result<Vec<(String, Vec<u8>)>, TextureError>
+{
+    use TextureError::*;
+    let mut pairs = vec![];
+    let start     = memory.mtell();
+    let end       = start + size;
+
+    while memory.mtell() + 4 <= end {
+        let len   = read_u32(memory, big_endian)? as usize;
+        let kv    = memory.read_bytes(len)?;
+        let nul   = kv.iter().position(|&b| b == 0).ok_or_else(|| {
+            HeaderFormatError("KTX key/value pair has no NUL terminated \
+                               key.".into())
+        })?;
+        let key   = String::from_utf8_lossy(&kv[..nul]).into_owned();
+        let mut value = kv[nul + 1..].to_vec();
+
+        // String values carry their own NUL terminator.
+        if value.last() == Some(&0) {
+            value.pop();
+        }
+        pairs.push((key, value));
+        skip_padding(memory, start)?;
+    }
+    memory.mseek(SeekFrom::Start(end as u64))?;
+    Ok(pairs)
+}
+
+/// Arrangements of the six faces of a cube map within a single image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeMapLayout {
+    /// Six faces side by side, +X, -X, +Y, -Y, +Z, -Z.
+    HorizontalStrip,
+    /// Six faces top to bottom, +X, -X, +Y, -Y, +Z, -Z.
+    VerticalStrip,
+    /// A 4x3 cross; -X, +Z, +X, -Z across the middle row, with +Y above and
+    /// -Y below +Z.
+    HorizontalCross,
+    /// A 3x4 cross; -X, +Z, +X across the second row, +Y above +Z, and -Y
+    /// then an upside down -Z below it.
+    VerticalCross,
+}
+
+impl CubeMapLayout {
+    /// Infers the layout from an image's aspect ratio.
+    pub fn detect(width: u32, height: u32) -> Option<Self> {
+        use CubeMapLayout::*;
+        [HorizontalStrip, VerticalStrip, HorizontalCross, VerticalCross]
+            .iter()
+            .copied()
+            .find(|layout| {
+                let (columns, rows) = layout.grid();
+                width > 0
+                    && width.is_multiple_of(columns)
+                    && width / columns * rows == height
+            })
+    }
+    /// Returns the layout's size in faces as (columns, rows).
+    pub fn grid(&self) -> (u32, u32) {
+        use CubeMapLayout::*;
+        match self {
+            HorizontalStrip => (6, 1),
+            VerticalStrip   => (1, 6),
+            HorizontalCross => (4, 3),
+            VerticalCross   => (3, 4),
+        }
+    }
+    /// Returns the (column, row, upside down) cell of each face, in +X, -X,
+    /// +Y, -Y, +Z, -Z order.
+    fn cells(&self) -> [(u32, u32, bool); 6] {
+        use CubeMapLayout::*;
+        match self {
+            HorizontalStrip => [(0, 0, false), (1, 0, false), (2, 0, false),
+                                (3, 0, false), (4, 0, false), (5, 0, false)],
+            VerticalStrip   => [(0, 0, false), (0, 1, false), (0, 2, false),
+                                (0, 3, false), (0, 4, false), (0, 5, false)],
+            HorizontalCross => [(2, 1, false), (0, 1, false), (1, 0, false),
+                                (1, 2, false), (1, 1, false), (3, 1, false)],
+            VerticalCross   => [(2, 1, false), (0, 1, false), (1, 0, false),
+                                (1, 2, false), (1, 1, false), (1, 3, true )],
+        }
+    }
+}
+
+/// One image of a texture; a mip level of a 2D texture, or a mip level of a
+/// single cube map face.
+#[derive(Clone, Copy)]
+struct TextureLevel {
+    target          : u32,
+    level           : u32,
     width           : u32,
-    n_mipmap        : u32,
-    flags           : u32,
-    data_size       : u32,
-    bpp             : u32,
-    bit_red         : u32,
-    bit_green       : u32,
-    bit_blue        : u32,
-    bit_alpha       : u32,
-    tag             : u32,
-    n_surface       : u32,
-}
-
-struct Texture {
+    height          : u32,
+    offset          : usize,
+    size            : usize,
+}
+
+/// The layout of a texture created empty to be rendered to, as by
+/// `RenderTarget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TexelFormat {
+    pub internal_format : u32,
+    pub format          : u32,
+    pub texel_type      : u32,
+    /// Bytes per texel, for the memory estimate.
+    pub bytes           : u8,
+}
+
+impl TexelFormat {
+    pub const RGBA8 : TexelFormat = TexelFormat {
+        internal_format : Ctx::RGBA,
+        format          : Ctx::RGBA,
+        texel_type      : Ctx::UNSIGNED_BYTE,
+        bytes           : 4,
+    };
+    pub const RGB8 : TexelFormat = TexelFormat {
+        internal_format : Ctx::RGB,
+        format          : Ctx::RGB,
+        texel_type      : Ctx::UNSIGNED_BYTE,
+        bytes           : 3,
+    };
+    /// Half float color for HDR rendering; WebGL2 with
+    /// `EXT_color_buffer_float` only.
+    pub const RGBA16F : TexelFormat = TexelFormat {
+        internal_format : Ctx2::RGBA16F,
+        format          : Ctx::RGBA,
+        texel_type      : Ctx2::HALF_FLOAT,
+        bytes           : 8,
+    };
+    /// Depth on WebGL1 through `WEBGL_depth_texture`.
+    pub const DEPTH : TexelFormat = TexelFormat {
+        internal_format : Ctx::DEPTH_COMPONENT,
+        format          : Ctx::DEPTH_COMPONENT,
+        texel_type      : Ctx::UNSIGNED_INT,
+        bytes           : 4,
+    };
+    /// Depth on WebGL2, which wants a sized internal format.
+    pub const DEPTH24 : TexelFormat = TexelFormat {
+        internal_format : Ctx2::DEPTH_COMPONENT24,
+        format          : Ctx::DEPTH_COMPONENT,
+        texel_type      : Ctx::UNSIGNED_INT,
+        bytes           : 4,
+    };
+
+    /// Returns the depth format the context can render to and sample, or
+    /// `None` without depth texture support.
+    pub fn depth(capabilities: &GlCapabilities) -> Option<TexelFormat> {
+        if capabilities.is_webgl2() {
+            Some( TexelFormat::DEPTH24 )
+        } else if capabilities.depth_texture {
+            Some( TexelFormat::DEPTH )
+        } else {
+            None
+        }
+    }
+    #[inline]
+    pub fn is_depth(&self) -> bool {
+        self.format == Ctx::DEPTH_COMPONENT
+    }
+    #[inline]
+    pub fn is_float(&self) -> bool {
+        self.texel_type == Ctx::FLOAT || self.texel_type == Ctx2::HALF_FLOAT
+    }
+}
+
+pub struct Texture<B: GlBackend = WebGlRenderingContext> {
     name            : String,
-    tid             : Option<WebGlTexture>,
+    tid             : Option<B::Texture>,
     width           : u16,
     height          : u16,
     bytes           : u8,
@@ -54,24 +562,178 @@ struct Texture {
     format          : u32,
     texel_type      : u32,
     texel_array     : Vec<u8>,
+    level_array     : Vec<TextureLevel>,
+    key_value_array : Vec<(String, Vec<u8>)>,
     n_mipmap        : u32,
     compression     : u32,
-    context         : Arc<WebGlRenderingContext>,
+    anisotropy      : f32,
+    context         : Arc<B>,
 }
 
-impl Texture {
-    pub async fn new(name                 : &str, 
-                     url                  : &str, 
+impl<B: GlBackend> Texture<B> {
+    pub async fn new(name                 : &str,
+                     url                  : &str,
                      flags                : u32,
                      filter               : u8,
                      anisotropic_filter   : f32,
-                     context              : Arc<WebGlRenderingContext>
+                     context              : Arc<B>
                     ) -> Result<Self, GfxError>
+    {
+        let mut m = Memory::mopen(url).await?;
+
+        Texture::from_memory(name, &mut m, flags, filter, anisotropic_filter,
+                             context)
+    }
+    /// Creates a texture from a file already read into `memory`, such as one
+    /// a `Preloader` fetched.
+    pub fn from_memory(name                 : &str,
+                       memory               : &mut Memory,
+                       flags                : u32,
+                       filter               : u8,
+                       anisotropic_filter   : f32,
+                       context              : Arc<B>
+                      ) -> Result<Self, GfxError>
+    {
+        let mut texture = Texture::empty(name, context);
+
+        texture.load(memory)?;
+        texture.generate_id(flags, filter, anisotropic_filter)?;
+        texture.free_texel_array();
+        Ok(texture)
+    }
+    /// Creates a cube map from six images, given in +X, -X, +Y, -Y, +Z, -Z
+    /// order. The faces must be square and share a size and format.
+    pub async fn new_cube_map(name                 : &str,
+                              urls                 : [&str; 6],
+                              flags                : u32,
+                              filter               : u8,
+                              anisotropic_filter   : f32,
+                              context              : Arc<B>
+                             ) -> Result<Self, GfxError>
+    {
+        let mut texture = Texture::empty(name, context.clone());
+
+        for (face, url) in urls.iter().enumerate() {
+            let mut m = Memory::mopen(url).await?;
+            let mut image = Texture::empty(url, context.clone());
+
+            image.load(&mut m)?;
+            texture.add_cube_face(face as u32, &mut image)?;
+        }
+        texture.generate_id(flags, filter, anisotropic_filter)?;
+        texture.free_texel_array();
+        Ok(texture)
+    }
+    /// Creates a cube map from a single uncompressed image holding all six
+    /// faces as a strip or cross; see `CubeMapLayout`.
+    pub async fn new_cube_map_from_layout(
+                              name                 : &str,
+                              url                  : &str,
+                              flags                : u32,
+                              filter               : u8,
+                              anisotropic_filter   : f32,
+                              context              : Arc<B>
+                             ) -> Result<Self, GfxError>
+    {
+        let mut m = Memory::mopen(url).await?;
+        let mut texture = Texture::empty(name, context);
+
+        texture.load(&mut m)?;
+        texture.split_cube_map_layout()?;
+        texture.generate_id(flags, filter, anisotropic_filter)?;
+        texture.free_texel_array();
+        Ok(texture)
+    }
+    /// Creates a texture from decoded texels, such as a packed atlas. The
+    /// channel count picks the format; 1 is luminance, 2 luminance alpha, 3
+    /// RGB and 4 RGBA.
+    pub fn from_image(name                 : &str,
+                      image                : &Image,
+                      flags                : u32,
+                      filter               : u8,
+                      anisotropic_filter   : f32,
+                      context              : Arc<B>
+                     ) -> Result<Self, GfxError>
     {
         use WebGlRenderingContext as Ctx;
-        let m = Memory::mopen(url).await?;
-        Ok (
-            Texture {
+
+        let format = match image.channels {
+            1 => Ctx::LUMINANCE,
+            2 => Ctx::LUMINANCE_ALPHA,
+            3 => Ctx::RGB,
+            4 => Ctx::RGBA,
+            n => {
+                let msg = format!("Image ({}) has {} channels; only 1 to 4 \
+                                   are supported.", name, n);
+                Err( TextureError::UnsupportedFormat(msg) )?
+            }
+        };
+        let mut texture = Texture::empty(name, context);
+
+        texture.internal_format = format;
+        texture.format          = format;
+        texture.texel_type      = Ctx::UNSIGNED_BYTE;
+        texture.bytes           = image.channels as u8;
+        texture.width           = image.width  as u16;
+        texture.height          = image.height as u16;
+        texture.set_images(vec![(Ctx::TEXTURE_2D, 0, image.clone())]);
+        texture.generate_id(flags, filter, anisotropic_filter)?;
+        texture.free_texel_array();
+        Ok(texture)
+    }
+    /// Creates an empty 2D texture of `width` by `height` texels to render
+    /// to, clamped to the edge and without mipmaps. `filter` is
+    /// `TEXTURE_FILTER_0X` for nearest sampling, which depth textures need
+    /// on WebGL1, or linear otherwise.
+    pub fn with_size(name    : &str,
+                     width   : u32,
+                     height  : u32,
+                     format  : &TexelFormat,
+                     filter  : u8,
+                     context : Arc<B>
+                    ) -> Result<Self, GfxError>
+    {
+        let mut texture = Texture::empty(name, context.clone());
+
+        texture.internal_format = format.internal_format;
+        texture.format          = format.format;
+        texture.texel_type      = format.texel_type;
+        texture.bytes           = format.bytes;
+        texture.width           = width  as u16;
+        texture.height          = height as u16;
+        texture.n_mipmap        = 1;
+        texture.size            = width * height * format.bytes as u32;
+        texture.tid             = context.create_texture();
+
+        let filter = if filter == TEXTURE_FILTER_0X { Ctx::NEAREST }
+                     else                           { Ctx::LINEAR  };
+
+        context.bind_texture(Ctx::TEXTURE_2D, texture.tid.as_ref());
+        untracked_bind(Binding::Textures);
+        context.tex_parameteri(Ctx::TEXTURE_2D, Ctx::TEXTURE_WRAP_S,
+                               Ctx::CLAMP_TO_EDGE as i32);
+        context.tex_parameteri(Ctx::TEXTURE_2D, Ctx::TEXTURE_WRAP_T,
+                               Ctx::CLAMP_TO_EDGE as i32);
+        context.tex_parameteri(Ctx::TEXTURE_2D, Ctx::TEXTURE_MAG_FILTER,
+                               filter as i32);
+        context.tex_parameteri(Ctx::TEXTURE_2D, Ctx::TEXTURE_MIN_FILTER,
+                               filter as i32);
+        context.tex_image_2d(Ctx::TEXTURE_2D,
+                             0,
+                             format.internal_format as i32,
+                             width  as i32,
+                             height as i32,
+                             0,
+                             format.format,
+                             format.texel_type,
+                             None)
+               .map_err(TextureError::UploadError)?;
+        context.bind_texture(Ctx::TEXTURE_2D, None);
+        Ok(texture)
+    }
+    fn empty(name: &str, context: Arc<B>) -> Self {
+        use WebGlRenderingContext as Ctx;
+        Texture {
                 name            : name.into(),
                 tid             : None,
                 width           : 0,
@@ -83,42 +745,195 @@ impl Texture {
                 format          : 0,
                 texel_type      : 0,
                 texel_array     : vec![],
+                level_array     : vec![],
+                key_value_array : vec![],
                 n_mipmap        : 0,
                 compression     : 0,
+                anisotropy      : 0.0,
                 context
-            })
+            }
+    }
+    #[inline]
+    pub fn tid(&self) -> &B::Texture {
+        self.tid.as_ref().expect("Texture tid not set.")
+    }
+    #[inline]
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+    /// Returns the estimated video memory used by the texture, in bytes,
+    /// including its mip levels.
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    /// Returns the anisotropic filtering level applied to the texture, or 0
+    /// if none was requested or the device doesn't support it.
+    #[inline]
+    pub fn anisotropic_filter(&self) -> f32 {
+        self.anisotropy
+    }
+    /// Returns the value stored under `key` in the texture file's key/value
+    /// data (KTX and KTX2 only).
+    pub fn key_value(&self, key: &str) -> Option<&[u8]> {
+        self.key_value_array.iter()
+                            .find(|(k, _)| k == key)
+                            .map(|(_, v)| v.as_slice())
+    }
+    fn delete_id(&mut self) {
+        if self.tid.is_some() {
+            self.context.delete_texture(self.tid.as_ref().unwrap());
+            self.tid = None;
+        }
+    }
+    fn free_texel_array(&mut self) {
+        self.texel_array = vec![];
+        self.level_array.clear();
+    }
+    fn load(&mut self, memory: &mut Memory) -> Result<(), TextureError> {
+        let bytes = memory.bytes();
+
+        if bytes.starts_with(&KTX_IDENTIFIER) {
+            self.load_ktx(memory)?;
+        } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+            self.load_ktx2(memory)?;
+        } else if bytes.starts_with(&DDS_IDENTIFIER) {
+            self.load_dds(memory)?;
+        } else if bytes.starts_with(&PKM_IDENTIFIER) {
+            self.load_pkm(memory)?;
+        } else if bytes.starts_with(&ASTC_IDENTIFIER) {
+            self.load_astc(memory)?;
+        } else if bytes.get(44..48) == Some(&PVR_IDENTIFIER[..]) {
+            self.load_pvr(memory)?;
+        } else {
+            let msg = format!("Texture ({}) isn't in a supported container \
+                               format.", self.name);
+            Err( TextureError::UnsupportedFormat(msg) )?
+        }
+        self.size = self.level_array.iter().map(|l| l.size as u32).sum();
+        Ok(())
+    }
+    /// Appends the 2D texture `image` as cube map face `face`.
+    fn add_cube_face(&mut self, face: u32, image: &mut Texture<B>)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as Ctx;
+        use TextureError::*;
+
+        if image.target != Ctx::TEXTURE_2D || image.width != image.height {
+            let msg = format!("Cube map face ({}) isn't a square 2D image.",
+                              image.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        if face == 0 {
+            self.target          = Ctx::TEXTURE_CUBE_MAP;
+            self.width           = image.width;
+            self.height          = image.height;
+            self.bytes           = image.bytes;
+            self.internal_format = image.internal_format;
+            self.format          = image.format;
+            self.texel_type      = image.texel_type;
+            self.compression     = image.compression;
+            self.n_mipmap        = image.n_mipmap;
+        } else if (image.width, image.format, image.texel_type,
+                   image.compression, image.n_mipmap)
+               != (self.width, self.format, self.texel_type,
+                   self.compression, self.n_mipmap)
+        {
+            let msg = format!("Cube map face ({}) doesn't match the size or \
+                               format of the first face.", image.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        let base = self.texel_array.len();
+
+        for l in &image.level_array {
+            self.level_array.push(
+                TextureLevel {
+                    target : Ctx::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    offset : base + l.offset,
+                    ..*l
+                });
+        }
+        self.texel_array.append(&mut image.texel_array);
+        self.size += image.size;
+        Ok(())
     }
-    fn load(&mut self, m: &Memory) {
-    
+    /// Cuts a loaded strip or cross image into the six faces of a cube map.
+    fn split_cube_map_layout(&mut self) -> Result<(), TextureError> {
+        use WebGlRenderingContext as Ctx;
+        use TextureError::*;
+
+        let layout = CubeMapLayout::detect(self.width  as u32,
+                                           self.height as u32);
+        let layout = match layout {
+            Some(layout) if self.target == Ctx::TEXTURE_2D
+                         && self.is_cpu_filterable() => layout,
+            _ => {
+                let msg = format!("Texture ({}) isn't an uncompressed strip \
+                                   or cross cube map layout.", self.name);
+                Err( UnsupportedFormat(msg) )?
+            }
+        };
+        let (_, image) = self.base_images()?.remove(0);
+        let (columns, _) = layout.grid();
+        let size         = image.width / columns;
+        let mut faces    = vec![];
+
+        for (face, &(column, row, flip)) in layout.cells().iter().enumerate() {
+            let face_image = image.crop(column * size, row * size, size,
+                                        size)?;
+            let face_image = if flip { face_image.rotate_180() }
+                             else    { face_image              };
+            faces.push((Ctx::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                        0,
+                        face_image));
+        }
+        self.set_images(faces);
+        self.target = Ctx::TEXTURE_CUBE_MAP;
+        self.width  = size as u16;
+        self.height = size as u16;
+        Ok(())
     }
     fn load_png(&self, memory: &Memory) {
-    
+
     }
-    fn load_pvr(&mut self, memory: &Memory) -> Result<(), TextureError> {
+    fn load_pvr(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
         use WebGlRenderingContext       as CTX;
         use WebglCompressedTexturePvrtc as CTP;
         use TextureError::*;
-        
+
         const PVRTC2: u8 = 24;
         const PVRTC4: u8 = 25;
-        
-        let header = memory.bytes_copy_into_new::<PvrHeader>()?;
 
-        for i in 0..4 {
-            if ((header.tag >> (i * 8)) & 0xFF) as u8 != PVR_IDENTIFIER[i] {
+        memory.mseek(SeekFrom::Start(0))?;
+
+        let header = memory.read_le::<PvrHeader>()?;
+
+        for (i, id) in PVR_IDENTIFIER.iter().enumerate() {
+            if ((header.tag >> (i * 8)) & 0xFF) as u8 != *id {
                 let msg = "PVR texture file has bad identifier field.";
                 Err( HeaderFormatError(msg.into()) )?
             }
         }
-        
+
         let ver = (header.flags & 0xFF) as u8;
-        
+
         if ver == PVRTC2 || ver == PVRTC4 {
             self.width    = header.width  as u16;
             self.height   = header.height as u16;
             self.bytes    = header.bpp    as u8;
             self.n_mipmap = header.n_mipmap + 1;
-            
+
             self.compression = {
                 if header.bit_alpha != 0 {
                     if header.bpp == 4 {
@@ -126,42 +941,787 @@ impl Texture {
                     } else {
                         CTP::COMPRESSED_RGBA_PVRTC_2BPPV1_IMG
                     }
+                } else if header.bpp == 4 {
+                    CTP::COMPRESSED_RGB_PVRTC_4BPPV1_IMG
                 } else {
-                    if header.bpp == 4 {
-                        CTP::COMPRESSED_RGB_PVRTC_4BPPV1_IMG
-                    } else {
-                        CTP::COMPRESSED_RGB_PVRTC_2BPPV1_IMG
-                    }
+                    CTP::COMPRESSED_RGB_PVRTC_2BPPV1_IMG
                 }
             };
-            let hdr_size  = std::mem::size_of::<PvrHeader>();
             let data_size = header.data_size as usize;
-            let bytes     = memory.bytes();
-            
-            if bytes.len() != data_size {
+
+            memory.mseek(SeekFrom::Start(header.hdr_size as u64))?;
+
+            let remaining = memory.size() - memory.mtell();
+
+            if remaining < data_size {
                 let msg = format!("PVR texture file has bad data_size field \
                                    value ({}). Computed size is ({}).",
-                                   data_size, bytes.len() - hdr_size);                
+                                   data_size, remaining);
                 Err( HeaderFormatError(msg) )?
             } else {
-                for i in hdr_size..data_size {
-                    self.texel_array.push(bytes[i]);
+                self.texel_array.extend_from_slice(
+                                    memory.read_bytes(data_size)?);
+            }
+
+            let cf = find_compressed_format(self.compression).unwrap();
+            let mut offset = 0;
+
+            for level in 0..self.n_mipmap {
+                let width  = (header.width  >> level).max(1);
+                let height = (header.height >> level).max(1);
+                let size   = cf.level_size(width, height);
+
+                if offset + size > data_size {
+                    break;
                 }
+                self.level_array.push(
+                    TextureLevel {
+                        target : CTX::TEXTURE_2D,
+                        level,
+                        width,
+                        height,
+                        offset,
+                        size
+                    });
+                offset += size;
             }
+            self.n_mipmap = self.level_array.len() as u32;
+        }
+        Ok(())
+    }
+    fn load_ktx(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as CTX;
+        use TextureError::*;
+
+        memory.mseek(SeekFrom::Start(12))?;
+
+        let big_endian = match memory.read_u32_le()? {
+            KTX_ENDIANNESS              => false,
+            e if e.swap_bytes() == KTX_ENDIANNESS => true,
+            e => {
+                let msg = format!("KTX texture file has bad endianness field \
+                                   ({:#010x}).", e);
+                Err( HeaderFormatError(msg) )?
+            }
+        };
+        let gl_type         = read_u32(memory, big_endian)?;
+        let gl_type_size    = read_u32(memory, big_endian)?;
+        let gl_format       = read_u32(memory, big_endian)?;
+        let gl_int_format   = read_u32(memory, big_endian)?;
+        let gl_base_format  = read_u32(memory, big_endian)?;
+        let width           = read_u32(memory, big_endian)?;
+        let height          = read_u32(memory, big_endian)?.max(1);
+        let depth           = read_u32(memory, big_endian)?;
+        let n_array         = read_u32(memory, big_endian)?;
+        let n_faces         = read_u32(memory, big_endian)?;
+        let n_mipmap        = read_u32(memory, big_endian)?.max(1);
+        let kv_size         = read_u32(memory, big_endian)? as usize;
+
+        if depth > 1 || n_array > 0 {
+            let msg = format!("KTX texture ({}) is a 3D or array texture, \
+                               which WebGL 1 doesn't support.", self.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        if n_faces != 1 && n_faces != 6 {
+            let msg = format!("KTX texture file has bad numberOfFaces field \
+                               ({}).", n_faces);
+            Err( HeaderFormatError(msg) )?
+        }
+        self.key_value_array = parse_key_values(memory, kv_size, big_endian)?;
+
+        let cf = if gl_type == 0 {
+            let cf = find_compressed_format(gl_int_format).ok_or_else(|| {
+                UnsupportedFormat(format!("KTX texture ({}) has unsupported \
+                                           compressed format ({:#06x}).",
+                                           self.name, gl_int_format))
+            })?;
+            self.compression     = gl_int_format;
+            self.internal_format = gl_int_format;
+            Some(cf)
+        } else {
+            self.bytes = texel_size(gl_format, gl_type).ok_or_else(|| {
+                UnsupportedFormat(format!("KTX texture ({}) has unsupported \
+                                           format ({:#06x}) and type \
+                                           ({:#06x}).",
+                                           self.name, gl_format, gl_type))
+            })?;
+            self.internal_format = gl_base_format;
+            self.format          = gl_format;
+            self.texel_type      = gl_type;
+            None
+        };
+        self.width    = width  as u16;
+        self.height   = height as u16;
+        self.n_mipmap = n_mipmap;
+        self.target   = if n_faces == 6 { CTX::TEXTURE_CUBE_MAP }
+                        else            { CTX::TEXTURE_2D       };
+
+        for level in 0..n_mipmap {
+            let image_size = read_u32(memory, big_endian)? as usize;
+            let width      = (width  >> level).max(1);
+            let height     = (height >> level).max(1);
+
+            for face in 0..n_faces {
+                let start = memory.mtell();
+                let image = memory.read_bytes(image_size)?;
+                let target = if n_faces == 6 {
+                                CTX::TEXTURE_CUBE_MAP_POSITIVE_X + face
+                             } else {
+                                CTX::TEXTURE_2D
+                             };
+                self.level_array.push(
+                    TextureLevel {
+                        target,
+                        level,
+                        width,
+                        height,
+                        offset : self.texel_array.len(),
+                        size   : image_size,
+                    });
+                self.texel_array.extend_from_slice(image);
+
+                // 16 bit packed texels have to be swapped into the platform's
+                // byte order.
+                if big_endian && gl_type_size == 2 {
+                    let start = self.texel_array.len() - image_size;
+                    for t in self.texel_array[start..].chunks_exact_mut(2) {
+                        t.swap(0, 1);
+                    }
+                }
+                skip_padding(memory, start)?;
+            }
+            if let Some(cf) = cf {
+                if cf.level_size(width, height) > image_size {
+                    let msg = format!("KTX texture ({}) mip level {} is \
+                                       smaller than its format requires.",
+                                       self.name, level);
+                    Err( HeaderFormatError(msg) )?
+                }
+            }
+        }
+        Ok(())
+    }
+    fn load_ktx2(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as CTX;
+        use TextureError::*;
+
+        const SUPERCOMPRESSION: [&str; 4] = ["None", "BasisLZ",
+                                             "Zstandard", "ZLIB"];
+
+        memory.mseek(SeekFrom::Start(12))?;
+
+        let mut header = [0; 13];
+        for field in header.iter_mut() {
+            *field = memory.read_u32_le()?;
+        }
+        let vk_format        = header[0];
+        let width            = header[2];
+        let height           = header[3].max(1);
+        let depth            = header[4];
+        let n_layers         = header[5];
+        let n_faces          = header[6];
+        let n_mipmap         = header[7].max(1);
+        let supercompression = header[8];
+        let kvd_offset       = header[11] as u64;
+        let kvd_size         = header[12] as usize;
+
+        if supercompression != 0 {
+            let scheme = SUPERCOMPRESSION.get(supercompression as usize)
+                                         .unwrap_or(&"Unknown");
+            let msg = format!("KTX2 texture ({}) uses supercompression \
+                               scheme {} ({}), which isn't supported.",
+                               self.name, supercompression, scheme);
+            Err( UnsupportedFormat(msg) )?
+        }
+        if depth > 0 || n_layers > 0 {
+            let msg = format!("KTX2 texture ({}) is a 3D or array texture, \
+                               which WebGL 1 doesn't support.", self.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        if n_faces != 1 && n_faces != 6 {
+            let msg = format!("KTX2 texture file has bad faceCount field \
+                               ({}).", n_faces);
+            Err( HeaderFormatError(msg) )?
+        }
+        let (internal_format, format, texel_type, texel_bytes) =
+            vk_format_to_gl(vk_format).ok_or_else(|| {
+                UnsupportedFormat(format!("KTX2 texture ({}) has unsupported \
+                                           vkFormat ({}).",
+                                           self.name, vk_format))
+            })?;
+
+        if texel_type == 0 {
+            self.compression = internal_format;
+        }
+        self.internal_format = internal_format;
+        self.format          = format;
+        self.texel_type      = texel_type;
+        self.bytes           = texel_bytes;
+        self.width           = width  as u16;
+        self.height          = height as u16;
+        self.n_mipmap        = n_mipmap;
+        self.target          = if n_faces == 6 { CTX::TEXTURE_CUBE_MAP }
+                               else            { CTX::TEXTURE_2D       };
+
+        memory.mseek(SeekFrom::Start(KTX2_HEADER_SIZE as u64))?;
+
+        let mut level_index = vec![];
+        for _ in 0..n_mipmap {
+            let offset     = memory.read_u64_le()?;
+            let level_size = memory.read_u64_le()? as usize;
+            memory.read_u64_le()?;
+            level_index.push((offset, level_size));
+        }
+        if kvd_size > 0 {
+            memory.mseek(SeekFrom::Start(kvd_offset))?;
+            self.key_value_array = parse_key_values(memory, kvd_size, false)?;
+        }
+        for (level, &(offset, level_size)) in level_index.iter().enumerate() {
+            let level      = level as u32;
+            let face_size  = level_size / n_faces as usize;
+            let width      = (width  >> level).max(1);
+            let height     = (height >> level).max(1);
+
+            memory.mseek(SeekFrom::Start(offset))?;
+
+            for face in 0..n_faces {
+                let image = memory.read_bytes(face_size)?;
+                let target = if n_faces == 6 {
+                                CTX::TEXTURE_CUBE_MAP_POSITIVE_X + face
+                             } else {
+                                CTX::TEXTURE_2D
+                             };
+                self.level_array.push(
+                    TextureLevel {
+                        target,
+                        level,
+                        width,
+                        height,
+                        offset : self.texel_array.len(),
+                        size   : face_size,
+                    });
+                self.texel_array.extend_from_slice(image);
+            }
+        }
+        Ok(())
+    }
+    fn load_dds(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext      as CTX;
+        use WebglCompressedTextureS3tc as S3;
+        use WebglCompressedTextureS3tcSrgb as S3S;
+        use TextureError::*;
+
+        // The header is the magic number then 31 u32 fields.
+        memory.mseek(SeekFrom::Start(4))?;
+
+        let mut header = [0; 31];
+        for field in header.iter_mut() {
+            *field = memory.read_u32_le()?;
+        }
+        let field = |offset: usize| header[offset / 4 - 1];
+
+        let flags    = field(8);
+        let height   = field(12).max(1);
+        let width    = field(16);
+        let n_mipmap = if flags & DDSD_MIPMAPCOUNT != 0 { field(28).max(1) }
+                       else                              { 1 };
+        let pf_flags = field(80);
+        let fourcc   = field(84).to_le_bytes();
+        let caps2    = field(112);
+
+        if caps2 & DDSCAPS2_VOLUME != 0 {
+            let msg = format!("DDS texture ({}) is a volume texture, which \
+                               WebGL 1 doesn't support.", self.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        let n_faces = if caps2 & DDSCAPS2_CUBEMAP == 0 {
+            1
+        } else if caps2 & DDSCAPS2_ALLFACES == DDSCAPS2_ALLFACES {
+            6
+        } else {
+            let msg = format!("DDS cube map ({}) doesn't have all six faces.",
+                              self.name);
+            Err( UnsupportedFormat(msg) )?
+        };
+        if pf_flags & DDPF_FOURCC == 0 {
+            let msg = format!("DDS texture ({}) isn't block compressed.",
+                              self.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        self.compression = match &fourcc {
+            b"DXT1" if pf_flags & DDPF_ALPHAPIXELS != 0 => {
+                S3::COMPRESSED_RGBA_S3TC_DXT1_EXT
+            },
+            b"DXT1" => S3::COMPRESSED_RGB_S3TC_DXT1_EXT,
+            b"DXT3" => S3::COMPRESSED_RGBA_S3TC_DXT3_EXT,
+            b"DXT5" => S3::COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            b"DX10" => {
+                let dxgi_format = memory.read_u32_le()?;
+                memory.mseek(SeekFrom::Current(8))?;
+                let array_size  = memory.read_u32_le()?;
+                memory.mseek(SeekFrom::Current(4))?;
+
+                if array_size > 1 {
+                    let msg = format!("DDS texture ({}) is an array \
+                                       texture, which WebGL 1 doesn't \
+                                       support.", self.name);
+                    Err( UnsupportedFormat(msg) )?
+                }
+                match dxgi_format {
+                    71 => S3::COMPRESSED_RGBA_S3TC_DXT1_EXT,
+                    72 => S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+                    74 => S3::COMPRESSED_RGBA_S3TC_DXT3_EXT,
+                    75 => S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+                    77 => S3::COMPRESSED_RGBA_S3TC_DXT5_EXT,
+                    78 => S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+                    _  => {
+                        let msg = format!("DDS texture ({}) has unsupported \
+                                           DXGI format ({}).",
+                                           self.name, dxgi_format);
+                        Err( UnsupportedFormat(msg) )?
+                    }
+                }
+            },
+            _ => {
+                let msg = format!("DDS texture ({}) has unsupported FourCC \
+                                   ({}).", self.name,
+                                   String::from_utf8_lossy(&fourcc));
+                Err( UnsupportedFormat(msg) )?
+            }
+        };
+        self.internal_format = self.compression;
+        self.width           = width  as u16;
+        self.height          = height as u16;
+        self.n_mipmap        = n_mipmap;
+        self.target          = if n_faces == 6 { CTX::TEXTURE_CUBE_MAP }
+                               else            { CTX::TEXTURE_2D       };
+
+        let cf = find_compressed_format(self.compression).unwrap();
+
+        // Unlike KTX, DDS stores each face's whole mip chain in turn.
+        for face in 0..n_faces {
+            let target = if n_faces == 6 {
+                            CTX::TEXTURE_CUBE_MAP_POSITIVE_X + face
+                         } else {
+                            CTX::TEXTURE_2D
+                         };
+            for level in 0..n_mipmap {
+                let width  = (width  >> level).max(1);
+                let height = (height >> level).max(1);
+                let size   = cf.level_size(width, height);
+                let image  = memory.read_bytes(size)?;
+                self.level_array.push(
+                    TextureLevel {
+                        target,
+                        level,
+                        width,
+                        height,
+                        offset : self.texel_array.len(),
+                        size,
+                    });
+                self.texel_array.extend_from_slice(image);
+            }
+        }
+        Ok(())
+    }
+    fn load_pkm(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext      as CTX;
+        use WebglCompressedTextureEtc  as ETC2;
+        use WebglCompressedTextureEtc1 as ET1;
+        use TextureError::*;
+
+        // PKM header fields are big endian u16s.
+        memory.mseek(SeekFrom::Start(6))?;
+        let format = memory.read_u16_be()?;
+
+        memory.mseek(SeekFrom::Start(12))?;
+        let width  = memory.read_u16_be()? as u32;
+        let height = memory.read_u16_be()? as u32;
+
+        self.compression = match format {
+            0 => ET1::COMPRESSED_RGB_ETC1_WEBGL,
+            1 => ETC2::COMPRESSED_RGB8_ETC2,
+            3 => ETC2::COMPRESSED_RGBA8_ETC2_EAC,
+            4 => ETC2::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            5 => ETC2::COMPRESSED_R11_EAC,
+            6 => ETC2::COMPRESSED_RG11_EAC,
+            7 => ETC2::COMPRESSED_SIGNED_R11_EAC,
+            8 => ETC2::COMPRESSED_SIGNED_RG11_EAC,
+            _ => {
+                let msg = format!("PKM texture ({}) has unsupported format \
+                                   ({}).", self.name, format);
+                Err( UnsupportedFormat(msg) )?
+            }
+        };
+        self.internal_format = self.compression;
+        self.width           = width  as u16;
+        self.height          = height as u16;
+        self.n_mipmap        = 1;
+
+        let cf    = find_compressed_format(self.compression).unwrap();
+        let size  = cf.level_size(width, height);
+        let image = memory.read_bytes(size)?;
+
+        self.level_array.push(
+            TextureLevel {
+                target : CTX::TEXTURE_2D,
+                level  : 0,
+                width,
+                height,
+                offset : 0,
+                size,
+            });
+        self.texel_array.extend_from_slice(image);
+        Ok(())
+    }
+    fn load_astc(&mut self, memory: &mut Memory)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as CTX;
+        use TextureError::*;
+
+        memory.mseek(SeekFrom::Start(4))?;
+
+        let block_w = memory.read_u8()? as u32;
+        let block_h = memory.read_u8()? as u32;
+        let block_d = memory.read_u8()? as u32;
+
+        // Dimensions are 24 bit little endian values.
+        let mut dim = || -> Result<u32, crate::memory::MemoryError> {
+            let b = memory.read_bytes(3)?;
+            Ok( u32::from_le_bytes([b[0], b[1], b[2], 0]) )
+        };
+        let width  = dim()?;
+        let height = dim()?;
+        let depth  = dim()?;
+
+        if block_d != 1 || depth != 1 {
+            let msg = format!("ASTC texture ({}) is a 3D texture, which \
+                               WebGL 1 doesn't support.", self.name);
+            Err( UnsupportedFormat(msg) )?
+        }
+        let cf = COMPRESSED_FORMAT_ARRAY
+                 .iter()
+                 .find(|f| f.family       == CompressionFamily::Astc
+                        && f.block_width  == block_w
+                        && f.block_height == block_h)
+                 .ok_or_else(|| {
+                     UnsupportedFormat(format!("ASTC texture ({}) has \
+                                                unsupported block size \
+                                                ({}x{}).",
+                                                self.name, block_w, block_h))
+                 })?;
+        self.compression     = cf.format;
+        self.internal_format = cf.format;
+        self.width           = width  as u16;
+        self.height          = height as u16;
+        self.n_mipmap        = 1;
+
+        let size  = cf.level_size(width, height);
+        let image = memory.read_bytes(size)?;
+
+        self.level_array.push(
+            TextureLevel {
+                target : CTX::TEXTURE_2D,
+                level  : 0,
+                width,
+                height,
+                offset : 0,
+                size,
+            });
+        self.texel_array.extend_from_slice(image);
+        Ok(())
+    }
+    /// Enables the WebGL extension the texture's compressed format needs.
+    /// ETC1 data is uploaded as ETC2 when only the ETC2 extension exists,
+    /// since ETC2 decoders are backward compatible with ETC1.
+    fn enable_compression(&mut self) -> Result<(), TextureError> {
+        use WebglCompressedTextureEtc  as ETC2;
+        use WebglCompressedTextureEtc1 as ET1;
+        use TextureError::*;
+
+        let cf = find_compressed_format(self.compression).ok_or_else(|| {
+            UnsupportedFormat(format!("Texture ({}) has unknown compressed \
+                                       format ({:#06x}).",
+                                       self.name, self.compression))
+        })?;
+        let enable = |family: CompressionFamily| {
+            self.context.enable_extension(family.extension())
+        };
+        if enable(cf.family) {
+            Ok(())
+        } else if self.compression == ET1::COMPRESSED_RGB_ETC1_WEBGL
+               && enable(CompressionFamily::Etc)
+        {
+            self.compression     = ETC2::COMPRESSED_RGB8_ETC2;
+            self.internal_format = ETC2::COMPRESSED_RGB8_ETC2;
+            Ok(())
+        } else {
+            let msg = format!("Texture ({}) needs the {} extension, \
+                               which isn't available.",
+                               self.name, cf.family.extension());
+            Err( UnsupportedFormat(msg) )
+        }
+    }
+    /// Returns whether the texels can be run through `Image` on the CPU.
+    fn is_cpu_filterable(&self) -> bool {
+        self.compression == 0
+            && self.texel_type == WebGlRenderingContext::UNSIGNED_BYTE
+    }
+    /// Returns each face's base level as an `Image`.
+    fn base_images(&self) -> Result<Vec<(u32, Image)>, TextureError> {
+        self.level_array.iter()
+                        .filter(|l| l.level == 0)
+                        .map(|l| {
+                            let data = &self.texel_array[l.offset..
+                                                         l.offset + l.size];
+                            Ok( (l.target, Image::new(l.width, l.height,
+                                                      self.bytes as usize,
+                                                      data.to_vec())?) )
+                        })
+                        .collect()
+    }
+    /// Replaces the texel and level arrays with `images`, given as (target,
+    /// level, image) triples.
+    fn set_images(&mut self, images: Vec<(u32, u32, Image)>) {
+        self.texel_array.clear();
+        self.level_array.clear();
+
+        for (target, level, image) in images {
+            self.level_array.push(
+                TextureLevel {
+                    target,
+                    level,
+                    width  : image.width,
+                    height : image.height,
+                    offset : self.texel_array.len(),
+                    size   : image.pixels.len(),
+                });
+            self.texel_array.extend_from_slice(&image.pixels);
+        }
+        self.n_mipmap = self.level_array.iter()
+                                        .map(|l| l.level + 1)
+                                        .max()
+                                        .unwrap_or(0);
+        self.size     = self.texel_array.len() as u32;
+    }
+    /// Upscales each face of a single level, non-power-of-two texture to the
+    /// next power of two.
+    fn resize_to_power_of_two(&mut self) -> Result<(), TextureError> {
+        let width  = (self.width  as u32).next_power_of_two();
+        let height = (self.height as u32).next_power_of_two();
+        let images = self.base_images()?
+                         .into_iter()
+                         .map(|(t, i)| (t, 0, i.resize(width, height)))
+                         .collect();
+        self.set_images(images);
+        self.width  = width  as u16;
+        self.height = height as u16;
+        Ok(())
+    }
+    /// Builds the full mip chain of each face on the CPU.
+    fn build_mipmaps(&mut self, filter: MipmapFilter, gamma_correct: bool)
+        -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as Ctx;
+
+        // Alpha only textures have no color to correct.
+        let gamma_correct = gamma_correct && self.format != Ctx::ALPHA;
+        let mut images    = vec![];
+
+        for (target, base) in self.base_images()? {
+            let levels = base.mipmaps(filter, gamma_correct);
+            images.push((target, 0, base));
+            for (i, image) in levels.into_iter().enumerate() {
+                images.push((target, i as u32 + 1, image));
+            }
+        }
+        self.set_images(images);
+        Ok(())
+    }
+    /// Applies `anisotropic_filter` through EXT_texture_filter_anisotropic,
+    /// clamped to the device maximum. Does nothing if the extension is
+    /// missing.
+    fn set_anisotropic_filter(&mut self, anisotropic_filter: f32) {
+        use ExtTextureFilterAnisotropic as Ext;
+
+        self.anisotropy = 0.0;
+
+        if anisotropic_filter <= 1.0 {
+            return;
+        }
+        let ctx = &self.context;
+        let ext = ["EXT_texture_filter_anisotropic",
+                   "WEBKIT_EXT_texture_filter_anisotropic"]
+                  .iter()
+                  .any(|name| ctx.enable_extension(name));
+        if !ext {
+            return;
+        }
+        let max = ctx.get_float(Ext::MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+                     .unwrap_or(1.0);
+        let anisotropy = anisotropic_filter.clamp(1.0, max.max(1.0));
+
+        ctx.tex_parameterf(self.target,
+                           Ext::TEXTURE_MAX_ANISOTROPY_EXT,
+                           anisotropy);
+        self.anisotropy = anisotropy;
+    }
+    fn generate_id(&mut self,
+                   mut flags            : u32,
+                   filter               : u8,
+                   anisotropic_filter   : f32) -> Result<(), TextureError>
+    {
+        use WebGlRenderingContext as Ctx;
+
+        if self.compression != 0 {
+            self.enable_compression()?;
+        }
+        self.delete_id();
+
+        // WebGL 1 only allows mipmaps and repeat wrapping on power of two
+        // textures; either resize, or fall back to what NPOT does allow.
+        // WebGL 2 allows both.
+        let npot = !(self.width  as u32).is_power_of_two()
+                || !(self.height as u32).is_power_of_two();
+
+        if npot && self.context.version() == GlVersion::WebGl1 {
+            if flags & TEXTURE_NPOT_RESIZE != 0
+                && self.is_cpu_filterable()
+                && self.n_mipmap == 1
+            {
+                self.resize_to_power_of_two()?;
+            } else {
+                if flags & TEXTURE_CLAMP == 0
+                    || flags & TEXTURE_MIPMAP != 0
+                    || self.n_mipmap > 1
+                {
+                    console_log!("Texture ({}) is {}x{}, which isn't a power \
+                                  of two; using clamp to edge without \
+                                  mipmaps.", self.name, self.width,
+                                  self.height);
+                }
+                flags |= TEXTURE_CLAMP;
+                flags &= !TEXTURE_MIPMAP;
+                self.level_array.retain(|l| l.level == 0);
+                self.n_mipmap = 1;
+                self.size     = self.level_array.iter()
+                                                .map(|l| l.size as u32)
+                                                .sum();
+            }
+        }
+        let mut gpu_mipmap = false;
+
+        if flags & TEXTURE_MIPMAP != 0 && self.n_mipmap == 1 {
+            if flags & TEXTURE_MIPMAP_CPU != 0 && self.is_cpu_filterable() {
+                let mip_filter = if flags & TEXTURE_MIPMAP_KAISER != 0
+                                      { MipmapFilter::Kaiser }
+                                 else { MipmapFilter::Box    };
+                self.build_mipmaps(mip_filter,
+                                   flags & TEXTURE_MIPMAP_SRGB != 0)?;
+            } else if self.compression == 0 {
+                gpu_mipmap = true;
+            } else {
+                console_log!("Texture ({}) is compressed and has no mip \
+                              levels to use; mipmapping disabled.",
+                              self.name);
+            }
+        }
+
+        let ctx = self.context.clone();
+
+        self.tid = ctx.create_texture();
+        ctx.bind_texture(self.target, self.tid.as_ref());
+        untracked_bind(Binding::Textures);
+
+        if self.compression == 0 {
+            match self.bytes {
+                3 => ctx.pixel_storei(Ctx::UNPACK_ALIGNMENT, 1),
+                4 => ctx.pixel_storei(Ctx::UNPACK_ALIGNMENT, 4),
+                2 => ctx.pixel_storei(Ctx::UNPACK_ALIGNMENT, 2),
+                _ => ctx.pixel_storei(Ctx::UNPACK_ALIGNMENT, 1),
+            }
+        }
+        if flags & TEXTURE_CLAMP != 0 {
+            ctx.tex_parameteri(self.target,
+                               Ctx::TEXTURE_WRAP_S,
+                               Ctx::CLAMP_TO_EDGE as i32);
+            ctx.tex_parameteri(self.target,
+                               Ctx::TEXTURE_WRAP_T,
+                               Ctx::CLAMP_TO_EDGE as i32);
+        }
+        self.set_anisotropic_filter(anisotropic_filter);
+
+        let (mag, min) = if self.n_mipmap > 1 || gpu_mipmap {
+            match filter {
+                TEXTURE_FILTER_1X => (Ctx::LINEAR,  Ctx::LINEAR_MIPMAP_NEAREST ),
+                TEXTURE_FILTER_2X => (Ctx::LINEAR,  Ctx::LINEAR_MIPMAP_NEAREST ),
+                TEXTURE_FILTER_3X => (Ctx::LINEAR,  Ctx::LINEAR_MIPMAP_LINEAR  ),
+                _                 => (Ctx::NEAREST, Ctx::NEAREST_MIPMAP_NEAREST),
+            }
+        } else {
+            match filter {
+                TEXTURE_FILTER_0X => (Ctx::NEAREST, Ctx::NEAREST),
+                _                 => (Ctx::LINEAR,  Ctx::LINEAR ),
+            }
+        };
+        ctx.tex_parameteri(self.target, Ctx::TEXTURE_MAG_FILTER, mag as i32);
+        ctx.tex_parameteri(self.target, Ctx::TEXTURE_MIN_FILTER, min as i32);
+
+        for l in &self.level_array {
+            let data = &self.texel_array[l.offset..l.offset + l.size];
+
+            if self.compression != 0 {
+                ctx.compressed_tex_image_2d(l.target,
+                                            l.level  as i32,
+                                            self.compression,
+                                            l.width  as i32,
+                                            l.height as i32,
+                                            0,
+                                            data);
+            } else {
+                ctx.tex_image_2d(l.target,
+                                 l.level  as i32,
+                                 self.internal_format as i32,
+                                 l.width  as i32,
+                                 l.height as i32,
+                                 0,
+                                 self.format,
+                                 self.texel_type,
+                                 Some(data))
+                   .map_err(TextureError::UploadError)?;
+            }
+        }
+        if gpu_mipmap {
+            ctx.generate_mipmap(self.target);
+
+            // A full mip chain adds roughly a third to the base level.
+            self.size += self.size / 3;
         }
         Ok(())
     }
 }
 
-impl Drop for Texture {
+impl<B: GlBackend> Drop for Texture<B> {
     fn drop(&mut self) {
-        self.context.delete_texture(self.tid.take().as_ref());
+        self.delete_id();
     }
 }
 
 #[derive(Debug)]
-enum TextureError {
+pub enum TextureError {
     HeaderFormatError(String),
+    UnsupportedFormat(String),
+    UploadError(JsValue),
+    ImageError(String),
     MemoryError(memory::MemoryError)
 }
 
@@ -182,6 +1742,16 @@ impl fmt::Display for TextureError {
             HeaderFormatError ( msg ) => {
                 write!(f, "{}", msg)
             },
+            UnsupportedFormat ( msg ) => {
+                write!(f, "{}", msg)
+            },
+            UploadError ( jsval ) => {
+                write!(f, "Uploading texels failed with error ({}).",
+                       jsval_to_string(jsval))
+            },
+            ImageError ( msg ) => {
+                write!(f, "{}", msg)
+            },
             MemoryError ( err ) => {
                 write!(f, "{}", err)
             }
@@ -194,4 +1764,3 @@ impl From<memory::MemoryError> for TextureError {
         TextureError::MemoryError(err)
     }
 }
-
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::rc::Weak;
+use std::sync::Arc;
+
+use futures::channel::oneshot;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::error::GfxError;
+use crate::texture::Texture;
+
+/// Textures are shared when they come from the same URL with the same flags,
+/// filter and anisotropy.
+type TextureKey = (String, u32, u8, u32);
+
+type TextureWaiter<B> = oneshot::Sender<Result<TextureHandle<B>, String>>;
+
+struct TextureEntry<B: GlBackend> {
+    texture   : Rc<Texture<B>>,
+    handles   : usize,
+    last_used : u64,
+}
+
+enum TextureSlot<B: GlBackend> {
+    Loading(Vec<TextureWaiter<B>>),
+    Ready(TextureEntry<B>),
+}
+
+struct ManagerInner<B: GlBackend> {
+    slots   : HashMap<TextureKey, TextureSlot<B>>,
+    budget  : usize,
+    clock   : u64,
+    context : Arc<B>,
+}
+
+impl<B: GlBackend> ManagerInner<B> {
+    fn vram_usage(&self) -> usize {
+        self.slots.values()
+                  .map(|slot| match slot {
+                      TextureSlot::Ready(e) => e.texture.size() as usize,
+                      TextureSlot::Loading(_) => 0,
+                  })
+                  .sum()
+    }
+    fn release(&mut self, key: &TextureKey) {
+        if let Some(TextureSlot::Ready(entry)) = self.slots.get_mut(key) {
+            entry.handles -= 1;
+            if entry.handles == 0 {
+                self.clock      += 1;
+                entry.last_used  = self.clock;
+                self.trim_to_budget();
+            }
+        }
+    }
+    fn trim_to_budget(&mut self) {
+        self.trim(self.budget);
+    }
+    /// Frees unused textures, least recently used first, until the resident
+    /// textures fit in `budget` bytes or none are left unused.
+    fn trim(&mut self, budget: usize) {
+        let mut usage = self.vram_usage();
+
+        while usage > budget {
+            let oldest = self.slots.iter()
+                                   .filter_map(|(k, slot)| match slot {
+                                       TextureSlot::Ready(e) if e.handles == 0
+                                           => Some((k, e.last_used)),
+                                       _ => None,
+                                   })
+                                   .min_by_key(|&(_, last_used)| last_used)
+                                   .map(|(k, _)| k.clone());
+            match oldest {
+                Some(key) => {
+                    if let Some(TextureSlot::Ready(e)) = self.slots.remove(&key) {
+                        usage -= e.texture.size() as usize;
+                    }
+                },
+                None => break,
+            }
+        }
+    }
+}
+
+/// Loads textures once and shares them. Loads of a texture already in flight
+/// wait for the first one rather than fetching it again. A texture is freed
+/// once its last handle drops, unless the memory budget leaves room to keep
+/// it resident for reuse.
+pub struct TextureManager<B: GlBackend = WebGlRenderingContext> {
+    inner : Rc<RefCell<ManagerInner<B>>>,
+}
+
+impl<B: GlBackend> Clone for TextureManager<B> {
+    fn clone(&self) -> Self {
+        TextureManager { inner: self.inner.clone() }
+    }
+}
+
+impl<B: GlBackend> TextureManager<B> {
+    pub fn new(context: Arc<B>) -> Self {
+        TextureManager {
+            inner: Rc::new(RefCell::new(ManagerInner {
+                slots   : HashMap::new(),
+                budget  : 0,
+                clock   : 0,
+                context,
+            }))
+        }
+    }
+    /// Returns a handle to the texture at `url`, loading it if no handle to
+    /// it is alive and it isn't being kept for reuse.
+    pub async fn load(&self,
+                      url                  : &str,
+                      flags                : u32,
+                      filter               : u8,
+                      anisotropic_filter   : f32
+                     ) -> Result<TextureHandle<B>, GfxError>
+    {
+        let key = (url.to_string(), flags, filter,
+                   anisotropic_filter.to_bits());
+        loop {
+            let waiter = {
+                let mut inner = self.inner.borrow_mut();
+
+                match inner.slots.get_mut(&key) {
+                    Some(TextureSlot::Ready(entry)) => {
+                        entry.handles += 1;
+                        return Ok( self.handle(&key, entry.texture.clone()) );
+                    },
+                    Some(TextureSlot::Loading(waiters)) => {
+                        let (tx, rx) = oneshot::channel();
+                        waiters.push(tx);
+                        rx
+                    },
+                    None => {
+                        inner.slots.insert(key.clone(),
+                                           TextureSlot::Loading(vec![]));
+                        break;
+                    },
+                }
+            };
+            match waiter.await {
+                Ok(Ok(handle)) => return Ok( handle ),
+                Ok(Err(msg))   => {
+                    Err( GfxError::new_resource_load_error(msg, None) )?
+                },
+                // The first load was dropped before finishing; start over.
+                Err(_) => continue,
+            }
+        }
+        let mut guard = LoadGuard { key: &key, manager: &self.inner,
+                                    armed: true };
+        let context   = self.inner.borrow().context.clone();
+        let result    = Texture::new(url, url, flags, filter,
+                                     anisotropic_filter, context).await;
+        let waiters   = match self.inner.borrow_mut().slots.remove(&key) {
+            Some(TextureSlot::Loading(waiters)) => waiters,
+            _ => vec![],
+        };
+        guard.armed = false;
+
+        match result {
+            Ok(texture) => {
+                let texture = Rc::new(texture);
+                let entry   = TextureEntry { texture   : texture.clone(),
+                                             handles   : waiters.len() + 1,
+                                             last_used : 0 };
+                self.inner.borrow_mut()
+                          .slots
+                          .insert(key.clone(), TextureSlot::Ready(entry));
+
+                for tx in waiters {
+                    let _ = tx.send(Ok(self.handle(&key, texture.clone())));
+                }
+                self.inner.borrow_mut().trim_to_budget();
+                Ok( self.handle(&key, texture) )
+            },
+            Err(e) => {
+                for tx in waiters {
+                    let _ = tx.send(Err(e.to_string()));
+                }
+                Err( e )
+            },
+        }
+    }
+    /// Returns the texture memory, in bytes, the manager tries to stay
+    /// within. Textures no handle refers to stay resident until the total
+    /// goes over it.
+    pub fn budget(&self) -> usize {
+        self.inner.borrow().budget
+    }
+    pub fn set_budget(&self, budget: usize) {
+        let mut inner = self.inner.borrow_mut();
+        inner.budget  = budget;
+        inner.trim_to_budget();
+    }
+    /// Returns the estimated video memory held by resident textures.
+    pub fn vram_usage(&self) -> usize {
+        self.inner.borrow().vram_usage()
+    }
+    /// Returns the number of resident textures.
+    pub fn texture_count(&self) -> usize {
+        self.inner.borrow()
+                  .slots
+                  .values()
+                  .filter(|slot| matches!(slot, TextureSlot::Ready(_)))
+                  .count()
+    }
+    /// Frees every texture no handle refers to, regardless of the budget.
+    pub fn purge(&self) {
+        self.inner.borrow_mut().trim(0);
+    }
+    fn handle(&self, key: &TextureKey, texture: Rc<Texture<B>>)
+        -> TextureHandle<B>
+    {
+        TextureHandle {
+            key     : key.clone(),
+            texture,
+            manager : Rc::downgrade(&self.inner),
+        }
+    }
+}
+
+/// Clears a load's slot if the load is dropped before it finishes, so
+/// waiting loads can retry.
+struct LoadGuard<'a, B: GlBackend> {
+    key     : &'a TextureKey,
+    manager : &'a Rc<RefCell<ManagerInner<B>>>,
+    armed   : bool,
+}
+
+impl<B: GlBackend> Drop for LoadGuard<'_, B> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.manager.borrow_mut().slots.remove(self.key);
+        }
+    }
+}
+
+/// A shared reference to a texture owned by a `TextureManager`.
+pub struct TextureHandle<B: GlBackend = WebGlRenderingContext> {
+    key     : TextureKey,
+    texture : Rc<Texture<B>>,
+    manager : Weak<RefCell<ManagerInner<B>>>,
+}
+
+impl<B: GlBackend> TextureHandle<B> {
+    /// Returns whether both handles refer to the same texture.
+    pub fn ptr_eq(a: &TextureHandle<B>, b: &TextureHandle<B>) -> bool {
+        Rc::ptr_eq(&a.texture, &b.texture)
+    }
+}
+
+impl<B: GlBackend> Deref for TextureHandle<B> {
+    type Target = Texture<B>;
+
+    fn deref(&self) -> &Texture<B> {
+        &self.texture
+    }
+}
+
+impl<B: GlBackend> Clone for TextureHandle<B> {
+    fn clone(&self) -> Self {
+        if let Some(inner) = self.manager.upgrade() {
+            if let Some(TextureSlot::Ready(entry)) = inner.borrow_mut()
+                                                          .slots
+                                                          .get_mut(&self.key)
+            {
+                entry.handles += 1;
+            }
+        }
+        TextureHandle {
+            key     : self.key.clone(),
+            texture : self.texture.clone(),
+            manager : self.manager.clone(),
+        }
+    }
+}
+
+impl<B: GlBackend> Drop for TextureHandle<B> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.manager.upgrade() {
+            inner.borrow_mut().release(&self.key);
+        }
+    }
+}
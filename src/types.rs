@@ -3,7 +3,7 @@
 
 //define M_PI (3.1415926536f)
 //DEG_TO_RAD    M_PI / 180.0f
-pub const M_PI       : f32 = 3.1415926536;
+pub const M_PI       : f32 = std::f32::consts::PI;
 pub const DEG_TO_RAD : f32 = M_PI / 180.0;
 pub const RAD_TO_DEG : f32 = 90.0 / M_PI;
 
@@ -15,15 +15,23 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace=console)]
     pub fn log(s: &str);
 }
 
+/// Writes to stderr where there's no browser console, such as in native
+/// tests.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log(s: &str) {
+    eprintln!("{}", s);
+}
+
 #[macro_export]
 macro_rules! console_log {
-    ($($t:tt)*) => (crate::utils::log(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => ($crate::utils::log(&format_args!($($t)*).to_string()))
 }
 
 pub fn jsval_to_string(jsval: &JsValue) -> String {
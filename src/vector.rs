@@ -1,5 +1,5 @@
 
-use js_sys::Math::{sin, cos, sqrt};
+use js_sys::Math::{sin, cos};
 
 use crate::matrix::Mat4;
 
@@ -38,9 +38,9 @@ impl Vec3 {
         let len = self.length();
         if len != 0.0 {
             let m = 1.0 / len;
-            self.x = self.x * m;
-            self.y = self.y * m;
-            self.z = self.z * m;
+            self.x *= m;
+            self.y *= m;
+            self.z *= m;
         }
         len
     }
@@ -62,9 +62,20 @@ impl Vec3 {
         self.z * v.z
     }
     pub fn length(&self) -> f32 {
-        // TODO - Find out if there are 32 bit options in the JS webasm
-        //        math API. Check to see how these casts impacts performance.
-        sqrt(self.dot() as f64) as f32
+        self.dot().sqrt()
+    }
+    /// Returns a unit length copy, or the vector itself if it's zero.
+    pub fn normalized(&self) -> Vec3 {
+        let mut v = *self;
+        v.normalize();
+        v
+    }
+    pub fn scale(&self, s: f32) -> Vec3 {
+        Vec3 {
+            x: self.x * s,
+            y: self.y * s,
+            z: self.z * s
+        }
     }
 }
 
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext;
+
+use crate::backend::GlBackend;
+use crate::capabilities::GlCapabilities;
+use crate::gfx::Gfx;
+use crate::gl_state::untracked_bind;
+use crate::gl_state::Binding;
+
+/// Where one vertex attribute is read from: the buffer, and the arguments
+/// of `vertex_attrib_pointer` and `vertex_attrib_divisor`.
+pub struct VertexAttribute<B: GlBackend = WebGlRenderingContext> {
+    pub buffer     : B::Buffer,
+    pub location   : u32,
+    pub size       : i32,
+    pub data_type  : u32,
+    pub normalized : bool,
+    pub stride     : i32,
+    pub offset     : i32,
+    /// Instances drawn per step through the attribute; 0 steps per vertex.
+    pub divisor    : u32,
+}
+
+impl<B: GlBackend> Clone for VertexAttribute<B> {
+    fn clone(&self) -> Self {
+        VertexAttribute { buffer: self.buffer.clone(), ..*self }
+    }
+}
+
+impl<B: GlBackend> VertexAttribute<B> {
+    /// Reads `size` tightly packed components of `data_type` per vertex
+    /// from the start of `buffer`.
+    pub fn new(buffer    : &B::Buffer,
+               location  : u32,
+               size      : i32,
+               data_type : u32) -> Self
+    {
+        VertexAttribute {
+            buffer     : buffer.clone(),
+            location,
+            size,
+            data_type,
+            normalized : false,
+            stride     : 0,
+            offset     : 0,
+            divisor    : 0,
+        }
+    }
+    pub fn normalized(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+    pub fn stride(mut self, stride: i32) -> Self {
+        self.stride = stride;
+        self
+    }
+    pub fn offset(mut self, offset: i32) -> Self {
+        self.offset = offset;
+        self
+    }
+    pub fn divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+}
+
+/// A vertex layout and the buffers it reads, set up once and bound in one
+/// call. It's a vertex array object where the context has them, natively on
+/// WebGL2 or through `OES_vertex_array_object` on WebGL1; elsewhere binding
+/// re-specifies each attribute. The buffers aren't owned and must outlive
+/// the vertex array.
+pub struct VertexArray<B: GlBackend = WebGlRenderingContext> {
+    attributes : Vec<VertexAttribute<B>>,
+    vao        : Option<B::VertexArray>,
+    context    : Arc<B>,
+}
+
+impl<B: GlBackend> VertexArray<B> {
+    /// Creates a vertex array object for `attributes` if `capabilities` has
+    /// them, or an emulated vertex array if not.
+    pub fn new(context      : Arc<B>,
+               capabilities : &GlCapabilities,
+               attributes   : Vec<VertexAttribute<B>>) -> Self
+    {
+        let vao = if capabilities.vertex_array_objects {
+            context.create_vertex_array()
+        } else {
+            None
+        };
+        let vertex_array = VertexArray { attributes, vao, context };
+
+        if let Some(vao) = &vertex_array.vao {
+            vertex_array.context.bind_vertex_array(Some(vao));
+            vertex_array.specify(None);
+            vertex_array.context.bind_vertex_array(None);
+            untracked_bind(Binding::Buffers);
+        }
+        vertex_array
+    }
+    /// Creates a vertex array that re-specifies its attributes on each bind,
+    /// whatever the context supports.
+    pub fn emulated(context    : Arc<B>,
+                    attributes : Vec<VertexAttribute<B>>) -> Self
+    {
+        VertexArray { attributes, vao: None, context }
+    }
+    #[inline]
+    pub fn is_emulated(&self) -> bool {
+        self.vao.is_none()
+    }
+    #[inline]
+    pub fn attributes(&self) -> &[VertexAttribute<B>] {
+        &self.attributes
+    }
+    /// Makes the attributes current for drawing, binding buffers through
+    /// `gfx`.
+    pub fn bind(&self, gfx: &Gfx<B>) {
+        use WebGlRenderingContext as Ctx;
+
+        match &self.vao {
+            Some(vao) => {
+                self.context.bind_vertex_array(Some(vao));
+                gfx.forget_buffer(Ctx::ELEMENT_ARRAY_BUFFER);
+            },
+            None => self.specify(Some(gfx)),
+        }
+    }
+    /// Undoes `bind`, so later buffer and attribute calls don't change or
+    /// read through this vertex array.
+    pub fn unbind(&self, gfx: &Gfx<B>) {
+        use WebGlRenderingContext as Ctx;
+
+        if self.vao.is_some() {
+            self.context.bind_vertex_array(None);
+            gfx.forget_buffer(Ctx::ELEMENT_ARRAY_BUFFER);
+            return;
+        }
+        for attribute in &self.attributes {
+            if attribute.divisor != 0 {
+                self.context.vertex_attrib_divisor(attribute.location, 0);
+            }
+            self.context.disable_vertex_attrib_array(attribute.location);
+        }
+        gfx.bind_buffer(Ctx::ARRAY_BUFFER, None);
+    }
+    /// Specifies the attributes, binding their buffers through `gfx` if
+    /// there's one.
+    fn specify(&self, gfx: Option<&Gfx<B>>) {
+        use WebGlRenderingContext as Ctx;
+        let ctx = &self.context;
+
+        for attribute in &self.attributes {
+            self.bind_array_buffer(gfx, Some(&attribute.buffer));
+            ctx.enable_vertex_attrib_array(attribute.location);
+            ctx.vertex_attrib_pointer(attribute.location,
+                                      attribute.size,
+                                      attribute.data_type,
+                                      attribute.normalized,
+                                      attribute.stride,
+                                      attribute.offset);
+            if attribute.divisor != 0 {
+                ctx.vertex_attrib_divisor(attribute.location,
+                                          attribute.divisor);
+            }
+        }
+        self.bind_array_buffer(gfx, None);
+    }
+    fn bind_array_buffer(&self,
+                         gfx    : Option<&Gfx<B>>,
+                         buffer : Option<&B::Buffer>)
+    {
+        use WebGlRenderingContext as Ctx;
+
+        match gfx {
+            Some(gfx) => gfx.bind_buffer(Ctx::ARRAY_BUFFER, buffer),
+            None      => self.context.bind_buffer(Ctx::ARRAY_BUFFER, buffer),
+        }
+    }
+}
+
+impl<B: GlBackend> Drop for VertexArray<B> {
+    fn drop(&mut self) {
+        if let Some(vao) = self.vao.take() {
+            self.context.delete_vertex_array(&vao);
+        }
+    }
+}
@@ -0,0 +1,450 @@
+use wasm_bindgen::prelude::*;
+
+use web_sys::WebGl2RenderingContext;
+use web_sys::WebGlBuffer;
+use web_sys::WebGlFramebuffer;
+use web_sys::WebGlProgram;
+use web_sys::WebGlRenderbuffer;
+use web_sys::WebGlRenderingContext;
+use web_sys::WebGlShader;
+use web_sys::WebGlTexture;
+use web_sys::WebGlUniformLocation;
+use web_sys::WebGlVertexArrayObject;
+
+use crate::backend::ActiveInfo;
+use crate::backend::GlBackend;
+use crate::backend::GlVersion;
+
+/// A browser context of either WebGL version, chosen when the canvas is
+/// set up by `Gfx::with_options`. Both share WebGL's object types, so code
+/// written against `GlBackend` runs on either; `GlCapabilities` tells which
+/// features the active one has.
+#[derive(Clone, Debug)]
+pub enum WebGlContext {
+    WebGl1(WebGlRenderingContext),
+    WebGl2(WebGl2RenderingContext),
+}
+
+impl WebGlContext {
+    /// Returns the WebGL1 context, if that's the active one.
+    pub fn webgl1(&self) -> Option<&WebGlRenderingContext> {
+        match self {
+            WebGlContext::WebGl1(context) => Some(context),
+            WebGlContext::WebGl2(_)       => None,
+        }
+    }
+    /// Returns the WebGL2 context, if that's the active one.
+    pub fn webgl2(&self) -> Option<&WebGl2RenderingContext> {
+        match self {
+            WebGlContext::WebGl1(_)       => None,
+            WebGlContext::WebGl2(context) => Some(context),
+        }
+    }
+}
+
+/// Forwards a `GlBackend` call to whichever context is active.
+macro_rules! dispatch {
+    ($self:ident, $call:ident($($arg:expr),*)) => {
+        match $self {
+            WebGlContext::WebGl1(c) => GlBackend::$call(c, $($arg),*),
+            WebGlContext::WebGl2(c) => GlBackend::$call(c, $($arg),*),
+        }
+    };
+}
+
+impl GlBackend for WebGlContext {
+    type Shader          = WebGlShader;
+    type Program         = WebGlProgram;
+    type Texture         = WebGlTexture;
+    type Buffer          = WebGlBuffer;
+    type UniformLocation = WebGlUniformLocation;
+    type VertexArray     = WebGlVertexArrayObject;
+    type Framebuffer     = WebGlFramebuffer;
+    type Renderbuffer    = WebGlRenderbuffer;
+
+    fn version(&self) -> GlVersion {
+        match self {
+            WebGlContext::WebGl1(_) => GlVersion::WebGl1,
+            WebGlContext::WebGl2(_) => GlVersion::WebGl2,
+        }
+    }
+    fn enable(&self, cap: u32) {
+        dispatch!(self, enable(cap))
+    }
+    fn disable(&self, cap: u32) {
+        dispatch!(self, disable(cap))
+    }
+    fn hint(&self, target: u32, mode: u32) {
+        dispatch!(self, hint(target, mode))
+    }
+    fn depth_mask(&self, flag: bool) {
+        dispatch!(self, depth_mask(flag))
+    }
+    fn depth_func(&self, func: u32) {
+        dispatch!(self, depth_func(func))
+    }
+    fn depth_range(&self, near: f32, far: f32) {
+        dispatch!(self, depth_range(near, far))
+    }
+    fn cull_face(&self, mode: u32) {
+        dispatch!(self, cull_face(mode))
+    }
+    fn front_face(&self, mode: u32) {
+        dispatch!(self, front_face(mode))
+    }
+    fn stencil_mask(&self, mask: u32) {
+        dispatch!(self, stencil_mask(mask))
+    }
+    fn stencil_func(&self, func: u32, reference: i32, mask: u32) {
+        dispatch!(self, stencil_func(func, reference, mask))
+    }
+    fn stencil_op(&self, fail: u32, depth_fail: u32, pass: u32) {
+        dispatch!(self, stencil_op(fail, depth_fail, pass))
+    }
+    fn blend_func_separate(&self,
+                           src_rgb   : u32,
+                           dst_rgb   : u32,
+                           src_alpha : u32,
+                           dst_alpha : u32)
+    {
+        dispatch!(self, blend_func_separate(src_rgb, dst_rgb, src_alpha,
+                                            dst_alpha))
+    }
+    fn blend_equation_separate(&self, mode_rgb: u32, mode_alpha: u32) {
+        dispatch!(self, blend_equation_separate(mode_rgb, mode_alpha))
+    }
+    fn blend_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        dispatch!(self, blend_color(red, green, blue, alpha))
+    }
+    fn color_mask(&self, red: bool, green: bool, blue: bool, alpha: bool) {
+        dispatch!(self, color_mask(red, green, blue, alpha))
+    }
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        dispatch!(self, viewport(x, y, width, height))
+    }
+    fn polygon_offset(&self, factor: f32, units: f32) {
+        dispatch!(self, polygon_offset(factor, units))
+    }
+    fn clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        dispatch!(self, clear_color(red, green, blue, alpha))
+    }
+    fn clear_depth(&self, depth: f32) {
+        dispatch!(self, clear_depth(depth))
+    }
+    fn clear_stencil(&self, stencil: i32) {
+        dispatch!(self, clear_stencil(stencil))
+    }
+    fn clear(&self, mask: u32) {
+        dispatch!(self, clear(mask))
+    }
+    fn get_error(&self) -> u32 {
+        dispatch!(self, get_error())
+    }
+    fn get_string(&self, pname: u32) -> Option<String> {
+        dispatch!(self, get_string(pname))
+    }
+    fn get_float(&self, pname: u32) -> Option<f32> {
+        dispatch!(self, get_float(pname))
+    }
+    fn supported_extensions(&self) -> Vec<String> {
+        dispatch!(self, supported_extensions())
+    }
+    fn enable_extension(&self, name: &str) -> bool {
+        dispatch!(self, enable_extension(name))
+    }
+    fn create_shader(&self, shader_type: u32) -> Option<WebGlShader> {
+        dispatch!(self, create_shader(shader_type))
+    }
+    fn delete_shader(&self, shader: &WebGlShader) {
+        dispatch!(self, delete_shader(shader))
+    }
+    fn shader_source(&self, shader: &WebGlShader, source: &str) {
+        dispatch!(self, shader_source(shader, source))
+    }
+    fn compile_shader(&self, shader: &WebGlShader) {
+        dispatch!(self, compile_shader(shader))
+    }
+    fn shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+        dispatch!(self, shader_info_log(shader))
+    }
+    fn shader_parameter(&self, shader: &WebGlShader, pname: u32) -> i32 {
+        dispatch!(self, shader_parameter(shader, pname))
+    }
+    fn create_program(&self) -> Option<WebGlProgram> {
+        dispatch!(self, create_program())
+    }
+    fn delete_program(&self, program: &WebGlProgram) {
+        dispatch!(self, delete_program(program))
+    }
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        dispatch!(self, attach_shader(program, shader))
+    }
+    fn link_program(&self, program: &WebGlProgram) {
+        dispatch!(self, link_program(program))
+    }
+    fn program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+        dispatch!(self, program_info_log(program))
+    }
+    fn program_parameter(&self, program: &WebGlProgram, pname: u32) -> i32 {
+        dispatch!(self, program_parameter(program, pname))
+    }
+    fn active_attrib(&self, program: &WebGlProgram, index: u32)
+        -> Option<ActiveInfo>
+    {
+        dispatch!(self, active_attrib(program, index))
+    }
+    fn active_uniform(&self, program: &WebGlProgram, index: u32)
+        -> Option<ActiveInfo>
+    {
+        dispatch!(self, active_uniform(program, index))
+    }
+    fn attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+        dispatch!(self, attrib_location(program, name))
+    }
+    fn uniform_location(&self, program: &WebGlProgram, name: &str)
+        -> Option<WebGlUniformLocation>
+    {
+        dispatch!(self, uniform_location(program, name))
+    }
+    fn use_program(&self, program: Option<&WebGlProgram>) {
+        dispatch!(self, use_program(program))
+    }
+    fn uniform1i(&self, location: Option<&WebGlUniformLocation>, x: i32) {
+        dispatch!(self, uniform1i(location, x))
+    }
+    fn uniform1f(&self, location: Option<&WebGlUniformLocation>, x: f32) {
+        dispatch!(self, uniform1f(location, x))
+    }
+    fn uniform2f(&self,
+                 location : Option<&WebGlUniformLocation>,
+                 x        : f32,
+                 y        : f32)
+    {
+        dispatch!(self, uniform2f(location, x, y))
+    }
+    fn uniform3f(&self,
+                 location : Option<&WebGlUniformLocation>,
+                 x        : f32,
+                 y        : f32,
+                 z        : f32)
+    {
+        dispatch!(self, uniform3f(location, x, y, z))
+    }
+    fn uniform4f(&self,
+                 location : Option<&WebGlUniformLocation>,
+                 x        : f32,
+                 y        : f32,
+                 z        : f32,
+                 w        : f32)
+    {
+        dispatch!(self, uniform4f(location, x, y, z, w))
+    }
+    fn uniform_matrix3fv(&self,
+                         location  : Option<&WebGlUniformLocation>,
+                         transpose : bool,
+                         data      : &[f32])
+    {
+        dispatch!(self, uniform_matrix3fv(location, transpose, data))
+    }
+    fn uniform_matrix4fv(&self,
+                         location  : Option<&WebGlUniformLocation>,
+                         transpose : bool,
+                         data      : &[f32])
+    {
+        dispatch!(self, uniform_matrix4fv(location, transpose, data))
+    }
+    fn create_texture(&self) -> Option<WebGlTexture> {
+        dispatch!(self, create_texture())
+    }
+    fn delete_texture(&self, texture: &WebGlTexture) {
+        dispatch!(self, delete_texture(texture))
+    }
+    fn active_texture(&self, unit: u32) {
+        dispatch!(self, active_texture(unit))
+    }
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        dispatch!(self, bind_texture(target, texture))
+    }
+    fn pixel_storei(&self, pname: u32, param: i32) {
+        dispatch!(self, pixel_storei(pname, param))
+    }
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        dispatch!(self, tex_parameteri(target, pname, param))
+    }
+    fn tex_parameterf(&self, target: u32, pname: u32, param: f32) {
+        dispatch!(self, tex_parameterf(target, pname, param))
+    }
+    fn tex_image_2d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        dispatch!(self, tex_image_2d(target, level, internal_format, width,
+                                     height, border, format, texel_type, data))
+    }
+    fn compressed_tex_image_2d(&self,
+                               target          : u32,
+                               level           : i32,
+                               internal_format : u32,
+                               width           : i32,
+                               height          : i32,
+                               border          : i32,
+                               data            : &[u8])
+    {
+        dispatch!(self, compressed_tex_image_2d(target, level, internal_format,
+                                                width, height, border, data))
+    }
+    fn generate_mipmap(&self, target: u32) {
+        dispatch!(self, generate_mipmap(target))
+    }
+    fn create_buffer(&self) -> Option<WebGlBuffer> {
+        dispatch!(self, create_buffer())
+    }
+    fn delete_buffer(&self, buffer: &WebGlBuffer) {
+        dispatch!(self, delete_buffer(buffer))
+    }
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        dispatch!(self, bind_buffer(target, buffer))
+    }
+    fn buffer_data(&self, target: u32, data: &[u8], usage: u32) {
+        dispatch!(self, buffer_data(target, data, usage))
+    }
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        dispatch!(self, enable_vertex_attrib_array(index))
+    }
+    fn disable_vertex_attrib_array(&self, index: u32) {
+        dispatch!(self, disable_vertex_attrib_array(index))
+    }
+    fn vertex_attrib_pointer(&self,
+                             index      : u32,
+                             size       : i32,
+                             data_type  : u32,
+                             normalized : bool,
+                             stride     : i32,
+                             offset     : i32)
+    {
+        dispatch!(self, vertex_attrib_pointer(index, size, data_type,
+                                              normalized, stride, offset))
+    }
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        dispatch!(self, draw_arrays(mode, first, count))
+    }
+    fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+        dispatch!(self, create_framebuffer())
+    }
+    fn delete_framebuffer(&self, framebuffer: &WebGlFramebuffer) {
+        dispatch!(self, delete_framebuffer(framebuffer))
+    }
+    fn bind_framebuffer(&self,
+                        target      : u32,
+                        framebuffer : Option<&WebGlFramebuffer>)
+    {
+        dispatch!(self, bind_framebuffer(target, framebuffer))
+    }
+    fn framebuffer_texture_2d(&self,
+                              target     : u32,
+                              attachment : u32,
+                              tex_target : u32,
+                              texture    : Option<&WebGlTexture>,
+                              level      : i32)
+    {
+        dispatch!(self, framebuffer_texture_2d(target, attachment, tex_target,
+                                               texture, level))
+    }
+    fn framebuffer_renderbuffer(&self,
+                                target       : u32,
+                                attachment   : u32,
+                                rb_target    : u32,
+                                renderbuffer : Option<&WebGlRenderbuffer>)
+    {
+        dispatch!(self, framebuffer_renderbuffer(target, attachment, rb_target,
+                                                 renderbuffer))
+    }
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        dispatch!(self, check_framebuffer_status(target))
+    }
+    fn create_renderbuffer(&self) -> Option<WebGlRenderbuffer> {
+        dispatch!(self, create_renderbuffer())
+    }
+    fn delete_renderbuffer(&self, renderbuffer: &WebGlRenderbuffer) {
+        dispatch!(self, delete_renderbuffer(renderbuffer))
+    }
+    fn bind_renderbuffer(&self,
+                         target       : u32,
+                         renderbuffer : Option<&WebGlRenderbuffer>)
+    {
+        dispatch!(self, bind_renderbuffer(target, renderbuffer))
+    }
+    fn renderbuffer_storage(&self,
+                            target          : u32,
+                            internal_format : u32,
+                            width           : i32,
+                            height          : i32)
+    {
+        dispatch!(self, renderbuffer_storage(target, internal_format, width,
+                                             height))
+    }
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        dispatch!(self, create_vertex_array())
+    }
+    fn delete_vertex_array(&self, vertex_array: &WebGlVertexArrayObject) {
+        dispatch!(self, delete_vertex_array(vertex_array))
+    }
+    fn bind_vertex_array(&self, vertex_array: Option<&WebGlVertexArrayObject>)
+    {
+        dispatch!(self, bind_vertex_array(vertex_array))
+    }
+    fn draw_arrays_instanced(&self,
+                             mode      : u32,
+                             first     : i32,
+                             count     : i32,
+                             instances : i32)
+    {
+        dispatch!(self, draw_arrays_instanced(mode, first, count, instances))
+    }
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        dispatch!(self, vertex_attrib_divisor(index, divisor))
+    }
+    fn draw_buffers(&self, buffers: &[u32]) {
+        dispatch!(self, draw_buffers(buffers))
+    }
+    fn tex_image_3d(&self,
+                    target          : u32,
+                    level           : i32,
+                    internal_format : i32,
+                    width           : i32,
+                    height          : i32,
+                    depth           : i32,
+                    border          : i32,
+                    format          : u32,
+                    texel_type      : u32,
+                    data            : Option<&[u8]>) -> Result<(), JsValue>
+    {
+        dispatch!(self, tex_image_3d(target, level, internal_format, width,
+                                     height, depth, border, format, texel_type,
+                                     data))
+    }
+    fn uniform_block_index(&self, program: &WebGlProgram, name: &str) -> u32 {
+        dispatch!(self, uniform_block_index(program, name))
+    }
+    fn uniform_block_binding(&self,
+                             program : &WebGlProgram,
+                             index   : u32,
+                             binding : u32)
+    {
+        dispatch!(self, uniform_block_binding(program, index, binding))
+    }
+    fn bind_buffer_base(&self,
+                        target : u32,
+                        index  : u32,
+                        buffer : Option<&WebGlBuffer>)
+    {
+        dispatch!(self, bind_buffer_base(target, index, buffer))
+    }
+}
@@ -0,0 +1,87 @@
+//! Tests for reading files out of archives; these don't need a browser.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use futures::executor::block_on;
+
+use blueshift_gfx::Archive;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::Memory;
+use blueshift_gfx::MemoryError;
+
+#[test]
+pub fn archive_read_entries() {
+    let archive = block_on(Archive::open("file:tests/pack.zip")).unwrap();
+    let mut names = archive.entry_names().collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, vec!["hello.txt", "shaders/vertex.glsl"]);
+
+    // Deflated and stored entries both come back as they went in.
+    let vertex = std::fs::read("tests/vertex.glsl").unwrap();
+    assert_eq!(archive.read("shaders/vertex.glsl").unwrap(), vertex);
+    assert_eq!(archive.read("hello.txt").unwrap(),
+               std::fs::read("tests/hello.txt").unwrap());
+    assert!(matches!(archive.read("shaders/"), Err(MemoryError::NotFound(_))));
+}
+
+#[test]
+pub fn archive_mopen_virtual_path() {
+    let m = block_on(Memory::mopen("file:tests/pack.zip#hello.txt")).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+
+    match block_on(Memory::mopen("file:tests/pack.zip#missing.txt")) {
+        Err(MemoryError::NotFound(url)) => {
+            assert_eq!(url, "file:tests/pack.zip#missing.txt")
+        },
+        r => panic!("Unexpected result {:?}.", r),
+    }
+}
+
+#[test]
+pub fn archive_corrupt() {
+    // Flip a byte of the deflated entry so its CRC no longer matches.
+    let mut bytes = std::fs::read("tests/pack.zip").unwrap();
+    let at = bytes.windows(19)
+                  .position(|w| w == b"shaders/vertex.glsl")
+                  .unwrap() + 19;
+    bytes[at + 4] ^= 0xFF;
+    InMemorySource::insert("mem:corrupt.zip", bytes);
+
+    assert!(block_on(Memory::mopen("mem:corrupt.zip#hello.txt")).is_ok());
+    assert!(matches!(
+        block_on(Memory::mopen("mem:corrupt.zip#shaders/vertex.glsl")),
+        Err(MemoryError::ArchiveError(_, _))));
+
+    assert!(matches!(Archive::from_bytes("mem:short.zip", vec![0; 8]),
+                     Err(MemoryError::ArchiveError(_, _))));
+}
+
+#[test]
+pub fn memory_mopen_gzip() {
+    let m = block_on(Memory::mopen("file:tests/hello.txt.gz")).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+
+    // Fetch already decoded a response sent with Content-Encoding: gzip.
+    InMemorySource::insert("mem:decoded.gz", b"not gzip".to_vec());
+    let m = block_on(Memory::mopen("mem:decoded.gz")).unwrap();
+    assert_eq!(m.as_str(), "not gzip");
+
+    let mut bytes = std::fs::read("tests/hello.txt.gz").unwrap();
+    bytes.truncate(12);
+    InMemorySource::insert("mem:truncated.gz", bytes);
+    assert!(matches!(block_on(Memory::mopen("mem:truncated.gz")),
+                     Err(MemoryError::ArchiveError(_, _))));
+}
+
+#[test]
+pub fn memory_mopen_fragment() {
+    // Only a fragment after a zip URL is a path in the archive.
+    InMemorySource::insert("mem:notes.txt#intro", b"notes".to_vec());
+    let m = block_on(Memory::mopen("mem:notes.txt#intro")).unwrap();
+    assert_eq!(m.as_str(), "notes");
+
+    let bytes = std::fs::read("tests/pack.zip").unwrap();
+    InMemorySource::insert("mem:pack.zip?v=2", bytes);
+    let m = block_on(Memory::mopen("mem:pack.zip?v=2#hello.txt")).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+}
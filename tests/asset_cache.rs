@@ -0,0 +1,96 @@
+//! Tests for the asset cache, using the in-memory store.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::rc::Rc;
+
+use futures::executor::block_on;
+
+use blueshift_gfx::set_asset_cache;
+use blueshift_gfx::AssetCache;
+use blueshift_gfx::CacheStore;
+use blueshift_gfx::CacheUsage;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::Memory;
+use blueshift_gfx::MemoryCacheStore;
+use blueshift_gfx::MemoryError;
+use blueshift_gfx::MopenOptions;
+
+#[test]
+pub fn asset_cache_serves_later_loads() {
+    let cache = AssetCache::open("assets", "1");
+    InMemorySource::insert("mem:cached.txt", b"first".to_vec());
+
+    let options = MopenOptions::new().cache(cache.clone());
+    let m = block_on(Memory::mopen_with("mem:cached.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "first");
+
+    // The cached copy wins over the changed source.
+    InMemorySource::insert("mem:cached.txt", b"second".to_vec());
+    let options = MopenOptions::new().cache(cache.clone());
+    let m = block_on(Memory::mopen_with("mem:cached.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "first");
+
+    let options = MopenOptions::new().cache(cache.clone()).no_cache();
+    let m = block_on(Memory::mopen_with("mem:cached.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "second");
+
+    assert!(block_on(cache.invalidate("mem:cached.txt")).unwrap());
+    assert!(!block_on(cache.invalidate("mem:cached.txt")).unwrap());
+
+    let options = MopenOptions::new().cache(cache.clone());
+    let m = block_on(Memory::mopen_with("mem:cached.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "second");
+}
+
+#[test]
+pub fn asset_cache_versions() {
+    let store: Rc<dyn CacheStore> = Rc::new(MemoryCacheStore::new());
+
+    let v1 = AssetCache::new(store.clone(), "1");
+    block_on(v1.put("a.txt", b"abc")).unwrap();
+    block_on(v1.put("b.txt", b"de")).unwrap();
+    assert_eq!(block_on(v1.usage()).unwrap(),
+               CacheUsage { entries: 2, bytes: 5 });
+
+    // A new version misses the old entries.
+    let v2 = AssetCache::new(store, "2");
+    assert_eq!(block_on(v2.get("a.txt")).unwrap(), None);
+    block_on(v2.put("a.txt", b"abcd")).unwrap();
+
+    assert_eq!(block_on(v2.purge_stale()).unwrap(), 2);
+    assert_eq!(block_on(v2.usage()).unwrap(),
+               CacheUsage { entries: 1, bytes: 4 });
+    assert_eq!(block_on(v2.clear()).unwrap(), 1);
+    assert_eq!(block_on(v2.usage()).unwrap(), CacheUsage::default());
+}
+
+#[test]
+pub fn asset_cache_global() {
+    let cache = AssetCache::open("assets", "1");
+    set_asset_cache(Some(cache.clone()));
+
+    // Only HTTP is cached; memory and files are always read afresh.
+    InMemorySource::insert("mem:global.txt", b"Hello".to_vec());
+    block_on(Memory::mopen("mem:global.txt")).unwrap();
+    assert_eq!(block_on(cache.get("mem:global.txt")).unwrap(), None);
+    InMemorySource::insert("mem:global.txt", b"Bye".to_vec());
+    let m = block_on(Memory::mopen("mem:global.txt")).unwrap();
+    assert_eq!(m.as_str(), "Bye");
+
+    // There's no HTTP source natively, so these can only come from the
+    // cache.
+    let url = "https://example.com/global.txt";
+    block_on(cache.put(url, b"Hello")).unwrap();
+    let m = block_on(Memory::mopen(url)).unwrap();
+    assert_eq!(m.as_str(), "Hello");
+
+    // Ranges always read the source.
+    let options = MopenOptions::new().range(1, Some(2));
+    assert!(matches!(block_on(Memory::mopen_with(url, options)),
+                     Err(MemoryError::UnsupportedScheme(_))));
+
+    set_asset_cache(None);
+    assert!(matches!(block_on(Memory::mopen(url)),
+                     Err(MemoryError::UnsupportedScheme(_))));
+}
@@ -0,0 +1,166 @@
+//! Tests for asset manifests and the preloader, using in-memory sources.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use futures::executor::block_on;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::AssetKind;
+use blueshift_gfx::AssetManifest;
+use blueshift_gfx::GfxError;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::ManifestError;
+use blueshift_gfx::MemoryError;
+use blueshift_gfx::PreloadProgress;
+use blueshift_gfx::Preloader;
+use blueshift_gfx::RecordingBackend;
+
+const MANIFEST: &str = r#"{
+    "base": "mem:manifest/",
+    "assets": [
+        { "name": "basic.vert", "type": "shader",  "url": "basic.vert" },
+        { "name": "crate",      "type": "texture", "url": "crate.ktx",
+          "filter": 2, "anisotropy": 4.0 },
+        { "name": "cube",       "type": "mesh",    "url": "cube.obj" },
+        { "name": "click",      "type": "sound",   "url": "mem:click.ogg",
+          "timeout": 5000, "cache": false }
+    ]
+}"#;
+
+fn insert_assets() {
+    InMemorySource::insert("mem:manifest/basic.vert",
+                           b"void main() {}".to_vec());
+    InMemorySource::insert("mem:manifest/crate.ktx", vec![0xAB; 64]);
+    InMemorySource::insert("mem:manifest/cube.obj", b"v 0 0 0".to_vec());
+    InMemorySource::insert("mem:click.ogg", vec![1, 2, 3]);
+}
+
+#[test]
+pub fn asset_manifest_parse() {
+    let manifest = AssetManifest::from_json(MANIFEST).unwrap();
+    assert_eq!(manifest.assets.len(), 4);
+
+    let texture = &manifest.assets[1];
+    assert_eq!(texture.kind, AssetKind::Texture);
+    assert_eq!((texture.filter, texture.anisotropy), (2, 4.0));
+    assert!(texture.cache);
+    assert_eq!(manifest.url_of(texture), "mem:manifest/crate.ktx");
+
+    let sound = &manifest.assets[3];
+    assert_eq!((sound.timeout, sound.cache), (Some(5000), false));
+    assert_eq!(manifest.url_of(sound), "mem:click.ogg");
+
+    let json = r#"{ "assets": [
+        { "name": "a", "type": "mesh", "url": "a.obj" },
+        { "name": "a", "type": "mesh", "url": "b.obj" } ] }"#;
+    assert!(matches!(AssetManifest::from_json(json),
+                     Err(ManifestError::DuplicateAsset(_))));
+    let json = r#"{ "assets": [ { "name": "a", "type": "video",
+                                  "url": "a.mp4" } ] }"#;
+    assert!(matches!(AssetManifest::from_json(json),
+                     Err(ManifestError::ManifestFormatError(_))));
+}
+
+#[test]
+pub fn asset_manifest_load_relative() {
+    InMemorySource::insert("mem:levels/one.json",
+        br#"{ "assets": [ { "name": "map", "type": "mesh",
+                            "url": "map.obj" } ] }"#.to_vec());
+    let manifest = block_on(AssetManifest::load("mem:levels/one.json"))
+                   .unwrap();
+    assert_eq!(manifest.url_of(&manifest.assets[0]), "mem:levels/map.obj");
+}
+
+#[test]
+pub fn preloader_loads_all() {
+    insert_assets();
+
+    let reports  = Rc::new(RefCell::new(vec![]));
+    let manifest = AssetManifest::from_json(MANIFEST).unwrap();
+    let mut registry = block_on(Preloader::new(manifest)
+                                         .concurrency(2)
+                                         .progress({
+                                             let reports = reports.clone();
+                                             move |p| reports.borrow_mut()
+                                                             .push(p)
+                                         })
+                                         .run()).unwrap();
+    assert_eq!(registry.len(), 4);
+    assert_eq!(registry.shader("basic.vert"), Some("void main() {}"));
+    assert_eq!(registry.mesh("cube").unwrap().as_str(), "v 0 0 0");
+    assert_eq!(registry.sound("click").unwrap().bytes(), &[1, 2, 3]);
+    assert_eq!(registry.texture("crate").unwrap().memory().size(), 64);
+    // Assets are only returned as their own type.
+    assert!(registry.shader("cube").is_none());
+    assert_eq!(registry.get("click").unwrap().kind(), AssetKind::Sound);
+
+    let reports = reports.borrow();
+    assert_eq!(reports.len(), 4);
+    assert_eq!(*reports.last().unwrap(),
+               PreloadProgress { loaded: 4, failed: 0, total: 4,
+                                 bytes: 14 + 64 + 7 + 3 });
+    assert_eq!(reports.last().unwrap().fraction(), 1.0);
+}
+
+#[test]
+pub fn preloader_collects_failures() {
+    insert_assets();
+
+    let json = r#"{ "base": "mem:manifest/", "assets": [
+        { "name": "cube",    "type": "mesh",   "url": "cube.obj" },
+        { "name": "missing", "type": "mesh",   "url": "missing.obj" },
+        { "name": "binary",  "type": "shader", "url": "crate.ktx" },
+        { "name": "remote",  "type": "sound",  "url": "http://x/a.ogg" } ] }"#;
+    let manifest = AssetManifest::from_json(json).unwrap();
+    let error    = block_on(Preloader::new(manifest).run()).err().unwrap();
+
+    // The asset that loaded is still available.
+    assert_eq!(error.registry.len(), 1);
+    assert!(error.registry.mesh("cube").is_some());
+
+    let mut failures = error.failures.iter()
+                                     .map(|f| (f.name.as_str(), &f.error))
+                                     .collect::<Vec<_>>();
+    failures.sort_by_key(|&(name, _)| name);
+    assert_eq!(failures.len(), 3);
+    assert!(matches!(failures[0],
+                     ("binary", GfxError::MemoryError(
+                                    MemoryError::Utf8Error(_, 0)))));
+    assert!(matches!(failures[1],
+                     ("missing", GfxError::MemoryError(
+                                     MemoryError::NotFound(_)))));
+    assert!(matches!(failures[2],
+                     ("remote", GfxError::MemoryError(
+                                    MemoryError::UnsupportedScheme(_)))));
+    assert!(error.to_string().starts_with("3 asset(s) failed to load"));
+}
+
+#[test]
+pub fn texture_asset_create() {
+    InMemorySource::insert("mem:manifest/rgba.ktx",
+                           include_bytes!("rgba.ktx").to_vec());
+    let json = r#"{ "base": "mem:manifest/", "assets": [
+        { "name": "rgba", "type": "texture", "url": "rgba.ktx" } ] }"#;
+    let manifest     = AssetManifest::from_json(json).unwrap();
+    let mut registry = block_on(Preloader::new(manifest).run()).unwrap();
+
+    // Creating again reads the file from the start, uploading the same.
+    let context = Arc::new(RecordingBackend::new());
+    let asset   = registry.texture("rgba").unwrap();
+    let mut uploads = vec![];
+    for _ in 0..2 {
+        let texture = asset.create("rgba", context.clone()).unwrap();
+        assert_eq!(texture.target(), Ctx::TEXTURE_2D);
+        uploads.push(context.take_calls()
+                            .into_iter()
+                            .filter(|c| c.starts_with("tex_image_2d"))
+                            .collect::<Vec<_>>());
+    }
+    assert!(!uploads[0].is_empty());
+    assert_eq!(uploads[0], uploads[1]);
+}
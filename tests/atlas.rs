@@ -0,0 +1,150 @@
+//! Tests for atlas packing and sprite sheet parsing; these don't need a
+//! browser.
+
+use blueshift_gfx::AtlasBuilder;
+use blueshift_gfx::AtlasError;
+use blueshift_gfx::Image;
+use blueshift_gfx::PackingAlgorithm;
+use blueshift_gfx::SpriteSheet;
+
+fn solid(width: u32, height: u32, value: u8) -> Image {
+    Image::new(width, height, 1, vec![value; (width * height) as usize])
+          .unwrap()
+}
+
+fn check_packing(algorithm: PackingAlgorithm) {
+    let mut builder = AtlasBuilder::new(256, 256).algorithm(algorithm)
+                                                 .padding(1)
+                                                 .extrude(1);
+    let sizes = [(30, 20), (16, 16), (40, 8), (8, 40), (12, 12), (25, 25)];
+
+    for (i, &(w, h)) in sizes.iter().enumerate() {
+        builder.add(&format!("sprite{}", i), solid(w, h, i as u8 + 1))
+               .unwrap();
+    }
+    let (atlas, regions) = builder.pack().unwrap();
+    assert!(atlas.is_power_of_two());
+
+    for (i, a) in regions.iter().enumerate() {
+        assert_eq!((a.width, a.height), sizes[i]);
+        assert!(a.x + a.width <= atlas.width && a.y + a.height <= atlas.height);
+
+        // Every texel of the region, and its extruded border, holds the
+        // sprite's own value.
+        for y in a.y - 1..a.y + a.height + 1 {
+            for x in a.x - 1..a.x + a.width + 1 {
+                let texel = atlas.pixels[(y * atlas.width + x) as usize];
+                assert_eq!(texel, i as u8 + 1);
+            }
+        }
+        for b in &regions[i + 1..] {
+            let apart = a.x + a.width + 2 <= b.x || b.x + b.width + 2 <= a.x
+                     || a.y + a.height + 2 <= b.y || b.y + b.height + 2 <= a.y;
+            assert!(apart, "{} overlaps {}", a.name, b.name);
+        }
+    }
+}
+
+#[test]
+pub fn atlas_pack_max_rects() {
+    check_packing(PackingAlgorithm::MaxRects);
+}
+
+#[test]
+pub fn atlas_pack_skyline() {
+    check_packing(PackingAlgorithm::Skyline);
+}
+
+#[test]
+pub fn atlas_pack_errors() {
+    let mut builder = AtlasBuilder::new(32, 32);
+    builder.add("big", solid(40, 8, 0)).unwrap();
+    assert!(matches!(builder.pack(), Err(AtlasError::DoesNotFit(_))));
+
+    let mut builder = AtlasBuilder::new(32, 32);
+    builder.add("gray", solid(4, 4, 0)).unwrap();
+    builder.add("rgb", Image::new(1, 1, 3, vec![0; 3]).unwrap()).unwrap();
+    assert!(matches!(builder.pack(), Err(AtlasError::ImageMismatch(_))));
+
+    let empty = Image { width: 0, height: 4, channels: 1, pixels: vec![] };
+    assert!(matches!(builder.add("empty", empty),
+                     Err(AtlasError::EmptyImage(_))));
+}
+
+#[test]
+pub fn atlas_pack_non_power_of_two_max() {
+    // A 1000 texel maximum packs into at most 512, never 1000 or 2000.
+    let mut builder = AtlasBuilder::new(1000, 1000);
+    for i in 0..4 {
+        builder.add(&format!("sprite{}", i), solid(200, 200, 1)).unwrap();
+    }
+    let (atlas, _) = builder.pack().unwrap();
+    assert!(atlas.is_power_of_two());
+    assert_eq!((atlas.width, atlas.height), (512, 512));
+
+    builder.add("sprite4", solid(200, 200, 1)).unwrap();
+    assert!(matches!(builder.pack(), Err(AtlasError::DoesNotFit(_))));
+
+    let mut builder = AtlasBuilder::new(0, 64);
+    builder.add("sprite", solid(1, 1, 1)).unwrap();
+    assert!(matches!(builder.pack(), Err(AtlasError::DoesNotFit(_))));
+}
+
+#[test]
+pub fn sprite_sheet_hash() {
+    let json = r#"{
+        "frames": {
+            "hero.png": {
+                "frame": {"x": 0, "y": 0, "w": 32, "h": 64},
+                "rotated": false,
+                "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 32, "h": 64},
+                "sourceSize": {"w": 32, "h": 64}
+            },
+            "coin.png": {
+                "frame": {"x": 32, "y": 0, "w": 16, "h": 32},
+                "rotated": true
+            }
+        },
+        "meta": {"image": "sheet.ktx", "size": {"w": 64, "h": 64}}
+    }"#;
+    let sheet = SpriteSheet::from_json(json).unwrap();
+    assert_eq!(sheet.image, "sheet.ktx");
+    assert_eq!(sheet.regions.len(), 2);
+
+    let hero = sheet.region("hero.png").unwrap();
+    assert_eq!(hero.uv, [0.0, 0.0, 0.5, 1.0]);
+
+    // A rotated 16x32 sprite covers 32x16 texels of the sheet.
+    let coin = sheet.region("coin.png").unwrap();
+    assert!(coin.rotated);
+    assert_eq!(coin.uv, [0.5, 0.0, 1.0, 0.25]);
+
+    // The region's top left corner maps to the right end of its first row.
+    let m = coin.texture_matrix().to_array();
+    let s = 0.0 * m[0] + 0.0 * m[4] + m[12];
+    let t = 0.0 * m[1] + 0.0 * m[5] + m[13];
+    assert_eq!((s, t), (1.0, 0.0));
+    let s = 1.0 * m[0] + 1.0 * m[4] + m[12];
+    let t = 1.0 * m[1] + 1.0 * m[5] + m[13];
+    assert_eq!((s, t), (0.5, 0.25));
+}
+
+#[test]
+pub fn sprite_sheet_array() {
+    let json = r#"{
+        "frames": [
+            {"filename": "a", "frame": {"x": 0, "y": 0, "w": 8, "h": 8}},
+            {"filename": "b", "frame": {"x": 8, "y": 0, "w": 8, "h": 8}}
+        ],
+        "meta": {"image": "sheet.ktx", "size": {"w": 16, "h": 8}}
+    }"#;
+    let sheet = SpriteSheet::from_json(json).unwrap();
+    let names = sheet.regions.iter().map(|r| r.name.as_str())
+                                    .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "b"]);
+    assert_eq!(sheet.region("b").unwrap().uv, [0.5, 0.0, 1.0, 1.0]);
+
+    assert!(matches!(SpriteSheet::from_json("{}"),
+                     Err(AtlasError::SheetFormatError(_))));
+}
@@ -0,0 +1,527 @@
+//! Native tests of GL call sequences, run against the recording backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use futures::executor::block_on;
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::ActiveInfo;
+use blueshift_gfx::CompressionFamily;
+use blueshift_gfx::CompressionSupport;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GfxError;
+use blueshift_gfx::GlVersion;
+use blueshift_gfx::Image;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::Memory;
+use blueshift_gfx::Program;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::Shader;
+use blueshift_gfx::Texture;
+use blueshift_gfx::TextureError;
+use blueshift_gfx::TEXTURE_CLAMP;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+use blueshift_gfx::TEXTURE_MIPMAP;
+use blueshift_gfx::TEXTURE_NPOT_RESIZE;
+
+fn backend() -> Arc<RecordingBackend> {
+    Arc::new(RecordingBackend::new())
+}
+
+#[test]
+pub fn shader_compile() {
+    let context = backend();
+    let mut shader = Shader::new("test.vert", Ctx::VERTEX_SHADER,
+                                 context.clone());
+    assert!(shader.compile("void main() {}"));
+    assert_eq!(*shader.sid(), 1);
+
+    // A shader compiles once.
+    assert!(!shader.compile("void main() {}"));
+    drop(shader);
+
+    assert_eq!(context.calls(), vec![
+        format!("create_shader({}) -> 1", Ctx::VERTEX_SHADER),
+        "shader_source(1, \"void main() {}\")".to_string(),
+        "compile_shader(1)".to_string(),
+        "shader_info_log(1)".to_string(),
+        format!("shader_parameter(1, {})", Ctx::COMPILE_STATUS),
+        "delete_shader(1)".to_string(),
+    ]);
+}
+
+#[test]
+pub fn shader_compile_failure() {
+    let context = backend();
+    context.script_compile(false, "ERROR: 0:1: syntax error");
+
+    let mut shader = Shader::new("bad.frag", Ctx::FRAGMENT_SHADER,
+                                 context.clone());
+    assert!(!shader.compile("void main() {"));
+
+    // The failed shader is deleted straight away, and not again on drop.
+    let calls = context.take_calls();
+    assert_eq!(calls.last().unwrap(), "delete_shader(1)");
+    drop(shader);
+    assert!(context.calls().is_empty());
+}
+
+#[test]
+pub fn program_link_reflection() {
+    let context = backend();
+    context.set_active_attribs(vec![
+        ActiveInfo { name: "POSITION".into(), var_type: Ctx::FLOAT_VEC3,
+                     size: 1 },
+        ActiveInfo { name: "TEXCOORD0".into(), var_type: Ctx::FLOAT_VEC2,
+                     size: 1 },
+    ]);
+    context.set_active_uniforms(vec![
+        ActiveInfo { name: "MODELVIEWPROJECTIONMATRIX".into(),
+                     var_type: Ctx::FLOAT_MAT4, size: 1 },
+    ]);
+    let mut program = Program::from_source("test".into(), "v", "f",
+                                           None, None, context.clone());
+    context.take_calls();
+
+    assert!(program.link());
+    assert_eq!(program.get_vertex_attrib_location("TEXCOORD0"), 1);
+    assert_eq!(*program.get_uniform_location("MODELVIEWPROJECTIONMATRIX"),
+               0);
+    assert_eq!(context.take_calls()[..5], [
+        "create_program() -> 3",
+        "attach_shader(3, 1)",
+        "attach_shader(3, 2)",
+        "link_program(3)",
+        "program_info_log(3)",
+    ]);
+    program.draw(&Gfx::from_context(context.clone()));
+    assert_eq!(context.take_calls().last().unwrap(), "use_program(Some(3))");
+}
+
+#[test]
+pub fn program_link_failure() {
+    let context = backend();
+    context.script_link(false, "error: varying mismatch");
+
+    let mut program = Program::from_source("test".into(), "v", "f",
+                                           None, None, context.clone());
+    assert!(!program.link());
+
+    let calls = context.take_calls();
+    assert_eq!(calls[calls.len() - 2..], [
+        format!("program_parameter(3, {})", Ctx::LINK_STATUS),
+        "delete_program(3)".to_string(),
+    ]);
+    // Nothing was reflected from the failed program.
+    assert!(!calls.iter().any(|c| c.starts_with("active_")));
+}
+
+#[test]
+pub fn gfx_start_and_errors() {
+    let context = backend();
+    context.set_string(Ctx::VENDOR, "Test Vendor");
+    context.push_error(Ctx::INVALID_ENUM);
+
+    let gfx   = Gfx::from_context(context.clone());
+    let calls = context.take_calls();
+
+    assert!(calls.contains(&format!("enable({})", Ctx::DEPTH_TEST)));
+    assert!(calls.contains(&format!("depth_func({})", Ctx::LESS)));
+    // Startup drains the error queue until NO_ERROR.
+    assert_eq!(calls[calls.len() - 2..], ["get_error()", "get_error()"]);
+
+    context.push_error(Ctx::OUT_OF_MEMORY);
+    context.push_error(0x1234);
+    assert_eq!(gfx.log_errors(), vec!["GL_OUT_OF_MEMORY", "UNSPECIFIED"]);
+    assert!(gfx.log_errors().is_empty());
+}
+
+#[test]
+pub fn texture_upload() {
+    let context = backend();
+    let texture = block_on(Texture::new("rgba-ktx", "file:tests/rgba.ktx",
+                                        TEXTURE_CLAMP, TEXTURE_FILTER_1X, 0.0,
+                                        context.clone())).unwrap();
+    assert_eq!(texture.key_value("KTXorientation"), Some(&b"S=r,T=d"[..]));
+
+    // The file holds a 2x2 RGBA texture and its 1x1 mip level.
+    let t = Ctx::TEXTURE_2D;
+    assert_eq!(context.take_calls(), vec![
+        "create_texture() -> 1".to_string(),
+        format!("bind_texture({}, Some(1))", t),
+        format!("pixel_storei({}, 4)", Ctx::UNPACK_ALIGNMENT),
+        format!("tex_parameteri({}, {}, {})", t, Ctx::TEXTURE_WRAP_S,
+                Ctx::CLAMP_TO_EDGE),
+        format!("tex_parameteri({}, {}, {})", t, Ctx::TEXTURE_WRAP_T,
+                Ctx::CLAMP_TO_EDGE),
+        format!("tex_parameteri({}, {}, {})", t, Ctx::TEXTURE_MAG_FILTER,
+                Ctx::LINEAR),
+        format!("tex_parameteri({}, {}, {})", t, Ctx::TEXTURE_MIN_FILTER,
+                Ctx::LINEAR_MIPMAP_NEAREST),
+        format!("tex_image_2d({}, 0, {}, 2, 2, 0, {}, {}, Some(16))", t,
+                Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_BYTE),
+        format!("tex_image_2d({}, 1, {}, 1, 1, 0, {}, {}, Some(4))", t,
+                Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_BYTE),
+    ]);
+    drop(texture);
+    assert_eq!(context.calls(), vec!["delete_texture(1)"]);
+}
+
+#[test]
+pub fn texture_anisotropy() {
+    let context = backend();
+    let load    = |anisotropy: f32| {
+        let texture = block_on(Texture::new("rgba-ktx", "file:tests/rgba.ktx",
+                                            TEXTURE_CLAMP, TEXTURE_FILTER_1X,
+                                            anisotropy, context.clone()))
+                      .unwrap();
+        let calls   = context.take_calls()
+                             .into_iter()
+                             .filter(|c| c.starts_with("tex_parameterf"))
+                             .collect::<Vec<String>>();
+        (texture.anisotropic_filter(), calls)
+    };
+    let set = |anisotropy: &str| {
+        vec![format!("tex_parameterf({}, {}, {})", Ctx::TEXTURE_2D, 0x84FE,
+                     anisotropy)]
+    };
+
+    // Without the extension filtering stays off.
+    assert_eq!(load(16.0), (0.0, vec![]));
+
+    context.set_extensions(&["EXT_texture_filter_anisotropic"]);
+    context.set_float(0x84FF, 8.0);
+
+    // Filtering is clamped to the device maximum.
+    assert_eq!(load(16.0), (8.0, set("8")));
+    assert_eq!(load(4.0), (4.0, set("4")));
+
+    // A level of 1 or less is no anisotropic filtering at all.
+    assert_eq!(load(1.0), (0.0, vec![]));
+    assert_eq!(load(0.5), (0.0, vec![]));
+}
+
+#[test]
+pub fn texture_gpu_mipmap() {
+    let context = backend();
+    let image   = Image::new(4, 4, 4, vec![255; 64]).unwrap();
+    let texture = Texture::from_image("white", &image, TEXTURE_MIPMAP,
+                                      TEXTURE_FILTER_1X, 0.0,
+                                      context.clone()).unwrap();
+    let calls   = context.calls();
+
+    // Without clamping, wrapping is left at the GL default of repeat.
+    assert!(!calls.iter().any(|c| c.contains(&Ctx::TEXTURE_WRAP_S
+                                                  .to_string())));
+    assert_eq!(calls.last().unwrap(),
+               &format!("generate_mipmap({})", Ctx::TEXTURE_2D));
+    // A full mip chain adds a third to the base level.
+    assert_eq!(texture.size(), 64 + 64 / 3);
+}
+
+/// Builds an uncompressed RGBA KTX file of `levels`, each given as its size
+/// and texels.
+fn rgba_ktx(levels: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes = vec![0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB,
+                         0x0D, 0x0A, 0x1A, 0x0A];
+    let header = [0x04030201, Ctx::UNSIGNED_BYTE, 1, Ctx::RGBA, Ctx::RGBA,
+                  Ctx::RGBA, levels[0].0, levels[0].1, 0, 0, 1,
+                  levels.len() as u32, 0];
+    for field in header {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    for (_, _, texels) in levels {
+        bytes.extend_from_slice(&(texels.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(texels);
+    }
+    bytes
+}
+
+#[test]
+pub fn texture_npot() {
+    let t       = Ctx::TEXTURE_2D;
+    let context = backend();
+    let image   = Image::new(3, 3, 4, vec![255; 36]).unwrap();
+    let clamped = format!("tex_parameteri({}, {}, {})", t,
+                          Ctx::TEXTURE_WRAP_S, Ctx::CLAMP_TO_EDGE);
+    let mipmap  = format!("generate_mipmap({})", t);
+
+    // WebGL1 drops mipmapping and clamps.
+    Texture::from_image("npot", &image, TEXTURE_MIPMAP, TEXTURE_FILTER_1X,
+                        0.0, context.clone()).unwrap();
+    let calls = context.take_calls();
+    assert!(calls.contains(&clamped));
+    assert!(!calls.contains(&mipmap));
+
+    // The dropped mip levels no longer count toward the size.
+    let mut memory = Memory::from_bytes("npot.ktx", rgba_ktx(&[
+                         (3, 3, vec![255; 36]), (1, 1, vec![255; 4])]));
+    let texture = Texture::from_memory("npot", &mut memory, 0,
+                                       TEXTURE_FILTER_1X, 0.0,
+                                       context.clone()).unwrap();
+    assert_eq!(texture.size(), 36);
+    assert_eq!(context.take_calls()
+                      .iter()
+                      .filter(|c| c.starts_with("tex_image_2d"))
+                      .count(), 1);
+
+    // WebGL2 allows both.
+    context.set_version(GlVersion::WebGl2);
+    Texture::from_image("npot", &image, TEXTURE_MIPMAP, TEXTURE_FILTER_1X,
+                        0.0, context.clone()).unwrap();
+    let calls = context.take_calls();
+    assert!(!calls.contains(&clamped));
+    assert!(calls.contains(&mipmap));
+
+    let mut memory = Memory::from_bytes("npot.ktx", rgba_ktx(&[
+                         (3, 3, vec![255; 36]), (1, 1, vec![255; 4])]));
+    let texture = Texture::from_memory("npot", &mut memory, 0,
+                                       TEXTURE_FILTER_1X, 0.0,
+                                       context.clone()).unwrap();
+    assert_eq!(texture.size(), 40);
+}
+
+#[test]
+pub fn texture_zero_size() {
+    let context = backend();
+
+    // Resizing a zero sized texture to a power of two is an error, not a
+    // panic.
+    let mut memory = Memory::from_bytes("empty.ktx",
+                                        rgba_ktx(&[(0, 4, vec![])]));
+    let texture = Texture::from_memory("empty", &mut memory,
+                                       TEXTURE_NPOT_RESIZE, TEXTURE_FILTER_1X,
+                                       0.0, context.clone());
+    assert!(matches!(texture, Err(GfxError::TextureError(
+                                      TextureError::ImageError(_)))));
+}
+
+/// Loads the texture file `bytes` through a `mem:` URL.
+fn load_texture(url: &str, bytes: Vec<u8>, context: &Arc<RecordingBackend>)
+    -> Result<Texture<RecordingBackend>, GfxError>
+{
+    InMemorySource::insert(url, bytes);
+    block_on(Texture::new(url, url, 0, TEXTURE_FILTER_1X, 0.0,
+                          context.clone()))
+}
+
+/// Returns the `compressed_tex_image_2d` calls made since last taken.
+fn compressed_uploads(context: &RecordingBackend) -> Vec<String> {
+    context.take_calls()
+           .into_iter()
+           .filter(|c| c.starts_with("compressed_tex_image_2d"))
+           .collect()
+}
+
+fn upload(target: u32, level: u32, format: u32, width: u32, height: u32,
+          size: usize) -> String
+{
+    format!("compressed_tex_image_2d({}, {}, {:#x}, {}, {}, 0, {})",
+            target, level, format, width, height, size)
+}
+
+/// Builds a DDS file of `fourcc` blocks, `extra` being the DX10 header if
+/// any, followed by `data`.
+fn dds(width    : u32,
+       height   : u32,
+       n_mipmap : u32,
+       caps2    : u32,
+       fourcc   : &[u8; 4],
+       extra    : &[u32],
+       data     : usize) -> Vec<u8>
+{
+    let mut header = [0u32; 31];
+    header[0]  = 124;
+    header[1]  = 0x1007 | if n_mipmap > 1 { 0x20000 } else { 0 };
+    header[2]  = height;
+    header[3]  = width;
+    header[6]  = n_mipmap;
+    header[18] = 32;
+    header[19] = 0x4;
+    header[20] = u32::from_le_bytes(*fourcc);
+    header[27] = caps2;
+
+    let mut bytes = b"DDS ".to_vec();
+    for field in header.iter().chain(extra) {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    bytes.resize(bytes.len() + data, 0xAA);
+    bytes
+}
+
+/// Builds a PKM file of `format` blocks followed by `data`.
+fn pkm(format: u16, width: u16, height: u16, data: usize) -> Vec<u8> {
+    let mut bytes = b"PKM 10".to_vec();
+    for field in [format, width, height, width, height] {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    bytes.resize(bytes.len() + data, 0xAA);
+    bytes
+}
+
+/// Builds an ASTC file of `block` sized blocks followed by `data`.
+fn astc(block: [u8; 3], size: [u32; 3], data: usize) -> Vec<u8> {
+    let mut bytes = vec![0x13, 0xAB, 0xA1, 0x5C];
+    bytes.extend_from_slice(&block);
+    for dim in size {
+        bytes.extend_from_slice(&dim.to_le_bytes()[..3]);
+    }
+    bytes.resize(bytes.len() + data, 0xAA);
+    bytes
+}
+
+fn is_unsupported<T>(result: Result<T, GfxError>) -> bool {
+    matches!(result, Err(GfxError::TextureError(
+                             TextureError::UnsupportedFormat(_))))
+}
+
+#[test]
+pub fn texture_dds() {
+    use web_sys::WebglCompressedTextureS3tc     as S3;
+    use web_sys::WebglCompressedTextureS3tcSrgb as S3S;
+
+    let t       = Ctx::TEXTURE_2D;
+    let context = backend();
+
+    // The S3TC extension is needed to upload.
+    let bytes = dds(8, 8, 1, 0, b"DXT1", &[], 32);
+    assert!(is_unsupported(load_texture("mem:ext.dds", bytes, &context)));
+    context.set_extensions(&["WEBGL_compressed_texture_s3tc",
+                             "WEBGL_compressed_texture_s3tc_srgb"]);
+    context.take_calls();
+
+    // DXT1 blocks are 8 bytes, and a level is at least one block.
+    let bytes   = dds(8, 8, 4, 0, b"DXT1", &[], 32 + 8 * 3);
+    let texture = load_texture("mem:dxt1.dds", bytes, &context).unwrap();
+    let dxt1    = S3::COMPRESSED_RGB_S3TC_DXT1_EXT;
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, dxt1, 8, 8, 32), upload(t, 1, dxt1, 4, 4, 8),
+        upload(t, 2, dxt1, 2, 2, 8),  upload(t, 3, dxt1, 1, 1, 8)]);
+    assert_eq!((texture.width(), texture.height()), (8, 8));
+    assert_eq!(texture.size(), 56);
+
+    // DXT5 blocks are 16 bytes.
+    let bytes   = dds(8, 4, 1, 0, b"DXT5", &[], 32);
+    let texture = load_texture("mem:dxt5.dds", bytes, &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, S3::COMPRESSED_RGBA_S3TC_DXT5_EXT, 8, 4, 32)]);
+    assert_eq!(texture.size(), 32);
+
+    // DX10 headers name a DXGI format, here sRGB BC1.
+    let bytes = dds(4, 4, 1, 0, b"DX10", &[72, 3, 0, 1, 0], 8);
+    load_texture("mem:dx10.dds", bytes, &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, S3S::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 4, 4, 8)]);
+
+    // Cube maps upload each face in turn.
+    let bytes   = dds(4, 4, 1, 0x200 | 0xFC00, b"DXT3", &[], 16 * 6);
+    let texture = load_texture("mem:cube.dds", bytes, &context).unwrap();
+    let dxt3    = S3::COMPRESSED_RGBA_S3TC_DXT3_EXT;
+    assert_eq!(compressed_uploads(&context),
+               (0..6).map(|face| {
+                          upload(Ctx::TEXTURE_CUBE_MAP_POSITIVE_X + face, 0,
+                                 dxt3, 4, 4, 16)
+                      })
+                     .collect::<Vec<String>>());
+    assert_eq!(texture.size(), 96);
+
+    let unknown = dds(4, 4, 1, 0, b"ATI2", &[], 16);
+    assert!(is_unsupported(load_texture("mem:ati2.dds", unknown, &context)));
+    let volume  = dds(4, 4, 1, 0x200000, b"DXT1", &[], 8);
+    assert!(is_unsupported(load_texture("mem:vol.dds", volume, &context)));
+    let faces   = dds(4, 4, 1, 0x200 | 0x400, b"DXT1", &[], 8);
+    assert!(is_unsupported(load_texture("mem:face.dds", faces, &context)));
+    let short   = dds(8, 8, 2, 0, b"DXT1", &[], 32);
+    assert!(load_texture("mem:short.dds", short, &context).is_err());
+}
+
+#[test]
+pub fn texture_pkm() {
+    use web_sys::WebglCompressedTextureEtc  as ETC2;
+    use web_sys::WebglCompressedTextureEtc1 as ET1;
+
+    let t       = Ctx::TEXTURE_2D;
+    let context = backend();
+    context.set_extensions(&["WEBGL_compressed_texture_etc1"]);
+
+    // ETC1 blocks are 8 bytes; partial blocks round up.
+    let texture = load_texture("mem:etc1.pkm", pkm(0, 8, 6, 32),
+                               &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, ET1::COMPRESSED_RGB_ETC1_WEBGL, 8, 6, 32)]);
+    assert_eq!((texture.width(), texture.height()), (8, 6));
+    assert_eq!(texture.size(), 32);
+
+    // ETC2 formats need the ETC extension, which can also take ETC1.
+    let rgba = pkm(3, 4, 4, 16);
+    assert!(is_unsupported(load_texture("mem:rgba.pkm", rgba.clone(),
+                                        &context)));
+    context.set_extensions(&["WEBGL_compressed_texture_etc"]);
+    context.take_calls();
+
+    let texture = load_texture("mem:rgba.pkm", rgba, &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, ETC2::COMPRESSED_RGBA8_ETC2_EAC, 4, 4, 16)]);
+    assert_eq!(texture.size(), 16);
+
+    load_texture("mem:r11.pkm", pkm(5, 8, 8, 32), &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, ETC2::COMPRESSED_R11_EAC, 8, 8, 32)]);
+
+    load_texture("mem:etc2.pkm", pkm(0, 4, 4, 8), &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, ETC2::COMPRESSED_RGB8_ETC2, 4, 4, 8)]);
+
+    // Format 2 is the unused ETC2 RGBA1 slot.
+    assert!(is_unsupported(load_texture("mem:two.pkm", pkm(2, 4, 4, 8),
+                                        &context)));
+    assert!(load_texture("mem:short.pkm", pkm(0, 8, 8, 8), &context)
+                .is_err());
+}
+
+#[test]
+pub fn texture_astc() {
+    use web_sys::WebglCompressedTextureAstc as AST;
+
+    let t       = Ctx::TEXTURE_2D;
+    let context = backend();
+    context.set_extensions(&["WEBGL_compressed_texture_astc"]);
+
+    // Every block is 16 bytes, whatever its footprint.
+    let texture = load_texture("mem:4x4.astc", astc([4, 4, 1], [8, 8, 1], 64),
+                               &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, AST::COMPRESSED_RGBA_ASTC_4X4_KHR, 8, 8, 64)]);
+    assert_eq!((texture.width(), texture.height()), (8, 8));
+    assert_eq!(texture.size(), 64);
+
+    let texture = load_texture("mem:5x5.astc",
+                               astc([5, 5, 1], [16, 16, 1], 256),
+                               &context).unwrap();
+    assert_eq!(compressed_uploads(&context), vec![
+        upload(t, 0, AST::COMPRESSED_RGBA_ASTC_5X5_KHR, 16, 16, 256)]);
+    assert_eq!(texture.size(), 256);
+
+    let square  = astc([3, 3, 1], [8, 8, 1], 144);
+    assert!(is_unsupported(load_texture("mem:3x3.astc", square, &context)));
+    let volume  = astc([4, 4, 4], [8, 8, 8], 1024);
+    assert!(is_unsupported(load_texture("mem:3d.astc", volume, &context)));
+    let short   = astc([4, 4, 1], [8, 8, 1], 32);
+    assert!(load_texture("mem:short.astc", short, &context).is_err());
+}
+
+#[test]
+pub fn compression_support_query() {
+    let context = RecordingBackend::new();
+    context.set_extensions(&["WEBGL_compressed_texture_etc"]);
+
+    let support = CompressionSupport::query_backend(&context);
+    assert!(support.supports(CompressionFamily::Etc));
+    assert!(!support.supports(CompressionFamily::S3tc));
+    assert_eq!(support.select(&[(Some(CompressionFamily::S3tc), "a.dds"),
+                                (Some(CompressionFamily::Etc),  "a.ktx"),
+                                (None,                          "a.png")]),
+               Some("a.ktx"));
+}
@@ -0,0 +1,132 @@
+//! Native tests of WebGL version handling, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGl2RenderingContext as Ctx2;
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::downgrade_glsl;
+use blueshift_gfx::is_glsl_300;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GlBackend;
+use blueshift_gfx::GlCapabilities;
+use blueshift_gfx::GlVersion;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::Shader;
+
+const VERTEX_300 : &str = "#version 300 es
+layout(location = 0) in vec3 position;
+in vec2 uv;
+out vec2 v_uv;
+void main() { v_uv = uv; gl_Position = vec4(position, 1.0); }";
+
+const FRAGMENT_300 : &str = "#version 300 es
+precision mediump float;
+uniform sampler2D diffuse;
+uniform samplerCube sky;
+in vec2 v_uv;
+out vec4 color;
+void main() {
+    color = texture(diffuse, v_uv) + texture (sky, vec3(v_uv, 1.0));
+}";
+
+#[test]
+pub fn webgl1_capabilities() {
+    let context = RecordingBackend::new();
+    context.set_extensions(&["ANGLE_instanced_arrays", "WEBGL_draw_buffers"]);
+    context.set_float(Ctx::MAX_TEXTURE_SIZE, 4096.0);
+    context.set_float(Ctx2::MAX_DRAW_BUFFERS, 4.0);
+
+    let caps = GlCapabilities::query(&context);
+    assert_eq!(caps.version, GlVersion::WebGl1);
+    assert!(!caps.is_webgl2());
+    assert!(!caps.vertex_array_objects);
+    assert!(caps.instancing);
+    assert_eq!(caps.max_draw_buffers, 4);
+    assert!(caps.multiple_render_targets());
+    assert!(!caps.texture_3d && !caps.uniform_buffers);
+    assert_eq!(caps.max_texture_size, 4096);
+    assert_eq!(caps.glsl_version(), "#version 100");
+
+    // The extensions are enabled as they're found.
+    assert!(context.calls().contains(
+        &"enable_extension(OES_vertex_array_object)".to_string()));
+
+    // Without WEBGL_draw_buffers a draw writes one color output.
+    let context = RecordingBackend::new();
+    context.set_float(Ctx2::MAX_DRAW_BUFFERS, 4.0);
+    assert_eq!(GlCapabilities::query(&context).max_draw_buffers, 1);
+}
+
+#[test]
+pub fn webgl2_capabilities() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_version(GlVersion::WebGl2);
+    context.set_float(Ctx2::MAX_DRAW_BUFFERS, 8.0);
+
+    let gfx  = Gfx::from_context(context.clone());
+    let caps = gfx.capabilities();
+    assert!(caps.is_webgl2());
+    assert!(caps.vertex_array_objects && caps.instancing);
+    assert!(caps.integer_textures && caps.texture_3d && caps.uniform_buffers);
+    assert_eq!(caps.max_draw_buffers, 8);
+    assert_eq!(caps.glsl_version(), "#version 300 es");
+
+    // WebGL2 needs no extensions for these; only float color buffers are
+    // one.
+    assert!(!caps.color_buffer_float);
+    assert_eq!(context.calls()
+                      .iter()
+                      .filter(|c| c.starts_with("enable_extension"))
+                      .collect::<Vec<_>>(),
+               vec!["enable_extension(EXT_color_buffer_float)"]);
+
+    context.take_calls();
+    context.draw_arrays_instanced(Ctx::TRIANGLES, 0, 3, 10);
+    context.draw_buffers(&[Ctx2::COLOR_ATTACHMENT0, Ctx2::COLOR_ATTACHMENT1]);
+    assert_eq!(context.take_calls(), vec![
+        format!("draw_arrays_instanced({}, 0, 3, 10)", Ctx::TRIANGLES),
+        format!("draw_buffers([{}, {}])", Ctx2::COLOR_ATTACHMENT0,
+                Ctx2::COLOR_ATTACHMENT1),
+    ]);
+}
+
+#[test]
+pub fn glsl_downgrade() {
+    assert!(is_glsl_300(VERTEX_300));
+    assert!(!is_glsl_300("void main() {}"));
+
+    assert_eq!(downgrade_glsl(VERTEX_300, Ctx::VERTEX_SHADER), "\
+attribute vec3 position;
+attribute vec2 uv;
+varying vec2 v_uv;
+void main() { v_uv = uv; gl_Position = vec4(position, 1.0); }");
+
+    assert_eq!(downgrade_glsl(FRAGMENT_300, Ctx::FRAGMENT_SHADER), "\
+precision mediump float;
+uniform sampler2D diffuse;
+uniform samplerCube sky;
+varying vec2 v_uv;
+void main() {
+    gl_FragColor = texture2D(diffuse, v_uv) + textureCube (sky, vec3(v_uv, 1.0));
+}");
+}
+
+#[test]
+pub fn shader_source_by_version() {
+    let webgl1 = Arc::new(RecordingBackend::new());
+    let mut shader = Shader::new("test.vert", Ctx::VERTEX_SHADER,
+                                 webgl1.clone());
+    assert!(shader.compile(VERTEX_300));
+    assert!(webgl1.calls()[1].starts_with("shader_source(1, \"attribute "));
+
+    let webgl2 = Arc::new(RecordingBackend::new());
+    webgl2.set_version(GlVersion::WebGl2);
+    let mut shader = Shader::new("test.vert", Ctx::VERTEX_SHADER,
+                                 webgl2.clone());
+    assert!(shader.compile(VERTEX_300));
+    assert!(webgl2.calls()[1].starts_with("shader_source(1, \"#version 300"));
+}
@@ -0,0 +1,36 @@
+//! Fixtures shared by the native tests. Each file includes this through
+//! `#[path]` so it builds on its own as well as from `tests/lib.rs`, and
+//! no file uses all of it.
+
+#![cfg(not(target_arch = "wasm32"))]
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use blueshift_gfx::ActiveInfo;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::RecordingBackend;
+
+/// A shader variable for the recorder to report as active.
+pub fn active(name: &str, var_type: u32) -> ActiveInfo {
+    active_array(name, var_type, 1)
+}
+
+/// An active shader array of `size` elements.
+pub fn active_array(name: &str, var_type: u32, size: i32) -> ActiveInfo {
+    ActiveInfo { name: name.into(), var_type, size }
+}
+
+/// Starts `Gfx` on `context`, dropping the calls and stats from startup.
+pub fn gfx_on(context: &Arc<RecordingBackend>) -> Gfx<RecordingBackend> {
+    let gfx = Gfx::from_context(context.clone());
+    context.take_calls();
+    gfx.begin_frame();
+    gfx
+}
+
+/// Starts `Gfx` on a new recorder.
+pub fn gfx() -> (Gfx<RecordingBackend>, Arc<RecordingBackend>) {
+    let context = Arc::new(RecordingBackend::new());
+    (gfx_on(&context), context)
+}
@@ -0,0 +1,181 @@
+//! Native tests of the state `Gfx` tracks, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::FrameStats;
+use blueshift_gfx::GlBackend;
+use blueshift_gfx::GlCapabilities;
+use blueshift_gfx::GlVersion;
+use blueshift_gfx::Image;
+use blueshift_gfx::Program;
+use blueshift_gfx::RenderTarget;
+use blueshift_gfx::RenderTargetOptions;
+use blueshift_gfx::TexelFormat;
+use blueshift_gfx::Texture;
+use blueshift_gfx::VertexArray;
+use blueshift_gfx::VertexAttribute;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+
+#[path = "common.rs"]
+mod common;
+
+use common::gfx;
+
+#[test]
+pub fn redundant_state_skipped() {
+    let (gfx, context) = gfx();
+
+    // Startup enabled depth testing and set LESS.
+    gfx.enable(Ctx::DEPTH_TEST);
+    gfx.depth_func(Ctx::LESS);
+    gfx.enable(Ctx::BLEND);
+    gfx.enable(Ctx::BLEND);
+    gfx.blend_func(Ctx::SRC_ALPHA, Ctx::ONE_MINUS_SRC_ALPHA);
+    gfx.blend_func(Ctx::SRC_ALPHA, Ctx::ONE_MINUS_SRC_ALPHA);
+    gfx.viewport(0, 0, 640, 480);
+    gfx.viewport(0, 0, 640, 480);
+
+    assert_eq!(context.take_calls(), vec![
+        format!("enable({})", Ctx::BLEND),
+        format!("blend_func_separate({}, {}, {}, {})", Ctx::SRC_ALPHA,
+                Ctx::ONE_MINUS_SRC_ALPHA, Ctx::SRC_ALPHA,
+                Ctx::ONE_MINUS_SRC_ALPHA),
+        "viewport(0, 0, 640, 480)".to_string(),
+    ]);
+    assert_eq!(gfx.current_viewport(), Some([0, 0, 640, 480]));
+
+    let stats = gfx.frame_stats();
+    assert_eq!((stats.state_changes, stats.redundant_calls), (3, 5));
+
+    // Forgotten state is set again.
+    gfx.invalidate_state();
+    gfx.enable(Ctx::DEPTH_TEST);
+    assert_eq!(context.take_calls(), vec![format!("enable({})",
+                                                  Ctx::DEPTH_TEST)]);
+}
+
+#[test]
+pub fn texture_units() {
+    let (gfx, context) = gfx();
+    let t = Ctx::TEXTURE_2D;
+
+    gfx.bind_texture(0, t, Some(&1));
+    gfx.bind_texture(1, t, Some(&2));
+    gfx.bind_texture(0, t, Some(&1));
+    gfx.bind_texture(1, Ctx::TEXTURE_CUBE_MAP, Some(&3));
+    gfx.bind_texture(0, t, None);
+
+    assert_eq!(context.take_calls(), vec![
+        format!("active_texture({})", Ctx::TEXTURE0),
+        format!("bind_texture({}, Some(1))", t),
+        format!("active_texture({})", Ctx::TEXTURE1),
+        format!("bind_texture({}, Some(2))", t),
+        format!("bind_texture({}, Some(3))", Ctx::TEXTURE_CUBE_MAP),
+        format!("active_texture({})", Ctx::TEXTURE0),
+        format!("bind_texture({}, None)", t),
+    ]);
+    assert_eq!(gfx.frame_stats().texture_binds, 4);
+}
+
+#[test]
+pub fn program_bound_once_per_frame() {
+    let (gfx, context) = gfx();
+    let mut program = Program::from_source("test".into(), "void main() {}",
+                                           "void main() {}", None, None,
+                                           context.clone());
+    assert!(program.link());
+    program.set_draw_callback(Box::new(|_| {}));
+    context.take_calls();
+
+    program.draw(&gfx);
+    program.draw(&gfx);
+    gfx.draw_arrays(Ctx::TRIANGLES, 0, 3);
+    assert_eq!(context.take_calls(), vec![
+        "use_program(Some(3))".to_string(),
+        format!("draw_arrays({}, 0, 3)", Ctx::TRIANGLES),
+    ]);
+
+    let last = gfx.begin_frame();
+    assert_eq!(last, FrameStats { state_changes   : 1,
+                                  redundant_calls : 1,
+                                  program_binds   : 1,
+                                  draw_calls      : 1,
+                                  ..Default::default() });
+    assert_eq!(gfx.frame_stats(), FrameStats::default());
+}
+
+#[test]
+pub fn draw_after_vertex_array_unbind() {
+    let (gfx, context) = gfx();
+    let caps = GlCapabilities::query(&*context);
+    let vbo  = context.create_buffer().unwrap();
+    let ibo  = context.create_buffer().unwrap();
+
+    let mut program = Program::from_source("test".into(), "void main() {}",
+                                           "void main() {}", None, None,
+                                           context.clone());
+    assert!(program.link());
+    let attribs = vec![VertexAttribute::new(&vbo, 0, 3, Ctx::FLOAT)];
+    program.set_vertex_array(Some(VertexArray::new(context.clone(), &caps,
+                                                   attribs)));
+    program.set_draw_callback(Box::new(|_| {}));
+
+    // The emulated vertex array leaves the array buffer unbound, so binding
+    // the buffer again must be sent.
+    gfx.bind_buffer(Ctx::ARRAY_BUFFER, Some(&vbo));
+    program.draw(&gfx);
+    context.take_calls();
+    gfx.bind_buffer(Ctx::ARRAY_BUFFER, Some(&vbo));
+    gfx.draw_arrays(Ctx::TRIANGLES, 0, 3);
+    assert_eq!(context.take_calls(), vec![
+        format!("bind_buffer({}, Some({}))", Ctx::ARRAY_BUFFER, vbo),
+        format!("draw_arrays({}, 0, 3)", Ctx::TRIANGLES),
+    ]);
+
+    // Binding a vertex array object replaces the element array buffer.
+    context.set_version(GlVersion::WebGl2);
+    let caps    = GlCapabilities::query(&*context);
+    let attribs = vec![VertexAttribute::new(&vbo, 0, 3, Ctx::FLOAT)];
+    program.set_vertex_array(Some(VertexArray::new(context.clone(), &caps,
+                                                   attribs)));
+    gfx.bind_buffer(Ctx::ELEMENT_ARRAY_BUFFER, Some(&ibo));
+    program.draw(&gfx);
+    context.take_calls();
+    gfx.bind_buffer(Ctx::ELEMENT_ARRAY_BUFFER, Some(&ibo));
+    assert_eq!(context.take_calls(), vec![
+        format!("bind_buffer({}, Some({}))", Ctx::ELEMENT_ARRAY_BUFFER, ibo),
+    ]);
+}
+
+#[test]
+pub fn draw_after_texture_load() {
+    let (gfx, context) = gfx();
+    let first  = Texture::with_size("first", 4, 4, &TexelFormat::RGBA8,
+                                    TEXTURE_FILTER_1X, context.clone())
+                         .unwrap();
+    gfx.bind_texture(0, Ctx::TEXTURE_2D, Some(first.tid()));
+    gfx.bind_framebuffer(None);
+
+    // Loading binds the new texture to the active unit, which stays active,
+    // and setting up a render target binds its framebuffer.
+    let image  = Image::new(4, 4, 4, vec![255; 64]).unwrap();
+    let second = Texture::from_image("second", &image, 0, TEXTURE_FILTER_1X,
+                                     0.0, context.clone()).unwrap();
+    let caps   = GlCapabilities::query(&*context);
+    let target = RenderTarget::new("target", 4, 4, &RenderTargetOptions::new(),
+                                   &caps, context.clone()).unwrap();
+    context.take_calls();
+
+    gfx.bind_texture(0, Ctx::TEXTURE_2D, Some(first.tid()));
+    gfx.bind_framebuffer(None);
+    gfx.draw_arrays(Ctx::TRIANGLES, 0, 3);
+    assert_eq!(context.take_calls(), vec![
+        format!("bind_texture({}, Some({}))", Ctx::TEXTURE_2D, first.tid()),
+        format!("bind_framebuffer({}, None)", Ctx::FRAMEBUFFER),
+        format!("draw_arrays({}, 0, 3)", Ctx::TRIANGLES),
+    ]);
+    drop((second, target));
+}
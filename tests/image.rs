@@ -0,0 +1,76 @@
+//! Tests for CPU side image processing; these don't need a browser.
+
+use blueshift_gfx::CubeMapLayout;
+use blueshift_gfx::Image;
+use blueshift_gfx::MipmapFilter;
+use blueshift_gfx::TextureError;
+
+#[test]
+pub fn image_downsample_box() {
+    let image = Image::new(2, 2, 1, vec![0, 100, 200, 100]).unwrap();
+    let level = image.downsample(MipmapFilter::Box, false);
+    assert_eq!(level, Image::new(1, 1, 1, vec![100]).unwrap());
+}
+
+#[test]
+pub fn image_downsample_gamma_correct() {
+    // Averaging black and white in linear space is brighter than 50% sRGB.
+    let image = Image::new(2, 1, 2, vec![0, 255, 255, 255]).unwrap();
+    let level = image.downsample(MipmapFilter::Box, true);
+    assert_eq!(level.pixels[0], 188);
+    assert_eq!(level.pixels[1], 255);
+}
+
+#[test]
+pub fn image_mipmaps_chain() {
+    let image  = Image::new(4, 2, 4, vec![50; 4 * 2 * 4]).unwrap();
+    let levels = image.mipmaps(MipmapFilter::Kaiser, false);
+    let sizes  = levels.iter()
+                       .map(|l| (l.width, l.height))
+                       .collect::<Vec<_>>();
+    assert_eq!(sizes, vec![(2, 1), (1, 1)]);
+    assert!(levels.iter().all(|l| l.pixels.iter().all(|&p| p == 50)));
+}
+
+#[test]
+pub fn image_resize_to_power_of_two() {
+    let image = Image::new(3, 5, 3, vec![7; 3 * 5 * 3]).unwrap();
+    assert!(!image.is_power_of_two());
+    let image = image.resize(4, 8);
+    assert!(image.is_power_of_two());
+    assert_eq!(image.pixels, vec![7; 4 * 8 * 3]);
+}
+
+#[test]
+pub fn image_crop_and_rotate() {
+    let image = Image::new(3, 2, 1, vec![1, 2, 3,
+                                         4, 5, 6]).unwrap();
+    assert_eq!(image.crop(1, 0, 2, 2).unwrap(),
+               Image::new(2, 2, 1, vec![2, 3, 5, 6]).unwrap());
+    assert_eq!(image.rotate_180(), Image::new(3, 2, 1, vec![6, 5, 4,
+                                                            3, 2, 1]).unwrap());
+}
+
+#[test]
+pub fn image_size_errors() {
+    assert!(matches!(Image::new(2, 2, 3, vec![0; 11]),
+                     Err(TextureError::ImageError(_))));
+    assert!(matches!(Image::new(0, 0, 4, vec![]),
+                     Err(TextureError::ImageError(_))));
+    assert!(matches!(Image::new(0, 4, 4, vec![]),
+                     Err(TextureError::ImageError(_))));
+
+    let image = Image::new(3, 2, 1, vec![0; 6]).unwrap();
+    assert!(matches!(image.crop(2, 0, 2, 1), Err(TextureError::ImageError(_))));
+    assert!(matches!(image.crop(0, 1, 1, u32::MAX),
+                     Err(TextureError::ImageError(_))));
+}
+
+#[test]
+pub fn cube_map_layout_detect() {
+    assert_eq!(CubeMapLayout::detect(384,  64), Some(CubeMapLayout::HorizontalStrip));
+    assert_eq!(CubeMapLayout::detect( 64, 384), Some(CubeMapLayout::VerticalStrip));
+    assert_eq!(CubeMapLayout::detect(256, 192), Some(CubeMapLayout::HorizontalCross));
+    assert_eq!(CubeMapLayout::detect(192, 256), Some(CubeMapLayout::VerticalCross));
+    assert_eq!(CubeMapLayout::detect(256, 256), None);
+}
@@ -1,5 +1,28 @@
+// Each test file includes common.rs itself so it also builds on its own.
+#![allow(clippy::duplicate_mod)]
 
+mod archive;
+mod asset_cache;
+mod asset_manifest;
+mod atlas;
+mod backend;
+mod capabilities;
+mod common;
+mod gl_state;
+mod image;
+mod light;
+mod material;
 mod memory;
+mod memory_reader;
+mod memory_source;
+mod post_process;
 mod program;
+mod render_state;
+mod render_target;
+mod shadow;
+mod skybox;
+mod texture;
+mod texture_manager;
+mod vertex_array;
 
 
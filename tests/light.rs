@@ -0,0 +1,161 @@
+//! Native tests of lights and their uniforms, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::Attenuation;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::Light;
+use blueshift_gfx::LightList;
+use blueshift_gfx::Mat4;
+use blueshift_gfx::MatrixMode;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::ShadingModel;
+use blueshift_gfx::UniformValue;
+use blueshift_gfx::Vec3;
+use blueshift_gfx::MAX_LIGHTS;
+
+#[path = "common.rs"]
+mod common;
+
+use common::active;
+use common::active_array;
+
+fn uniform(uniforms: &[(String, UniformValue)], name: &str) -> UniformValue {
+    uniforms.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| *v)
+            .unwrap_or_else(|| panic!("{} wasn't set.", name))
+}
+
+fn near(value: UniformValue, expected: &[f32]) -> bool {
+    let actual = match value {
+        UniformValue::Vec2(v) => v.to_vec(),
+        UniformValue::Vec3(v) => v.to_vec(),
+        UniformValue::Vec4(v) => v.to_vec(),
+        _                     => return false,
+    };
+    actual.len() == expected.len()
+        && actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-5)
+}
+
+#[test]
+pub fn list_and_attenuation() {
+    let mut lights = LightList::new();
+    assert!(lights.is_empty());
+
+    for _ in 0..MAX_LIGHTS {
+        assert!(lights.push(Light::directional(Vec3::new(0.0, -1.0, 0.0))));
+    }
+    assert!(!lights.push(Light::point(Vec3::new_zeroed(), Attenuation::NONE)));
+    assert_eq!(lights.len(), MAX_LIGHTS);
+
+    lights.clear();
+    assert!(lights.is_empty());
+
+    assert_eq!(Attenuation::range(10.0), Attenuation::new(1.0, 0.45, 0.75));
+    assert_eq!(Attenuation::default(), Attenuation::NONE);
+}
+
+#[test]
+pub fn view_space_uniforms() {
+    let view = Mat4::look_at(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new_zeroed(),
+                             &Vec3::new(0.0, 1.0, 0.0));
+    let mut lights = LightList::new();
+    lights.set_ambient([0.1, 0.1, 0.1]);
+    lights.push(Light::directional(Vec3::new(0.0, -2.0, 0.0))
+                      .color([1.0, 0.5, 0.0])
+                      .intensity(2.0));
+    lights.push(Light::point(Vec3::new(1.0, 0.0, 0.0),
+                             Attenuation::range(10.0)));
+    lights.push(Light::spot(Vec3::new(0.0, 0.0, 5.0),
+                            Vec3::new(0.0, 0.0, -1.0), 60.0, 90.0,
+                            Attenuation::NONE));
+
+    let uniforms = lights.uniforms(&view);
+    assert_eq!(uniform(&uniforms, "LIGHTCOUNT"), UniformValue::Int(3));
+    assert_eq!(uniform(&uniforms, "AMBIENT"),
+               UniformValue::Vec3([0.1, 0.1, 0.1]));
+
+    // Directional lights point toward the light, unattenuated and uncut.
+    assert!(near(uniform(&uniforms, "LIGHTPOSITION[0]"),
+                 &[0.0, 1.0, 0.0, 0.0]));
+    assert!(near(uniform(&uniforms, "LIGHTCOLOR[0]"), &[2.0, 1.0, 0.0]));
+    assert!(near(uniform(&uniforms, "LIGHTATTENUATION[0]"), &[1.0, 0.0, 0.0]));
+    assert!(near(uniform(&uniforms, "LIGHTCONE[0]"), &[-2.0, -1.0]));
+
+    assert!(near(uniform(&uniforms, "LIGHTPOSITION[1]"),
+                 &[1.0, 0.0, -5.0, 1.0]));
+    assert!(near(uniform(&uniforms, "LIGHTATTENUATION[1]"),
+                 &[1.0, 0.45, 0.75]));
+
+    // The spot sits at the camera looking down its axis.
+    assert!(near(uniform(&uniforms, "LIGHTPOSITION[2]"),
+                 &[0.0, 0.0, 0.0, 1.0]));
+    assert!(near(uniform(&uniforms, "LIGHTDIRECTION[2]"), &[0.0, 0.0, -1.0]));
+    assert!(near(uniform(&uniforms, "LIGHTCONE[2]"), &[0.0, 0.5]));
+}
+
+#[test]
+pub fn program_uploads() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_active_uniforms(vec![
+        active("LIGHTCOUNT",                    Ctx::INT),
+        active_array("LIGHTCOLOR[0]",           Ctx::FLOAT_VEC3, 8),
+        active("MODELVIEWPROJECTIONMATRIX",     Ctx::FLOAT_MAT4),
+        active("NORMALMATRIX",                  Ctx::FLOAT_MAT3)]);
+    let mut gfx = Gfx::from_context(context.clone());
+
+    let program = ShadingModel::BlinnPhong.program(&["DIFFUSE_MAP"],
+                                                   context.clone())
+                                          .unwrap();
+    let calls = context.take_calls();
+    let sources = calls.iter()
+                       .filter(|c| c.starts_with("shader_source"))
+                       .collect::<Vec<&String>>();
+    assert_eq!(sources.len(), 2);
+    assert!(sources.iter().all(|s| s.contains("#define DIFFUSE_MAP")));
+
+    let mut lights = LightList::new();
+    lights.push(Light::directional(Vec3::new(0.0, 0.0, -1.0)));
+    lights.push(Light::point(Vec3::new_zeroed(), Attenuation::NONE)
+                      .intensity(0.5));
+
+    gfx.use_program(Some(program.pid()));
+    context.take_calls();
+    program.set_lights(&lights, &Mat4::new_identity());
+
+    let calls = context.take_calls();
+    assert_eq!(calls, vec!["uniform1i(Some(0), 2)",
+                           "uniform3f(Some(1), 1, 1, 1)",
+                           "uniform3f(Some(1001), 0.5, 0.5, 0.5)"]);
+
+    // Translation leaves the normal matrix alone.
+    gfx.set_matrix_mode(MatrixMode::ModelView);
+    let mut model = Mat4::new_identity();
+    model.translate(&Vec3::new(1.0, 2.0, 3.0));
+    gfx.load_matrix(&model);
+    gfx.set_matrix_uniforms(&program);
+
+    let calls = context.take_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls[0].starts_with("uniform_matrix4fv(Some(2), false"));
+    assert_eq!(calls[1], "uniform_matrix3fv(Some(3), false, \
+                          [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0])");
+}
+
+#[test]
+pub fn shading_models_link() {
+    let context = Arc::new(RecordingBackend::new());
+
+    for model in [ShadingModel::BlinnPhong, ShadingModel::MetallicRoughness] {
+        assert!(model.fragment_shader().contains("light_incoming"));
+        assert!(model.program(&[], context.clone()).is_ok());
+    }
+    context.script_link(false, "error");
+    assert!(ShadingModel::MetallicRoughness.program(&[], context).is_err());
+}
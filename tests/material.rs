@@ -0,0 +1,203 @@
+//! Native tests of materials and material files, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::program_variant_key;
+use blueshift_gfx::FullscreenPass;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GfxError;
+use blueshift_gfx::Material;
+use blueshift_gfx::MaterialError;
+use blueshift_gfx::MaterialLibrary;
+use blueshift_gfx::RasterState;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::RenderState;
+use blueshift_gfx::TexelFormat;
+use blueshift_gfx::Texture;
+use blueshift_gfx::UniformValue;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+
+#[path = "common.rs"]
+mod common;
+
+use common::active;
+use common::gfx_on;
+
+fn setup() -> (Gfx<RecordingBackend>, Arc<RecordingBackend>) {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_active_uniforms(vec![active("DIFFUSEMAP", Ctx::SAMPLER_2D),
+                                     active("DIFFUSE",    Ctx::FLOAT_VEC4),
+                                     active("SHININESS",  Ctx::FLOAT)]);
+    (gfx_on(&context), context)
+}
+
+fn texture(name: &str, context: &Arc<RecordingBackend>)
+    -> Rc<Texture<RecordingBackend>>
+{
+    Rc::new(Texture::with_size(name, 4, 4, &TexelFormat::RGBA8,
+                               TEXTURE_FILTER_1X, context.clone()).unwrap())
+}
+
+fn compiles(calls: &[String]) -> usize {
+    calls.iter().filter(|c| c.starts_with("compile_shader")).count()
+}
+
+#[test]
+pub fn apply_binds_everything() {
+    let (gfx, context) = setup();
+    let program  = Rc::new(FullscreenPass::program("flat", "void main() {}",
+                                                   context.clone())
+                                          .unwrap());
+    let diffuse  = texture("diffuse", &context);
+    let other    = texture("other", &context);
+    let material = Material::new("flat", program.clone())
+                       .with_texture("DIFFUSEMAP", 2, other)
+                       .with_texture("DIFFUSEMAP", 3, diffuse.clone())
+                       .with_param("SHININESS", 8.0.into())
+                       .with_param("FRESNEL", 1.0.into())
+                       .with_state(RenderState::TRANSPARENT);
+
+    assert_eq!(material.textures().len(), 1);
+    assert!(Rc::ptr_eq(material.texture("DIFFUSEMAP").unwrap(), &diffuse));
+    assert_eq!(material.param("SHININESS"), Some(UniformValue::Float(8.0)));
+    context.take_calls();
+
+    material.apply(&gfx);
+    let calls = context.take_calls();
+    let tid   = format!("{:?}", diffuse.tid());
+
+    assert_eq!(calls.first(),
+               Some(&format!("use_program(Some({:?}))", program.pid())));
+    assert!(calls.contains(&"depth_mask(false)".to_string()));
+    assert!(calls.contains(&format!("active_texture({})", Ctx::TEXTURE0 + 3)));
+    assert!(calls.contains(&format!("bind_texture({}, Some({}))",
+                                    Ctx::TEXTURE_2D, tid)));
+    assert!(calls.contains(&"uniform1i(Some(0), 3)".to_string()));
+    assert!(calls.contains(&"uniform1f(Some(2), 8)".to_string()));
+
+    // Applying again only uploads uniforms; the state is already set.
+    material.apply(&gfx);
+    let calls = context.take_calls();
+    assert_eq!(calls, vec!["uniform1i(Some(0), 3)", "uniform1f(Some(2), 8)"]);
+}
+
+#[test]
+pub fn library_loads_json() {
+    let (_, context) = setup();
+    let mut library  = MaterialLibrary::new(context.clone());
+    library.add_texture("crate.ktx", texture("crate", &context));
+    library.add_texture("detail.ktx", texture("detail", &context));
+    context.take_calls();
+
+    let crate_material = library.create_from_json(r#"
+        { "name": "crate",
+          "program": "blinn_phong",
+          "defines": ["DIFFUSE_MAP"],
+          "textures": { "DIFFUSEMAP": { "texture": "crate.ktx" },
+                        "DETAILMAP":  { "texture": "detail.ktx", "unit": 0 } },
+          "params": { "DIFFUSE": [1.0, 0.5, 0.5, 1.0],
+                      "SHININESS": 16,
+                      "LAYERS": { "int": 2 } },
+          "double_sided": true }"#).unwrap();
+    assert_eq!(compiles(&context.take_calls()), 2);
+
+    let units = crate_material.textures()
+                              .iter()
+                              .map(|b| (b.uniform.as_str(), b.unit))
+                              .collect::<Vec<(&str, u32)>>();
+    assert_eq!(units, vec![("DETAILMAP", 0), ("DIFFUSEMAP", 1)]);
+    assert_eq!(crate_material.param("DIFFUSE"),
+               Some(UniformValue::Vec4([1.0, 0.5, 0.5, 1.0])));
+    assert_eq!(crate_material.param("SHININESS"),
+               Some(UniformValue::Float(16.0)));
+    assert_eq!(crate_material.param("LAYERS"), Some(UniformValue::Int(2)));
+    // Double sided only stops culling; the rest is the preset's.
+    assert_eq!(*crate_material.state(),
+               RenderState { raster: RasterState::NO_CULL,
+                             ..RenderState::OPAQUE });
+
+    // The same variant is shared; other defines build another.
+    let glass = library.create_from_json(r#"
+        { "name": "glass", "program": "blinn_phong",
+          "defines": ["DIFFUSE_MAP"], "state": "transparent" }"#).unwrap();
+    assert_eq!(compiles(&context.take_calls()), 0);
+    assert!(Rc::ptr_eq(glass.program(), crate_material.program()));
+    assert_eq!(*glass.state(), RenderState::TRANSPARENT);
+
+    library.create_from_json(r#"
+        { "name": "metal", "program": "metallic_roughness" }"#).unwrap();
+    assert_eq!(compiles(&context.take_calls()), 2);
+
+    assert!(library.material("glass").is_some());
+    assert!(library.program(&program_variant_key(
+                "blinn_phong", &["DIFFUSE_MAP".into()])).is_some());
+}
+
+#[test]
+pub fn library_programs() {
+    let (_, context) = setup();
+    let mut library  = MaterialLibrary::new(context.clone());
+    let program      = Rc::new(FullscreenPass::program("flat",
+                                                       "void main() {}",
+                                                       context.clone())
+                                              .unwrap());
+    library.add_program("flat", program.clone());
+    library.add_program(&program_variant_key("flat", &["B".into(),
+                                                       "A".into()]),
+                        program.clone());
+    assert_eq!(program_variant_key("flat", &["B".into(), "A".into()]),
+               "flat#A#B");
+
+    let material = library.create_from_json(r#"
+        { "name": "m", "program": "flat", "defines": ["A", "B"] }"#).unwrap();
+    assert!(Rc::ptr_eq(material.program(), &program));
+    assert!(library.create_from_json(r#"
+        { "name": "m", "program": "flat" }"#).is_ok());
+}
+
+#[test]
+pub fn load_errors() {
+    let (_, context) = setup();
+    let mut library  = MaterialLibrary::new(context.clone());
+
+    let missing_program = library.create_from_json(r#"
+        { "name": "m", "program": "toon" }"#);
+    assert!(matches!(missing_program,
+                     Err(GfxError::MaterialError(
+                         MaterialError::MissingProgram(_)))));
+
+    let missing_texture = library.create_from_json(r#"
+        { "name": "m", "program": "blinn_phong",
+          "textures": { "DIFFUSEMAP": { "texture": "none.ktx" } } }"#);
+    assert!(matches!(missing_texture,
+                     Err(GfxError::MaterialError(
+                         MaterialError::MissingTexture(_)))));
+
+    let bad_param = library.create_from_json(r#"
+        { "name": "m", "program": "blinn_phong",
+          "params": { "DIFFUSE": [1.0, 0.5, 0.5, 1.0, 1.0] } }"#);
+    assert!(matches!(bad_param,
+                     Err(GfxError::MaterialError(
+                         MaterialError::MaterialFormatError(_)))));
+
+    let shared_unit = library.create_from_json(r#"
+        { "name": "m", "program": "blinn_phong",
+          "textures": { "DIFFUSEMAP": { "texture": "none.ktx", "unit": 1 },
+                        "DETAILMAP":  { "texture": "none.ktx", "unit": 1 } }
+        }"#);
+    assert!(matches!(shared_unit,
+                     Err(GfxError::MaterialError(
+                         MaterialError::MaterialFormatError(_)))));
+
+    let bad_json = library.create_from_json(r#"{ "name": "m" }"#);
+    assert!(matches!(bad_json,
+                     Err(GfxError::MaterialError(
+                         MaterialError::MaterialFormatError(_)))));
+    assert!(library.material("m").is_none());
+}
@@ -3,10 +3,16 @@
 #![cfg(target_arch = "wasm32")]
 #![cfg(feature = "test_accessors")]
 
+use std::cell::Cell;
+use std::rc::Rc;
+
 use wasm_bindgen_test::*;
+use web_sys::AbortController;
 use web_sys::console;
 
 use blueshift_gfx::Memory;
+use blueshift_gfx::MemoryError;
+use blueshift_gfx::MopenOptions;
 
 wasm_bindgen_test_configure!(run_in_browser);
 
@@ -66,3 +72,62 @@ pub async fn memory_mopen_file_url() {
 
 
 
+
+#[wasm_bindgen_test]
+pub async fn memory_mopen_with_progress() {
+    let received = Rc::new(Cell::new(0));
+    let options  = MopenOptions::new().progress({
+        let received = received.clone();
+        move |n, _| received.set(n)
+    });
+    let m = Memory::mopen_with("http://localhost:8000/tests/rgba.ktx",
+                               options).await.unwrap();
+    assert_eq!(received.get(), m.size());
+}
+
+#[wasm_bindgen_test]
+pub async fn memory_mopen_with_abort() {
+    let controller = AbortController::new().unwrap();
+    controller.abort();
+
+    let options = MopenOptions::new().signal(controller.signal());
+    match Memory::mopen_with("http://localhost:8000/tests/hello.txt",
+                             options).await {
+        Err(MemoryError::Aborted(url)) => assert!(url.ends_with("hello.txt")),
+        r => panic!("Unexpected result {:?}.", r),
+    }
+}
+
+#[wasm_bindgen_test]
+pub async fn memory_mopen_with_timeout() {
+    // Nothing answers at this address, so the request can only end when
+    // the timer aborts it.
+    let options = MopenOptions::new().timeout(100);
+    match Memory::mopen_with("http://10.255.255.1/tests/hello.txt",
+                             options).await {
+        Err(MemoryError::Timeout(_, 100)) => {},
+        r => panic!("Unexpected result {:?}.", r),
+    }
+}
+
+#[wasm_bindgen_test]
+pub async fn memory_mopen_with_range() {
+    let options = MopenOptions::new().range(7, Some(5));
+    let m = Memory::mopen_with("http://localhost:8000/tests/hello.txt",
+                               options).await.unwrap();
+    // Servers that ignore Range still give back only the requested bytes.
+    assert_eq!(m.as_str(), "World");
+}
+
+#[wasm_bindgen_test]
+pub async fn memory_mopen_not_modified() {
+    let url = "http://localhost:8000/tests/hello.txt";
+    let m   = Memory::mopen(url).await.unwrap();
+    let lm  = m.last_modified().expect("server should send Last-Modified");
+
+    let options = MopenOptions::new().if_modified_since(lm);
+    match Memory::mopen_with(url, options).await {
+        Err(e) => assert!(e.is_not_modified()),
+        Ok(_)  => panic!("mopen_with() should have reported 304."),
+    }
+}
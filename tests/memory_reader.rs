@@ -0,0 +1,114 @@
+//! Tests for reading from a Memory buffer; these don't need a browser.
+
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+
+use blueshift_gfx::from_bytes_struct;
+use blueshift_gfx::Memory;
+use blueshift_gfx::MemoryError;
+
+from_bytes_struct! {
+    #[derive(Debug, PartialEq)]
+    struct Header {
+        tag     : [u8; 4],
+        version : u16,
+        flags   : u16,
+        scale   : f32,
+    }
+}
+
+#[test]
+pub fn memory_read_numbers() {
+    let mut m = Memory::from_bytes("numbers", vec![0x01, 0x02, 0x03, 0x04,
+                                                   0x01, 0x02, 0x03, 0x04,
+                                                   0x00, 0x00, 0x80, 0x3F,
+                                                   0xFF]);
+    assert_eq!(m.read_u16_le().unwrap(), 0x0201);
+    assert_eq!(m.read_u16_be().unwrap(), 0x0304);
+    assert_eq!(m.read_u32_be().unwrap(), 0x01020304);
+    assert_eq!(m.read_f32_le().unwrap(), 1.0);
+    assert_eq!(m.mtell(), 12);
+    assert_eq!(m.read_u8().unwrap(), 0xFF);
+
+    // Reading past the end fails and leaves the position alone.
+    assert!(matches!(m.read_u32_le(), Err(MemoryError::OutOfBounds(_, 13, 4))));
+    assert_eq!(m.mtell(), 13);
+}
+
+#[test]
+pub fn memory_seek() {
+    let mut m = Memory::from_bytes("seek", (0..10).collect());
+    assert_eq!(m.mseek(SeekFrom::End(-2)).unwrap(), 8);
+    assert_eq!(m.mseek(SeekFrom::Current(-3)).unwrap(), 5);
+    assert_eq!(m.read_u8().unwrap(), 5);
+    assert!(matches!(m.mseek(SeekFrom::Current(-7)),
+                     Err(MemoryError::SeekError(_, -1))));
+    assert!(m.mseek(SeekFrom::Start(11)).is_err());
+
+    let mut buf = [0; 8];
+    m.mseek(SeekFrom::Start(6)).unwrap();
+    assert_eq!(m.mread(&mut buf), 4);
+    assert_eq!(&buf[..4], &[6, 7, 8, 9]);
+}
+
+#[test]
+pub fn memory_read_strings() {
+    let mut m = Memory::from_bytes("text", b"key\0line one\r\nline two\n\
+                                             last".to_vec());
+    assert_eq!(m.read_cstr().unwrap(), "key");
+    assert_eq!(m.read_line().unwrap(), Some("line one"));
+    assert_eq!(m.read_line().unwrap(), Some("line two"));
+    assert_eq!(m.read_line().unwrap(), Some("last"));
+    assert_eq!(m.read_line().unwrap(), None);
+
+    let mut m = Memory::from_bytes("text", b"no terminator".to_vec());
+    assert!(m.read_cstr().is_err());
+}
+
+#[test]
+pub fn memory_std_io() {
+    let mut m = Memory::from_bytes("io", b"first\nsecond\n".to_vec());
+    let lines = (&mut m).lines().map(|l| l.unwrap()).collect::<Vec<_>>();
+    assert_eq!(lines, vec!["first", "second"]);
+
+    m.rewind().unwrap();
+    let mut head = [0; 5];
+    m.read_exact(&mut head).unwrap();
+    assert_eq!(&head, b"first");
+    assert!(m.seek(SeekFrom::Current(100)).is_err());
+}
+
+#[test]
+pub fn memory_read_struct() {
+    // Start at an odd offset so the header isn't aligned.
+    let mut bytes = vec![0xEE];
+    bytes.extend_from_slice(b"HDR1");
+    bytes.extend_from_slice(&[0x02, 0x00, 0x00, 0x01]);
+    bytes.extend_from_slice(&2.5f32.to_le_bytes());
+    bytes.extend_from_slice(&[0x01, 0x00, 0x00, 0x02]);
+
+    let mut m = Memory::from_bytes("struct", bytes);
+    m.mseek(SeekFrom::Start(1)).unwrap();
+
+    assert_eq!(m.read_le::<Header>().unwrap(),
+               Header { tag: *b"HDR1", version: 2, flags: 0x100, scale: 2.5 });
+    assert_eq!(m.read_slice_be::<u16>(2).unwrap(), vec![0x100, 0x2]);
+    assert!(matches!(m.read_le::<Header>(),
+                     Err(MemoryError::OutOfBounds(_, 17, 12))));
+
+    let m = Memory::from_bytes("short", vec![0; 4]);
+    assert!(m.peek_le::<Header>().is_err());
+    assert_eq!(m.peek_le::<u32>().unwrap(), 0);
+}
+
+#[test]
+#[allow(deprecated)]
+pub fn memory_deprecated_copies() {
+    let m = Memory::from_bytes("short", 7u32.to_ne_bytes().to_vec());
+    assert_eq!(m.bytes_copy_into_new::<u32>().unwrap(), 7);
+    assert!(matches!(m.bytes_copy_into_new::<u64>(),
+                     Err(MemoryError::RefTransmuteError(..))));
+    assert!(unsafe { m.bytes_as_ref::<u64>() }.is_err());
+}
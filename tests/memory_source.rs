@@ -0,0 +1,141 @@
+//! Tests for the non-browser Memory sources.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use futures::executor::block_on;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use blueshift_gfx::register_memory_source;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::Memory;
+use blueshift_gfx::MemoryError;
+use blueshift_gfx::MemorySource;
+use blueshift_gfx::MopenOptions;
+
+#[test]
+pub fn memory_mopen_file() {
+    let m = block_on(Memory::mopen("file:tests/hello.txt")).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+
+    // Relative URLs are read from the file system outside the browser.
+    let m = block_on(Memory::mopen("tests/hello.txt")).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+
+    match block_on(Memory::mopen("file:tests/not_there.txt")) {
+        Err(MemoryError::IoError(url, e)) => {
+            assert_eq!(url, "file:tests/not_there.txt");
+            assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+        },
+        r => panic!("Unexpected result {:?}.", r),
+    }
+}
+
+#[test]
+pub fn memory_mopen_mem() {
+    InMemorySource::insert("mem:source/greeting", b"hi".to_vec());
+    InMemorySource::insert_static("mem://source/static", b"static");
+
+    let m = block_on(Memory::mopen("mem:source/greeting")).unwrap();
+    assert_eq!(m.bytes(), b"hi");
+    let m = block_on(Memory::mopen("mem:source/static")).unwrap();
+    assert_eq!(m.bytes(), b"static");
+
+    InMemorySource::remove("mem:source/greeting");
+    assert!(matches!(block_on(Memory::mopen("mem:source/greeting")),
+                     Err(MemoryError::NotFound(_))));
+}
+
+#[test]
+pub fn memory_mopen_unsupported_scheme() {
+    // Fetch isn't available outside the browser.
+    assert!(matches!(block_on(Memory::mopen("http://localhost:8000/x.txt")),
+                     Err(MemoryError::UnsupportedScheme(_))));
+}
+
+struct UpperSource;
+
+impl MemorySource for UpperSource {
+    fn open<'a>(&'a self, url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>
+    {
+        async move {
+            Ok( url.to_uppercase().into_bytes() )
+        }.boxed_local()
+    }
+}
+
+#[test]
+pub fn memory_register_source() {
+    register_memory_source("upper", Arc::new(UpperSource));
+    // Schemes are case insensitive.
+    let m = block_on(Memory::mopen("Upper:abc")).unwrap();
+    assert_eq!(m.as_str(), "UPPER:ABC");
+}
+
+#[test]
+pub fn memory_mopen_with_progress() {
+    let calls   = Rc::new(RefCell::new(vec![]));
+    let options = MopenOptions::new().progress({
+        let calls = calls.clone();
+        move |received, total| calls.borrow_mut().push((received, total))
+    });
+    // Archives are shared once open, so use one no other test reads.
+    let bytes = std::fs::read("tests/pack.zip").unwrap();
+    let size  = bytes.len();
+    InMemorySource::insert("mem:progress.zip", bytes);
+
+    let m = block_on(Memory::mopen_with("mem:progress.zip#hello.txt",
+                                        options)).unwrap();
+    assert_eq!(m.as_str().trim(), "Hello, World!");
+
+    // Progress is for the archive, which is read in one step.
+    assert_eq!(*calls.borrow(), vec![(size, Some(size))]);
+}
+
+#[test]
+pub fn memory_mopen_with_range() {
+    InMemorySource::insert("mem:range.txt", b"Hello, World!".to_vec());
+
+    let options = MopenOptions::new().range(7, Some(5));
+    let m = block_on(Memory::mopen_with("mem:range.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "World");
+    assert_eq!(m.status(), 206);
+
+    // Open ended and out of bounds ranges are clamped to the data.
+    let options = MopenOptions::new().range(7, None);
+    let m = block_on(Memory::mopen_with("mem:range.txt", options)).unwrap();
+    assert_eq!(m.as_str(), "World!");
+
+    let options = MopenOptions::new().range(20, Some(4));
+    let m = block_on(Memory::mopen_with("mem:range.txt", options)).unwrap();
+    assert_eq!(m.size(), 0);
+
+    let m = block_on(Memory::mopen("mem:range.txt")).unwrap();
+    assert_eq!(m.status(), 200);
+    assert_eq!(m.etag(), None);
+}
+
+#[test]
+pub fn memory_mopen_with_empty_range() {
+    // Nothing is read for a zero-length range, so it doesn't matter that
+    // there's nothing there.
+    let options = MopenOptions::new().range(3, Some(0));
+    let m = block_on(Memory::mopen_with("mem:empty-range.txt", options))
+                    .unwrap();
+    assert_eq!((m.size(), m.status()), (0, 206));
+}
+
+#[test]
+pub fn memory_mopen_with_archive_range() {
+    // The range applies to the entry, not the archive.
+    let options = MopenOptions::new().range(0, Some(5));
+    let m = block_on(Memory::mopen_with("tests/pack.zip#hello.txt",
+                                        options)).unwrap();
+    assert_eq!(m.as_str(), "Hello");
+    assert_eq!(m.status(), 206);
+}
@@ -0,0 +1,169 @@
+//! Native tests of the post-processing chain, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::Bloom;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GaussianBlur;
+use blueshift_gfx::PostEffect;
+use blueshift_gfx::PostProcessChain;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::RenderTargetOptions;
+use blueshift_gfx::ShaderEffect;
+use blueshift_gfx::UniformValue;
+
+#[path = "common.rs"]
+mod common;
+
+use common::active;
+use common::gfx_on;
+
+fn setup() -> (Gfx<RecordingBackend>, Arc<RecordingBackend>) {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_active_attribs(vec![active("POSITION", Ctx::FLOAT_VEC2)]);
+    context.set_active_uniforms(vec![active("INPUT",     Ctx::SAMPLER_2D),
+                                     active("EXPOSURE",  Ctx::FLOAT),
+                                     active("DIRECTION", Ctx::FLOAT_VEC2)]);
+    (gfx_on(&context), context)
+}
+
+fn chain(gfx: &Gfx<RecordingBackend>) -> PostProcessChain<RecordingBackend> {
+    PostProcessChain::new(320, 240, &RenderTargetOptions::new(),
+                          gfx.capabilities(), gfx.context().clone()).unwrap()
+}
+
+fn framebuffer_binds(calls: &[String]) -> Vec<String> {
+    calls.iter()
+         .filter(|c| c.starts_with("bind_framebuffer"))
+         .cloned()
+         .collect()
+}
+
+#[test]
+pub fn order_and_toggles() {
+    let (gfx, context) = setup();
+    let mut chain      = chain(&gfx);
+
+    chain.push(Box::new(ShaderEffect::vignette(context.clone()).unwrap()));
+    chain.push(Box::new(ShaderEffect::fxaa(context.clone()).unwrap()));
+    chain.insert(0, Box::new(ShaderEffect::tone_mapping(context.clone())
+                                          .unwrap()));
+    assert_eq!(chain.names(), vec!["tone_mapping", "vignette", "fxaa"]);
+
+    assert!(chain.set_enabled("vignette", false));
+    assert!(!chain.set_enabled("sepia", false));
+    assert!(!chain.is_enabled("vignette"));
+    assert!(chain.is_enabled("fxaa"));
+
+    let tone = chain.effect_mut("tone_mapping").unwrap();
+    assert!(tone.set_param("EXPOSURE", 2.0.into()));
+    assert!(!tone.set_param("CONTRAST", 2.0.into()));
+    assert_eq!(tone.param("EXPOSURE"), Some(UniformValue::Float(2.0)));
+    assert_eq!(tone.param("GAMMA"), Some(UniformValue::Float(2.2)));
+
+    assert!(chain.remove("fxaa").is_some());
+    assert_eq!(chain.names(), vec!["tone_mapping", "vignette"]);
+}
+
+#[test]
+pub fn effects_ping_pong() {
+    let (gfx, context) = setup();
+    let mut chain      = chain(&gfx);
+
+    chain.push(Box::new(ShaderEffect::tone_mapping(context.clone()).unwrap()));
+    chain.push(Box::new(ShaderEffect::vignette(context.clone()).unwrap()));
+    chain.push(Box::new(ShaderEffect::fxaa(context.clone()).unwrap()));
+    chain.set_enabled("vignette", false);
+    chain.effect_mut("tone_mapping")
+         .unwrap()
+         .set_param("EXPOSURE", 2.0.into());
+
+    let scene = format!("{:?}", Some(chain.scene_target().framebuffer()));
+    gfx.viewport(0, 0, 640, 480);
+    gfx.begin_frame();
+    context.take_calls();
+
+    chain.begin(&gfx);
+    chain.end(&gfx);
+
+    let calls = context.take_calls();
+    let fb    = Ctx::FRAMEBUFFER;
+    let binds = framebuffer_binds(&calls);
+
+    // The scene, then tone mapping into the other target, then FXAA over
+    // the canvas.
+    assert_eq!(binds.len(), 4);
+    assert_eq!(binds[0], format!("bind_framebuffer({}, {})", fb, scene));
+    assert_eq!(binds[1], format!("bind_framebuffer({}, None)", fb));
+    assert_ne!(binds[2], binds[0]);
+    assert_eq!(binds[3], format!("bind_framebuffer({}, None)", fb));
+
+    assert_eq!(gfx.frame_stats().draw_calls, 2);
+    assert!(calls.contains(&"uniform1f(Some(1), 2)".to_string()));
+    assert_eq!(calls.iter().rev().find(|c| c.starts_with("viewport")),
+               Some(&"viewport(0, 0, 640, 480)".to_string()));
+
+    // Depth testing is off for the effects and back on afterwards.
+    let depth = calls.iter()
+                     .filter(|c| c.contains(&format!("({})", Ctx::DEPTH_TEST)))
+                     .cloned()
+                     .collect::<Vec<String>>();
+    assert_eq!(depth, vec![format!("disable({})", Ctx::DEPTH_TEST),
+                           format!("enable({})", Ctx::DEPTH_TEST)]);
+}
+
+#[test]
+pub fn nothing_enabled_copies() {
+    let (gfx, context) = setup();
+    let mut chain      = chain(&gfx);
+
+    chain.push(Box::new(ShaderEffect::vignette(context.clone()).unwrap()));
+    chain.set_enabled("vignette", false);
+    gfx.begin_frame();
+    context.take_calls();
+
+    chain.begin(&gfx);
+    chain.end(&gfx);
+    assert_eq!(gfx.frame_stats().draw_calls, 1);
+    assert_eq!(framebuffer_binds(&context.take_calls()).len(), 2);
+}
+
+#[test]
+pub fn multi_pass_effects() {
+    let (gfx, context) = setup();
+    let caps           = *gfx.capabilities();
+    let mut chain      = chain(&gfx);
+
+    let mut blur = GaussianBlur::new(320, 240, &caps, context.clone()).unwrap();
+    assert!(blur.set_param("RADIUS", 2.0.into()));
+    assert!(!blur.set_param("RADIUS", 2.into()));
+
+    let bloom = Bloom::new(320, 240, &caps, context.clone()).unwrap();
+    assert_eq!(bloom.param("THRESHOLD"), Some(UniformValue::Float(1.0)));
+    assert_eq!(bloom.param("RADIUS"), Some(UniformValue::Float(1.0)));
+
+    chain.push(Box::new(blur));
+    chain.push(Box::new(bloom));
+    gfx.begin_frame();
+
+    chain.begin(&gfx);
+    chain.end(&gfx);
+
+    // Two blur passes, then bright pass, two blur passes and composite.
+    assert_eq!(gfx.frame_stats().draw_calls, 6);
+    assert!(context.take_calls().contains(
+        &format!("uniform2f(Some(2), {}, 0)", 2.0 / 320.0)));
+}
+
+#[test]
+pub fn link_failure() {
+    let (_, context) = setup();
+    context.script_link(false, "error");
+
+    assert!(ShaderEffect::new("broken", "void main() {}", context).is_err());
+}
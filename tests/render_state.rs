@@ -0,0 +1,82 @@
+//! Native tests of render state presets, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::BlendState;
+use blueshift_gfx::DepthState;
+use blueshift_gfx::RasterState;
+use blueshift_gfx::RenderState;
+use blueshift_gfx::StencilState;
+
+#[path = "common.rs"]
+mod common;
+
+use common::gfx;
+
+#[test]
+pub fn presets() {
+    assert_eq!(RenderState::default(), RenderState::OPAQUE);
+    assert_eq!(BlendState::default(), BlendState::OPAQUE);
+
+    let alpha = BlendState::ALPHA;
+    assert_eq!((alpha.src_rgb, alpha.dst_rgb),
+               (Ctx::SRC_ALPHA, Ctx::ONE_MINUS_SRC_ALPHA));
+    assert_eq!((alpha.src_alpha, alpha.dst_alpha),
+               (Ctx::ONE, Ctx::ONE_MINUS_SRC_ALPHA));
+    assert_eq!(BlendState::PREMULTIPLIED.src_rgb, Ctx::ONE);
+
+    assert_eq!(DepthState::READ_ONLY, DepthState { test  : true,
+                                                   write : false,
+                                                   func  : Ctx::LEQUAL });
+    assert_eq!(RenderState::TRANSPARENT.depth, DepthState::READ_ONLY);
+    assert_eq!(RenderState::TRANSPARENT.raster, RasterState::DEFAULT);
+
+    let equal = StencilState::equal(1);
+    assert_eq!((equal.func, equal.write_mask), (Ctx::EQUAL, 0));
+    assert_eq!(StencilState::not_equal(1).func, Ctx::NOTEQUAL);
+}
+
+#[test]
+pub fn start_state_is_opaque() {
+    let (gfx, context) = gfx();
+
+    // Startup applied the opaque state, so applying it again sends nothing.
+    gfx.apply_render_state(&RenderState::OPAQUE);
+    assert!(context.take_calls().is_empty());
+}
+
+#[test]
+pub fn apply_changes_only() {
+    let (gfx, context) = gfx();
+
+    gfx.apply_render_state(&RenderState::TRANSPARENT);
+    assert_eq!(context.take_calls(), vec![
+        format!("enable({})", Ctx::BLEND),
+        format!("blend_func_separate({}, {}, {}, {})", Ctx::SRC_ALPHA,
+                Ctx::ONE_MINUS_SRC_ALPHA, Ctx::ONE, Ctx::ONE_MINUS_SRC_ALPHA),
+        format!("blend_equation_separate({}, {})", Ctx::FUNC_ADD,
+                Ctx::FUNC_ADD),
+        format!("depth_func({})", Ctx::LEQUAL),
+        "depth_mask(false)".to_string(),
+    ]);
+
+    let shadow_caster = RasterState { polygon_offset: Some((2.0, 4.0)),
+                                      ..RasterState::CULL_FRONT };
+    let state = RenderState::OPAQUE.with_stencil(StencilState::write(1))
+                                   .with_raster(shadow_caster);
+    gfx.apply_render_state(&state);
+    assert_eq!(context.take_calls(), vec![
+        format!("disable({})", Ctx::BLEND),
+        format!("depth_func({})", Ctx::LESS),
+        "depth_mask(true)".to_string(),
+        format!("enable({})", Ctx::STENCIL_TEST),
+        format!("stencil_func({}, 1, 0xffffffff)", Ctx::ALWAYS),
+        format!("stencil_op({}, {}, {})", Ctx::KEEP, Ctx::KEEP, Ctx::REPLACE),
+        format!("cull_face({})", Ctx::FRONT),
+        format!("enable({})", Ctx::POLYGON_OFFSET_FILL),
+        "polygon_offset(2, 4)".to_string(),
+    ]);
+}
@@ -0,0 +1,193 @@
+//! Native tests of render targets, run against the recording backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGl2RenderingContext as Ctx2;
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::DepthBuffer;
+use blueshift_gfx::GfxError;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GlVersion;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::RenderTarget;
+use blueshift_gfx::RenderTargetError;
+use blueshift_gfx::RenderTargetOptions;
+use blueshift_gfx::TexelFormat;
+
+#[path = "common.rs"]
+mod common;
+
+use common::gfx_on;
+
+fn create_target(gfx     : &Gfx<RecordingBackend>,
+                 options : &RenderTargetOptions)
+    -> Result<RenderTarget<RecordingBackend>, GfxError>
+{
+    RenderTarget::new("target", 256, 128, options, gfx.capabilities(),
+                      gfx.context().clone())
+}
+
+#[test]
+pub fn attachments() {
+    let context = Arc::new(RecordingBackend::new());
+    let gfx     = gfx_on(&context);
+    let target  = create_target(&gfx, &RenderTargetOptions::new())
+                      .unwrap();
+    let fb      = Ctx::FRAMEBUFFER;
+
+    let calls = context.take_calls();
+    assert!(calls.contains(&format!("tex_image_2d({}, 0, {}, 256, 128, 0, \
+                                     {}, {}, None)", Ctx::TEXTURE_2D,
+                                    Ctx::RGBA, Ctx::RGBA, Ctx::UNSIGNED_BYTE)));
+    assert!(calls.contains(&format!("renderbuffer_storage({}, {}, 256, 128)",
+                                    Ctx::RENDERBUFFER,
+                                    Ctx::DEPTH_COMPONENT16)));
+    let attach = calls.iter()
+                      .skip_while(|c| !c.starts_with("create_framebuffer"))
+                      .cloned()
+                      .collect::<Vec<String>>();
+    assert_eq!(attach, vec![
+        "create_framebuffer() -> 3".to_string(),
+        format!("bind_framebuffer({}, Some(3))", fb),
+        format!("framebuffer_texture_2d({}, {}, {}, Some(1), 0)", fb,
+                Ctx::COLOR_ATTACHMENT0, Ctx::TEXTURE_2D),
+        format!("framebuffer_renderbuffer({}, {}, {}, Some(2))", fb,
+                Ctx::DEPTH_ATTACHMENT, Ctx::RENDERBUFFER),
+        format!("check_framebuffer_status({})", fb),
+        format!("bind_framebuffer({}, None)", fb),
+    ]);
+
+    let color = target.color(0);
+    assert_eq!((color.width(), color.height()), (256, 128));
+    assert_eq!(color.size(), 256 * 128 * 4);
+    assert!(target.depth_texture().is_none());
+
+    drop(target);
+    assert_eq!(context.take_calls(), vec!["delete_framebuffer(3)",
+                                          "delete_renderbuffer(2)",
+                                          "delete_texture(1)"]);
+}
+
+#[test]
+pub fn incomplete() {
+    let context = Arc::new(RecordingBackend::new());
+    let gfx     = gfx_on(&context);
+    context.set_framebuffer_status(Ctx::FRAMEBUFFER_INCOMPLETE_ATTACHMENT);
+
+    match create_target(&gfx, &RenderTargetOptions::new()) {
+        Err(GfxError::RenderTargetError(RenderTargetError::Incomplete(msg))) => {
+            assert!(msg.contains("target"));
+            assert!(msg.contains("FRAMEBUFFER_INCOMPLETE_ATTACHMENT"));
+        },
+        _ => panic!("Expected an incomplete render target."),
+    }
+}
+
+#[test]
+pub fn unsupported_attachments() {
+    let context = Arc::new(RecordingBackend::new());
+    let gfx     = gfx_on(&context);
+
+    let two = RenderTargetOptions::new().colors(&[TexelFormat::RGBA8; 2]);
+    assert!(matches!(create_target(&gfx, &two),
+                     Err(GfxError::RenderTargetError(
+                         RenderTargetError::Unsupported(_)))));
+
+    let depth = RenderTargetOptions::new()
+                    .colors(&[])
+                    .depth(DepthBuffer::Texture(TexelFormat::DEPTH));
+    assert!(matches!(create_target(&gfx, &depth),
+                     Err(GfxError::RenderTargetError(
+                         RenderTargetError::Unsupported(_)))));
+
+    // WEBGL_depth_texture makes depth textures available.
+    let context = Arc::new(RecordingBackend::new());
+    context.set_extensions(&["WEBGL_depth_texture"]);
+    let gfx    = gfx_on(&context);
+    let format = TexelFormat::depth(gfx.capabilities()).unwrap();
+    assert_eq!(format, TexelFormat::DEPTH);
+
+    let depth  = depth.depth(DepthBuffer::Texture(format));
+    let target = create_target(&gfx, &depth).unwrap();
+    let depth  = target.depth_texture().unwrap();
+    assert_eq!((depth.width(), depth.height()), (256, 128));
+    assert!(target.colors().is_empty());
+    assert!(context.calls().contains(
+        &format!("framebuffer_texture_2d({}, {}, {}, Some(1), 0)",
+                 Ctx::FRAMEBUFFER, Ctx::DEPTH_ATTACHMENT, Ctx::TEXTURE_2D)));
+}
+
+#[test]
+pub fn multiple_render_targets() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_version(GlVersion::WebGl2);
+    context.set_float(Ctx2::MAX_DRAW_BUFFERS, 4.0);
+    let gfx     = gfx_on(&context);
+
+    // Float colors need EXT_color_buffer_float to be renderable.
+    let options = RenderTargetOptions::new()
+                      .colors(&[TexelFormat::RGBA8, TexelFormat::RGBA16F])
+                      .depth(DepthBuffer::DepthStencil);
+    assert!(!gfx.capabilities().color_buffer_float);
+    assert!(matches!(create_target(&gfx, &options),
+                     Err(GfxError::RenderTargetError(
+                         RenderTargetError::Unsupported(_)))));
+
+    context.set_extensions(&["EXT_color_buffer_float"]);
+    let gfx     = gfx_on(&context);
+    assert!(gfx.capabilities().color_buffer_float);
+    let target  = create_target(&gfx, &options).unwrap();
+    assert_eq!(target.colors().len(), 2);
+    assert_eq!(TexelFormat::depth(gfx.capabilities()),
+               Some(TexelFormat::DEPTH24));
+
+    let calls = context.take_calls();
+    assert!(calls.contains(&format!("draw_buffers([{}, {}])",
+                                    Ctx::COLOR_ATTACHMENT0,
+                                    Ctx::COLOR_ATTACHMENT0 + 1)));
+    assert!(calls.contains(&format!("framebuffer_renderbuffer({}, {}, {}, \
+                                     Some(3))", Ctx::FRAMEBUFFER,
+                                    Ctx::DEPTH_STENCIL_ATTACHMENT,
+                                    Ctx::RENDERBUFFER)));
+}
+
+#[test]
+pub fn bind_restores_viewport() {
+    let context = Arc::new(RecordingBackend::new());
+    let gfx     = gfx_on(&context);
+    let outer   = create_target(&gfx, &RenderTargetOptions::new())
+                      .unwrap();
+    let inner   = RenderTarget::new("inner", 64, 64,
+                                    &RenderTargetOptions::new(),
+                                    gfx.capabilities(),
+                                    context.clone()).unwrap();
+    let fb      = Ctx::FRAMEBUFFER;
+
+    gfx.viewport(0, 0, 640, 480);
+    context.take_calls();
+
+    outer.bind(&gfx);
+    inner.bind(&gfx);
+    inner.unbind(&gfx);
+    outer.unbind(&gfx);
+
+    assert_eq!(context.take_calls(), vec![
+        format!("bind_framebuffer({}, Some(3))", fb),
+        "viewport(0, 0, 256, 128)".to_string(),
+        format!("bind_framebuffer({}, Some(6))", fb),
+        "viewport(0, 0, 64, 64)".to_string(),
+        format!("bind_framebuffer({}, Some(3))", fb),
+        "viewport(0, 0, 256, 128)".to_string(),
+        format!("bind_framebuffer({}, None)", fb),
+        "viewport(0, 0, 640, 480)".to_string(),
+    ]);
+    assert_eq!(gfx.current_framebuffer(), None);
+
+    // The color result is sampled like any texture.
+    gfx.bind_texture(0, Ctx::TEXTURE_2D, Some(outer.color(0).tid()));
+    assert!(context.take_calls().contains(
+        &format!("bind_texture({}, Some(1))", Ctx::TEXTURE_2D)));
+}
@@ -0,0 +1,223 @@
+//! Native tests of shadow maps and the matrices they're built from, run
+//! against the recording backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::cascade_splits;
+use blueshift_gfx::ActiveInfo;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::Mat4;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::ShadowMap;
+use blueshift_gfx::ShadowPass;
+use blueshift_gfx::Vec3;
+use blueshift_gfx::MAT4_BIAS;
+use blueshift_gfx::MAT4_IDENTITY;
+use blueshift_gfx::MAX_SHADOW_CASCADES;
+
+#[path = "common.rs"]
+mod common;
+
+use common::active;
+use common::gfx_on;
+
+fn near(a: &Vec3, b: &Vec3) -> bool {
+    a.diff(b).dot() < 1e-8
+}
+
+fn setup(extensions: &[&str]) -> (Gfx<RecordingBackend>, Arc<RecordingBackend>)
+{
+    let context = Arc::new(RecordingBackend::new());
+    context.set_extensions(extensions);
+    context.set_active_attribs(vec![active("POSITION", Ctx::FLOAT_VEC3)]);
+    (gfx_on(&context), context)
+}
+
+#[test]
+pub fn matrix_builders() {
+    let eye  = Vec3::new(0.0, 0.0, 5.0);
+    let view = Mat4::look_at(&eye, &Vec3::new_zeroed(),
+                             &Vec3::new(0.0, 1.0, 0.0));
+    assert!(near(&view.transform_point(&Vec3::new_zeroed()),
+                 &Vec3::new(0.0, 0.0, -5.0)));
+    assert!(near(&view.transform_point(&Vec3::new(1.0, 2.0, 5.0)),
+                 &Vec3::new(1.0, 2.0, 0.0)));
+
+    let ortho = Mat4::orthographic(-2.0, 2.0, -1.0, 1.0, 1.0, 3.0);
+    assert!(near(&ortho.transform_point(&Vec3::new(2.0, -1.0, -1.0)),
+                 &Vec3::new(1.0, -1.0, -1.0)));
+    assert!(near(&ortho.transform_point(&Vec3::new(-2.0, 1.0, -3.0)),
+                 &Vec3::new(-1.0, 1.0, 1.0)));
+
+    let proj = Mat4::perspective(90.0, 2.0, 1.0, 10.0);
+    assert!(near(&proj.transform_point(&Vec3::new(2.0, 1.0, -1.0)),
+                 &Vec3::new(1.0, 1.0, -1.0)));
+    assert!(near(&proj.transform_point(&Vec3::new(0.0, 0.0, -10.0)),
+                 &Vec3::new(0.0, 0.0, 1.0)));
+
+    let inverse = view.inverse().unwrap();
+    assert!(near(&inverse.transform_point(&Vec3::new(0.0, 0.0, -5.0)),
+                 &Vec3::new_zeroed()));
+    assert!(Mat4::new().inverse().is_none());
+
+    // Bias takes clip space to texture coordinates and depth.
+    assert!(near(&MAT4_BIAS.transform_point(&Vec3::new(-1.0, 1.0, 0.0)),
+                 &Vec3::new(0.0, 1.0, 0.5)));
+}
+
+#[test]
+pub fn splits() {
+    let uniform = cascade_splits(1.0, 100.0, 4, 0.0);
+    assert_eq!(uniform, vec![25.75, 50.5, 75.25, 100.0]);
+
+    let log = cascade_splits(1.0, 100.0, 2, 1.0);
+    assert!((log[0] - 10.0).abs() < 1e-4);
+
+    let blend = cascade_splits(0.5, 200.0, 4, 0.75);
+    assert!(blend.windows(2).all(|w| w[0] < w[1]));
+    assert!((blend[3] - 200.0).abs() < 1e-3);
+}
+
+#[test]
+pub fn depth_texture_or_packed() {
+    let (gfx, _) = setup(&["WEBGL_depth_texture"]);
+    let map      = ShadowMap::new("shadow", 512, 2, gfx.capabilities(),
+                                  gfx.context().clone()).unwrap();
+    assert!(!map.is_packed());
+    assert!(map.target().colors().is_empty());
+    assert_eq!(map.texture().tid(),
+               map.target().depth_texture().unwrap().tid());
+    assert_eq!((map.target().width(), map.target().height()), (1024, 512));
+
+    let (gfx, _) = setup(&[]);
+    let map      = ShadowMap::new("shadow", 512, 1, gfx.capabilities(),
+                                  gfx.context().clone()).unwrap();
+    assert!(map.is_packed());
+    assert!(map.target().depth_texture().is_none());
+    assert_eq!(map.texture().tid(), map.target().color(0).tid());
+
+    for cascades in [0, MAX_SHADOW_CASCADES + 1] {
+        assert!(ShadowMap::new("shadow", 512, cascades, gfx.capabilities(),
+                               gfx.context().clone()).is_err());
+    }
+}
+
+#[test]
+pub fn shadow_matrix_tiles() {
+    let (gfx, _) = setup(&["WEBGL_depth_texture"]);
+    let mut map  = ShadowMap::new("shadow", 256, 2, gfx.capabilities(),
+                                  gfx.context().clone()).unwrap();
+
+    map.set_directional(1, &Vec3::new(0.0, -1.0, 0.0), &Vec3::new_zeroed(),
+                        10.0);
+    let m = map.shadow_matrix(1);
+
+    // The center lands in the middle of the second tile, halfway in depth.
+    assert!(near(&m.transform_point(&Vec3::new_zeroed()),
+                 &Vec3::new(0.75, 0.5, 0.5)));
+    // The top of the sphere is nearest the light.
+    assert!(near(&m.transform_point(&Vec3::new(0.0, 10.0, 0.0)),
+                 &Vec3::new(0.75, 0.5, 0.0)));
+
+    let camera = Mat4::look_at(&Vec3::new(0.0, 2.0, 0.0),
+                               &Vec3::new(0.0, 2.0, -1.0),
+                               &Vec3::new(0.0, 1.0, 0.0));
+    map.fit_cascades(&camera, 60.0, 1.5, 0.1, 50.0, 0.5,
+                     &Vec3::new(1.0, -1.0, 0.0));
+    assert_eq!(map.splits().len(), 2);
+    assert!((map.splits()[1] - 50.0).abs() < 1e-3);
+
+    // A point on the view axis inside the first slice lands in the first
+    // tile.
+    let split = map.splits()[0];
+    let p     = map.shadow_matrix(0)
+                   .transform_point(&Vec3::new(0.0, 2.0, -split * 0.5));
+    assert!(p.diff(&Vec3::new(0.25, 0.5, 0.5)).dot() < 0.25 * 0.25);
+    assert_ne!(map.view_projection(0).to_array(), MAT4_IDENTITY.to_array());
+}
+
+#[test]
+pub fn pass_draws_each_cascade() {
+    let (gfx, context) = setup(&[]);
+    let map  = ShadowMap::new("shadow", 128, 3, gfx.capabilities(),
+                              gfx.context().clone()).unwrap();
+    context.set_active_uniforms(vec![
+        ActiveInfo { name: "LIGHTMVP".into(), var_type: Ctx::FLOAT_MAT4,
+                     size: 1 }]);
+    let pass = ShadowPass::new(gfx.capabilities(), context.clone()).unwrap();
+    gfx.viewport(0, 0, 640, 480);
+    gfx.clear_color(0.2, 0.3, 0.4, 1.0);
+    context.take_calls();
+
+    pass.begin(&gfx, &map);
+    for cascade in 0..map.cascades() {
+        pass.cascade(&gfx, &map, cascade);
+        pass.set_model_matrix(&MAT4_IDENTITY);
+    }
+    pass.end(&gfx, &map);
+
+    let calls     = context.take_calls();
+    let viewports = calls.iter()
+                         .filter(|c| c.starts_with("viewport"))
+                         .cloned()
+                         .collect::<Vec<String>>();
+    assert_eq!(viewports, vec!["viewport(0, 0, 384, 128)",
+                               "viewport(0, 0, 128, 128)",
+                               "viewport(128, 0, 128, 128)",
+                               "viewport(256, 0, 128, 128)",
+                               "viewport(0, 0, 640, 480)"]);
+    assert!(calls.contains(&"polygon_offset(2, 4)".to_string()));
+    let clears = calls.iter()
+                      .filter(|c| c.starts_with("clear"))
+                      .cloned()
+                      .collect::<Vec<String>>();
+    assert_eq!(clears, vec!["clear_color(1, 1, 1, 1)".to_string(),
+                            format!("clear({:#x})",
+                                    Ctx::DEPTH_BUFFER_BIT
+                                    | Ctx::COLOR_BUFFER_BIT),
+                            "clear_color(0.2, 0.3, 0.4, 1)".to_string()]);
+    assert_eq!(gfx.current_clear_color(), Some([0.2, 0.3, 0.4, 1.0]));
+    assert_eq!(calls.iter()
+                    .filter(|c| c.starts_with("uniform_matrix4fv"))
+                    .count(), 3);
+    assert_eq!(calls.iter().rev().find(|c| c.starts_with("bind_framebuffer")),
+               Some(&format!("bind_framebuffer({}, None)", Ctx::FRAMEBUFFER)));
+}
+
+#[test]
+pub fn apply_uploads_cascades() {
+    let (gfx, context) = setup(&["WEBGL_depth_texture"]);
+    let mut map = ShadowMap::new("shadow", 256, 2, gfx.capabilities(),
+                                 gfx.context().clone()).unwrap();
+    map.set_directional(0, &Vec3::new(0.0, -1.0, 0.0), &Vec3::new_zeroed(),
+                        5.0);
+    map.set_directional(1, &Vec3::new(0.0, -1.0, 0.0), &Vec3::new_zeroed(),
+                        20.0);
+
+    context.set_active_uniforms(vec![
+        ActiveInfo { name: "SHADOWMAP".into(), var_type: Ctx::SAMPLER_2D,
+                     size: 1 },
+        ActiveInfo { name: "SHADOWMATRIX[0]".into(), var_type: Ctx::FLOAT_MAT4,
+                     size: 4 },
+        ActiveInfo { name: "SHADOWSPLITS".into(), var_type: Ctx::FLOAT_VEC4,
+                     size: 1 }]);
+    let pass = ShadowPass::new(gfx.capabilities(), context.clone()).unwrap();
+    gfx.use_program(Some(pass.program().pid()));
+    context.take_calls();
+
+    map.apply(&gfx, pass.program(), 3);
+
+    let calls = context.take_calls();
+    assert!(calls.contains(&format!("active_texture({})", Ctx::TEXTURE0 + 3)));
+    assert!(calls.contains(&"uniform1i(Some(0), 3)".to_string()));
+    assert!(calls.contains(&format!("uniform_matrix4fv(Some(1), false, {:?})",
+                                    map.shadow_matrix(0).to_array())));
+    assert!(calls.contains(&format!("uniform_matrix4fv(Some(1001), false, \
+                                     {:?})", map.shadow_matrix(1).to_array())));
+    assert!(calls.contains(&format!("uniform4f(Some(2), {}, {}, -1, -1)",
+                                    f32::MAX, f32::MAX)));
+}
@@ -0,0 +1,83 @@
+//! Native tests of the skybox, run against the recording backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::ActiveInfo;
+use blueshift_gfx::DepthState;
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GfxError;
+use blueshift_gfx::Memory;
+use blueshift_gfx::RasterState;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::Skybox;
+use blueshift_gfx::TexelFormat;
+use blueshift_gfx::Texture;
+use blueshift_gfx::TextureError;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+
+/// Builds an uncompressed RGBA KTX cube map with 1x1 faces.
+fn cube_ktx() -> Vec<u8> {
+    let mut bytes = vec![0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB,
+                         0x0D, 0x0A, 0x1A, 0x0A];
+    let header = [0x04030201, Ctx::UNSIGNED_BYTE, 1, Ctx::RGBA, Ctx::RGBA,
+                  Ctx::RGBA, 1, 1, 0, 0, 6, 1, 0, 4];
+    for field in header {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[255; 24]);
+    bytes
+}
+
+#[test]
+pub fn draw_restores_state() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_active_attribs(vec![
+        ActiveInfo { name: "POSITION".into(), var_type: Ctx::FLOAT_VEC3,
+                     size: 1 }]);
+    context.set_active_uniforms(vec![
+        ActiveInfo { name: "MODELVIEWPROJECTIONMATRIX".into(),
+                     var_type: Ctx::FLOAT_MAT4, size: 1 },
+        ActiveInfo { name: "CUBEMAP".into(), var_type: Ctx::SAMPLER_CUBE,
+                     size: 1 }]);
+    let gfx     = Gfx::from_context(context.clone());
+    let skybox  = Skybox::new(context.clone()).unwrap();
+
+    let flat = Texture::with_size("flat", 1, 1, &TexelFormat::RGBA8,
+                                  TEXTURE_FILTER_1X, context.clone())
+                       .unwrap();
+    assert!(matches!(skybox.draw(&gfx, &flat),
+                     Err(GfxError::TextureError(
+                         TextureError::UnsupportedFormat(_)))));
+
+    let mut memory = Memory::from_bytes("sky.ktx", cube_ktx());
+    let cube       = Texture::from_memory("sky", &mut memory, 0,
+                                          TEXTURE_FILTER_1X, 0.0,
+                                          context.clone()).unwrap();
+    assert_eq!(cube.target(), Ctx::TEXTURE_CUBE_MAP);
+
+    let depth  = DepthState { test: true, write: true, func: Ctx::GREATER };
+    let raster = RasterState { cull_face      : Some(Ctx::FRONT),
+                               polygon_offset : Some((1.0, 2.0)),
+                               ..RasterState::DEFAULT };
+    gfx.apply_depth_state(&depth);
+    gfx.apply_raster_state(&raster);
+    context.take_calls();
+
+    skybox.draw(&gfx, &cube).unwrap();
+    let calls = context.take_calls();
+    assert!(calls.contains(&format!("draw_arrays({}, 0, 36)",
+                                    Ctx::TRIANGLES)));
+    assert_eq!(gfx.current_depth_state(), Some(depth));
+    assert_eq!(gfx.current_raster_state(), Some(raster));
+
+    // State the cache doesn't know is left at the defaults.
+    gfx.invalidate_state();
+    assert_eq!(gfx.current_depth_state(), None);
+    skybox.draw(&gfx, &cube).unwrap();
+    assert_eq!(gfx.current_depth_state(), Some(DepthState::DEFAULT));
+    assert_eq!(gfx.current_raster_state(), Some(RasterState::DEFAULT));
+}
@@ -0,0 +1,150 @@
+//! Test suite for the Web and headless browsers.
+
+#![cfg(target_arch = "wasm32")]
+#![cfg(feature = "test_accessors")]
+
+use std::sync::Arc;
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+use web_sys::WebGlRenderingContext;
+
+use blueshift_gfx::CompressionFamily;
+use blueshift_gfx::CompressionSupport;
+use blueshift_gfx::Texture;
+use blueshift_gfx::TextureHandle;
+use blueshift_gfx::TextureManager;
+use blueshift_gfx::TEXTURE_CLAMP;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn new_context() -> Arc<WebGlRenderingContext> {
+    let document = web_sys::window().unwrap().document().unwrap();
+    
+    let canvas =  document.create_element("canvas")
+                          .unwrap()
+                          .dyn_into::<web_sys::HtmlCanvasElement>()
+                          .expect("Failed to get canvas.");
+
+    let context =   canvas.get_context("webgl")
+                          .unwrap()
+                          .unwrap()
+                          .dyn_into::<WebGlRenderingContext>()
+                          .expect("Failed to get context.");
+    Arc::new(context)
+}
+
+#[wasm_bindgen_test]
+pub async fn texture_new_ktx() {
+    match Texture::new("rgba-ktx", 
+                       "http://localhost:8000/tests/rgba.ktx",
+                       TEXTURE_CLAMP, TEXTURE_FILTER_1X, 0.0,
+                       new_context()).await 
+    {
+        Ok(t) => {
+            assert_eq!(t.key_value("KTXorientation"), Some(&b"S=r,T=d"[..]));
+            assert_eq!(t.anisotropic_filter(), 0.0);
+        },
+        Err(e) => {
+            panic!("Texture creation error: {}", e.to_string());
+        }
+    }
+}
+
+#[wasm_bindgen_test]
+pub async fn texture_new_unsupported_container() {
+    // A text file isn't a texture container; it should be reported, not
+    // panic.
+    match Texture::new("hello", 
+                       "http://localhost:8000/tests/hello.txt",
+                       0, TEXTURE_FILTER_1X, 0.0,
+                       new_context()).await 
+    {
+        Ok(_)  => panic!("Texture::new() should have generated an error."),
+        Err(e) => assert!(e.to_string().contains("supported container")),
+    }
+}
+
+#[wasm_bindgen_test]
+pub fn compression_support_select() {
+    let support  = CompressionSupport::query(&new_context());
+    let variants = [(Some(CompressionFamily::Astc), "tex.astc"),
+                    (Some(CompressionFamily::S3tc), "tex.dds"),
+                    (None,                          "tex.ktx")];
+    let expected = if support.supports(CompressionFamily::Astc)      { "tex.astc" }
+                   else if support.supports(CompressionFamily::S3tc) { "tex.dds"  }
+                   else                                              { "tex.ktx"  };
+
+    assert_eq!(support.select(&variants), Some(expected));
+    assert_eq!(CompressionSupport::default().select(&variants), Some("tex.ktx"));
+}
+
+#[wasm_bindgen_test]
+pub async fn texture_anisotropic_filter_clamped() {
+    use web_sys::ExtTextureFilterAnisotropic as Ext;
+
+    let context = new_context();
+    let t = Texture::new("rgba-ktx", 
+                         "http://localhost:8000/tests/rgba.ktx",
+                         TEXTURE_CLAMP, TEXTURE_FILTER_1X, 1.0e6,
+                         context.clone()).await.unwrap();
+    // Either the extension is missing and filtering is off, or the value was
+    // clamped to the device maximum.
+    let supported = ["EXT_texture_filter_anisotropic",
+                     "WEBKIT_EXT_texture_filter_anisotropic"]
+                    .iter()
+                    .any(|name| matches!(context.get_extension(name),
+                                         Ok(Some(_))));
+    let expected  = if supported {
+        context.get_parameter(Ext::MAX_TEXTURE_MAX_ANISOTROPY_EXT)
+               .unwrap()
+               .as_f64()
+               .unwrap() as f32
+    } else {
+        0.0
+    };
+    assert_eq!(t.anisotropic_filter(), expected);
+}
+
+#[wasm_bindgen_test]
+pub async fn texture_manager_shares_loads() {
+    let manager = TextureManager::new(new_context());
+    let url     = "http://localhost:8000/tests/rgba.ktx";
+
+    let (a, b) = futures::join!(manager.load(url, TEXTURE_CLAMP,
+                                             TEXTURE_FILTER_1X, 0.0),
+                                manager.load(url, TEXTURE_CLAMP,
+                                             TEXTURE_FILTER_1X, 0.0));
+    let (a, b) = (a.unwrap(), b.unwrap());
+    assert!(TextureHandle::ptr_eq(&a, &b));
+    assert_eq!(manager.texture_count(), 1);
+    assert_eq!(manager.vram_usage(), a.size() as usize);
+
+    // Different flags make a separate texture.
+    let c = manager.load(url, 0, TEXTURE_FILTER_1X, 0.0).await.unwrap();
+    assert!(!TextureHandle::ptr_eq(&a, &c));
+    assert_eq!(manager.texture_count(), 2);
+
+    drop(a);
+    drop(c);
+    assert_eq!(manager.texture_count(), 1);
+    drop(b);
+    assert_eq!(manager.texture_count(), 0);
+    assert_eq!(manager.vram_usage(), 0);
+}
+
+#[wasm_bindgen_test]
+pub async fn texture_manager_budget() {
+    let manager = TextureManager::new(new_context());
+    let url     = "http://localhost:8000/tests/rgba.ktx";
+
+    manager.set_budget(1 << 20);
+    let a = manager.load(url, 0, TEXTURE_FILTER_1X, 0.0).await.unwrap();
+    drop(a);
+
+    // Kept resident within the budget, and freed once it shrinks.
+    assert_eq!(manager.texture_count(), 1);
+    manager.set_budget(0);
+    assert_eq!(manager.texture_count(), 0);
+}
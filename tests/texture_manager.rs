@@ -0,0 +1,165 @@
+//! Native tests of texture sharing and residency, run against the recording
+//! backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::executor::block_on;
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+
+use blueshift_gfx::register_memory_source;
+use blueshift_gfx::InMemorySource;
+use blueshift_gfx::MemoryError;
+use blueshift_gfx::MemorySource;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::TextureHandle;
+use blueshift_gfx::TextureManager;
+use blueshift_gfx::TEXTURE_CLAMP;
+use blueshift_gfx::TEXTURE_FILTER_1X;
+
+const KTX : &[u8] = include_bytes!("rgba.ktx");
+
+/// Returns pending once, so a load started alongside it finds this one in
+/// flight.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Serves the test KTX file after yielding, counting the reads.
+struct SlowSource(AtomicUsize);
+
+impl MemorySource for SlowSource {
+    fn open<'a>(&'a self, _url: &'a str)
+        -> LocalBoxFuture<'a, Result<Vec<u8>, MemoryError>>
+    {
+        async move {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            YieldOnce(false).await;
+            Ok( KTX.to_vec() )
+        }.boxed_local()
+    }
+}
+
+fn manager() -> (TextureManager<RecordingBackend>, Arc<RecordingBackend>) {
+    let context = Arc::new(RecordingBackend::new());
+    (TextureManager::new(context.clone()), context)
+}
+
+fn load(manager: &TextureManager<RecordingBackend>, url: &str, flags: u32)
+    -> TextureHandle<RecordingBackend>
+{
+    block_on(manager.load(url, flags, TEXTURE_FILTER_1X, 0.0)).unwrap()
+}
+
+fn deletes(context: &RecordingBackend) -> usize {
+    context.take_calls()
+           .iter()
+           .filter(|c| c.starts_with("delete_texture"))
+           .count()
+}
+
+#[test]
+pub fn concurrent_loads_share() {
+    let source = Arc::new(SlowSource(AtomicUsize::new(0)));
+    register_memory_source("slowtex", source.clone());
+
+    let (manager, _) = manager();
+    let url          = "slowtex:shared.ktx";
+    let (a, b) = block_on(async {
+        futures::join!(manager.load(url, TEXTURE_CLAMP, TEXTURE_FILTER_1X, 0.0),
+                       manager.load(url, TEXTURE_CLAMP, TEXTURE_FILTER_1X, 0.0))
+    });
+    let (a, b) = (a.unwrap(), b.unwrap());
+
+    // The second load waited for the first instead of reading again.
+    assert_eq!(source.0.load(Ordering::SeqCst), 1);
+    assert!(TextureHandle::ptr_eq(&a, &b));
+    assert_eq!(manager.texture_count(), 1);
+    assert_eq!(manager.vram_usage(), a.size() as usize);
+
+    // Different flags make a separate texture.
+    let c = load(&manager, url, 0);
+    assert!(!TextureHandle::ptr_eq(&a, &c));
+    assert_eq!(source.0.load(Ordering::SeqCst), 2);
+    assert_eq!(manager.texture_count(), 2);
+}
+
+#[test]
+pub fn last_handle_frees() {
+    InMemorySource::insert_static("mem:manager/free.ktx", KTX);
+    let (manager, context) = manager();
+
+    let a = load(&manager, "mem:manager/free.ktx", 0);
+    let b = a.clone();
+    let c = load(&manager, "mem:manager/free.ktx", 0);
+    assert!(TextureHandle::ptr_eq(&a, &c));
+    context.take_calls();
+
+    // Without a budget a texture goes when its last handle does.
+    drop(a);
+    drop(c);
+    assert_eq!(manager.texture_count(), 1);
+    assert_eq!(deletes(&context), 0);
+    drop(b);
+    assert_eq!(manager.texture_count(), 0);
+    assert_eq!(manager.vram_usage(), 0);
+    assert_eq!(deletes(&context), 1);
+}
+
+#[test]
+pub fn budget_trims_least_recently_used() {
+    for name in ["a", "b", "c"] {
+        InMemorySource::insert_static(&format!("mem:manager/{}.ktx", name),
+                                      KTX);
+    }
+    let (manager, context) = manager();
+    let a    = load(&manager, "mem:manager/a.ktx", 0);
+    let size = a.size() as usize;
+    manager.set_budget(size * 2);
+
+    let b = load(&manager, "mem:manager/b.ktx", 0);
+    context.take_calls();
+
+    // Unused textures stay resident within the budget.
+    drop(a);
+    drop(b);
+    assert_eq!(manager.texture_count(), 2);
+    assert_eq!(deletes(&context), 0);
+
+    // Going over it frees the one released longest ago.
+    let c = load(&manager, "mem:manager/c.ktx", 0);
+    assert_eq!(manager.texture_count(), 2);
+    assert_eq!(manager.vram_usage(), size * 2);
+    assert_eq!(deletes(&context), 1);
+
+    // A resident texture is reused without loading it again.
+    let b = load(&manager, "mem:manager/b.ktx", 0);
+    assert!(!context.take_calls()
+                    .iter()
+                    .any(|c| c.starts_with("create_texture")));
+    drop(b);
+    drop(c);
+
+    manager.set_budget(0);
+    assert_eq!(manager.texture_count(), 0);
+    assert_eq!(deletes(&context), 2);
+}
@@ -0,0 +1,129 @@
+//! Native tests of vertex arrays, run against the recording backend.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::sync::Arc;
+
+use web_sys::WebGlRenderingContext as Ctx;
+
+use blueshift_gfx::Gfx;
+use blueshift_gfx::GlBackend;
+use blueshift_gfx::GlCapabilities;
+use blueshift_gfx::GlVersion;
+use blueshift_gfx::Program;
+use blueshift_gfx::RecordingBackend;
+use blueshift_gfx::VertexArray;
+use blueshift_gfx::VertexAttribute;
+
+fn attributes(context: &RecordingBackend)
+    -> Vec<VertexAttribute<RecordingBackend>>
+{
+    let vbo = context.create_buffer().unwrap();
+    vec![VertexAttribute::new(&vbo, 0, 3, Ctx::FLOAT).stride(20),
+         VertexAttribute::new(&vbo, 1, 2, Ctx::FLOAT).stride(20).offset(12)]
+}
+
+fn specify_calls() -> Vec<String> {
+    vec![
+        format!("bind_buffer({}, Some(1))", Ctx::ARRAY_BUFFER),
+        "enable_vertex_attrib_array(0)".to_string(),
+        format!("vertex_attrib_pointer(0, 3, {}, false, 20, 0)", Ctx::FLOAT),
+        format!("bind_buffer({}, Some(1))", Ctx::ARRAY_BUFFER),
+        "enable_vertex_attrib_array(1)".to_string(),
+        format!("vertex_attrib_pointer(1, 2, {}, false, 20, 12)", Ctx::FLOAT),
+        format!("bind_buffer({}, None)", Ctx::ARRAY_BUFFER),
+    ]
+}
+
+#[test]
+pub fn native_vertex_array() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_version(GlVersion::WebGl2);
+    let caps    = GlCapabilities::query(&*context);
+    let attribs = attributes(&context);
+    context.take_calls();
+
+    // The layout is specified once, inside the vertex array object.
+    let gfx = Gfx::from_context(context.clone());
+    context.take_calls();
+    let vao = VertexArray::new(context.clone(), &caps, attribs);
+    assert!(!vao.is_emulated());
+
+    let mut expected = vec!["create_vertex_array() -> 2".to_string(),
+                            "bind_vertex_array(Some(2))".to_string()];
+    expected.extend(specify_calls());
+    expected.push("bind_vertex_array(None)".to_string());
+    assert_eq!(context.take_calls(), expected);
+
+    vao.bind(&gfx);
+    vao.unbind(&gfx);
+    drop(vao);
+    assert_eq!(context.take_calls(), vec!["bind_vertex_array(Some(2))",
+                                          "bind_vertex_array(None)",
+                                          "delete_vertex_array(2)"]);
+}
+
+#[test]
+pub fn emulated_vertex_array() {
+    // WebGL1 without OES_vertex_array_object.
+    let context = Arc::new(RecordingBackend::new());
+    let caps    = GlCapabilities::query(&*context);
+    let vbo     = context.create_buffer().unwrap();
+    let attribs = vec![VertexAttribute::new(&vbo, 0, 3, Ctx::FLOAT).stride(20),
+                       VertexAttribute::new(&vbo, 1, 2, Ctx::FLOAT).stride(20)
+                                                                 .offset(12),
+                       VertexAttribute::new(&vbo, 2, 4, Ctx::UNSIGNED_BYTE)
+                                       .normalized(true)
+                                       .divisor(1)];
+    context.take_calls();
+
+    let gfx = Gfx::from_context(context.clone());
+    context.take_calls();
+    let vao = VertexArray::new(context.clone(), &caps, attribs);
+    assert!(vao.is_emulated());
+    assert!(context.take_calls().is_empty());
+
+    // Each bind re-specifies the attributes, binding the shared buffer once.
+    vao.bind(&gfx);
+    let mut expected = specify_calls();
+    expected.remove(3);
+    expected.pop();
+    expected.extend([
+        "enable_vertex_attrib_array(2)".to_string(),
+        format!("vertex_attrib_pointer(2, 4, {}, true, 0, 0)",
+                Ctx::UNSIGNED_BYTE),
+        "vertex_attrib_divisor(2, 1)".to_string(),
+        format!("bind_buffer({}, None)", Ctx::ARRAY_BUFFER),
+    ]);
+    assert_eq!(context.take_calls(), expected);
+
+    // The array buffer is already unbound.
+    vao.unbind(&gfx);
+    drop(vao);
+    assert_eq!(context.take_calls(), vec!["disable_vertex_attrib_array(0)",
+                                          "disable_vertex_attrib_array(1)",
+                                          "vertex_attrib_divisor(2, 0)",
+                                          "disable_vertex_attrib_array(2)"]);
+}
+
+#[test]
+pub fn program_draw_binds_vertex_array() {
+    let context = Arc::new(RecordingBackend::new());
+    context.set_version(GlVersion::WebGl2);
+    let caps    = GlCapabilities::query(&*context);
+
+    let mut program = Program::from_source("test".into(), "void main() {}",
+                                           "void main() {}", None, None,
+                                           context.clone());
+    assert!(program.link());
+    let attribs = attributes(&context);
+    program.set_vertex_array(Some(VertexArray::new(context.clone(), &caps,
+                                                   attribs)));
+    let gfx = Gfx::from_context(context.clone());
+    context.take_calls();
+
+    program.draw(&gfx);
+    assert_eq!(context.take_calls(), vec!["use_program(Some(3))",
+                                          "bind_vertex_array(Some(5))",
+                                          "bind_vertex_array(None)"]);
+}